@@ -0,0 +1,136 @@
+use crate::config::Settings;
+use anyhow::{Context, Result};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::authentication::Credentials,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+// Alerts firing within this long of an email are batched into the next one
+pub const ALERT_EMAIL_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// Transport security of the SMTP connection (`--smtp-tls`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    None, // Plain text, only for a relay on a trusted network
+    #[default]
+    Starttls, // Upgraded after connecting, port 587 by default
+    Tls,  // TLS from the start, port 465 by default
+}
+
+/// SMTP server the alert emails are sent through.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>, // None = the default port of `tls`
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub tls: SmtpTls,
+}
+
+/// Alert emails of `--alert-email`: the alerts that fired since the last email wait
+/// here and are sent together, at most once per `ALERT_EMAIL_COOLDOWN`.
+#[derive(Debug)]
+pub struct AlertEmailer {
+    pub to: String,
+    pub smtp: SmtpConfig,
+    pending: Vec<String>, // "<node>: <alert>", oldest first
+    last_sent: Option<Instant>,
+}
+
+impl AlertEmailer {
+    /// None without `--alert-email`. `resolve` made sure an SMTP host comes with it.
+    pub fn from_settings(settings: &Settings) -> Option<AlertEmailer> {
+        let to = settings.alert_email.clone()?;
+        let host = settings.smtp_host.clone()?;
+        Some(AlertEmailer {
+            to,
+            smtp: SmtpConfig {
+                host,
+                port: settings.smtp_port,
+                user: settings.smtp_user.clone(),
+                password: settings.smtp_password.clone(),
+                tls: settings.smtp_tls,
+            },
+            pending: Vec::new(),
+            last_sent: None,
+        })
+    }
+
+    /// Adds alerts that just fired to the next email.
+    pub fn queue(&mut self, alerts: impl IntoIterator<Item = String>) {
+        self.pending.extend(alerts);
+    }
+
+    /// Subject and body of the email to send now, if alerts are waiting and the
+    /// cooldown since the last one is over.
+    pub fn take_due(&mut self, now: Instant) -> Option<(String, String)> {
+        if self.pending.is_empty()
+            || self
+                .last_sent
+                .is_some_and(|sent| now.saturating_duration_since(sent) < ALERT_EMAIL_COOLDOWN)
+        {
+            return None;
+        }
+        self.last_sent = Some(now);
+        let alerts = std::mem::take(&mut self.pending);
+        let subject = match alerts.as_slice() {
+            [alert] => format!("antop alert: {}", alert),
+            _ => format!("antop: {} new alerts", alerts.len()),
+        };
+        let mut body = format!(
+            "New alerts, {}\n\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        );
+        for alert in alerts {
+            body.push_str(&format!("- {}\n", alert));
+        }
+        Some((subject, body))
+    }
+}
+
+/// Sends a plain text email to `to`. It comes from the SMTP user when that is an
+/// address, otherwise from `to` itself.
+pub async fn send_alert_email(
+    config: &SmtpConfig,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> Result<()> {
+    let to: Mailbox = to
+        .parse()
+        .with_context(|| format!("Invalid alert email address '{}'", to))?;
+    let from = config
+        .user
+        .as_deref()
+        .and_then(|user| user.parse::<Mailbox>().ok())
+        .unwrap_or_else(|| to.clone());
+    let message = Message::builder()
+        .from(from)
+        .to(to)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .context("Failed to build the alert email")?;
+
+    let mut transport = match config.tls {
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+    };
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let (Some(user), Some(password)) = (&config.user, &config.password) {
+        transport = transport.credentials(Credentials::new(user.clone(), password.clone()));
+    }
+    transport
+        .build()
+        .send(message)
+        .await
+        .with_context(|| format!("Failed to send the alert email through {}", config.host))?;
+    Ok(())
+}
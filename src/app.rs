@@ -1,17 +1,71 @@
-use crate::metrics::{NodeMetrics, parse_metrics};
+use crate::{
+    alert::AlertEmailer,
+    config::Settings,
+    hooks::{HookOutcome, HookRunner},
+    log_tail::LogTail,
+    output::{NodeSnapshot, fleet_snapshot},
+    process::ProcessStats,
+    recording::Replay,
+    replay::HistoryPlayback,
+    serve::StatusServer,
+    systemd::{SystemdConfig, UnitCommand},
+    webhook::{WebhookEvent, WebhookNotifier},
+};
+use antop::{
+    discovery::{DiscoveredNode, DiscoverySource},
+    fetch::{FetchError, FetchErrorKind, FetchResult},
+    metrics::{
+        NAT_SUSPECT_INBOUND_RATIO, NodeMetrics, check_prometheus_format, is_metrics_identical,
+        parse_metrics,
+    },
+    state::{NodeStatus, PersistedState, StatusCounts},
+};
+use chrono::{DateTime, Local};
+use ratatui::style::Color;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering, // Add Ordering
-    collections::{HashMap, VecDeque},
-    fs,                        // Add fs for directory sizing
-    io,                        // Add io for error handling
-    path::{Path, PathBuf},     // Add Path
-    time::{Duration, Instant}, // Import Duration
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs,                                                // Add fs for directory sizing
+    io,                                                // Add io for error handling
+    path::{Path, PathBuf},                             // Add Path
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH}, // Import Duration
 };
+use unicode_width::UnicodeWidthStr;
 
-// Number of data points to keep for sparklines
+// Number of data points to keep for the error trend sparklines
 pub const SPARKLINE_HISTORY_LENGTH: usize = 60;
 // Storage per node in bytes (35 GB)
 pub const STORAGE_PER_NODE_BYTES: u64 = 35 * 1_000_000_000;
+// Memory is sampled on its own slow clock for the leak trend, whatever the update
+// interval: 60 samples every 30s look at the last 30 minutes
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+const MEMORY_HISTORY_LENGTH: usize = 60;
+// Samples needed before a memory trend means anything (5 minutes)
+const MEMORY_TREND_MIN_SAMPLES: usize = 10;
+// Updates kept for the fleet records / rewards growth charts (5 minutes at 1s)
+pub const GROWTH_HISTORY_LENGTH: usize = 300;
+// Recent fetch errors kept per node for the detail popup
+pub const RECENT_ERRORS_LENGTH: usize = 5;
+// Ended hook runs kept per node for the detail popup
+pub const RECENT_HOOKS_LENGTH: usize = 5;
+// Status bar messages kept for the message history popup (`M`)
+const STATUS_HISTORY_LENGTH: usize = 50;
+// How long a status bar message stays, warnings and errors longer to be read in time
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(3);
+const STATUS_ERROR_DURATION: Duration = Duration::from_secs(10);
+// Distance from the fleet mean, in standard deviations, beyond which a node is an outlier
+const ANOMALY_Z_SCORE: f64 = 2.0;
+// Cells of the availability timeline column
+pub const TIMELINE_BUCKETS: usize = 12;
+// Update intervals per timeline cell, the span covered follows the tick rate
+const TIMELINE_TICKS_PER_BUCKET: u32 = 5;
+// How long the row of a node that just went down stays inverted
+const ROW_FLASH_DURATION: Duration = Duration::from_millis(1500);
+// Live peers histogram buckets: label and exclusive upper bound (None = no bound)
+pub const PEERS_HISTOGRAM_BUCKETS: [(&str, Option<u64>); 3] =
+    [("<10", Some(10)), ("10-50", Some(51)), (">50", None)];
 
 // Discrete tick rate levels
 const TICK_LEVELS: [Duration; 13] = [
@@ -30,52 +84,682 @@ const TICK_LEVELS: [Duration; 13] = [
     Duration::from_secs(3600), // 1h
 ];
 
+/// Update intervals set directly by the digit keys, also listed in the help overlay.
+pub const TICK_PRESETS: [(char, Duration); 9] = [
+    ('1', Duration::from_secs(1)),
+    ('2', Duration::from_secs(5)),
+    ('3', Duration::from_secs(30)),
+    ('4', Duration::from_secs(60)),
+    ('5', Duration::from_secs(300)),
+    ('6', Duration::from_secs(600)),
+    ('7', Duration::from_secs(900)),
+    ('8', Duration::from_secs(1800)),
+    ('9', Duration::from_secs(3600)),
+];
+
+/// How much detail the node table shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewMode {
+    #[default]
+    Compact,
+    Detailed, // Adds trend sparklines next to some columns
+}
+
+impl ViewMode {
+    pub fn toggle(self) -> ViewMode {
+        match self {
+            ViewMode::Compact => ViewMode::Detailed,
+            ViewMode::Detailed => ViewMode::Compact,
+        }
+    }
+}
+
+/// Which byte counters the Rx/Tx cells and the summary In/Out totals show.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TotalsMode {
+    #[default]
+    Lifetime, // Counters as reported by the node since it started
+    Session, // Bytes transferred since antop started
+}
+
+impl TotalsMode {
+    pub fn toggle(self) -> TotalsMode {
+        match self {
+            TotalsMode::Lifetime => TotalsMode::Session,
+            TotalsMode::Session => TotalsMode::Lifetime,
+        }
+    }
+}
+
+/// How the count columns (Peers, Routing, Recs, Err) are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Notation {
+    #[default]
+    Grouped, // Thousands separators: 183,429
+    Compact, // Short-scale suffixes: 183K, 1.2M, 5.6B
+}
+
+impl Notation {
+    pub fn toggle(self) -> Notation {
+        match self {
+            Notation::Grouped => Notation::Compact,
+            Notation::Compact => Notation::Grouped,
+        }
+    }
+}
+
+/// Units of the byte sizes and speeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeUnits {
+    #[default]
+    Decimal, // Powers of 1000: kB, MB, GB
+    Binary, // Powers of 1024: KiB, MiB, GiB, like `du -h` and the antnode docs
+}
+
+impl SizeUnits {
+    pub fn toggle(self) -> SizeUnits {
+        match self {
+            SizeUnits::Decimal => SizeUnits::Binary,
+            SizeUnits::Binary => SizeUnits::Decimal,
+        }
+    }
+}
+
+/// Marker the sparklines are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartMarker {
+    #[default]
+    Braille, // Thin lines, 2x4 dots per cell
+    Dot,   // One dot per cell
+    Block, // Full cells, the easiest to see
+}
+
+impl ChartMarker {
+    pub fn next(self) -> ChartMarker {
+        match self {
+            ChartMarker::Braille => ChartMarker::Dot,
+            ChartMarker::Dot => ChartMarker::Block,
+            ChartMarker::Block => ChartMarker::Braille,
+        }
+    }
+}
+
+/// What the Rx/Tx speed sparklines record for an interval where the fetch failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GapFill {
+    #[default]
+    Zero, // Drop to 0, like an idle node
+    Hold, // Carry the last known speed forward
+    Gap,  // No sample, the line bridges over the missing points
+}
+
+impl GapFill {
+    /// Sample to record for a failed fetch, given the last recorded one.
+    pub fn fill(self, last: Option<u64>) -> Option<u64> {
+        match self {
+            GapFill::Zero => Some(0),
+            GapFill::Hold => last,
+            GapFill::Gap => None,
+        }
+    }
+}
+
+/// Key the node list is sorted by. `s` cycles the primary key, rows it ranks equal are
+/// ordered by the secondary key (`--sort-secondary`), then by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name, // Natural order of the node directories
+    Status, // Running, starting, error, stopped
+    // Highest first, nodes without the value last
+    Cpu,
+    Memory,
+    Records,
+    Uptime,
+    TailMode, // Most recently updated first, nodes never updated last (`--tail-mode`)
+}
+
+impl SortKey {
+    pub fn next(self) -> SortKey {
+        match self {
+            SortKey::Name => SortKey::Status,
+            SortKey::Status => SortKey::Cpu,
+            SortKey::Cpu => SortKey::Memory,
+            SortKey::Memory => SortKey::Records,
+            SortKey::Records => SortKey::Uptime,
+            SortKey::Uptime => SortKey::TailMode,
+            SortKey::TailMode => SortKey::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Status => "Status",
+            SortKey::Cpu => "CPU",
+            SortKey::Memory => "Memory",
+            SortKey::Records => "Records",
+            SortKey::Uptime => "Uptime",
+            SortKey::TailMode => "Last update",
+        }
+    }
+}
+
+/// What the storage gauge measures (`--storage-gauge`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageGauge {
+    #[default]
+    Allocation, // Record stores against `STORAGE_PER_NODE_BYTES` per node
+    Filesystem, // Used against total space of the filesystem holding the record stores
+}
+
+/// Space of the fullest filesystem holding record stores, see `StorageGauge::Filesystem`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilesystemUsage {
+    pub used_bytes: u64,
+    pub total_bytes: u64, // Used plus available to unprivileged users, like `df`
+    pub filesystems: usize, // Distinct filesystems holding record stores
+}
+
+/// Ways a node can stand out from the rest of the fleet, see `App::detect_anomalies`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalyType {
+    HighCpu,
+    HighMemory,
+    HighErrors,
+    LowPeers,
+    PeerLoss, // Losing live peers much faster than the rest of the fleet
+}
+
+impl AnomalyType {
+    pub const ALL: [AnomalyType; 5] = [
+        AnomalyType::HighCpu,
+        AnomalyType::HighMemory,
+        AnomalyType::HighErrors,
+        AnomalyType::LowPeers,
+        AnomalyType::PeerLoss,
+    ];
+
+    // The metric compared across the fleet
+    fn value(self, metrics: &NodeMetrics) -> Option<f64> {
+        match self {
+            AnomalyType::HighCpu => metrics.cpu_percent(),
+            AnomalyType::HighMemory => metrics.memory_used_mb,
+            AnomalyType::HighErrors => Some(metrics.total_errors() as f64),
+            AnomalyType::LowPeers => metrics.connected_peers.map(|peers| peers as f64),
+            AnomalyType::PeerLoss => metrics.peer_count_rate,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AnomalyType::HighCpu => "high CPU",
+            AnomalyType::HighMemory => "high memory",
+            AnomalyType::HighErrors => "high errors",
+            AnomalyType::LowPeers => "low peers",
+            AnomalyType::PeerLoss => "losing peers",
+        }
+    }
+}
+
+/// Availability of a node over one cell of the timeline column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    Up,      // Running for the whole cell
+    Down,    // Failing for the whole cell
+    Partial, // Both running and failing during the cell
+    Unknown, // Not watched yet
+}
+
+/// A failed fetch kept in `App::recent_errors`. The same error repeated in a row is
+/// kept once, with the number of times it happened.
+#[derive(Debug, Clone)]
+pub struct RecentError {
+    pub message: String,
+    pub last_seen: DateTime<Local>,
+    pub count: u32,
+}
+
+/// How serious a status bar message is, sets its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message shown in the bottom bar for a few seconds, an info one until the next key
+/// press at most, and kept in `App::status_history` afterwards.
+#[derive(Debug, Clone)]
+pub struct StatusMessage {
+    pub text: String,
+    pub severity: Severity,
+    pub at: DateTime<Local>,
+}
+
+/// Which screen/popup currently receives the keyboard input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UiMode {
+    #[default]
+    Normal,
+    Detail,     // Detail popup for the selected node
+    Help,       // Keybindings and legend overlay
+    Errors,     // Error category breakdown for the selected node
+    Goto,       // `:` prompt to jump to a node by name prefix or row number
+    Actions,    // Action menu for the selected node (`Space`)
+    Messages,   // Status message history (`M`)
+    RawMetrics, // Raw Prometheus text of the selected node (`r` in the detail popup)
+    Confirm,    // Yes / no prompt before `pending_unit_action` runs
+}
+
+/// Actions on the selected node. Each one has its own key, the ones available for
+/// the node are also listed in the action menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeAction {
+    ShowDetails,
+    ShowErrors,
+    OpenLog,
+    OpenMetrics,
+    CopyUrl,
+    CopyPath,
+    StartUnit, // systemd actions, only with `--systemd` or `[systemd]`
+    StopUnit,
+    RestartUnit,
+}
+
+impl NodeAction {
+    /// Every action, in menu order.
+    pub const ALL: [NodeAction; 9] = [
+        NodeAction::ShowDetails,
+        NodeAction::ShowErrors,
+        NodeAction::OpenLog,
+        NodeAction::OpenMetrics,
+        NodeAction::CopyUrl,
+        NodeAction::CopyPath,
+        NodeAction::StartUnit,
+        NodeAction::StopUnit,
+        NodeAction::RestartUnit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NodeAction::ShowDetails => "Show details",
+            NodeAction::ShowErrors => "Show errors by category",
+            NodeAction::OpenLog => "Open the log in $PAGER",
+            NodeAction::OpenMetrics => "Open the raw metrics in $PAGER",
+            NodeAction::CopyUrl => "Copy the metrics URL",
+            NodeAction::CopyPath => "Copy the directory path",
+            NodeAction::StartUnit => "Start the systemd unit",
+            NodeAction::StopUnit => "Stop the systemd unit",
+            NodeAction::RestartUnit => "Restart the systemd unit",
+        }
+    }
+
+    /// What the systemd actions ask systemctl to do.
+    pub fn unit_command(self) -> Option<UnitCommand> {
+        match self {
+            NodeAction::StartUnit => Some(UnitCommand::Start),
+            NodeAction::StopUnit => Some(UnitCommand::Stop),
+            NodeAction::RestartUnit => Some(UnitCommand::Restart),
+            _ => None,
+        }
+    }
+}
+
+/// Which nodes the list shows, cycled with `n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListViewFilter {
+    #[default]
+    All,
+    Running,
+    Stopped,
+    Error,
+    Shunned, // Running nodes that were shunned by other peers
+}
+
+impl ListViewFilter {
+    pub fn next(self) -> ListViewFilter {
+        match self {
+            ListViewFilter::All => ListViewFilter::Running,
+            ListViewFilter::Running => ListViewFilter::Stopped,
+            ListViewFilter::Stopped => ListViewFilter::Error,
+            ListViewFilter::Error => ListViewFilter::Shunned,
+            ListViewFilter::Shunned => ListViewFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ListViewFilter::All => "All",
+            ListViewFilter::Running => "Running",
+            ListViewFilter::Stopped => "Stopped",
+            ListViewFilter::Error => "Error",
+            ListViewFilter::Shunned => "Shunned",
+        }
+    }
+}
+
+/// Colors of the node statuses, set with `--color-running`, `--color-stopped` and
+/// `--color-error` for terminals or eyes where the default green and red don't work.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorConfig {
+    pub running: Color,
+    pub stopped: Color,
+    pub error: Color,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig {
+            running: Color::Green,
+            stopped: Color::DarkGray,
+            error: Color::Red,
+        }
+    }
+}
+
+impl ColorConfig {
+    /// Colors from the settings, already checked when they were resolved.
+    fn from_settings(settings: &Settings) -> ColorConfig {
+        let default = ColorConfig::default();
+        let parse = |color: &Option<String>, fallback: Color| {
+            color
+                .as_deref()
+                .and_then(|color| color.parse().ok())
+                .unwrap_or(fallback)
+        };
+        ColorConfig {
+            running: parse(&settings.color_running, default.running),
+            stopped: parse(&settings.color_stopped, default.stopped),
+            error: parse(&settings.color_error, default.error),
+        }
+    }
+
+    /// Color of a node status. Initializing nodes are always yellow.
+    pub fn status_color(&self, status: NodeStatus) -> Color {
+        match status {
+            NodeStatus::Running => self.running,
+            NodeStatus::Initializing => Color::Yellow,
+            NodeStatus::Error => self.error,
+            NodeStatus::Stopped => self.stopped,
+        }
+    }
+}
+
+/// Raw counter values captured at one fetch, used for windowed rate calculations.
+#[derive(Debug, Clone, Copy)]
+pub struct CounterSample {
+    pub at: Instant,
+    pub bytes_in: Option<u64>,
+    pub bytes_out: Option<u64>,
+    pub put_record_errors: Option<u64>,
+    pub incoming_connection_errors: Option<u64>,
+    pub outgoing_connection_errors: Option<u64>,
+    pub kad_get_closest_peers_errors: Option<u64>,
+    pub connected_peers: Option<u64>, // A gauge, see `windowed_change_rate`
+}
+
+/// Error counters of a node when they were zeroed with `z`. The displayed counts
+/// start from them, the metrics keep their lifetime values.
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorBaseline {
+    pub put_record_errors: u64,
+    pub incoming_connection_errors: u64,
+    pub outgoing_connection_errors: u64,
+    pub kad_get_closest_peers_errors: u64,
+}
+
+impl ErrorBaseline {
+    pub fn from_metrics(metrics: &NodeMetrics) -> ErrorBaseline {
+        ErrorBaseline {
+            put_record_errors: metrics.put_record_errors.unwrap_or(0),
+            incoming_connection_errors: metrics.incoming_connection_errors.unwrap_or(0),
+            outgoing_connection_errors: metrics.outgoing_connection_errors.unwrap_or(0),
+            kad_get_closest_peers_errors: metrics.kad_get_closest_peers_errors.unwrap_or(0),
+        }
+    }
+
+    /// Errors counted since the baseline. A counter below its baseline means the node
+    /// restarted, all of it is new then.
+    pub fn errors_since(&self, metrics: &NodeMetrics) -> u64 {
+        let since = |current: Option<u64>, baseline: u64| {
+            let current = current.unwrap_or(0);
+            current.checked_sub(baseline).unwrap_or(current)
+        };
+        since(metrics.put_record_errors, self.put_record_errors)
+            + since(
+                metrics.incoming_connection_errors,
+                self.incoming_connection_errors,
+            )
+            + since(
+                metrics.outgoing_connection_errors,
+                self.outgoing_connection_errors,
+            )
+            + since(
+                metrics.kad_get_closest_peers_errors,
+                self.kad_get_closest_peers_errors,
+            )
+    }
+}
+
+/// Sums over the marked nodes, for the selection footer. Only nodes answering with
+/// metrics count towards the values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkedTotals {
+    pub marked: usize,
+    pub answering: usize,
+    pub cpu: f64,
+    pub memory_mb: f64,
+    pub speed_in_bps: f64,
+    pub speed_out_bps: f64,
+    pub data_in_bytes: u64, // Per the totals mode, like the Rx/Tx columns
+    pub data_out_bytes: u64,
+    pub records: u64,
+}
+
+/// Current totals of a group of nodes, see `App::node_group`. Only nodes answering
+/// with metrics count towards the values.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupSummary {
+    pub nodes: usize,
+    pub running: usize,
+    pub errors: usize,
+    pub speed_in_bps: f64,
+    pub speed_out_bps: f64,
+    pub records: u64,
+    pub rewards: u64,
+}
+
 /// Holds the application state.
 pub struct App {
     // --- Core Node Data ---
     pub nodes: Vec<String>, // Stores discovered node *directory paths*
     pub node_urls: HashMap<String, String>, // Maps node directory path to metrics URL
+    // Where each node's metrics URL was found, keyed by node directory path
+    pub node_discovery_sources: HashMap<String, DiscoverySource>,
+    // Log files the discovery skipped for their size and already warned about
+    pub oversized_logs: Vec<PathBuf>,
     // Store parsed metrics or error string, keyed by *metrics URL*
     pub node_metrics: HashMap<String, Result<NodeMetrics, String>>,
+    // Category of the last failed fetch, keyed by *metrics URL*, see `FetchErrorKind`.
+    // Errors not coming from a fetch (a URL just discovered) have none.
+    pub fetch_error_kinds: HashMap<String, FetchErrorKind>,
+    // Prometheus text of the last successful fetch, keyed by *metrics URL*
+    raw_metrics_cache: HashMap<String, String>,
     // Map node directory path to its RECORD STORE path
     pub node_record_store_paths: HashMap<String, PathBuf>,
+    // Stable short ID (#001, #002...) per node directory path
+    pub node_short_ids: HashMap<String, u16>,
 
     // --- Metrics History & Calculation ---
     pub previous_metrics: HashMap<String, NodeMetrics>, // Keyed by metrics URL
     pub last_update: Instant,
-    pub previous_update_time: Instant, // Store the time of the previous update
-    pub speed_in_history: HashMap<String, VecDeque<u64>>, // Keyed by metrics URL
-    pub speed_out_history: HashMap<String, VecDeque<u64>>, // Keyed by metrics URL
+    // Speeds keyed by metrics URL, None where the fetch failed (see `GapFill`)
+    pub speed_in_history: HashMap<String, VecDeque<Option<u64>>>,
+    pub speed_out_history: HashMap<String, VecDeque<Option<u64>>>,
+    pub gap_fill: GapFill,
+    pub errors_history: HashMap<String, VecDeque<u64>>, // Total error count, keyed by metrics URL
+    pub cpu_history: HashMap<String, VecDeque<f64>>,    // CPU %, keyed by metrics URL
+    // Reported CPU % of the last `cpu_avg_window` fetches, keyed by metrics URL
+    pub cpu_raw_history: HashMap<String, VecDeque<f64>>,
+    pub cpu_avg_window: usize, // Samples averaged into `NodeMetrics::cpu_smoothed_percent`
+    pub memory_history: HashMap<String, VecDeque<u64>>, // Memory MB, keyed by metrics URL
+    pub memory_sampled_at: HashMap<String, Instant>, // Last memory sample, keyed by metrics URL
+    pub last_successful_fetch: HashMap<String, Instant>, // Last answer, keyed by metrics URL
+    pub memory_leak_threshold: f64, // MB per minute of steady growth flagged as a possible leak
+    pub peers_history: HashMap<String, VecDeque<u64>>, // Live peers, keyed by metrics URL
+    // Live peers gained (lost when negative) per second at each update, keyed by metrics URL
+    pub peers_rate_history: HashMap<String, VecDeque<f64>>,
+    // Number of data points kept per history type
+    pub bw_history_length: usize,
+    pub cpu_history_length: usize,
+    pub peers_history_length: usize,
+    pub counter_samples: HashMap<String, VecDeque<CounterSample>>, // Keyed by metrics URL
+    pub rate_window: usize, // Number of intervals speeds are averaged over
+    // First bandwidth counters seen for each node, used as session offsets. Keyed by metrics URL
+    pub initial_bandwidth_in: HashMap<String, u64>,
+    pub initial_bandwidth_out: HashMap<String, u64>,
+    // Error counters when they were last zeroed with `z`, keyed by metrics URL
+    pub error_baseline: HashMap<String, ErrorBaseline>,
+    pub errors_zeroed_at: Option<Instant>,
+    // Consecutive updates that returned the exact same metrics, keyed by metrics URL
+    pub metrics_change_count: HashMap<String, u32>,
+    pub stale_threshold: u32, // Identical updates after which a running node shows as stale
 
     // --- Calculated Totals & Summaries ---
     pub total_speed_in_history: VecDeque<u64>,
     pub total_speed_out_history: VecDeque<u64>,
     pub total_cpu_usage: f64,
+    pub total_memory_used_mb: f64, // Summed over the nodes reporting it
+    pub system_memory_bytes: Option<u64>, // RAM of this machine, None where unknown
     pub total_allocated_storage: u64,
     pub total_used_storage_bytes: Option<u64>,
+    pub storage_gauge: StorageGauge,
+    pub filesystem_usage: Option<FilesystemUsage>, // Only with `StorageGauge::Filesystem`
     pub summary_total_in_speed: f64,
     pub summary_total_out_speed: f64,
     pub summary_total_data_in_bytes: u64,
     pub summary_total_data_out_bytes: u64,
+    pub summary_session_bandwidth_in_bytes: u64,
+    pub summary_session_bandwidth_out_bytes: u64,
     pub summary_total_records: u64,
     pub summary_total_rewards: u64,
     pub summary_total_live_peers: u64,
+    pub summary_stale_count: usize,
+    pub summary_relay_node_count: usize, // Answering nodes that relay for other peers
+    pub summary_max_node_speed: f64,     // Rx + Tx of the busiest node, the full traffic bar
+    // Fleet records and rewards totals per update, oldest first, for the growth charts
+    pub records_history: VecDeque<u64>,
+    pub rewards_history: VecDeque<u64>,
+    // Last records and rewards seen per metrics URL, held while a node is down so
+    // the growth charts don't dip with every failed fetch
+    last_records_rewards: HashMap<String, (u64, u64)>,
+    // Last fetch errors per metrics URL, oldest first, kept after the node recovers
+    pub recent_errors: HashMap<String, VecDeque<RecentError>>,
+    // Ended `[hooks]` runs per node directory path, oldest first
+    pub hook_log: HashMap<String, VecDeque<HookOutcome>>,
+    // Fleet outliers keyed by node directory path, refreshed every update
+    pub anomalies: HashMap<String, Vec<AnomalyType>>,
+    // Nodes per live peers bucket, see `PEERS_HISTOGRAM_BUCKETS`
+    pub peers_histogram: [usize; PEERS_HISTOGRAM_BUCKETS.len()],
+
+    // --- Background Fetch ---
+    pub fetch_in_progress: bool,     // A metrics sweep is in flight
+    pub fetch_done: usize,           // Nodes answered so far in the current sweep
+    pub fetch_total: usize,          // Nodes queried in the current sweep
+    pub last_fetch_started: Instant, // Sweeps start every `tick_rate` from here
+    pub max_concurrent_fetches: Option<usize>, // Nodes fetched at once, None = all
+    pub max_redirects: usize,        // Followed per fetch, 0 = none
 
     // --- UI State & Config ---
-    pub status_message: Option<String>,
+    pub status_message: Option<StatusMessage>,
+    pub status_message_expiry: Option<Instant>, // When the status message is cleared
+    pub status_history: VecDeque<StatusMessage>, // Newest last, bounded to STATUS_HISTORY_LENGTH
+    pub last_statuses: HashMap<String, NodeStatus>, // Status at the previous update, keyed by dir path
+    pub last_stale: HashSet<String>, // Running nodes that were stale at the previous update
+    pub row_flash_until: HashMap<String, Instant>, // Rows of nodes that just went down, keyed by dir path
+    pub bell: bool,                                // Ring the terminal bell when a node goes down
+    pub bell_pending: bool,                        // A node went down since the bell last rang
+    pub pause_on_focus_loss: bool, // No sweeps while the terminal reports it lost focus
+    pub unfocused: bool,           // The terminal reported a focus loss, and no focus gain since
+    // Reward gains waiting to be shown in the status bar, as (node name, amount)
+    pub reward_notifications: VecDeque<(String, u64)>,
+    // Reward gain currently shown, with the update it is shown for
+    pub current_reward_notification: Option<(String, u64, Instant)>,
     pub scroll_offset: usize, // Track the scroll position for the node list
+    pub selected: usize,      // Row of the selected node in `visible_nodes()`
+    selected_dir: Option<String>, // The selected node itself, followed when rows move
+    sorted_order: Vec<String>, // Every node in display order, see `refresh_node_order`
+    visible_order: Vec<String>, // `sorted_order` through the list view filter
+    pub marked_nodes: HashSet<String>, // Nodes marked with `*`, keyed by dir path
+    pub pinned_nodes: HashSet<String>, // Nodes pinned to the top with `p`, keyed by dir path
+    pub list_view_filter: ListViewFilter,
+    pub sort_key: SortKey,              // Primary sort key, cycled with `s`
+    pub sort_secondary: SortKey,        // Breaks the ties of the primary key
+    pub goto_input: String,             // Text typed in the `:` goto prompt
+    pub action_menu_selected: usize,    // Highlighted entry of the action menu
+    pub raw_metrics_scroll: (u16, u16), // (rows, columns) scrolled in the raw metrics popup
+    pub ui_mode: UiMode,
+    pub show_short_ids: bool, // Show `#001` instead of the directory name in the Node column
+    pub show_full_paths: bool, // Show the whole directory path in the Node column, toggled with `P`
+    pub name_regex: Option<Regex>, // Node names taken from the directory paths, see `node_name`
+    pub color_config: ColorConfig, // Status colors
     pub tick_rate: Duration,  // Current update interval
+    pub view_mode: ViewMode,
+    pub totals_mode: TotalsMode,
+    pub show_fleet_panel: bool, // Fleet-wide distributions between the summary and the table
+    pub show_timeline: bool,    // Availability timeline column on the right of the table
+    pub show_traffic_bars: bool, // Rx + Tx speed bars left of Status, toggled with `b`
+    pub group_by_prefix: bool,  // Summary line per node group above the table
+    // Nodes of each group by group name, see `regroup_nodes`. Empty without grouping
+    pub group_members: BTreeMap<String, Vec<String>>,
+    // Summary of each group with the update it was computed at, keyed by group name.
+    // Only the groups of the nodes fetched by an update are computed again
+    pub group_cache: HashMap<String, (GroupSummary, Instant)>,
+    // Up (true) / down (false) changes with their time, keyed by dir path, oldest first
+    pub availability_changes: HashMap<String, VecDeque<(Instant, bool)>>,
+    pub notation: Notation,
+    pub size_units: SizeUnits,
+    pub title: String, // Header title, see `--title`
+    pub chart_marker: ChartMarker,
+    pub swap_rx_tx_colors: bool, // Rx in magenta and Tx in cyan, for who can't tell them apart
+    pub state_file: Option<PathBuf>, // Where the history is saved for crash recovery
+    pub log_tail: Option<LogTail>, // Log tail pane of the selected node, when open
+    pub log_pane_height: u16,    // Rows taken from the node table by the log tail pane
+    pub offline_source: Option<String>, // Snapshot file shown instead of live nodes, see `--json-input`
+    pub replay: Option<Replay>,         // History database stepped through, see `--replay`
+    pub history_playback: Option<HistoryPlayback>, // State file replayed by `antop history`
+    pub status_server: Option<StatusServer>, // JSON status endpoint of `--serve`
+    pub alert_emailer: Option<AlertEmailer>, // Alert emails of `--alert-email`
+    pub webhook: Option<WebhookNotifier>, // Webhook posts of the `[notifications]` config
+    pub hooks: Option<HookRunner>,      // Commands of the `[hooks]` config
+    pub systemd: Option<SystemdConfig>, // Units of the nodes, see `--systemd`
+    // ActiveState of the units of the nodes that didn't answer at the last query, keyed by dir path
+    pub unit_states: HashMap<String, String>,
+    // Command and node directory waiting for a yes in the `Confirm` prompt
+    pub pending_unit_action: Option<(UnitCommand, String)>,
+    // Process of the nodes that didn't answer at the last sample, keyed by dir path
+    pub node_processes: HashMap<String, ProcessStats>,
+    // Alerts raised at the previous update, keyed by dir path, see `alert_key`
+    last_alert_keys: HashMap<String, HashSet<String>>,
 }
 
 impl App {
     /// Creates a new App instance.
     /// `discovered_node_dirs`: List of full directory paths found matching the path glob.
-    /// `initial_node_urls`: List of (directory_path, metrics_url) found initially from logs.
-    /// `_node_path_glob_str`: Original glob pattern string (currently unused here but kept for potential future use).
+    /// `initial_nodes`: Nodes whose metrics URL was found initially, with where it was found.
+    /// `settings`: Effective settings merged from the CLI, the config profile and the defaults.
     pub fn new(
         mut discovered_node_dirs: Vec<String>,
-        initial_node_urls: Vec<(String, String)>,
-        _node_path_glob_str: String, // Keep param for signature consistency
+        initial_nodes: Vec<DiscoveredNode>,
+        settings: &Settings,
     ) -> App {
         // Use the custom comparison function for sorting
         discovered_node_dirs.sort_by(|a, b| compare_node_dirs(a, b));
@@ -87,10 +771,12 @@ impl App {
         let speed_out_history = HashMap::new();
 
         // Populate maps based on initially discovered URLs
-        for (dir_path, url) in &initial_node_urls {
-            node_urls_map.insert(dir_path.clone(), url.clone());
+        let mut node_discovery_sources = HashMap::new();
+        for node in initial_nodes {
+            node_urls_map.insert(node.dir_path.clone(), node.url.clone());
             // Initialize metrics status for nodes with URLs
-            metrics_map.insert(url.clone(), Err("Fetching...".to_string()));
+            metrics_map.insert(node.url, Err("Fetching...".to_string()));
+            node_discovery_sources.insert(node.dir_path, node.source);
         }
 
         // Discover record store paths based on ALL discovered directories
@@ -110,191 +796,771 @@ impl App {
             }
         }
 
-        App {
+        let node_short_ids = assign_node_ids(&discovered_node_dirs);
+
+        let mut app = App {
             nodes: discovered_node_dirs, // Store the naturally sorted list
             node_urls: node_urls_map,    // Store mapping for nodes with found URLs
-            node_metrics: metrics_map,   // Initialize metrics only for those with URLs
+            node_discovery_sources,
+            oversized_logs: Vec::new(),
+            node_metrics: metrics_map, // Initialize metrics only for those with URLs
+            fetch_error_kinds: HashMap::new(),
+            raw_metrics_cache: HashMap::new(),
             previous_metrics: HashMap::new(),
             last_update: now,
             speed_in_history,
             speed_out_history,
-            previous_update_time: now,
-            total_speed_in_history: VecDeque::with_capacity(SPARKLINE_HISTORY_LENGTH),
-            total_speed_out_history: VecDeque::with_capacity(SPARKLINE_HISTORY_LENGTH),
+            gap_fill: settings.gap_fill,
+            errors_history: HashMap::new(),
+            cpu_history: HashMap::new(),
+            cpu_raw_history: HashMap::new(),
+            cpu_avg_window: settings.cpu_avg_window,
+            memory_history: HashMap::new(),
+            memory_sampled_at: HashMap::new(),
+            last_successful_fetch: HashMap::new(),
+            memory_leak_threshold: settings.memory_leak_threshold,
+            metrics_change_count: HashMap::new(),
+            stale_threshold: settings.stale_threshold,
+            peers_history: HashMap::new(),
+            peers_rate_history: HashMap::new(),
+            bw_history_length: settings.bw_history_length,
+            cpu_history_length: settings.cpu_history_length,
+            peers_history_length: settings.peers_history_length,
+            counter_samples: HashMap::new(),
+            rate_window: settings.rate_window,
+            initial_bandwidth_in: HashMap::new(),
+            initial_bandwidth_out: HashMap::new(),
+            error_baseline: HashMap::new(),
+            errors_zeroed_at: None,
+            total_speed_in_history: VecDeque::with_capacity(settings.bw_history_length),
+            total_speed_out_history: VecDeque::with_capacity(settings.bw_history_length),
             total_cpu_usage: 0.0,
+            total_memory_used_mb: 0.0,
+            system_memory_bytes: system_memory_bytes(),
             // Calculate allocated storage based on nodes *with record stores*
             total_allocated_storage: node_record_store_paths.len() as u64 * STORAGE_PER_NODE_BYTES,
             total_used_storage_bytes: None, // Initialize as None, calculated in update_metrics
+            storage_gauge: settings.storage_gauge,
+            filesystem_usage: None,
             summary_total_in_speed: 0.0,
             summary_total_out_speed: 0.0,
             summary_total_data_in_bytes: 0,
             summary_total_data_out_bytes: 0,
+            summary_session_bandwidth_in_bytes: 0,
+            summary_session_bandwidth_out_bytes: 0,
             summary_total_records: 0,
             summary_total_rewards: 0,
             summary_total_live_peers: 0,
+            summary_stale_count: 0,
+            summary_relay_node_count: 0,
+            summary_max_node_speed: 0.0,
+            records_history: VecDeque::with_capacity(GROWTH_HISTORY_LENGTH),
+            rewards_history: VecDeque::with_capacity(GROWTH_HISTORY_LENGTH),
+            last_records_rewards: HashMap::new(),
+            peers_histogram: [0; PEERS_HISTOGRAM_BUCKETS.len()],
+            recent_errors: HashMap::new(),
+            hook_log: HashMap::new(),
+            anomalies: HashMap::new(),
             node_record_store_paths, // Use the map populated above
+            node_short_ids,
+            fetch_in_progress: false,
+            fetch_done: 0,
+            fetch_total: 0,
+            last_fetch_started: now,
+            max_concurrent_fetches: settings.max_concurrent_fetches,
+            max_redirects: settings.max_redirects,
             status_message: None,
+            status_message_expiry: None,
+            status_history: VecDeque::new(),
+            last_statuses: HashMap::new(),
+            last_stale: HashSet::new(),
+            row_flash_until: HashMap::new(),
+            bell: settings.bell,
+            pause_on_focus_loss: settings.pause_on_focus_loss,
+            unfocused: false,
+            bell_pending: false,
+            reward_notifications: VecDeque::new(),
+            current_reward_notification: None,
             scroll_offset: 0,
-            tick_rate: TICK_LEVELS[3], // Default tick rate (1 second)
+            selected: 0,
+            selected_dir: None,
+            sorted_order: Vec::new(),
+            visible_order: Vec::new(),
+            marked_nodes: HashSet::new(),
+            pinned_nodes: HashSet::new(),
+            list_view_filter: ListViewFilter::default(),
+            sort_key: if settings.tail_mode {
+                SortKey::TailMode
+            } else {
+                SortKey::default()
+            },
+            sort_secondary: settings.sort_secondary,
+            goto_input: String::new(),
+            action_menu_selected: 0,
+            raw_metrics_scroll: (0, 0),
+            ui_mode: UiMode::default(),
+            show_short_ids: settings.short_ids,
+            show_full_paths: false,
+            // Already checked when the settings were resolved
+            name_regex: settings
+                .name_regex
+                .as_deref()
+                .and_then(|pattern| Regex::new(pattern).ok()),
+            color_config: ColorConfig::from_settings(settings),
+            tick_rate: Duration::from_millis(settings.interval_ms), // Default is 1 second
+            view_mode: ViewMode::default(),
+            totals_mode: settings.totals,
+            show_fleet_panel: false,
+            show_timeline: settings.timeline,
+            show_traffic_bars: false,
+            group_by_prefix: settings.group_by_prefix,
+            group_members: BTreeMap::new(),
+            group_cache: HashMap::new(),
+            availability_changes: HashMap::new(),
+            notation: settings.notation,
+            size_units: settings.units,
+            title: expand_title(&settings.title),
+            chart_marker: settings.chart_marker,
+            swap_rx_tx_colors: settings.swap_rx_tx_colors,
+            state_file: settings.state_file.as_ref().map(PathBuf::from),
+            log_tail: None,
+            log_pane_height: settings.log_pane_height,
+            offline_source: None,
+            replay: None,
+            history_playback: None,
+            status_server: None,
+            alert_emailer: AlertEmailer::from_settings(settings),
+            webhook: None,
+            hooks: HookRunner::new(settings.hooks.as_ref()),
+            systemd: settings.systemd.clone(),
+            unit_states: HashMap::new(),
+            pending_unit_action: None,
+            node_processes: HashMap::new(),
+            last_alert_keys: HashMap::new(),
+        };
+        app.regroup_nodes();
+        app.refresh_node_order();
+        app
+    }
+
+    /// Creates an App showing the nodes of a JSON snapshot read from `path` instead of
+    /// live ones (`--json-input`). Their metrics are set once, nothing is fetched.
+    pub fn from_snapshot(nodes: Vec<NodeSnapshot>, path: &str, settings: &Settings) -> App {
+        let dirs = nodes.iter().map(|node| node.dir_path.clone()).collect();
+        let discovered = nodes
+            .iter()
+            .filter_map(|node| {
+                Some(DiscoveredNode {
+                    dir_path: node.dir_path.clone(),
+                    url: node.url.clone()?,
+                    source: DiscoverySource::JsonInput(PathBuf::from(path)),
+                })
+            })
+            .collect();
+        let mut app = App::new(dirs, discovered, settings);
+        for node in nodes {
+            if let Some(url) = node.url {
+                let result = match node.error {
+                    Some(error) => Err(error),
+                    None => Ok(node.metrics),
+                };
+                app.node_metrics.insert(url, result);
+            }
         }
+        app.update_totals();
+        app.regroup_nodes();
+        app.refresh_node_order();
+        app.offline_source = Some(
+            Path::new(path)
+                .file_name()
+                .map_or(path.to_string(), |name| name.to_string_lossy().into_owned()),
+        );
+        app
+    }
+
+    /// Steps `--replay` `delta` recorded updates forward (or back) and shows the nodes
+    /// as they were then. Nodes missing from that update show as not recorded.
+    pub fn step_replay(&mut self, delta: isize) -> Result<(), anyhow::Error> {
+        let Some(replay) = &mut self.replay else {
+            return Ok(());
+        };
+        if !replay.step(delta) && delta != 0 {
+            return Ok(());
+        }
+        let nodes = replay.snapshot()?;
+        for url in self.node_urls.values() {
+            self.node_metrics
+                .insert(url.clone(), Err("Not recorded at this time".to_string()));
+        }
+        for node in nodes {
+            if let Some(url) = self.node_urls.get(&node.dir_path) {
+                let result = match node.error {
+                    Some(error) => Err(error),
+                    None => Ok(node.metrics),
+                };
+                self.node_metrics.insert(url.clone(), result);
+            }
+        }
+        self.update_totals();
+        self.regroup_nodes(); // All the metrics were replaced
+        self.refresh_node_order();
+        Ok(())
+    }
+
+    /// Serializes the sparkline histories and the availability timelines so they survive
+    /// a crash or restart.
+    pub fn serialize_state(&self) -> Result<Vec<u8>, bincode::Error> {
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        let availability_changes = self
+            .availability_changes
+            .iter()
+            .map(|(dir_path, changes)| {
+                let changes = changes
+                    .iter()
+                    .map(|(changed_at, up)| (unix_millis(*changed_at, now, wall_now), *up))
+                    .collect();
+                (dir_path.clone(), changes)
+            })
+            .collect();
+        bincode::serialize(&PersistedState {
+            speed_in_history: self.speed_in_history.clone(),
+            speed_out_history: self.speed_out_history.clone(),
+            errors_history: self.errors_history.clone(),
+            cpu_history: self.cpu_history.clone(),
+            memory_history: self.memory_history.clone(),
+            peers_history: self.peers_history.clone(),
+            total_speed_in_history: self.total_speed_in_history.clone(),
+            total_speed_out_history: self.total_speed_out_history.clone(),
+            availability_changes,
+        })
+    }
+
+    /// Creates a new App like `App::new`, with the histories restored from `data`
+    /// (as produced by `serialize_state`). Histories of nodes that are no longer
+    /// discovered are dropped, and all histories are trimmed to the current lengths.
+    /// The timelines keep the changes still in the timeline span.
+    pub fn deserialize_state(
+        data: &[u8],
+        nodes: Vec<String>,
+        node_urls: Vec<DiscoveredNode>,
+        settings: &Settings,
+    ) -> Result<App, bincode::Error> {
+        let state: PersistedState = bincode::deserialize(data)?;
+        let mut app = App::new(nodes, node_urls, settings);
+
+        let known_urls: HashSet<String> = app.node_urls.values().cloned().collect();
+        app.speed_in_history =
+            restore_histories(state.speed_in_history, &known_urls, app.bw_history_length);
+        app.speed_out_history =
+            restore_histories(state.speed_out_history, &known_urls, app.bw_history_length);
+        app.errors_history =
+            restore_histories(state.errors_history, &known_urls, SPARKLINE_HISTORY_LENGTH);
+        app.cpu_history = restore_histories(state.cpu_history, &known_urls, app.cpu_history_length);
+        app.memory_history =
+            restore_histories(state.memory_history, &known_urls, MEMORY_HISTORY_LENGTH);
+        app.peers_history =
+            restore_histories(state.peers_history, &known_urls, app.peers_history_length);
+        app.total_speed_in_history = state.total_speed_in_history;
+        app.total_speed_out_history = state.total_speed_out_history;
+        trim_front(&mut app.total_speed_in_history, app.bw_history_length);
+        trim_front(&mut app.total_speed_out_history, app.bw_history_length);
+
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        let timeline_span = app.timeline_span();
+        for (dir_path, saved) in state.availability_changes {
+            if !app.nodes.contains(&dir_path) {
+                continue;
+            }
+            let mut changes = VecDeque::new();
+            for (millis, up) in saved {
+                if let Some(changed_at) = instant_at(millis, now, wall_now) {
+                    record_availability(&mut changes, up, changed_at, timeline_span);
+                }
+            }
+            if let Some(window_start) = now.checked_sub(timeline_span) {
+                while changes.len() > 1 && changes[1].0 <= window_start {
+                    changes.pop_front();
+                }
+            }
+            if !changes.is_empty() {
+                app.availability_changes.insert(dir_path, changes);
+            }
+        }
+        Ok(app)
+    }
+
+    /// Writes the serialized state to `state_file`, if one is configured. The file is
+    /// replaced atomically so a crash while saving keeps the previous state.
+    pub fn save_state(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.state_file else {
+            return Ok(());
+        };
+        let data = self.serialize_state()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
     }
 
     /// Updates metrics, calculates speeds, totals, and used storage.
-    /// Takes results from fetch_metrics: Vec<(address, Result<raw_data, error_string>)>
-    pub fn update_metrics(&mut self, results: Vec<(String, Result<String, String>)>) {
+    /// Takes the results of a `fetch_metrics_in_background` sweep.
+    pub fn update_metrics(&mut self, results: Vec<FetchResult>) {
         let update_start_time = Instant::now();
-        let delta_time = update_start_time
-            .duration_since(self.previous_update_time)
-            .as_secs_f64();
 
         let mut new_metrics_map = HashMap::new();
         let mut next_previous_metrics = HashMap::new();
+        // Metrics URL -> node directory path, to name the nodes that earned rewards
+        let url_to_dir: HashMap<String, String> = self
+            .node_urls
+            .iter()
+            .map(|(dir, url)| (url.clone(), dir.clone()))
+            .collect();
+        // Nodes of this update, their groups are summarized again
+        let mut changed_urls: HashSet<String> = results.iter().map(|r| r.url.clone()).collect();
 
-        for (addr, result) in results {
+        for FetchResult {
+            url: addr,
+            body: result,
+        } in results
+        {
+            // A body that isn't Prometheus text counts as a failed fetch, not a blank node
+            let result = result.and_then(|raw_data| {
+                check_prometheus_format(&raw_data)
+                    .map(|()| raw_data)
+                    .map_err(|message| FetchError::new(FetchErrorKind::Body, message))
+            });
+            let bw_history_length = self.bw_history_length;
             let history_in = self
                 .speed_in_history
                 .entry(addr.clone())
-                .or_insert_with(|| VecDeque::with_capacity(SPARKLINE_HISTORY_LENGTH));
+                .or_insert_with(|| VecDeque::with_capacity(bw_history_length));
             let history_out = self
                 .speed_out_history
                 .entry(addr.clone())
-                .or_insert_with(|| VecDeque::with_capacity(SPARKLINE_HISTORY_LENGTH));
+                .or_insert_with(|| VecDeque::with_capacity(bw_history_length));
 
             match result {
                 Ok(raw_data) => {
                     let mut current_metrics = parse_metrics(&raw_data);
+                    self.raw_metrics_cache.insert(addr.clone(), raw_data);
+                    self.fetch_error_kinds.remove(&addr);
+                    self.last_successful_fetch
+                        .insert(addr.clone(), update_start_time);
 
-                    if let Some(prev_metrics) = self.previous_metrics.get(&addr) {
-                        if delta_time > 0.0 {
-                            if let (Some(current_in), Some(prev_in)) = (
-                                current_metrics.bandwidth_inbound_bytes,
-                                prev_metrics.bandwidth_inbound_bytes,
-                            ) {
-                                if current_in >= prev_in {
-                                    let delta_bytes = current_in - prev_in;
-                                    current_metrics.speed_in_bps =
-                                        Some(delta_bytes as f64 / delta_time);
-                                } else {
-                                    current_metrics.speed_in_bps = Some(0.0);
-                                }
-                            }
-
-                            if let (Some(current_out), Some(prev_out)) = (
-                                current_metrics.bandwidth_outbound_bytes,
-                                prev_metrics.bandwidth_outbound_bytes,
-                            ) {
-                                if current_out >= prev_out {
-                                    let delta_bytes = current_out - prev_out;
-                                    current_metrics.speed_out_bps =
-                                        Some(delta_bytes as f64 / delta_time);
-                                } else {
-                                    current_metrics.speed_out_bps = Some(0.0);
-                                }
-                            }
+                    // Remember the first counters seen as session offsets. A counter
+                    // going backwards means the node restarted, count from zero again.
+                    if let Some(current_in) = current_metrics.bandwidth_inbound_bytes {
+                        let initial_in = self
+                            .initial_bandwidth_in
+                            .entry(addr.clone())
+                            .or_insert(current_in);
+                        if current_in < *initial_in {
+                            *initial_in = 0;
                         }
                     }
+                    if let Some(current_out) = current_metrics.bandwidth_outbound_bytes {
+                        let initial_out = self
+                            .initial_bandwidth_out
+                            .entry(addr.clone())
+                            .or_insert(current_out);
+                        if current_out < *initial_out {
+                            *initial_out = 0;
+                        }
+                    }
+
+                    // Keep the raw counters of the last `rate_window` intervals and
+                    // compute speeds across the whole window to smooth out tick jitter
+                    let samples = self
+                        .counter_samples
+                        .entry(addr.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(self.rate_window + 1));
+                    samples.push_back(CounterSample {
+                        at: update_start_time,
+                        bytes_in: current_metrics.bandwidth_inbound_bytes,
+                        bytes_out: current_metrics.bandwidth_outbound_bytes,
+                        put_record_errors: current_metrics.put_record_errors,
+                        incoming_connection_errors: current_metrics.incoming_connection_errors,
+                        outgoing_connection_errors: current_metrics.outgoing_connection_errors,
+                        kad_get_closest_peers_errors: current_metrics.kad_get_closest_peers_errors,
+                        connected_peers: current_metrics.connected_peers,
+                    });
+                    while samples.len() > self.rate_window + 1 {
+                        samples.pop_front();
+                    }
+                    current_metrics.speed_in_bps = windowed_rate(samples, |s| s.bytes_in);
+                    current_metrics.speed_out_bps = windowed_rate(samples, |s| s.bytes_out);
+                    current_metrics.peer_count_rate =
+                        windowed_change_rate(samples, |s| s.connected_peers);
+                    if let Some(rate) = current_metrics.peer_count_rate {
+                        let history_rate = self
+                            .peers_rate_history
+                            .entry(addr.clone())
+                            .or_insert_with(|| VecDeque::with_capacity(self.peers_history_length));
+                        push_bounded(history_rate, rate, self.peers_history_length);
+                    }
 
                     let speed_in_val = current_metrics.speed_in_bps.unwrap_or(0.0).max(0.0) as u64;
                     let speed_out_val =
                         current_metrics.speed_out_bps.unwrap_or(0.0).max(0.0) as u64;
 
-                    history_in.push_back(speed_in_val);
-                    history_out.push_back(speed_out_val);
-
-                    if history_in.len() > SPARKLINE_HISTORY_LENGTH {
-                        history_in.pop_front();
-                    }
-                    if history_out.len() > SPARKLINE_HISTORY_LENGTH {
-                        history_out.pop_front();
-                    }
+                    push_bounded(history_in, Some(speed_in_val), bw_history_length);
+                    push_bounded(history_out, Some(speed_out_val), bw_history_length);
+                    // Missing samples have no point, the line joins their neighbours
                     current_metrics.chart_data_in = Some(
                         history_in
                             .iter()
                             .enumerate()
-                            .map(|(i, &val)| (i as f64, val as f64))
+                            .filter_map(|(i, &val)| Some((i as f64, val? as f64)))
                             .collect(),
                     );
                     current_metrics.chart_data_out = Some(
                         history_out
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(i, &val)| Some((i as f64, val? as f64)))
+                            .collect(),
+                    );
+
+                    // Errors are only sampled when the node answered, a failed fetch
+                    // says nothing about the node's error counters
+                    let history_errors = self
+                        .errors_history
+                        .entry(addr.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(SPARKLINE_HISTORY_LENGTH));
+                    push_bounded(
+                        history_errors,
+                        current_metrics.total_errors(),
+                        SPARKLINE_HISTORY_LENGTH,
+                    );
+                    current_metrics.chart_data_errors = Some(
+                        history_errors
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &val)| (i as f64, val as f64))
+                            .collect(),
+                    );
+
+                    // The reported CPU is a snapshot, show the mean of the last samples
+                    if let Some(cpu) = current_metrics.cpu_usage_percentage {
+                        let window = self.cpu_avg_window;
+                        let raw = self
+                            .cpu_raw_history
+                            .entry(addr.clone())
+                            .or_insert_with(|| VecDeque::with_capacity(window));
+                        push_bounded(raw, cpu, window);
+                        current_metrics.cpu_smoothed_percent =
+                            Some(raw.iter().sum::<f64>() / raw.len() as f64);
+                    }
+
+                    // CPU and peers move on slower time scales, each keeps its own history length
+                    let history_cpu = self
+                        .cpu_history
+                        .entry(addr.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(self.cpu_history_length));
+                    push_bounded(
+                        history_cpu,
+                        current_metrics.cpu_percent().unwrap_or(0.0),
+                        self.cpu_history_length,
+                    );
+                    current_metrics.chart_data_cpu = Some(
+                        history_cpu
+                            .iter()
+                            .enumerate()
+                            .map(|(i, &val)| (i as f64, val))
+                            .collect(),
+                    );
+                    // Memory only counts for its trend, a steady climb hints at a leak
+                    let history_memory = self
+                        .memory_history
+                        .entry(addr.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(MEMORY_HISTORY_LENGTH));
+                    let sample_due = self
+                        .memory_sampled_at
+                        .get(&addr)
+                        .is_none_or(|at| at.elapsed() >= MEMORY_SAMPLE_INTERVAL);
+                    if let Some(memory) = current_metrics.memory_used_mb
+                        && sample_due
+                    {
+                        push_bounded(history_memory, memory.round() as u64, MEMORY_HISTORY_LENGTH);
+                        self.memory_sampled_at
+                            .insert(addr.clone(), update_start_time);
+                    }
+                    let samples_per_min = 60.0 / MEMORY_SAMPLE_INTERVAL.as_secs_f64();
+                    current_metrics.memory_leak_mb_per_min = detect_memory_leak(history_memory)
+                        .map(|mb_per_sample| mb_per_sample * samples_per_min)
+                        .filter(|mb_per_min| *mb_per_min > self.memory_leak_threshold);
+                    let history_peers = self
+                        .peers_history
+                        .entry(addr.clone())
+                        .or_insert_with(|| VecDeque::with_capacity(self.peers_history_length));
+                    push_bounded(
+                        history_peers,
+                        current_metrics.connected_peers.unwrap_or(0),
+                        self.peers_history_length,
+                    );
+                    current_metrics.chart_data_peers = Some(
+                        history_peers
                             .iter()
                             .enumerate()
                             .map(|(i, &val)| (i as f64, val as f64))
                             .collect(),
                     );
 
+                    // Queue a notification when the wallet balance grew since the last poll
+                    if let (Some(current), Some(previous)) = (
+                        current_metrics.reward_wallet_balance,
+                        self.previous_metrics
+                            .get(&addr)
+                            .and_then(|m| m.reward_wallet_balance),
+                    ) && current > previous
+                    {
+                        let name = url_to_dir
+                            .get(&addr)
+                            .map_or_else(|| addr.clone(), |dir| self.node_display_name(dir));
+                        self.reward_notifications
+                            .push_back((name, current - previous));
+                    }
+
+                    // A metrics server still answering with frozen values hints at a hung node
+                    let unchanged = self
+                        .previous_metrics
+                        .get(&addr)
+                        .is_some_and(|previous| is_metrics_identical(previous, &current_metrics));
+                    let change_count = self.metrics_change_count.entry(addr.clone()).or_insert(0);
+                    *change_count = if unchanged { *change_count + 1 } else { 0 };
+
                     next_previous_metrics.insert(addr.clone(), current_metrics.clone());
                     new_metrics_map.insert(addr.clone(), Ok(current_metrics.clone())); // Clone to avoid move
                 }
-                Err(e) => {
-                    new_metrics_map.insert(addr.clone(), Err(e));
-                    history_in.push_back(0);
-                    history_out.push_back(0);
-
-                    if history_in.len() > SPARKLINE_HISTORY_LENGTH {
-                        history_in.pop_front();
-                    }
-                    if history_out.len() > SPARKLINE_HISTORY_LENGTH {
-                        history_out.pop_front();
+                Err(FetchError { kind, message: e }) => {
+                    self.fetch_error_kinds.insert(addr.clone(), kind);
+                    let errors = self.recent_errors.entry(addr.clone()).or_default();
+                    match errors.back_mut() {
+                        Some(last) if last.message == e => {
+                            last.last_seen = Local::now();
+                            last.count += 1;
+                        }
+                        _ => push_bounded(
+                            errors,
+                            RecentError {
+                                message: e.clone(),
+                                last_seen: Local::now(),
+                                count: 1,
+                            },
+                            RECENT_ERRORS_LENGTH,
+                        ),
                     }
+                    new_metrics_map.insert(addr.clone(), Err(e));
+                    self.metrics_change_count.remove(&addr);
+                    // A failed fetch is a missing sample, not necessarily an idle node
+                    let fill_in = self.gap_fill.fill(history_in.back().copied().flatten());
+                    let fill_out = self.gap_fill.fill(history_out.back().copied().flatten());
+                    push_bounded(history_in, fill_in, bw_history_length);
+                    push_bounded(history_out, fill_out, bw_history_length);
                 }
             }
         }
 
         self.previous_metrics = next_previous_metrics;
-        self.previous_update_time = self.last_update;
+        // Nodes left out of this update lose their metrics, their groups change too
+        changed_urls.extend(
+            self.node_metrics
+                .keys()
+                .filter(|url| !new_metrics_map.contains_key(*url))
+                .cloned(),
+        );
         self.node_metrics = new_metrics_map;
         self.last_update = update_start_time;
+        self.refresh_node_order();
+        self.detect_status_transitions();
+        self.update_totals();
+        self.refresh_group_cache(&changed_urls);
+        self.queue_new_alerts();
+        self.publish_status();
+    }
+
+    // Hands the alerts raised since the previous update to the alert emailer, the
+    // webhook and the hooks, if any. Alerts of the first update are all new, nodes
+    // already down at startup included. Down nodes reach the webhook and the hooks
+    // through `detect_status_transitions`.
+    fn queue_new_alerts(&mut self) {
+        if self.alert_emailer.is_none() && self.webhook.is_none() && self.hooks.is_none() {
+            return;
+        }
+        let mut fired = Vec::new();
+        let mut alert_keys = HashMap::new();
+        for dir_path in &self.nodes {
+            let previous = self.last_alert_keys.get(dir_path);
+            let mut keys = HashSet::new();
+            for alert in self.node_alerts(dir_path) {
+                let key = alert_key(&alert).to_string();
+                if !previous.is_some_and(|previous| previous.contains(&key)) {
+                    fired.push((dir_path, alert));
+                }
+                keys.insert(key);
+            }
+            alert_keys.insert(dir_path.clone(), keys);
+        }
+        let events: Vec<(String, WebhookEvent, Option<String>)> = fired
+            .iter()
+            .filter_map(|(dir_path, alert)| {
+                let event = match alert_key(alert) {
+                    "NAT?" => WebhookEvent::Nat,
+                    "Down" => return None,
+                    _ => WebhookEvent::Outlier,
+                };
+                Some(((*dir_path).clone(), event, Some(alert.clone())))
+            })
+            .collect();
+        let fired: Vec<String> = fired
+            .into_iter()
+            .map(|(dir_path, alert)| format!("{}: {}", self.node_display_name(dir_path), alert))
+            .collect();
+        self.last_alert_keys = alert_keys;
+        if let Some(emailer) = &mut self.alert_emailer {
+            emailer.queue(fired);
+        }
+        self.notify_events(events);
+    }
+
+    // Queues (node directory, event, value) notifications on the webhook and the
+    // hooks, if any
+    fn notify_events(&mut self, events: Vec<(String, WebhookEvent, Option<String>)>) {
+        let now = Instant::now();
+        for (dir_path, event, value) in events {
+            let node = self.node_name(&dir_path);
+            if let Some(webhook) = &mut self.webhook {
+                webhook.notify(&node, event, value.clone(), now);
+            }
+            if let Some(hooks) = &mut self.hooks {
+                hooks.notify(&node, &dir_path, event, value, now);
+            }
+        }
+    }
 
+    /// Hands the current state to the `--serve` status endpoint, if running.
+    pub fn publish_status(&self) {
+        if let Some(server) = &self.status_server {
+            server.publish(fleet_snapshot(self));
+        }
+    }
+
+    /// Fleet totals, histograms and outliers from the current metrics of every node.
+    pub fn update_totals(&mut self) {
         // --- Calculate Totals ---
         let mut current_total_speed_in: f64 = 0.0;
         let mut current_total_speed_out: f64 = 0.0;
         let mut current_total_cpu: f64 = 0.0;
+        let mut current_total_memory: f64 = 0.0;
         // Initialize accumulators for other summary fields
         let mut current_total_data_in: u64 = 0;
         let mut current_total_data_out: u64 = 0;
+        let mut current_session_data_in: u64 = 0;
+        let mut current_session_data_out: u64 = 0;
         let mut current_total_records: u64 = 0;
         let mut current_total_rewards: u64 = 0;
         let mut current_total_live_peers: u64 = 0;
+        let mut current_relay_node_count: usize = 0;
+        let mut current_max_node_speed: f64 = 0.0;
+        let mut current_peers_histogram = [0usize; PEERS_HISTOGRAM_BUCKETS.len()];
 
-        for metrics in self.node_metrics.values().flatten() {
-            // Use flatten()
-            if let Some(cpu) = metrics.cpu_usage_percentage {
+        for (addr, metrics) in self
+            .node_metrics
+            .iter()
+            .filter_map(|(addr, result)| Some((addr, result.as_ref().ok()?)))
+        {
+            if let Some(cpu) = metrics.cpu_percent() {
                 current_total_cpu += cpu;
             }
+            current_total_memory += metrics.memory_used_mb.unwrap_or(0.0);
             // Sum speeds for total history
             current_total_speed_in += metrics.speed_in_bps.unwrap_or(0.0);
             current_total_speed_out += metrics.speed_out_bps.unwrap_or(0.0);
+            current_max_node_speed = current_max_node_speed.max(metrics.total_speed_bps());
 
             // Sum other summary fields
             current_total_data_in += metrics.bandwidth_inbound_bytes.unwrap_or(0);
             current_total_data_out += metrics.bandwidth_outbound_bytes.unwrap_or(0);
+            let (session_in, session_out) = self.session_bandwidth(addr, metrics);
+            current_session_data_in += session_in.unwrap_or(0);
+            current_session_data_out += session_out.unwrap_or(0);
             current_total_records += metrics.records_stored.unwrap_or(0);
             current_total_rewards += metrics.reward_wallet_balance.unwrap_or(0);
+            if let (Some(records), Some(rewards)) =
+                (metrics.records_stored, metrics.reward_wallet_balance)
+            {
+                self.last_records_rewards
+                    .insert(addr.clone(), (records, rewards));
+            }
             current_total_live_peers += metrics.connected_peers.unwrap_or(0);
+            if metrics.is_relay() {
+                current_relay_node_count += 1;
+            }
+            if let Some(peers) = metrics.connected_peers
+                && let Some(bucket) = PEERS_HISTOGRAM_BUCKETS
+                    .iter()
+                    .position(|(_, upper)| upper.is_none_or(|upper| peers < upper))
+            {
+                current_peers_histogram[bucket] += 1;
+            }
         }
         self.total_cpu_usage = current_total_cpu;
+        self.total_memory_used_mb = current_total_memory;
         // Store calculated summary totals
         self.summary_total_in_speed = current_total_speed_in;
         self.summary_total_out_speed = current_total_speed_out;
         self.summary_total_data_in_bytes = current_total_data_in;
         self.summary_total_data_out_bytes = current_total_data_out;
+        self.summary_session_bandwidth_in_bytes = current_session_data_in;
+        self.summary_session_bandwidth_out_bytes = current_session_data_out;
         self.summary_total_records = current_total_records;
         self.summary_total_rewards = current_total_rewards;
         self.summary_total_live_peers = current_total_live_peers;
+        self.summary_relay_node_count = current_relay_node_count;
+        self.summary_max_node_speed = current_max_node_speed;
+        self.summary_stale_count = self
+            .nodes
+            .iter()
+            .filter(|dir_path| self.node_status(dir_path) == NodeStatus::Running)
+            .filter_map(|dir_path| self.node_urls.get(dir_path))
+            .filter(|url| self.is_stale(url))
+            .count();
+        self.peers_histogram = current_peers_histogram;
+        self.anomalies = self.detect_anomalies();
 
         // Update total speed history
         let total_in_val = current_total_speed_in.max(0.0) as u64;
         let total_out_val = current_total_speed_out.max(0.0) as u64;
 
-        self.total_speed_in_history.push_back(total_in_val);
-        self.total_speed_out_history.push_back(total_out_val);
-
-        if self.total_speed_in_history.len() > SPARKLINE_HISTORY_LENGTH {
-            self.total_speed_in_history.pop_front();
-        }
-        if self.total_speed_out_history.len() > SPARKLINE_HISTORY_LENGTH {
-            self.total_speed_out_history.pop_front();
-        }
+        push_bounded(
+            &mut self.total_speed_in_history,
+            total_in_val,
+            self.bw_history_length,
+        );
+        push_bounded(
+            &mut self.total_speed_out_history,
+            total_out_val,
+            self.bw_history_length,
+        );
+        let (held_records, held_rewards) = self
+            .last_records_rewards
+            .values()
+            .fold((0, 0), |(records, rewards), &(r, w)| {
+                (records + r, rewards + w)
+            });
+        push_bounded(
+            &mut self.records_history,
+            held_records,
+            GROWTH_HISTORY_LENGTH,
+        );
+        push_bounded(
+            &mut self.rewards_history,
+            held_rewards,
+            GROWTH_HISTORY_LENGTH,
+        );
 
         // --- Calculate Total Used Storage ---
         let mut current_total_used: u64 = 0;
@@ -319,46 +1585,1479 @@ impl App {
         } else {
             self.total_used_storage_bytes = None;
         }
-    }
 
-    /// Adjusts the application's tick rate (update interval) through discrete levels.
-    /// `increase`: true to increase interval (slower updates), false to decrease (faster updates).
-    pub fn adjust_tick_rate(&mut self, increase: bool) {
-        // Find the current index in the TICK_LEVELS array
-        let current_index = TICK_LEVELS.iter().position(|&d| d == self.tick_rate);
+        if self.storage_gauge == StorageGauge::Filesystem {
+            self.filesystem_usage = filesystem_usage(self.node_record_store_paths.values());
+        }
+    }
 
-        let new_index = match current_index {
-            Some(index) => {
-                if increase {
-                    (index + 1).min(TICK_LEVELS.len() - 1)
-                } else {
-                    index.saturating_sub(1)
-                }
-            }
-            None => {
-                // If current tick_rate isn't exactly in levels, find the closest
-                if increase {
-                    // Find first level *greater than* current
-                    TICK_LEVELS
-                        .iter()
-                        .position(|&d| d > self.tick_rate)
-                        .unwrap_or(TICK_LEVELS.len() - 1) // Default to max if none greater
-                } else {
-                    // Find last level *less than* current
-                    TICK_LEVELS
-                        .iter()
-                        .rposition(|&d| d < self.tick_rate)
-                        .unwrap_or(0) // Default to min if none smaller
-                }
-            }
-        };
+    /// Name shown for a node: its short ID if enabled, otherwise `node_name`.
+    pub fn node_display_name(&self, dir_path: &str) -> String {
+        if self.show_short_ids
+            && let Some(id) = self.node_short_ids.get(dir_path)
+        {
+            return format_node_id(*id);
+        }
+        self.node_name(dir_path)
+    }
 
-        self.tick_rate = TICK_LEVELS[new_index];
+    /// Name of a node: the first capture group of `--name-regex` (or its whole match
+    /// without groups) in the directory path, otherwise the directory name.
+    pub fn node_name(&self, dir_path: &str) -> String {
+        if let Some(captures) = self
+            .name_regex
+            .as_ref()
+            .and_then(|re| re.captures(dir_path))
+            && let Some(name) = captures.get(1).or_else(|| captures.get(0))
+            && !name.as_str().is_empty()
+        {
+            return name.as_str().to_string();
+        }
+        Path::new(dir_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(dir_path) // Fallback to full path if extraction fails
+            .to_string()
+    }
 
-        // Optional: Add a status message (can be done in ui/run_app instead)
-        // self.status_message = Some(format!("Update interval set to: {:.1?}s", self.tick_rate.as_secs_f64()));
+    /// Group of a node for `--group-by-prefix`: its name without the trailing number
+    /// and the separators before it, `antnode-12` is in `antnode`. A name that is only
+    /// a number is its own group.
+    pub fn node_group(&self, dir_path: &str) -> String {
+        let name = self.node_name(dir_path);
+        let prefix = name
+            .trim_end_matches(|c: char| c.is_ascii_digit())
+            .trim_end_matches(['-', '_', '.', ' ']);
+        if prefix.is_empty() {
+            name
+        } else {
+            prefix.to_string()
+        }
     }
-}
+
+    /// Sorts the nodes into their groups again and computes every group summary, after
+    /// the nodes, their names or all their metrics changed. Empties both without grouping.
+    pub fn regroup_nodes(&mut self) {
+        self.group_members.clear();
+        self.group_cache.clear();
+        if !self.group_by_prefix {
+            return;
+        }
+        for dir_path in &self.nodes {
+            self.group_members
+                .entry(self.node_group(dir_path))
+                .or_default()
+                .push(dir_path.clone());
+        }
+        let now = Instant::now();
+        self.group_cache = self
+            .group_members
+            .iter()
+            .map(|(group, members)| (group.clone(), (self.compute_group_summary(members), now)))
+            .collect();
+    }
+
+    /// Totals of the nodes of a group, computed from their current metrics.
+    pub fn compute_group_summary(&self, members: &[String]) -> GroupSummary {
+        let mut summary = GroupSummary {
+            nodes: members.len(),
+            ..GroupSummary::default()
+        };
+        for dir_path in members {
+            match self.node_status(dir_path) {
+                NodeStatus::Running => summary.running += 1,
+                NodeStatus::Error => summary.errors += 1,
+                NodeStatus::Initializing | NodeStatus::Stopped => {}
+            }
+            let Some(Ok(metrics)) = self
+                .node_urls
+                .get(dir_path)
+                .and_then(|url| self.node_metrics.get(url))
+            else {
+                continue;
+            };
+            summary.speed_in_bps += metrics.speed_in_bps.unwrap_or(0.0);
+            summary.speed_out_bps += metrics.speed_out_bps.unwrap_or(0.0);
+            summary.records += metrics.records_stored.unwrap_or(0);
+            summary.rewards += metrics.reward_wallet_balance.unwrap_or(0);
+        }
+        summary
+    }
+
+    /// Cached summary of a group, as of the last update that fetched one of its nodes.
+    pub fn node_group_summary(&self, group: &str) -> Option<&GroupSummary> {
+        self.group_cache.get(group).map(|(summary, _)| summary)
+    }
+
+    /// Computes again the summaries of the groups with a node whose metrics URL is in
+    /// `changed_urls`, the others keep their cached summary.
+    pub fn refresh_group_cache(&mut self, changed_urls: &HashSet<String>) {
+        let now = Instant::now();
+        let refreshed: Vec<(String, GroupSummary)> = self
+            .group_members
+            .iter()
+            .filter(|(group, members)| {
+                !self.group_cache.contains_key(*group)
+                    || members.iter().any(|dir_path| {
+                        self.node_urls
+                            .get(dir_path)
+                            .is_some_and(|url| changed_urls.contains(url))
+                    })
+            })
+            .map(|(group, members)| (group.clone(), self.compute_group_summary(members)))
+            .collect();
+        for (group, summary) in refreshed {
+            self.group_cache.insert(group, (summary, now));
+        }
+    }
+
+    /// Per-second rate of one counter of a node over the rate window, like the speeds.
+    pub fn counter_rate(
+        &self,
+        url: &str,
+        counter: impl Fn(&CounterSample) -> Option<u64>,
+    ) -> Option<f64> {
+        windowed_rate(self.counter_samples.get(url)?, counter)
+    }
+
+    /// Shows `text` in the status bar for 3 seconds, 10 for warnings and errors, and
+    /// records it in the message history.
+    pub fn set_status(&mut self, severity: Severity, text: impl Into<String>) {
+        let duration = match severity {
+            Severity::Info => STATUS_MESSAGE_DURATION,
+            Severity::Warning | Severity::Error => STATUS_ERROR_DURATION,
+        };
+        self.set_status_for(severity, text, duration);
+    }
+
+    /// Shows `text` in the status bar for `duration`, an info message until the next key
+    /// press at most, and records it in the message history.
+    pub fn set_status_for(
+        &mut self,
+        severity: Severity,
+        text: impl Into<String>,
+        duration: Duration,
+    ) {
+        let message = StatusMessage {
+            text: text.into(),
+            severity,
+            at: Local::now(),
+        };
+        if self.status_history.len() == STATUS_HISTORY_LENGTH {
+            self.status_history.pop_front();
+        }
+        self.status_history.push_back(message.clone());
+        self.status_message = Some(message);
+        self.status_message_expiry = Some(Instant::now() + duration);
+    }
+
+    /// Keeps the outcome of a hook run in the log of its node and shows it in the
+    /// status bar, as a warning when the hook failed.
+    pub fn record_hook(&mut self, outcome: HookOutcome) {
+        let severity = if outcome.succeeded() {
+            Severity::Info
+        } else {
+            Severity::Warning
+        };
+        self.set_status(severity, outcome.text());
+        let log = self.hook_log.entry(outcome.node_dir.clone()).or_default();
+        push_bounded(log, outcome, RECENT_HOOKS_LENGTH);
+    }
+
+    /// Clears the status message once its time is up.
+    pub fn clear_status_message_after_duration(&mut self, now: Instant) {
+        if self
+            .status_message_expiry
+            .is_some_and(|expiry| now > expiry)
+        {
+            self.status_message = None;
+            self.status_message_expiry = None;
+        }
+    }
+
+    /// Clears an info message on a key press. Warnings and errors stay their time, so
+    /// they can still be read while using the keyboard.
+    pub fn dismiss_status_message(&mut self) {
+        if self
+            .status_message
+            .as_ref()
+            .is_some_and(|message| message.severity == Severity::Info)
+        {
+            self.status_message = None;
+            self.status_message_expiry = None;
+        }
+    }
+
+    /// Zeroes the displayed error counts of the answering nodes, from their current
+    /// counters. Nodes down at that time keep showing their lifetime counts.
+    pub fn zero_errors(&mut self) {
+        self.error_baseline = self
+            .node_metrics
+            .iter()
+            .filter_map(|(url, result)| {
+                Some((
+                    url.clone(),
+                    ErrorBaseline::from_metrics(result.as_ref().ok()?),
+                ))
+            })
+            .collect();
+        self.errors_zeroed_at = Some(Instant::now());
+        let count = self.error_baseline.len();
+        self.set_status(Severity::Info, format!("Errors zeroed for {} nodes", count));
+    }
+
+    /// Opens the log tail pane on the selected node, or closes it.
+    pub fn toggle_log_tail(&mut self) {
+        self.log_tail = match self.log_tail {
+            Some(_) => None,
+            None => self
+                .selected_node()
+                .map(|dir| LogTail::open(node_log_file(dir))),
+        };
+    }
+
+    /// Points the open log tail pane at the selected node's log, after the selection moved.
+    pub fn sync_log_tail(&mut self) {
+        let Some(log_file) = self.selected_node().map(|dir| node_log_file(dir)) else {
+            return; // Keep the last log when the view has no nodes
+        };
+        if let Some(tail) = &self.log_tail
+            && tail.path != log_file
+        {
+            self.log_tail = Some(LogTail::open(log_file));
+        }
+    }
+
+    /// Marks the start of a metrics sweep over `total` nodes.
+    pub fn start_fetch(&mut self, total: usize) {
+        self.fetch_in_progress = true;
+        self.fetch_done = 0;
+        self.fetch_total = total;
+        self.last_fetch_started = Instant::now();
+    }
+
+    /// Prometheus text of the last successful fetch of `url`, kept when later fetches fail.
+    pub fn raw_metrics_for_node(&self, url: &str) -> Option<&str> {
+        self.raw_metrics_cache.get(url).map(String::as_str)
+    }
+
+    /// Scrolls the raw metrics popup by `rows` and `columns`, staying within the text
+    /// of the selected node.
+    pub fn scroll_raw_metrics(&mut self, rows: i32, columns: i32) {
+        let text = self
+            .selected_node()
+            .and_then(|dir_path| self.node_urls.get(dir_path))
+            .and_then(|url| self.raw_metrics_for_node(url))
+            .unwrap_or_default();
+        let max_row = text.lines().count().saturating_sub(1);
+        let max_column = text
+            .lines()
+            .map(|line| line.width())
+            .max()
+            .unwrap_or(0)
+            .saturating_sub(1);
+        let scroll = |current: u16, delta: i32, max: usize| {
+            (current as i64 + delta as i64).clamp(0, max.min(u16::MAX as usize) as i64) as u16
+        };
+        self.raw_metrics_scroll = (
+            scroll(self.raw_metrics_scroll.0, rows, max_row),
+            scroll(self.raw_metrics_scroll.1, columns, max_column),
+        );
+    }
+
+    /// True when sweeps are held back because the terminal is unfocused.
+    pub fn fetch_paused(&self) -> bool {
+        self.pause_on_focus_loss && self.unfocused
+    }
+
+    /// Time left until the next sweep is due.
+    pub fn next_fetch_in(&self) -> Duration {
+        self.next_fetch_in_from(Instant::now())
+    }
+
+    /// Like `next_fetch_in`, counted from `now` instead of the current time.
+    pub fn next_fetch_in_from(&self, now: Instant) -> Duration {
+        self.tick_rate
+            .saturating_sub(now.saturating_duration_since(self.last_fetch_started))
+    }
+
+    /// Applies the `changed` settings (by config file name) of a reloaded config. The
+    /// ones that only take effect at startup, like the node path, are left as they are
+    /// and returned. Histories shrink at their next update.
+    pub fn reload_settings(&mut self, settings: &Settings, changed: &[String]) -> Vec<String> {
+        let mut not_applied = Vec::new();
+        for key in changed {
+            match key.as_str() {
+                "interval_ms" => self.set_tick_rate(Duration::from_millis(settings.interval_ms)),
+                "totals" => self.totals_mode = settings.totals,
+                "rate_window" => self.rate_window = settings.rate_window,
+                "short_ids" => self.show_short_ids = settings.short_ids,
+                "notation" => self.notation = settings.notation,
+                "units" => self.size_units = settings.units,
+                "chart_marker" => self.chart_marker = settings.chart_marker,
+                "swap_rx_tx_colors" => self.swap_rx_tx_colors = settings.swap_rx_tx_colors,
+                "gap_fill" => self.gap_fill = settings.gap_fill,
+                "sort_secondary" => {
+                    self.sort_secondary = settings.sort_secondary;
+                    self.refresh_node_order();
+                }
+                "storage_gauge" => self.storage_gauge = settings.storage_gauge,
+                "bw_history_length" => self.bw_history_length = settings.bw_history_length,
+                "cpu_history_length" => self.cpu_history_length = settings.cpu_history_length,
+                "cpu_avg_window" => self.cpu_avg_window = settings.cpu_avg_window,
+                "peers_history_length" => self.peers_history_length = settings.peers_history_length,
+                "log_pane_height" => self.log_pane_height = settings.log_pane_height,
+                "max_concurrent_fetches" => {
+                    self.max_concurrent_fetches = settings.max_concurrent_fetches
+                }
+                "bell" => self.bell = settings.bell,
+                "timeline" => self.show_timeline = settings.timeline,
+                "group_by_prefix" => {
+                    self.group_by_prefix = settings.group_by_prefix;
+                    self.regroup_nodes();
+                }
+                "tail_mode" => {
+                    self.sort_key = if settings.tail_mode {
+                        SortKey::TailMode
+                    } else {
+                        SortKey::default()
+                    };
+                    self.refresh_node_order();
+                }
+                "memory_leak_threshold" => {
+                    self.memory_leak_threshold = settings.memory_leak_threshold
+                }
+                "stale_threshold" => self.stale_threshold = settings.stale_threshold,
+                "max_redirects" => self.max_redirects = settings.max_redirects,
+                // Already checked when the settings were resolved
+                "name_regex" => {
+                    self.name_regex = settings
+                        .name_regex
+                        .as_deref()
+                        .and_then(|pattern| Regex::new(pattern).ok());
+                    self.regroup_nodes();
+                }
+                "color_running" | "color_stopped" | "color_error" => {
+                    self.color_config = ColorConfig::from_settings(settings)
+                }
+                "title" => self.title = expand_title(&settings.title),
+                _ => not_applied.push(key.clone()),
+            }
+        }
+        not_applied
+    }
+
+    /// Whether the node in `dir_path` has no metrics: no URL found, or a failed fetch.
+    pub fn is_unanswered(&self, dir_path: &str) -> bool {
+        matches!(
+            self.node_status(dir_path),
+            NodeStatus::Stopped | NodeStatus::Error
+        )
+    }
+
+    /// Directories of the nodes without metrics, see `is_unanswered`.
+    pub fn unanswered_nodes(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .filter(|dir_path| self.is_unanswered(dir_path))
+            .cloned()
+            .collect()
+    }
+
+    /// Units of the nodes that don't answer, (dir path, unit), whose state is worth
+    /// showing. Empty without `--systemd`.
+    pub fn units_to_query(&self) -> Vec<(String, String)> {
+        let Some(systemd) = &self.systemd else {
+            return Vec::new();
+        };
+        self.unanswered_nodes()
+            .into_iter()
+            .map(|dir_path| {
+                let unit = systemd.unit_name(&dir_path);
+                (dir_path, unit)
+            })
+            .collect()
+    }
+
+    /// ActiveState of the unit of the node in `dir_path`, while the node doesn't answer.
+    pub fn unit_state(&self, dir_path: &str) -> Option<&str> {
+        self.unit_states
+            .get(dir_path)
+            .filter(|_| self.is_unanswered(dir_path))
+            .map(String::as_str)
+    }
+
+    /// CPU and memory of the process of the node in `dir_path`, while it runs without
+    /// metrics.
+    pub fn node_process(&self, dir_path: &str) -> Option<&ProcessStats> {
+        self.node_processes
+            .get(dir_path)
+            .filter(|_| self.is_unanswered(dir_path))
+    }
+
+    /// Current status of the node in `dir_path`.
+    pub fn node_status(&self, dir_path: &str) -> NodeStatus {
+        let url = self.node_urls.get(dir_path);
+        NodeStatus::from_fetch(
+            url.is_some(),
+            url.and_then(|url| self.node_metrics.get(url)),
+        )
+    }
+
+    /// Flags the running nodes whose CPU, memory or error count is more than
+    /// `ANOMALY_Z_SCORE` standard deviations above the fleet mean, or whose live peers
+    /// or peer rate (when losing peers) are that far below it. Keyed by node directory
+    /// path. Note that a node can only be that far from the mean in a fleet of more
+    /// than 5 nodes.
+    pub fn detect_anomalies(&self) -> HashMap<String, Vec<AnomalyType>> {
+        let running: Vec<(&String, &NodeMetrics)> = self
+            .nodes
+            .iter()
+            .filter_map(|dir_path| {
+                let url = self.node_urls.get(dir_path)?;
+                let metrics = self.node_metrics.get(url)?.as_ref().ok()?;
+                Some((dir_path, metrics))
+            })
+            .collect();
+
+        let mut anomalies: HashMap<String, Vec<AnomalyType>> = HashMap::new();
+        for anomaly in AnomalyType::ALL {
+            let values: Vec<(&String, f64)> = running
+                .iter()
+                .filter_map(|(dir_path, metrics)| Some((*dir_path, anomaly.value(metrics)?)))
+                .collect();
+            if values.len() < 3 {
+                continue; // Too few nodes for a meaningful spread
+            }
+            let count = values.len() as f64;
+            let mean = values.iter().map(|(_, value)| value).sum::<f64>() / count;
+            let variance = values
+                .iter()
+                .map(|(_, value)| (value - mean).powi(2))
+                .sum::<f64>()
+                / count;
+            let std_dev = variance.sqrt();
+            if std_dev == 0.0 {
+                continue;
+            }
+            for (dir_path, value) in values {
+                let z_score = (value - mean) / std_dev;
+                let is_outlier = match anomaly {
+                    AnomalyType::LowPeers => z_score < -ANOMALY_Z_SCORE,
+                    // Only an actual loss, not a node gaining peers slower than the rest
+                    AnomalyType::PeerLoss => z_score < -ANOMALY_Z_SCORE && value < 0.0,
+                    _ => z_score > ANOMALY_Z_SCORE,
+                };
+                if is_outlier {
+                    anomalies.entry(dir_path.clone()).or_default().push(anomaly);
+                }
+            }
+        }
+        anomalies
+    }
+
+    /// Alerts currently raised for a node: down, fleet outlier, or possibly behind a NAT.
+    pub fn node_alerts(&self, dir_path: &str) -> Vec<String> {
+        let mut alerts = Vec::new();
+        let metrics_result = self
+            .node_urls
+            .get(dir_path)
+            .and_then(|url| self.node_metrics.get(url));
+        if let Some(Err(e)) = metrics_result {
+            alerts.push(format!("Down: {}", e));
+        }
+        for anomaly in self.anomalies.get(dir_path).into_iter().flatten() {
+            alerts.push(format!("Outlier: {} for the fleet", anomaly.label()));
+        }
+        if let Some(Ok(metrics)) = metrics_result
+            && let Some(ratio) = metrics.inbound_ratio()
+            && ratio < NAT_SUSPECT_INBOUND_RATIO
+        {
+            alerts.push(format!(
+                "NAT?: only {:.0}% of connections are inbound",
+                ratio
+            ));
+        }
+        alerts
+    }
+
+    /// Number of alerts of the fleet and the most pressing one with the name of its
+    /// node: a down node before an outlier before a NAT suspect, the first node in the
+    /// sort order among equals.
+    pub fn top_alert(&self) -> (usize, Option<(String, String)>) {
+        let severity = |alert: &str| match alert_key(alert) {
+            "Down" => 0,
+            "NAT?" => 2,
+            _ => 1,
+        };
+        let mut count = 0;
+        let mut top: Option<(&String, String)> = None;
+        for dir_path in self.sorted_nodes() {
+            for alert in self.node_alerts(dir_path) {
+                count += 1;
+                if top
+                    .as_ref()
+                    .is_none_or(|(_, top)| severity(&alert) < severity(top))
+                {
+                    top = Some((dir_path, alert));
+                }
+            }
+        }
+        (
+            count,
+            top.map(|(dir_path, alert)| (self.node_name(dir_path), alert)),
+        )
+    }
+
+    /// Plain text report of every node with an alert, see `node_alerts`, with its
+    /// status, metrics URL and the last metrics fetched. Meant to be attached to bug reports.
+    pub fn generate_warning_report(&self) -> String {
+        let alerting: Vec<(&String, Vec<String>)> = self
+            .nodes
+            .iter()
+            .map(|dir_path| (dir_path, self.node_alerts(dir_path)))
+            .filter(|(_, alerts)| !alerts.is_empty())
+            .collect();
+
+        let mut report = format!(
+            "antop warning report, {}\n{} of {} nodes alerting\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            alerting.len(),
+            self.nodes.len()
+        );
+        for (dir_path, alerts) in alerting {
+            let url = self.node_urls.get(dir_path);
+            let metrics_result = url.and_then(|url| self.node_metrics.get(url));
+            let status = match self.node_status(dir_path) {
+                NodeStatus::Running => "Running",
+                NodeStatus::Initializing => "Initializing",
+                NodeStatus::Error => "Error",
+                NodeStatus::Stopped => "Stopped",
+            };
+
+            report.push_str(&format!("\n== {} ==\n", self.node_display_name(dir_path)));
+            report.push_str(&format!("Path:        {}\n", dir_path));
+            report.push_str(&format!("Status:      {}\n", status));
+            report.push_str(&format!(
+                "Metrics URL: {}\n",
+                url.map_or("-", String::as_str)
+            ));
+            report.push_str("Alerts:\n");
+            for alert in alerts {
+                report.push_str(&format!("  - {}\n", alert));
+            }
+            if let Some(Ok(metrics)) = metrics_result {
+                report.push_str("Metrics:\n");
+                for (name, value) in report_metric_values(metrics) {
+                    report.push_str(&format!("  {:<28} {}\n", name, value));
+                }
+            }
+        }
+        report
+    }
+
+    /// Compares each node's status with the one seen at the previous update, once per
+    /// update. Nodes that went down, failing or stale, flash and raise a warning (and the
+    /// bell, if enabled), nodes that recovered are named in the same message, or in an
+    /// info message when none went down.
+    fn detect_status_transitions(&mut self) {
+        let now = Instant::now();
+        let mut went_down = Vec::new();
+        let mut recovered = Vec::new();
+        let timeline_span = self.timeline_span();
+        for dir_path in &self.nodes {
+            let status = self.node_status(dir_path);
+            let stale = status == NodeStatus::Running
+                && self
+                    .node_urls
+                    .get(dir_path)
+                    .is_some_and(|url| self.is_stale(url));
+            let was_stale = if stale {
+                !self.last_stale.insert(dir_path.clone())
+            } else {
+                self.last_stale.remove(dir_path)
+            };
+            // Up means running with changing metrics, a stale node counts as down
+            let was_up = match self.last_statuses.insert(dir_path.clone(), status) {
+                Some(NodeStatus::Running) => Some(!was_stale),
+                Some(NodeStatus::Error) => Some(false),
+                _ => None,
+            };
+            let up = match status {
+                NodeStatus::Running => Some(!stale),
+                NodeStatus::Error => Some(false),
+                _ => None,
+            };
+            match (was_up, up) {
+                (Some(true), Some(false)) => went_down.push(dir_path.clone()),
+                (Some(false), Some(true)) => recovered.push(dir_path.clone()),
+                _ => {}
+            }
+            if matches!(status, NodeStatus::Running | NodeStatus::Error) {
+                let changes = self
+                    .availability_changes
+                    .entry(dir_path.clone())
+                    .or_default();
+                record_availability(changes, status == NodeStatus::Running, now, timeline_span);
+            }
+        }
+        self.row_flash_until.retain(|_, until| *until > now);
+
+        if self.webhook.is_some() || self.hooks.is_some() {
+            let mut events: Vec<(String, WebhookEvent, Option<String>)> = recovered
+                .iter()
+                .map(|dir_path| (dir_path.clone(), WebhookEvent::NodeRecovered, None))
+                .collect();
+            for dir_path in &went_down {
+                let error = match self
+                    .node_urls
+                    .get(dir_path)
+                    .and_then(|url| self.node_metrics.get(url))
+                {
+                    Some(Err(error)) => Some(error.clone()),
+                    Some(Ok(_)) => Some(format!(
+                        "Stale: same metrics for {} updates",
+                        self.stale_threshold
+                    )),
+                    None => None,
+                };
+                events.push((dir_path.clone(), WebhookEvent::NodeDown, error));
+            }
+            self.notify_events(events);
+        }
+        // One message for both, so a recovery isn't hidden by a node going down
+        let mut parts = Vec::new();
+        if !went_down.is_empty() {
+            parts.push(format!("Node down: {}", self.display_names(&went_down)));
+        }
+        if !recovered.is_empty() {
+            parts.push(format!("Back up: {}", self.display_names(&recovered)));
+        }
+        if !parts.is_empty() {
+            let severity = if went_down.is_empty() {
+                Severity::Info
+            } else {
+                Severity::Warning
+            };
+            self.set_status(severity, parts.join("; "));
+        }
+        if !went_down.is_empty() {
+            for dir_path in went_down {
+                self.row_flash_until
+                    .insert(dir_path, now + ROW_FLASH_DURATION);
+            }
+            self.bell_pending = self.bell;
+        }
+    }
+
+    /// Time covered by the Rx/Tx sparklines, one point per update, it follows the tick rate.
+    pub fn bandwidth_chart_span(&self) -> Duration {
+        self.tick_rate * self.bw_history_length as u32
+    }
+
+    /// Time covered by one cell of the availability timeline.
+    pub fn timeline_bucket_duration(&self) -> Duration {
+        self.tick_rate * TIMELINE_TICKS_PER_BUCKET
+    }
+
+    /// Time covered by the whole availability timeline, it follows the tick rate.
+    pub fn timeline_span(&self) -> Duration {
+        self.timeline_bucket_duration() * TIMELINE_BUCKETS as u32
+    }
+
+    /// Availability of a node in each timeline cell, oldest first, the last cell ending now.
+    pub fn availability_timeline(&self, dir_path: &str) -> [Availability; TIMELINE_BUCKETS] {
+        let mut timeline = [Availability::Unknown; TIMELINE_BUCKETS];
+        let Some(changes) = self.availability_changes.get(dir_path) else {
+            return timeline;
+        };
+        let now = Instant::now();
+        let bucket_duration = self.timeline_bucket_duration();
+
+        for (i, availability) in timeline.iter_mut().enumerate() {
+            let Some(bucket_start) =
+                now.checked_sub(bucket_duration * (TIMELINE_BUCKETS - i) as u32)
+            else {
+                continue; // Before the monotonic clock started
+            };
+            let bucket_end = bucket_start + bucket_duration;
+
+            // Each change holds until the next one, the last one until now
+            let (mut was_up, mut was_down) = (false, false);
+            for (j, (changed_at, up)) in changes.iter().enumerate() {
+                let until = changes.get(j + 1).map_or(now, |(next, _)| *next);
+                if *changed_at < bucket_end && until > bucket_start {
+                    if *up {
+                        was_up = true;
+                    } else {
+                        was_down = true;
+                    }
+                }
+            }
+            *availability = match (was_up, was_down) {
+                (true, false) => Availability::Up,
+                (false, true) => Availability::Down,
+                (true, true) => Availability::Partial,
+                (false, false) => Availability::Unknown,
+            };
+        }
+        timeline
+    }
+
+    // Comma separated display names of some nodes
+    fn display_names(&self, dir_paths: &[String]) -> String {
+        dir_paths
+            .iter()
+            .map(|dir_path| self.node_display_name(dir_path))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether the node behind `url` kept returning the same metrics for `stale_threshold`
+    /// updates in a row. Stale nodes still count as running.
+    pub fn is_stale(&self, url: &str) -> bool {
+        self.metrics_change_count
+            .get(url)
+            .is_some_and(|&count| count >= self.stale_threshold)
+    }
+
+    /// Whether the row of `dir_path` is flashing because the node just went down.
+    pub fn is_row_flashing(&self, dir_path: &str) -> bool {
+        self.row_flash_until
+            .get(dir_path)
+            .is_some_and(|until| Instant::now() < *until)
+    }
+
+    /// Counts the nodes in each status, for the top bar breakdown.
+    pub fn status_counts(&self) -> StatusCounts {
+        let mut counts = StatusCounts::default();
+        for dir_path in &self.nodes {
+            counts.add(self.node_status(dir_path));
+        }
+        counts
+    }
+
+    /// Nodes shown in the list, in display order, after applying `list_view_filter`.
+    pub fn visible_nodes(&self) -> &[String] {
+        &self.visible_order
+    }
+
+    /// Every node, filtered out or not, in the order of `sort_key`, the pinned ones
+    /// first.
+    pub fn sorted_nodes(&self) -> &[String] {
+        &self.sorted_order
+    }
+
+    /// Sorts and filters the nodes again for `sorted_nodes` and `visible_nodes`, after
+    /// each update and whenever the nodes, the sort keys, the pins or the list view
+    /// filter change. The rows and the selection read the order several times per
+    /// frame and per key press, they don't sort a large fleet each time.
+    pub fn refresh_node_order(&mut self) {
+        let mut sorted: Vec<&String> = self.nodes.iter().collect();
+        if self.sort_key != SortKey::Name {
+            let now = Instant::now();
+            // A stable sort, rows equal on both keys keep the natural order of `nodes`
+            sorted.sort_by(|a, b| {
+                self.compare_nodes(a, b, self.sort_key, now)
+                    .then_with(|| self.compare_nodes(a, b, self.sort_secondary, now))
+            });
+        }
+        // Stable as well, the pinned nodes keep the sort order among themselves
+        if !self.pinned_nodes.is_empty() {
+            sorted.sort_by_key(|dir_path| !self.pinned_nodes.contains(*dir_path));
+        }
+        let sorted: Vec<String> = sorted.into_iter().cloned().collect();
+        self.visible_order = sorted
+            .iter()
+            .filter(|dir_path| self.matches_filter(dir_path))
+            .cloned()
+            .collect();
+        self.sorted_order = sorted;
+    }
+
+    // Orders two node directories by `key`, with the time of the last answers counted
+    // from `now` in whole seconds
+    fn compare_nodes(&self, a: &str, b: &str, key: SortKey, now: Instant) -> Ordering {
+        let status_rank = |dir_path: &str| match self.node_status(dir_path) {
+            NodeStatus::Running => 0,
+            NodeStatus::Initializing => 1,
+            NodeStatus::Error => 2,
+            NodeStatus::Stopped => 3,
+        };
+        let value = |dir_path: &str| {
+            let metrics = self
+                .node_urls
+                .get(dir_path)
+                .and_then(|url| self.node_metrics.get(url))
+                .and_then(|result| result.as_ref().ok())?;
+            match key {
+                SortKey::Name | SortKey::Status | SortKey::TailMode => None,
+                SortKey::Cpu => metrics.cpu_percent(),
+                SortKey::Memory => metrics.memory_used_mb,
+                SortKey::Records => metrics.records_stored.map(|records| records as f64),
+                SortKey::Uptime => metrics.uptime_seconds.map(|uptime| uptime as f64),
+            }
+        };
+        // Nodes that answered within the same second tie, and keep their name order
+        let updated_secs_ago = |dir_path: &str| {
+            let url = self.node_urls.get(dir_path)?;
+            let at = self.last_successful_fetch.get(url)?;
+            Some(now.saturating_duration_since(*at).as_secs())
+        };
+        match key {
+            SortKey::Name => compare_node_dirs(a, b),
+            SortKey::Status => status_rank(a).cmp(&status_rank(b)),
+            SortKey::TailMode => match (updated_secs_ago(a), updated_secs_ago(b)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            _ => match (value(a), value(b)) {
+                (Some(a), Some(b)) => b.total_cmp(&a),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        }
+    }
+
+    // Whether the node in `dir_path` passes the current list view filter
+    fn matches_filter(&self, dir_path: &str) -> bool {
+        let status = self.node_status(dir_path);
+        match self.list_view_filter {
+            ListViewFilter::All => true,
+            ListViewFilter::Running => status == NodeStatus::Running,
+            ListViewFilter::Stopped => status == NodeStatus::Stopped,
+            ListViewFilter::Error => status == NodeStatus::Error,
+            ListViewFilter::Shunned => self
+                .node_urls
+                .get(dir_path)
+                .and_then(|url| self.node_metrics.get(url))
+                .and_then(|result| result.as_ref().ok())
+                .is_some_and(|metrics| metrics.shunned_count.unwrap_or(0) > 0),
+        }
+    }
+
+    /// Adds a node directory that appeared after startup. Returns false when it is already known.
+    pub fn add_node(&mut self, dir_path: String) -> bool {
+        if self.nodes.contains(&dir_path) {
+            return false;
+        }
+        let record_store_path = Path::new(&dir_path).join("record_store");
+        if record_store_path.is_dir() {
+            self.node_record_store_paths
+                .insert(dir_path.clone(), record_store_path);
+        }
+        self.nodes.push(dir_path);
+        self.nodes.sort_by(|a, b| compare_node_dirs(a, b));
+        self.node_short_ids = assign_node_ids(&self.nodes);
+        self.regroup_nodes();
+        self.refresh_node_order();
+        true
+    }
+
+    /// Sorts by the next key, the selected node stays selected on its new row.
+    pub fn cycle_sort_key(&mut self) {
+        self.sort_key = self.sort_key.next();
+        self.refresh_node_order();
+        self.resolve_selection();
+    }
+
+    /// Switches to the next list view filter and goes back to the top of the list.
+    pub fn cycle_list_view_filter(&mut self) {
+        self.list_view_filter = self.list_view_filter.next();
+        self.refresh_node_order();
+        self.scroll_offset = 0;
+        self.select_row(0);
+    }
+
+    /// Actions that make sense for the selected node, in menu order: the log needs a
+    /// log file, the URL a discovered metrics server, and raw metrics a real one.
+    pub fn available_node_actions(&self) -> Vec<NodeAction> {
+        let Some(dir_path) = self.selected_node() else {
+            return Vec::new();
+        };
+        let has_url = self.node_urls.contains_key(dir_path);
+        let live = matches!(
+            self.node_discovery_sources.get(dir_path),
+            Some(DiscoverySource::LogFile(_) | DiscoverySource::Override(_))
+        );
+        NodeAction::ALL
+            .into_iter()
+            .filter(|action| match action {
+                NodeAction::ShowDetails | NodeAction::ShowErrors | NodeAction::CopyPath => true,
+                NodeAction::OpenLog => node_log_file(dir_path).is_file(),
+                NodeAction::OpenMetrics => has_url && live,
+                NodeAction::CopyUrl => has_url,
+                // Stopped nodes have no discovery source, any node can be started
+                NodeAction::StartUnit | NodeAction::StopUnit | NodeAction::RestartUnit => {
+                    self.systemd.is_some()
+                }
+            })
+            .collect()
+    }
+
+    /// Marks the selected node, or unmarks it, and moves to the next row so that
+    /// consecutive nodes are marked with repeated presses.
+    pub fn toggle_mark(&mut self) {
+        let Some(dir_path) = self.selected_node().cloned() else {
+            return;
+        };
+        if !self.marked_nodes.remove(&dir_path) {
+            self.marked_nodes.insert(dir_path);
+        }
+        self.move_selection(1);
+    }
+
+    /// Pins the selected node to the top of the list, or unpins it. The selection
+    /// follows the node to its new row.
+    pub fn toggle_pin(&mut self) {
+        let Some(dir_path) = self.selected_node().cloned() else {
+            return;
+        };
+        if !self.pinned_nodes.remove(&dir_path) {
+            self.pinned_nodes.insert(dir_path);
+        }
+        self.refresh_node_order();
+        self.resolve_selection();
+    }
+
+    /// Marks every node of the current list view. Marks on hidden nodes are kept.
+    pub fn mark_all_visible(&mut self) {
+        let visible = self.visible_nodes().to_vec();
+        self.marked_nodes.extend(visible);
+    }
+
+    /// Unmarks the nodes of the current list view.
+    pub fn unmark_all_visible(&mut self) {
+        let visible = self.visible_nodes().to_vec();
+        for dir_path in visible {
+            self.marked_nodes.remove(&dir_path);
+        }
+    }
+
+    /// Current CPU, memory, bandwidth and records summed over the marked nodes.
+    pub fn marked_totals(&self) -> MarkedTotals {
+        let mut totals = MarkedTotals {
+            marked: self.marked_nodes.len(),
+            ..MarkedTotals::default()
+        };
+        for dir_path in &self.marked_nodes {
+            let Some((url, Ok(metrics))) = self
+                .node_urls
+                .get(dir_path)
+                .and_then(|url| Some((url, self.node_metrics.get(url)?)))
+            else {
+                continue;
+            };
+            let (data_in, data_out) = self.displayed_bandwidth(url, metrics);
+            totals.answering += 1;
+            totals.cpu += metrics.cpu_percent().unwrap_or(0.0);
+            totals.memory_mb += metrics.memory_used_mb.unwrap_or(0.0);
+            totals.speed_in_bps += metrics.speed_in_bps.unwrap_or(0.0);
+            totals.speed_out_bps += metrics.speed_out_bps.unwrap_or(0.0);
+            totals.data_in_bytes += data_in.unwrap_or(0);
+            totals.data_out_bytes += data_out.unwrap_or(0);
+            totals.records += metrics.records_stored.unwrap_or(0);
+        }
+        totals
+    }
+
+    /// Directory path of the selected node, if any. The node is followed wherever its
+    /// row went, the selected row only counts once the node is no longer visible.
+    pub fn selected_node(&self) -> Option<&String> {
+        let visible = self.visible_nodes();
+        let on_row = visible.get(self.selected);
+        match &self.selected_dir {
+            // Still on its row, as `resolve_selection` leaves it
+            Some(selected) if on_row == Some(selected) => on_row,
+            Some(selected) => visible
+                .iter()
+                .find(|dir_path| *dir_path == selected)
+                .or(on_row),
+            None => on_row,
+        }
+    }
+
+    /// Moves the selection by `delta` rows, staying within the visible node list.
+    pub fn move_selection(&mut self, delta: isize) {
+        self.resolve_selection();
+        self.select_row(self.selected.saturating_add_signed(delta));
+    }
+
+    /// Points `selected` back at the row of the selected node after the visible list
+    /// changed (new nodes, status changes under a view filter), so the selection
+    /// follows the node rather than the row. When the node is gone from the list, the
+    /// nearest row is selected instead.
+    pub fn resolve_selection(&mut self) {
+        let row = self.selected_dir.as_ref().and_then(|selected| {
+            self.visible_nodes()
+                .iter()
+                .position(|dir_path| dir_path == selected)
+        });
+        match row {
+            Some(row) => self.selected = row,
+            None => self.select_row(self.selected),
+        }
+    }
+
+    // Selects the node on `row` of the visible list, or on the last row past the end
+    fn select_row(&mut self, row: usize) {
+        let visible = self.visible_nodes();
+        let row = row.min(visible.len().saturating_sub(1));
+        self.selected_dir = visible.get(row).cloned();
+        self.selected = row;
+    }
+
+    /// Rows of the visible list the goto prompt input points at. A bare number is a
+    /// 1-based row number, anything else a case-insensitive prefix of the node name
+    /// (directory name or short ID).
+    pub fn goto_matches(&self) -> Vec<usize> {
+        let input = self.goto_input.trim().to_lowercase();
+        if input.is_empty() {
+            return Vec::new();
+        }
+        let visible = self.visible_nodes();
+        if let Ok(row) = input.parse::<usize>() {
+            return (1..=visible.len())
+                .contains(&row)
+                .then_some(row - 1)
+                .into_iter()
+                .collect();
+        }
+        visible
+            .iter()
+            .enumerate()
+            .filter(|(_, dir_path)| {
+                let dir_name = Path::new(dir_path.as_str())
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                dir_name.starts_with(&input)
+                    || self
+                        .node_display_name(dir_path)
+                        .to_lowercase()
+                        .starts_with(&input)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Selects the node the goto prompt input points at. Returns false, leaving the
+    /// selection alone, unless exactly one node matches.
+    pub fn goto_selected_input(&mut self) -> bool {
+        match self.goto_matches().as_slice() {
+            [index] => {
+                self.select_row(*index); // The table scrolls it into view
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Bytes received/sent by the node at `url` since antop first saw it.
+    pub fn session_bandwidth(
+        &self,
+        url: &str,
+        metrics: &NodeMetrics,
+    ) -> (Option<u64>, Option<u64>) {
+        let session_in = metrics.bandwidth_inbound_bytes.map(|current| {
+            current.saturating_sub(
+                self.initial_bandwidth_in
+                    .get(url)
+                    .copied()
+                    .unwrap_or(current),
+            )
+        });
+        let session_out = metrics.bandwidth_outbound_bytes.map(|current| {
+            current.saturating_sub(
+                self.initial_bandwidth_out
+                    .get(url)
+                    .copied()
+                    .unwrap_or(current),
+            )
+        });
+        (session_in, session_out)
+    }
+
+    /// Bytes received/sent by the node at `url` according to the current totals mode.
+    pub fn displayed_bandwidth(
+        &self,
+        url: &str,
+        metrics: &NodeMetrics,
+    ) -> (Option<u64>, Option<u64>) {
+        match self.totals_mode {
+            TotalsMode::Lifetime => (
+                metrics.bandwidth_inbound_bytes,
+                metrics.bandwidth_outbound_bytes,
+            ),
+            TotalsMode::Session => self.session_bandwidth(url, metrics),
+        }
+    }
+
+    /// Fleet-wide bytes received/sent since antop started, whatever the totals mode.
+    /// Nodes restarted since then count from their restart.
+    pub fn rolling_bandwidth_total(&self) -> (u64, u64) {
+        (
+            self.summary_session_bandwidth_in_bytes,
+            self.summary_session_bandwidth_out_bytes,
+        )
+    }
+
+    /// Fleet-wide bytes received/sent according to the current totals mode.
+    pub fn displayed_summary_bandwidth(&self) -> (u64, u64) {
+        match self.totals_mode {
+            TotalsMode::Lifetime => (
+                self.summary_total_data_in_bytes,
+                self.summary_total_data_out_bytes,
+            ),
+            TotalsMode::Session => self.rolling_bandwidth_total(),
+        }
+    }
+
+    /// Raises or lowers the number of nodes fetched at once, doubling or halving it.
+    /// Raising it to the number of nodes or more lifts the limit, lowering it from
+    /// unlimited starts at half the nodes.
+    pub fn adjust_fetch_concurrency(&mut self, increase: bool) {
+        let node_count = self.node_urls.len().max(1);
+        self.max_concurrent_fetches = match (self.max_concurrent_fetches, increase) {
+            (None, true) => None,
+            (None, false) => Some((node_count / 2).max(1)),
+            (Some(limit), true) if limit * 2 >= node_count => None,
+            (Some(limit), true) => Some(limit * 2),
+            (Some(limit), false) => Some((limit / 2).max(1)),
+        };
+    }
+
+    /// Adjusts the application's tick rate (update interval) through discrete levels.
+    /// `increase`: true to increase interval (slower updates), false to decrease (faster updates).
+    pub fn adjust_tick_rate(&mut self, increase: bool) {
+        // Find the current index in the TICK_LEVELS array
+        let current_index = TICK_LEVELS.iter().position(|&d| d == self.tick_rate);
+
+        let new_index = match current_index {
+            Some(index) => {
+                if increase {
+                    (index + 1).min(TICK_LEVELS.len() - 1)
+                } else {
+                    index.saturating_sub(1)
+                }
+            }
+            None => {
+                // If current tick_rate isn't exactly in levels, find the closest
+                if increase {
+                    // Find first level *greater than* current
+                    TICK_LEVELS
+                        .iter()
+                        .position(|&d| d > self.tick_rate)
+                        .unwrap_or(TICK_LEVELS.len() - 1) // Default to max if none greater
+                } else {
+                    // Find last level *less than* current
+                    TICK_LEVELS
+                        .iter()
+                        .rposition(|&d| d < self.tick_rate)
+                        .unwrap_or(0) // Default to min if none smaller
+                }
+            }
+        };
+
+        self.tick_rate = TICK_LEVELS[new_index];
+
+        // Optional: Add a status message (can be done in ui/run_app instead)
+        // self.status_message = Some(format!("Update interval set to: {:.1?}s", self.tick_rate.as_secs_f64()));
+    }
+
+    /// Sets the update interval, kept within the fastest and slowest of `TICK_LEVELS`.
+    /// `+` / `-` step on from the nearest level when it is not one of them.
+    pub fn set_tick_rate(&mut self, tick_rate: Duration) {
+        self.tick_rate = tick_rate.clamp(TICK_LEVELS[0], TICK_LEVELS[TICK_LEVELS.len() - 1]);
+    }
+}
+
+/// Assigns short IDs (1, 2, 3...) to node directories following their natural sort order,
+/// so a node keeps its ID across restarts as long as the set of directories is unchanged.
+pub fn assign_node_ids(dirs: &[String]) -> HashMap<String, u16> {
+    let mut sorted: Vec<&String> = dirs.iter().collect();
+    sorted.sort_by(|a, b| compare_node_dirs(a, b));
+    sorted
+        .into_iter()
+        .enumerate()
+        .map(|(index, dir)| (dir.clone(), (index + 1).min(u16::MAX as usize) as u16))
+        .collect()
+}
+
+/// Appends an up/down change when the state differs from the last one, and drops the
+/// changes older than `span` except the last of them, which gives the state at its start.
+fn record_availability(
+    changes: &mut VecDeque<(Instant, bool)>,
+    up: bool,
+    now: Instant,
+    span: Duration,
+) {
+    if changes.back().is_none_or(|(_, last_up)| *last_up != up) {
+        changes.push_back((now, up));
+    }
+    if let Some(window_start) = now.checked_sub(span) {
+        while changes.len() > 1 && changes[1].0 <= window_start {
+            changes.pop_front();
+        }
+    }
+}
+
+/// Unix milliseconds of `instant`, from the matching `now` and `wall_now`.
+fn unix_millis(instant: Instant, now: Instant, wall_now: SystemTime) -> u64 {
+    let wall = wall_now
+        .checked_sub(now.saturating_duration_since(instant))
+        .unwrap_or(UNIX_EPOCH);
+    wall.duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_millis() as u64)
+}
+
+/// Instant of the Unix milliseconds `millis`, from the matching `now` and `wall_now`.
+/// None when it is before the monotonic clock started, later than now is now.
+fn instant_at(millis: u64, now: Instant, wall_now: SystemTime) -> Option<Instant> {
+    let wall = UNIX_EPOCH + Duration::from_millis(millis);
+    now.checked_sub(wall_now.duration_since(wall).unwrap_or_default())
+}
+
+/// Every metric value of a node for the warning report, "-" when the node doesn't report it.
+fn report_metric_values(metrics: &NodeMetrics) -> Vec<(&'static str, String)> {
+    fn value<T: std::fmt::Display>(value: Option<T>) -> String {
+        value.map_or_else(|| "-".to_string(), |value| value.to_string())
+    }
+    vec![
+        ("uptime_seconds", value(metrics.uptime_seconds)),
+        (
+            "memory_used_mb",
+            value(metrics.memory_used_mb.map(|mb| format!("{:.1}", mb))),
+        ),
+        (
+            "cpu_usage_percentage",
+            value(
+                metrics
+                    .cpu_usage_percentage
+                    .map(|cpu| format!("{:.2}", cpu)),
+            ),
+        ),
+        ("connected_peers", value(metrics.connected_peers)),
+        (
+            "peers_in_routing_table",
+            value(metrics.peers_in_routing_table),
+        ),
+        (
+            "estimated_network_size",
+            value(metrics.estimated_network_size),
+        ),
+        (
+            "bandwidth_inbound_bytes",
+            value(metrics.bandwidth_inbound_bytes),
+        ),
+        (
+            "bandwidth_outbound_bytes",
+            value(metrics.bandwidth_outbound_bytes),
+        ),
+        (
+            "speed_in_bps",
+            value(metrics.speed_in_bps.map(|bps| format!("{:.0}", bps))),
+        ),
+        (
+            "speed_out_bps",
+            value(metrics.speed_out_bps.map(|bps| format!("{:.0}", bps))),
+        ),
+        ("records_stored", value(metrics.records_stored)),
+        (
+            "reward_wallet_balance",
+            value(metrics.reward_wallet_balance),
+        ),
+        ("put_record_errors", value(metrics.put_record_errors)),
+        (
+            "incoming_connection_errors",
+            value(metrics.incoming_connection_errors),
+        ),
+        (
+            "outgoing_connection_errors",
+            value(metrics.outgoing_connection_errors),
+        ),
+        (
+            "kad_get_closest_peers_errors",
+            value(metrics.kad_get_closest_peers_errors),
+        ),
+        ("shunned_count", value(metrics.shunned_count)),
+        (
+            "connections_dialer_total",
+            value(metrics.connections_dialer_total),
+        ),
+        (
+            "connections_listener_total",
+            value(metrics.connections_listener_total),
+        ),
+        ("relay_hop_requests", value(metrics.relay_hop_requests)),
+        (
+            "relay_hop_circuits_accepted",
+            value(metrics.relay_hop_circuits_accepted),
+        ),
+    ]
+}
+
+/// Returns the log file of a node: `<node_dir>/logs/antnode.log`.
+pub fn node_log_file(dir_path: &str) -> PathBuf {
+    Path::new(dir_path).join("logs").join("antnode.log")
+}
+
+/// Formats a short node ID for display, e.g. `#007`.
+pub fn format_node_id(id: u16) -> String {
+    format!("#{:03}", id)
+}
+
+/// Appends `value` to `history`, dropping the oldest entries beyond `max_len`.
+fn push_bounded<T>(history: &mut VecDeque<T>, value: T, max_len: usize) {
+    history.push_back(value);
+    trim_front(history, max_len);
+}
+
+/// Header title of `--title`, with `{host}` replaced with the local hostname.
+fn expand_title(title: &str) -> String {
+    if !title.contains("{host}") {
+        return title.to_string();
+    }
+    let host = hostname::get()
+        .map(|host| host.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "unknown".to_string());
+    title.replace("{host}", &host)
+}
+
+/// Drops the oldest entries of `history` beyond `max_len`.
+fn trim_front<T>(history: &mut VecDeque<T>, max_len: usize) {
+    while history.len() > max_len {
+        history.pop_front();
+    }
+}
+
+/// Keeps the restored histories of `known_urls` only, trimmed to `max_len`.
+fn restore_histories<T>(
+    histories: HashMap<String, VecDeque<T>>,
+    known_urls: &HashSet<String>,
+    max_len: usize,
+) -> HashMap<String, VecDeque<T>> {
+    histories
+        .into_iter()
+        .filter(|(url, _)| known_urls.contains(url))
+        .map(|(url, mut history)| {
+            trim_front(&mut history, max_len);
+            (url, history)
+        })
+        .collect()
+}
+
+/// Slope of the least squares line through the memory samples of a node, in MB per
+/// sample. A steadily positive slope hints at a memory leak, the caller converts it to
+/// MB per minute with `MEMORY_SAMPLE_INTERVAL`. None until `MEMORY_TREND_MIN_SAMPLES` samples.
+pub fn detect_memory_leak(history: &VecDeque<u64>) -> Option<f64> {
+    if history.len() < MEMORY_TREND_MIN_SAMPLES {
+        return None;
+    }
+    let count = history.len() as f64;
+    let mean_x = (count - 1.0) / 2.0;
+    let mean_y = history.iter().sum::<u64>() as f64 / count;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (i, &memory) in history.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        covariance += dx * (memory as f64 - mean_y);
+        variance += dx * dx;
+    }
+    Some(covariance / variance)
+}
+
+/// Average per-second rate of a counter across a window of samples.
+/// Only the most recent run of non-decreasing values is used, so a node restart
+/// (counter going back to zero) doesn't produce a bogus negative or huge rate.
+/// Returns None until at least two usable samples are available.
+fn windowed_rate(
+    samples: &VecDeque<CounterSample>,
+    counter: impl Fn(&CounterSample) -> Option<u64>,
+) -> Option<f64> {
+    let newest = samples.back()?;
+    let newest_value = counter(newest)?;
+
+    let mut oldest = (newest.at, newest_value);
+    for sample in samples.iter().rev().skip(1) {
+        match counter(sample) {
+            Some(value) if value <= oldest.1 => oldest = (sample.at, value),
+            _ => break,
+        }
+    }
+
+    let elapsed = newest.at.duration_since(oldest.0).as_secs_f64();
+    if elapsed > 0.0 {
+        Some((newest_value - oldest.1) as f64 / elapsed)
+    } else {
+        None
+    }
+}
+
+/// Per-second change of a gauge (like live peers) across the samples of the rate
+/// window, negative when it went down. Returns None until two samples have a value.
+fn windowed_change_rate(
+    samples: &VecDeque<CounterSample>,
+    gauge: impl Fn(&CounterSample) -> Option<u64>,
+) -> Option<f64> {
+    let (newest_at, newest) = samples
+        .iter()
+        .rev()
+        .find_map(|sample| Some((sample.at, gauge(sample)?)))?;
+    let (oldest_at, oldest) = samples
+        .iter()
+        .find_map(|sample| Some((sample.at, gauge(sample)?)))?;
+    let elapsed = newest_at.duration_since(oldest_at).as_secs_f64();
+    (elapsed > 0.0).then(|| (newest as f64 - oldest as f64) / elapsed)
+}
+
+/// Total RAM of this machine, for the fleet memory gauge. None where it can't be read.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn system_memory_bytes() -> Option<u64> {
+    nix::sys::sysinfo::sysinfo()
+        .ok()
+        .map(|info| info.ram_total())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn system_memory_bytes() -> Option<u64> {
+    None
+}
+
+/// Space of the fullest of the filesystems holding `paths`, None when none can be
+/// queried. Paths on the same filesystem are counted once.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // The statvfs types are 32-bit on some platforms
+fn filesystem_usage<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> Option<FilesystemUsage> {
+    let mut filesystems = HashMap::new();
+    for path in paths {
+        let Ok(stat) = nix::sys::statvfs::statvfs(path.as_path()) else {
+            continue;
+        };
+        let fragment_size = stat.fragment_size() as u64;
+        let used_bytes =
+            (stat.blocks() as u64).saturating_sub(stat.blocks_free() as u64) * fragment_size;
+        let total_bytes = used_bytes + stat.blocks_available() as u64 * fragment_size;
+        filesystems.insert(stat.filesystem_id(), (used_bytes, total_bytes));
+    }
+    let count = filesystems.len();
+    filesystems
+        .into_values()
+        .filter(|&(_, total_bytes)| total_bytes > 0)
+        .max_by(|a, b| (a.0 as f64 / a.1 as f64).total_cmp(&(b.0 as f64 / b.1 as f64)))
+        .map(|(used_bytes, total_bytes)| FilesystemUsage {
+            used_bytes,
+            total_bytes,
+            filesystems: count,
+        })
+}
+
+#[cfg(not(unix))]
+fn filesystem_usage<'a>(_paths: impl Iterator<Item = &'a PathBuf>) -> Option<FilesystemUsage> {
+    None
+}
 
 /// Recursively calculate the total size of a directory.
 /// Includes basic error handling for permissions etc.
@@ -396,6 +3095,15 @@ fn calculate_dir_size(path: &PathBuf) -> io::Result<u64> {
     Ok(total_size)
 }
 
+// What identifies an alert of `App::node_alerts` from one update to the next: its text,
+// except for the error of a down node and the ratio of a NAT suspect, which vary
+fn alert_key(alert: &str) -> &str {
+    match alert.split_once(':') {
+        Some((kind @ ("Down" | "NAT?"), _)) => kind,
+        _ => alert,
+    }
+}
+
 // --- Helper for Natural Sorting Node Directories ---
 
 // Extracts the non-numeric prefix and the numeric suffix from a path's filename.
@@ -405,11 +3113,15 @@ fn extract_prefix_suffix(path_str: &str) -> (String, u64) {
         .and_then(|name| name.to_str())
         .unwrap_or(path_str);
 
-    let numeric_suffix_start = file_name.rfind(|c: char| !c.is_ascii_digit());
+    // Byte index right after the last non-digit, which may be a multi-byte character
+    let numeric_suffix_start = file_name
+        .char_indices()
+        .rfind(|(_, c)| !c.is_ascii_digit())
+        .map(|(index, c)| index + c.len_utf8());
     match numeric_suffix_start {
         Some(index) => {
-            let prefix = file_name[..=index].to_string();
-            let suffix_str = &file_name[index + 1..];
+            let prefix = file_name[..index].to_string();
+            let suffix_str = &file_name[index..];
             let suffix_num = suffix_str.parse::<u64>().unwrap_or(0); // Default to 0 if no suffix or parse fails
             (prefix, suffix_num)
         }
@@ -435,3 +3147,536 @@ fn compare_node_dirs(a: &str, b: &str) -> Ordering {
         .cmp(&prefix_b)
         .then_with(|| suffix_a.cmp(&suffix_b))
 }
+
+#[cfg(test)]
+impl App {
+    /// An app watching a node in each of `dirs`, with the default settings and the
+    /// metrics URL `http://127.0.0.1:<13001 + index>/metrics`. Nothing fetched yet.
+    pub fn for_tests(dirs: &[&str]) -> App {
+        let discovered = dirs
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| DiscoveredNode {
+                dir_path: dir.to_string(),
+                url: format!("http://127.0.0.1:{}/metrics", 13001 + i),
+                source: DiscoverySource::Demo,
+            })
+            .collect();
+        let dirs = dirs.iter().map(|dir| dir.to_string()).collect();
+        App::new(dirs, discovered, &Settings::for_tests())
+    }
+
+    /// One update where the node on row `i` of `nodes` answers `answer(i)`.
+    pub fn answer_all(&mut self, answer: impl Fn(usize) -> Result<String, FetchError>) {
+        let results = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, dir_path)| {
+                Some(FetchResult {
+                    url: self.node_urls.get(dir_path)?.clone(),
+                    body: answer(i),
+                })
+            })
+            .collect();
+        self.update_metrics(results);
+    }
+}
+
+/// Metrics text of a node with `records` records, `rewards` in its wallet and 25
+/// connected peers, as antnode serves it.
+#[cfg(test)]
+pub fn test_metrics_body(records: u64, rewards: u64) -> String {
+    format!(
+        "# HELP ant_node_uptime Uptime\n\
+         ant_node_uptime 93784\n\
+         ant_networking_process_memory_used_mb 212.5\n\
+         ant_networking_process_cpu_usage_percentage 3.25\n\
+         ant_networking_connected_peers 25\n\
+         ant_networking_peers_in_routing_table 180\n\
+         ant_networking_records_stored {}\n\
+         ant_node_current_reward_wallet_balance {}\n\
+         libp2p_bandwidth_bytes_total{{direction=\"Inbound\"}} 1500000\n\
+         libp2p_bandwidth_bytes_total{{direction=\"Outbound\"}} 900000\n",
+        records, rewards
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRS: [&str; 2] = ["/nodes/antnode-1", "/nodes/antnode-2"];
+
+    fn restore(app: &App, dirs: &[&str]) -> App {
+        let data = app.serialize_state().unwrap();
+        let fresh = App::for_tests(dirs);
+        let discovered = fresh
+            .node_urls
+            .iter()
+            .map(|(dir_path, url)| DiscoveredNode {
+                dir_path: dir_path.clone(),
+                url: url.clone(),
+                source: DiscoverySource::Demo,
+            })
+            .collect();
+        App::deserialize_state(
+            &data,
+            fresh.nodes.clone(),
+            discovered,
+            &Settings::for_tests(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn state_keeps_the_availability_timeline() {
+        let mut app = App::for_tests(&DIRS);
+        let now = Instant::now();
+        let bucket = app.timeline_bucket_duration();
+        app.availability_changes.insert(
+            DIRS[0].to_string(),
+            VecDeque::from([(now - bucket * 7 / 2, true), (now - bucket * 3 / 2, false)]),
+        );
+
+        let restored = restore(&app, &DIRS);
+        let changes = &restored.availability_changes[DIRS[0]];
+        assert_eq!(changes.len(), 2);
+        for ((saved_at, saved_up), (restored_at, restored_up)) in
+            app.availability_changes[DIRS[0]].iter().zip(changes)
+        {
+            assert_eq!(saved_up, restored_up);
+            // Milliseconds in the file, and a little time passes between the two clocks
+            let drift = restored_at
+                .max(saved_at)
+                .duration_since(*restored_at.min(saved_at));
+            assert!(drift < Duration::from_millis(50), "drifted by {:?}", drift);
+        }
+        let timeline = restored.availability_timeline(DIRS[0]);
+        assert_eq!(timeline[TIMELINE_BUCKETS - 3], Availability::Up);
+        assert_eq!(timeline[TIMELINE_BUCKETS - 1], Availability::Down);
+        assert!(!restored.availability_changes.contains_key(DIRS[1]));
+    }
+
+    #[test]
+    fn state_drops_the_timelines_of_gone_nodes_and_old_changes() {
+        let mut app = App::for_tests(&DIRS);
+        let now = Instant::now();
+        let span = app.timeline_span();
+        let old = now.checked_sub(span * 2).unwrap();
+        app.availability_changes.insert(
+            DIRS[0].to_string(),
+            VecDeque::from([
+                (old, true),
+                (old + Duration::from_secs(1), false),
+                (now - span / 2, true),
+            ]),
+        );
+        app.availability_changes
+            .insert(DIRS[1].to_string(), VecDeque::from([(now, true)]));
+
+        let restored = restore(&app, &DIRS[..1]);
+        // The last change before the span gives the state at its start
+        let ups: Vec<bool> = restored.availability_changes[DIRS[0]]
+            .iter()
+            .map(|(_, up)| *up)
+            .collect();
+        assert_eq!(ups, [false, true]);
+        assert!(!restored.availability_changes.contains_key(DIRS[1]));
+    }
+
+    #[test]
+    fn wall_clock_conversion_round_trips() {
+        let (now, wall_now) = (Instant::now(), SystemTime::now());
+        let instant = now - Duration::from_secs(90);
+        let millis = unix_millis(instant, now, wall_now);
+        let restored = instant_at(millis, now, wall_now).unwrap();
+        // Up to a millisecond earlier, the file has no finer time
+        assert!(restored <= instant && instant - restored < Duration::from_millis(1));
+        // A change saved with a clock ahead of this one is taken as now
+        let ahead = unix_millis(now, now, wall_now) + 60_000;
+        assert_eq!(instant_at(ahead, now, wall_now), Some(now));
+    }
+
+    #[test]
+    fn timeline_buckets_are_up_down_or_partial() {
+        use Availability::{Down, Partial, Unknown, Up};
+        let mut app = App::for_tests(&DIRS);
+        app.tick_rate = Duration::from_secs(1);
+        let now = Instant::now();
+        let bucket = app.timeline_bucket_duration();
+        // Down from the middle of the second cell, up from the middle of the fourth,
+        // down again from the middle of the eleventh
+        app.availability_changes.insert(
+            DIRS[0].to_string(),
+            VecDeque::from([
+                (now - bucket * 21 / 2, false),
+                (now - bucket * 17 / 2, true),
+                (now - bucket * 3 / 2, false),
+            ]),
+        );
+
+        assert_eq!(
+            app.availability_timeline(DIRS[0]),
+            [
+                Unknown, Down, Down, Partial, Up, Up, Up, Up, Up, Up, Partial, Down
+            ]
+        );
+        assert_eq!(
+            app.availability_timeline(DIRS[1]),
+            [Unknown; TIMELINE_BUCKETS]
+        );
+    }
+
+    #[test]
+    fn timeline_span_follows_the_tick_rate() {
+        use Availability::{Down, Partial, Unknown, Up};
+        let mut app = App::for_tests(&DIRS);
+        let now = Instant::now();
+        app.availability_changes.insert(
+            DIRS[0].to_string(),
+            VecDeque::from([
+                (now - Duration::from_secs(325), false),
+                (now - Duration::from_millis(32_500), true),
+            ]),
+        );
+
+        // 5 seconds per cell, the last minute
+        app.tick_rate = Duration::from_secs(1);
+        assert_eq!(app.timeline_span(), Duration::from_secs(60));
+        assert_eq!(
+            app.availability_timeline(DIRS[0]),
+            [
+                Down, Down, Down, Down, Down, Partial, Up, Up, Up, Up, Up, Up
+            ]
+        );
+
+        // 50 seconds per cell, the last 10 minutes
+        app.tick_rate = Duration::from_secs(10);
+        assert_eq!(app.timeline_span(), Duration::from_secs(600));
+        assert_eq!(
+            app.availability_timeline(DIRS[0]),
+            [
+                Unknown, Unknown, Unknown, Unknown, Unknown, Down, Down, Down, Down, Down, Down,
+                Partial
+            ]
+        );
+    }
+
+    const FLEET: [&str; 4] = [
+        "/nodes/antnode-1",
+        "/nodes/antnode-2",
+        "/nodes/antnode-3",
+        "/nodes/antnode-4",
+    ];
+
+    #[test]
+    fn selection_follows_the_node_when_the_rows_are_sorted_again() {
+        let mut app = App::for_tests(&FLEET);
+        app.answer_all(|i| Ok(test_metrics_body(100 * (i as u64 + 1), 0)));
+        app.move_selection(0);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[0]));
+
+        // Most records first, antnode-1 has the fewest
+        app.sort_key = SortKey::Records;
+        app.refresh_node_order();
+        app.resolve_selection();
+        assert_eq!(app.selected, 3);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[0]));
+
+        // Now it has the most
+        app.answer_all(|i| Ok(test_metrics_body(100 * (4 - i as u64), 0)));
+        app.resolve_selection();
+        assert_eq!(app.selected, 0);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[0]));
+    }
+
+    #[test]
+    fn selection_falls_back_to_the_nearest_row_when_the_node_is_filtered_out() {
+        let mut app = App::for_tests(&FLEET);
+        app.list_view_filter = ListViewFilter::Running;
+        app.answer_all(|_| Ok(test_metrics_body(100, 0)));
+        app.move_selection(2);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[2]));
+
+        // antnode-3 fails and leaves the running list, antnode-4 moves up to its row
+        let fail = |failing: &'static [usize]| {
+            move |i| match i {
+                i if failing.contains(&i) => {
+                    Err(FetchError::new(FetchErrorKind::Timeout, "Timed out"))
+                }
+                _ => Ok(test_metrics_body(100, 0)),
+            }
+        };
+        app.answer_all(fail(&[2]));
+        app.resolve_selection();
+        assert_eq!(app.selected, 2);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[3]));
+
+        // antnode-4 fails too, the row above the end of the list is selected
+        app.answer_all(fail(&[2, 3]));
+        app.resolve_selection();
+        assert_eq!(app.selected, 1);
+        assert_eq!(app.selected_node().map(String::as_str), Some(FLEET[1]));
+    }
+
+    #[test]
+    fn bodies_that_are_not_prometheus_text_are_bad_bodies() {
+        let bodies = [
+            "<!DOCTYPE html>\n<html><body><h1>Welcome</h1></body></html>\n",
+            "{\"status\": \"ok\", \"records\": 12}",
+            "",
+            // The least a node serves, one known metric without any HELP line
+            "ant_networking_records_stored 12\n",
+        ];
+        let mut app = App::for_tests(&FLEET);
+        app.answer_all(|i| Ok(bodies[i].to_string()));
+
+        let url = |i: usize| &app.node_urls[FLEET[i]];
+        for (i, looks_like) in ["HTML", "JSON", "an empty body"].into_iter().enumerate() {
+            assert_eq!(
+                app.fetch_error_kinds.get(url(i)),
+                Some(&FetchErrorKind::Body)
+            );
+            let error = app.node_metrics[url(i)].as_ref().unwrap_err();
+            assert!(error.contains(looks_like), "{}", error);
+        }
+        assert!(!app.fetch_error_kinds.contains_key(url(3)));
+        let metrics = app.node_metrics[url(3)].as_ref().unwrap();
+        assert_eq!(metrics.records_stored, Some(12));
+    }
+
+    #[test]
+    fn a_node_going_stale_is_a_node_going_down_once() {
+        let mut app = App::for_tests(&DIRS);
+        app.stale_threshold = 2;
+        app.bell = true;
+        // antnode-1 keeps serving the same metrics, antnode-2 new ones at each update
+        let mut round = 0;
+        let mut update = |app: &mut App| {
+            round += 1;
+            app.answer_all(|i| Ok(test_metrics_body(if i == 0 { 100 } else { round }, 0)));
+        };
+        update(&mut app);
+        update(&mut app);
+        assert!(app.status_message.is_none());
+
+        update(&mut app);
+        assert!(app.is_stale(&app.node_urls[DIRS[0]]));
+        let message = app.status_message.take().unwrap();
+        assert_eq!(message.severity, Severity::Warning);
+        assert_eq!(message.text, "Node down: antnode-1");
+        assert!(app.row_flash_until.contains_key(DIRS[0]));
+        assert!(app.bell_pending);
+
+        // Still stale, nothing new
+        app.bell_pending = false;
+        update(&mut app);
+        assert!(app.status_message.is_none());
+        assert!(!app.bell_pending);
+
+        // New metrics again
+        app.answer_all(|i| Ok(test_metrics_body(200, i as u64)));
+        let message = app.status_message.unwrap();
+        assert_eq!(message.severity, Severity::Info);
+        assert_eq!(message.text, "Back up: antnode-1");
+    }
+
+    #[test]
+    fn recoveries_and_failures_of_one_update_share_a_message() {
+        let mut app = App::for_tests(&DIRS);
+        let fail = |failing: usize| {
+            move |i| match i {
+                i if i == failing => Err(FetchError::new(FetchErrorKind::Timeout, "Timed out")),
+                _ => Ok(test_metrics_body(i as u64, 0)),
+            }
+        };
+        app.answer_all(fail(0));
+        app.answer_all(fail(1));
+        let message = app.status_message.unwrap();
+        assert_eq!(message.severity, Severity::Warning);
+        assert_eq!(message.text, "Node down: antnode-2; Back up: antnode-1");
+    }
+
+    #[test]
+    fn status_messages_are_cleared_after_their_duration() {
+        let mut app = App::for_tests(&DIRS);
+        let now = Instant::now();
+        app.set_status(Severity::Info, "Saved");
+        app.clear_status_message_after_duration(now + Duration::from_secs(2));
+        assert!(app.status_message.is_some());
+        app.clear_status_message_after_duration(now + Duration::from_secs(4));
+        assert!(app.status_message.is_none());
+
+        app.set_status(Severity::Error, "Failed");
+        app.clear_status_message_after_duration(now + Duration::from_secs(4));
+        assert!(app.status_message.is_some());
+        app.clear_status_message_after_duration(now + Duration::from_secs(11));
+        assert!(app.status_message.is_none());
+
+        app.set_status_for(Severity::Info, "Longer", Duration::from_secs(60));
+        app.clear_status_message_after_duration(now + Duration::from_secs(30));
+        assert!(app.status_message.is_some());
+        assert_eq!(app.status_history.len(), 3);
+    }
+
+    #[test]
+    fn a_key_press_only_dismisses_info_messages() {
+        let mut app = App::for_tests(&DIRS);
+        app.set_status(Severity::Warning, "Node down");
+        app.dismiss_status_message();
+        assert!(app.status_message.is_some());
+        app.set_status(Severity::Error, "Fetch failed");
+        app.dismiss_status_message();
+        assert!(app.status_message.is_some());
+        app.set_status(Severity::Info, "Saved");
+        app.dismiss_status_message();
+        assert!(app.status_message.is_none());
+        assert!(app.status_message_expiry.is_none());
+    }
+
+    fn grouped_app(dirs: &[&str]) -> App {
+        let mut app = App::for_tests(dirs);
+        app.group_by_prefix = true;
+        app.regroup_nodes();
+        app
+    }
+
+    #[test]
+    fn nodes_are_grouped_by_name_without_their_number() {
+        let app = App::for_tests(&[]);
+        assert_eq!(app.node_group("/srv/antnode-12"), "antnode");
+        assert_eq!(app.node_group("/srv/vps1-node-3"), "vps1-node");
+        assert_eq!(app.node_group("/srv/node_07"), "node");
+        assert_eq!(app.node_group("/srv/archive"), "archive");
+        assert_eq!(app.node_group("/srv/42"), "42");
+    }
+
+    #[test]
+    fn group_summaries_add_up_their_nodes() {
+        let mut app = grouped_app(&["/a/east-1", "/a/east-2", "/a/west-1"]);
+        app.answer_all(|i| match i {
+            1 => Err(FetchError::new(FetchErrorKind::Connection, "refused")),
+            _ => Ok(test_metrics_body(100 + i as u64, 10)),
+        });
+        let east = app.node_group_summary("east").unwrap();
+        assert_eq!((east.nodes, east.running, east.errors), (2, 1, 1));
+        assert_eq!((east.records, east.rewards), (100, 10));
+        let west = app.node_group_summary("west").unwrap();
+        assert_eq!((west.nodes, west.running, west.records), (1, 1, 102));
+    }
+
+    #[test]
+    fn only_the_groups_of_fetched_nodes_are_computed_again() {
+        let mut app = grouped_app(&["/a/east-1", "/a/east-2", "/a/west-1"]);
+        app.answer_all(|_| Ok(test_metrics_body(1, 1)));
+        let computed_at = |app: &App, group: &str| app.group_cache[group].1;
+        let (east_at, west_at) = (computed_at(&app, "east"), computed_at(&app, "west"));
+
+        // A west node changed, its group is computed again, east keeps its summary
+        let west_url = app.node_urls["/a/west-1"].clone();
+        let fetched = |urls: &[String], records| {
+            urls.iter()
+                .map(|url| FetchResult {
+                    url: url.clone(),
+                    body: Ok(test_metrics_body(records, 1)),
+                })
+                .collect::<Vec<_>>()
+        };
+        app.refresh_group_cache(&HashSet::from([west_url.clone()]));
+        assert_eq!(computed_at(&app, "east"), east_at);
+        assert!(computed_at(&app, "west") > west_at);
+
+        // The east nodes left out of an update lose their metrics, they change as well
+        app.update_metrics(fetched(&[west_url], 5));
+        assert_eq!(app.node_group_summary("west").unwrap().records, 5);
+        assert_eq!(app.node_group_summary("east").unwrap().running, 0);
+    }
+
+    #[test]
+    fn cached_summaries_match_a_full_computation() {
+        let dirs: Vec<String> = (0..60).map(|i| format!("/a/g{}-{}", i % 7, i)).collect();
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+        let mut app = grouped_app(&dirs);
+        for update in 0..3u64 {
+            app.answer_all(|i| Ok(test_metrics_body(update * 100 + i as u64, update)));
+        }
+        app.add_node("/a/g3-60".to_string());
+        assert_eq!(app.group_members["g3"].len(), 10);
+        for (group, members) in &app.group_members {
+            assert_eq!(
+                app.node_group_summary(group),
+                Some(&app.compute_group_summary(members)),
+                "{}",
+                group
+            );
+        }
+    }
+
+    #[test]
+    fn the_cache_empties_without_grouping() {
+        let mut app = grouped_app(&["/a/east-1"]);
+        app.answer_all(|_| Ok(test_metrics_body(1, 1)));
+        assert!(!app.group_cache.is_empty());
+        app.group_by_prefix = false;
+        app.regroup_nodes();
+        app.answer_all(|_| Ok(test_metrics_body(1, 1)));
+        assert!(app.group_members.is_empty() && app.group_cache.is_empty());
+    }
+
+    // cargo test --release group_cache_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn group_cache_benchmark() {
+        const NODES: usize = 2_000;
+        const GROUPS: usize = 200;
+        const ROUNDS: u32 = 200;
+        let dirs: Vec<String> = (0..NODES)
+            .map(|i| format!("/bench/host{}-node-{}", i % GROUPS, i))
+            .collect();
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+        let mut app = grouped_app(&dirs);
+        app.answer_all(|i| Ok(test_metrics_body(i as u64, 1)));
+
+        // A typical sweep of a large fleet where a few nodes changed
+        let changed: HashSet<String> = app
+            .nodes
+            .iter()
+            .take(NODES / 100)
+            .map(|dir_path| app.node_urls[dir_path].clone())
+            .collect();
+
+        // Every node sorted into its group and every group summed, at each update
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for dir_path in &app.nodes {
+                groups
+                    .entry(app.node_group(dir_path))
+                    .or_default()
+                    .push(dir_path.clone());
+            }
+            let summaries: Vec<GroupSummary> = groups
+                .values()
+                .map(|members| app.compute_group_summary(members))
+                .collect();
+            std::hint::black_box(summaries);
+        }
+        let uncached = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            app.refresh_group_cache(&changed);
+        }
+        let cached = start.elapsed() / ROUNDS;
+
+        println!(
+            "{} nodes in {} groups, {} changed: uncached {:?}, cached {:?} per update",
+            NODES,
+            GROUPS,
+            changed.len(),
+            uncached,
+            cached
+        );
+    }
+}
@@ -1,20 +1,321 @@
-use clap::Parser;
-
-/// Returns the default path for nodes, expanding the tilde.
-fn default_node_path() -> String {
-    shellexpand::tilde("~/.local/share/autonomi/node/*").into_owned()
-}
+use crate::{
+    alert::SmtpTls,
+    app::{ChartMarker, GapFill, Notation, SizeUnits, SortKey, StorageGauge},
+};
+use antop::discovery::MatchStrategy;
+use clap::{Parser, Subcommand};
 
 /// Command-line arguments
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
     /// Glob pattern for the root directories of the running nodes
-    #[arg(long, default_value_t = default_node_path())]
-    pub path: String,
+    /// [default: ~/.local/share/autonomi/node/*]
+    #[arg(long)]
+    pub path: Option<String>,
 
     /// Glob pattern for the log files to scan for metrics server addresses (e.g., "/path/to/nodes/*/logs/antnode.log")
     /// If not specified, it defaults to the node path appended with "/logs/antnode.log".
     #[arg(long)]
     pub log_path: Option<String>,
+
+    /// Log file glob of each node, `{name}` replaced with the basename of its directory
+    /// (e.g. "/var/log/antnode/{name}/current"), for logs kept away from the node
+    /// directories. Replaces `--log-path`
+    #[arg(long, value_name = "PATTERN")]
+    pub discovery_log_glob_override: Option<String>,
+
+    /// Which "Metrics server on" address of a log is used when it has several: the one
+    /// from the initial startup or the latest one [default: last]
+    #[arg(long, value_enum)]
+    pub log_match_strategy: Option<MatchStrategy>,
+
+    /// Use the N-th "Metrics server on" address of each log (1-indexed), or its last one
+    /// when it has fewer. Overrides `--log-match-strategy`
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub log_match_nth: Option<u64>,
+
+    /// Log files larger than this many bytes are not scanned for metrics addresses
+    /// [default: 100000000]
+    #[arg(long, value_name = "BYTES")]
+    pub max_log_file_size: Option<u64>,
+
+    /// Initial update interval in milliseconds [default: 1000]
+    #[arg(long)]
+    pub interval_ms: Option<u64>,
+
+    /// Maximum number of nodes fetched at the same time, `[` / `]` adjust it at runtime
+    /// [default: all at once]
+    #[arg(long)]
+    pub max_concurrent_fetches: Option<usize>,
+
+    /// Maximum number of HTTP redirects followed by a metrics fetch, for proxies that
+    /// redirect `/metrics` to `/metrics/`, 0 to follow none [default: 5]
+    #[arg(long, value_name = "N")]
+    pub max_redirects: Option<usize>,
+
+    /// Number of update intervals speeds are averaged over [default: 1]
+    #[arg(long)]
+    pub rate_window: Option<usize>,
+
+    /// Number of data points kept for the Rx/Tx speed sparklines [default: 60]
+    #[arg(long)]
+    pub bw_history_length: Option<usize>,
+
+    /// Number of data points kept for the CPU sparkline [default: 60]
+    #[arg(long)]
+    pub cpu_history_length: Option<usize>,
+
+    /// Number of updates the CPU usage is averaged over, the nodes report a snapshot
+    /// that spikes. 1 shows it as reported [default: 5]
+    #[arg(long, value_name = "N")]
+    pub cpu_avg_window: Option<usize>,
+
+    /// Number of data points kept for the live peers sparkline [default: 60]
+    #[arg(long)]
+    pub peers_history_length: Option<usize>,
+
+    /// File the sparkline history is saved to and restored from across restarts
+    /// [default: ~/.local/share/antop/state.bin]
+    #[arg(long)]
+    pub state_file: Option<String>,
+
+    /// Number of rows taken by the log tail pane (`L`), borders included [default: 12]
+    #[arg(long)]
+    pub log_pane_height: Option<u16>,
+
+    /// Watch the parent directory of `--path` and add new node directories as soon as they
+    /// are created, instead of waiting for the next discovery scan
+    #[arg(long)]
+    pub watch_new_nodes: bool,
+
+    /// Ring the terminal bell when a node goes down
+    #[arg(long)]
+    pub bell: bool,
+
+    /// Stop fetching while the terminal is unfocused (e.g. another tmux window is shown),
+    /// resume on focus. Needs a terminal that reports focus changes
+    #[arg(long)]
+    pub pause_on_focus_loss: bool,
+
+    /// Memory growth in MB per minute, steady over the last samples, above which a node
+    /// is flagged with a possible memory leak [default: 0.5]
+    #[arg(long, value_name = "MB_PER_MIN")]
+    pub memory_leak_threshold: Option<f64>,
+
+    /// Number of updates in a row returning exactly the same metrics after which a
+    /// running node is shown as stale [default: 10]
+    #[arg(long, value_name = "UPDATES")]
+    pub stale_threshold: Option<u32>,
+
+    /// Only show nodes whose metrics server is bound to this network interface
+    /// (e.g. `eth1`) or to an address starting with this IP prefix (e.g. `192.168.1.`).
+    /// URLs from `--url-overrides` are filtered as well
+    #[arg(long, value_name = "IF")]
+    pub network_interface: Option<String>,
+
+    /// TOML file mapping node directories to the metrics URL to use for them, for
+    /// nodes behind a proxy. The other nodes keep the URL found in their logs
+    #[arg(long, value_name = "FILE")]
+    pub url_overrides: Option<String>,
+
+    /// Serve the fleet status as JSON on this address (e.g. `0.0.0.0:8080`), at
+    /// `/api/status` and `/api/nodes/<name>`, and as Prometheus text at `/metrics`,
+    /// while the TUI runs
+    #[arg(long, value_name = "ADDR")]
+    pub serve: Option<String>,
+
+    /// Email new alerts (node down, fleet outlier, NAT suspect) to this address, batched
+    /// at most every 5 minutes. Needs `--smtp-host`
+    #[arg(long, value_name = "ADDRESS")]
+    pub alert_email: Option<String>,
+
+    /// SMTP server the alert emails are sent through
+    #[arg(long, value_name = "HOST")]
+    pub smtp_host: Option<String>,
+
+    /// SMTP server port [default: 587 with starttls, 465 with tls, 25 with none]
+    #[arg(long, value_name = "PORT")]
+    pub smtp_port: Option<u16>,
+
+    /// SMTP user, also the sender of the alert emails when it is an address
+    #[arg(long, value_name = "USER")]
+    pub smtp_user: Option<String>,
+
+    /// Security of the SMTP connection [default: starttls]
+    #[arg(long, value_enum)]
+    pub smtp_tls: Option<SmtpTls>,
+
+    /// Write one point per node to InfluxDB after each update (e.g.
+    /// `http://localhost:8086`), or append the line protocol to a `file:///path`
+    #[arg(long, value_name = "URL")]
+    pub influx_url: Option<String>,
+
+    /// InfluxDB API token
+    #[arg(long, value_name = "TOKEN")]
+    pub influx_token: Option<String>,
+
+    /// InfluxDB bucket the points are written to, needed with an http(s) `--influx-url`
+    #[arg(long, value_name = "BUCKET")]
+    pub influx_bucket: Option<String>,
+
+    /// InfluxDB organization of the bucket, needed by InfluxDB 2
+    #[arg(long, value_name = "ORG")]
+    pub influx_org: Option<String>,
+
+    /// Directory the `W` key writes table snapshots and `e` history CSVs to, the current directory by default
+    #[arg(long, value_name = "DIR")]
+    pub snapshot_dir: Option<String>,
+
+    /// Header title instead of "Autonomi Node Dashboard", `{host}` is replaced with the
+    /// local hostname (e.g. "antop @ {host}"), to tell apart dashboards of several hosts
+    #[arg(long, value_name = "TEXT")]
+    pub title: Option<String>,
+
+    /// Rewrite a small JSON file with the fleet totals, the status counts and the top
+    /// alert after the updates (at most once per second), for status bar widgets
+    #[arg(long, value_name = "PATH")]
+    pub status_file: Option<String>,
+
+    /// Show the systemd unit state of the nodes that don't answer and add start, stop
+    /// and restart to the action menu, each node running as `antnode@<directory
+    /// name>.service`. Other units are set in the `[systemd]` section of the config file
+    #[arg(long)]
+    pub systemd: bool,
+
+    /// Regex matched against each node directory path, its first capture group is
+    /// shown as the node name (e.g. `antnode-(\w+)-data`). Nodes it doesn't match keep
+    /// their directory name
+    #[arg(long, value_name = "REGEX")]
+    pub name_regex: Option<String>,
+
+    /// Color of running nodes: a name (green, lightblue, darkgray...), a 256-color
+    /// index or #RRGGBB [default: green]
+    #[arg(long, value_name = "COLOR")]
+    pub color_running: Option<String>,
+
+    /// Color of stopped nodes, like `--color-running` [default: darkgray]
+    #[arg(long, value_name = "COLOR")]
+    pub color_stopped: Option<String>,
+
+    /// Color of nodes failing to answer, like `--color-running` [default: red]
+    #[arg(long, value_name = "COLOR")]
+    pub color_error: Option<String>,
+
+    /// Show the availability timeline column, toggled with `a`
+    #[arg(long)]
+    pub timeline: bool,
+
+    /// Sort the nodes by their last successful update, the most recent first, so the
+    /// ones that stop answering sink to the bottom. `s` cycles to it as well
+    #[arg(long)]
+    pub tail_mode: bool,
+
+    /// Show a summary line per group of nodes above the table. Nodes are grouped by
+    /// their name without its trailing number, `vps1-node-3` is in `vps1-node`
+    #[arg(long)]
+    pub group_by_prefix: bool,
+
+    /// Show short stable IDs (#001, #002...) instead of directory names in the Node column
+    #[arg(long)]
+    pub short_ids: bool,
+
+    /// Notation for the Peers, Routing, Recs and Err columns [default: grouped]
+    #[arg(long, value_enum)]
+    pub notation: Option<Notation>,
+
+    /// Units of byte sizes and speeds: powers of 1000 (kB, MB, GB) or of 1024
+    /// (KiB, MiB, GiB) [default: decimal]
+    #[arg(long, value_enum)]
+    pub units: Option<SizeUnits>,
+
+    /// Marker the sparklines are drawn with, cycled with `m` [default: braille]
+    #[arg(long, value_enum)]
+    pub chart_marker: Option<ChartMarker>,
+
+    /// Draw Rx in magenta and Tx in cyan instead of the other way round, toggled with `c`
+    #[arg(long)]
+    pub swap_rx_tx_colors: bool,
+
+    /// What the Rx/Tx sparklines record when a fetch fails: drop to zero, hold the
+    /// last speed, or leave a gap the line bridges over [default: zero]
+    #[arg(long, value_enum)]
+    pub gap_fill: Option<GapFill>,
+
+    /// Sort key ordering the rows the `s` sort key ranks equal, e.g. the nodes of one
+    /// status [default: name]
+    #[arg(long, value_enum, value_name = "KEY")]
+    pub sort_secondary: Option<SortKey>,
+
+    /// What the storage gauge compares the record stores with: 35 GB allocated per node,
+    /// or the space of the filesystem holding them (the fullest one when there are
+    /// several) [default: allocation]
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub storage_gauge: Option<StorageGauge>,
+
+    /// Path to the config file [default: ~/.config/antop/config.toml]
+    #[arg(long)]
+    pub config: Option<String>,
+
+    /// Name of the config file profile to use (overrides `default_profile`)
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Show this many fake nodes with generated metrics instead of the real ones,
+    /// for demos and screenshots. Discovery, fetching and the state file are skipped.
+    #[arg(long, value_name = "NODES", num_args = 0..=1, default_missing_value = "8")]
+    pub demo: Option<usize>,
+
+    /// Show the nodes of a JSON snapshot file instead of the live ones, read-only:
+    /// nothing is discovered or fetched. One object per node with its `dir_path`,
+    /// `url` and metrics fields.
+    #[arg(long, value_name = "PATH", conflicts_with = "demo")]
+    pub json_input: Option<String>,
+
+    /// Record one row per node and update to this SQLite database (created if missing),
+    /// to look back at later with `--replay`
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["demo", "json_input"])]
+    pub record: Option<String>,
+
+    /// Step through a database written by `--record` instead of showing the live nodes,
+    /// Left / Right going back and forth in time. Nothing is discovered or fetched.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["demo", "json_input", "record"])]
+    pub replay: Option<String>,
+
+    /// Print the effective settings (CLI flags merged with the config file) as TOML and exit
+    #[arg(long)]
+    pub dump_config: bool,
+
+    /// Write a Grafana dashboard (JSON) for the discovered nodes to this path and exit.
+    /// Its panels query the nodes' own metrics through a Prometheus data source.
+    #[arg(long, value_name = "PATH")]
+    pub export_grafana_dashboard: Option<String>,
+
+    /// Draw inline in the main screen instead of the alternate screen, without mouse capture.
+    /// Keeps the terminal scrollback intact, friendlier over unreliable SSH links.
+    #[arg(long)]
+    pub no_altscreen: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands, the dashboard of the live nodes runs without one.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay the sparkline history saved in a state file as an animation, from the
+    /// oldest update to the latest. Space pauses, Left / Right step, q quits.
+    History {
+        /// State file to replay [default: the one of the live dashboard]
+        #[arg(long, value_name = "PATH")]
+        state_file: Option<String>,
+
+        /// Playback speed, 1 shows an update every half second, 2 twice as fast
+        /// (0.01 to 100)
+        #[arg(long, value_name = "FACTOR", default_value_t = 1.0)]
+        replay_speed: f64,
+    },
+    /// Check the config file, the flags, the node directories and their metrics URLs
+    /// without starting the dashboard, one line per check. Exits with 1 when one fails.
+    Validate,
 }
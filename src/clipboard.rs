@@ -0,0 +1,55 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::{
+    env,
+    io::{self, Write},
+};
+
+/// How a value ended up on the clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    System, // Local system clipboard (X11/Wayland/macOS/Windows)
+    Osc52,  // Escape sequence asking the terminal emulator to set its clipboard
+}
+
+/// Access to the clipboard, kept alive for the whole session: on X11 the copied
+/// text is only served while the clipboard handle exists.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    /// Copies `text`. Over SSH the system clipboard belongs to the remote host, so
+    /// OSC 52 is used to reach the local terminal instead. Returns a printable error
+    /// when no clipboard could be reached.
+    pub fn copy(&mut self, text: &str) -> Result<CopyMethod, String> {
+        if is_ssh_session() {
+            return copy_osc52(text)
+                .map(|_| CopyMethod::Osc52)
+                .map_err(|e| e.to_string());
+        }
+
+        if self.system.is_none() {
+            self.system = Some(arboard::Clipboard::new().map_err(|e| e.to_string())?);
+        }
+        match self.system.as_mut() {
+            Some(system) => system
+                .set_text(text)
+                .map(|_| CopyMethod::System)
+                .map_err(|e| e.to_string()),
+            None => Err("Clipboard unavailable".to_string()),
+        }
+    }
+}
+
+// SSH sets these in the remote shell environment
+fn is_ssh_session() -> bool {
+    env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+}
+
+// Writes the OSC 52 "set clipboard" sequence to the terminal
+fn copy_osc52(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
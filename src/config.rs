@@ -0,0 +1,791 @@
+use crate::{
+    alert::SmtpTls,
+    app::{ChartMarker, GapFill, Notation, SizeUnits, SortKey, StorageGauge, TotalsMode},
+    cli::Cli,
+    hooks::HooksConfig,
+    influx::InfluxTarget,
+    systemd::SystemdConfig,
+    webhook::NotificationsConfig,
+};
+use antop::discovery::MatchStrategy;
+use anyhow::{Context, Result, anyhow, bail};
+use lettre::message::Mailbox;
+use ratatui::style::Color;
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+// Default glob pattern for the root directories of the running nodes
+pub const DEFAULT_NODE_PATH: &str = "~/.local/share/autonomi/node/*";
+// Default update interval (1 second)
+pub const DEFAULT_INTERVAL_MS: u64 = 1000;
+// Default number of intervals speeds are averaged over (1 = instantaneous)
+pub const DEFAULT_RATE_WINDOW: usize = 1;
+// Default number of data points kept for each sparkline type
+pub const DEFAULT_BW_HISTORY_LENGTH: usize = 60;
+pub const DEFAULT_CPU_HISTORY_LENGTH: usize = 60;
+pub const DEFAULT_CPU_AVG_WINDOW: usize = 5;
+pub const DEFAULT_PEERS_HISTORY_LENGTH: usize = 60;
+// Default number of rows taken by the log tail pane, borders included
+pub const DEFAULT_LOG_PANE_HEIGHT: u16 = 12;
+// Default steady memory growth, in MB per minute, flagged as a possible leak
+pub const DEFAULT_MEMORY_LEAK_THRESHOLD: f64 = 0.5;
+// Default number of identical updates in a row after which a running node is stale
+pub const DEFAULT_STALE_THRESHOLD: u32 = 10;
+// Default number of redirects a metrics fetch follows
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+// Default size above which a log file is left out of the discovery, in bytes (100 MB)
+pub const DEFAULT_MAX_LOG_FILE_SIZE: u64 = 100_000_000;
+// Default header title, `{host}` in a `--title` is replaced with the local hostname
+pub const DEFAULT_TITLE: &str = "Autonomi Node Dashboard";
+// Profile used when `--profile` is not given and the config has no `default_profile`
+const DEFAULT_PROFILE_NAME: &str = "default";
+// Environment variable overriding the `smtp_password` config key
+const SMTP_PASSWORD_VAR: &str = "ANTOP_SMTP_PASSWORD";
+// Printed by `--dump-config` in place of the passwords, tokens and webhook URLs
+const REDACTED: &str = "<redacted>";
+
+/// Serializes a secret setting as `REDACTED`, an unset one stays unset.
+pub fn redact<T, S: Serializer>(secret: &Option<T>, serializer: S) -> Result<S::Ok, S::Error> {
+    match secret {
+        Some(_) => serializer.serialize_some(REDACTED),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serializes a required secret setting as `REDACTED`.
+pub fn redact_required<S: Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(REDACTED)
+}
+
+/// A set of monitoring settings. Used both for the global defaults at the top
+/// of the config file and for each named `[profiles.<name>]` table. There is no
+/// theme or column selection, a profile changes the look only through the
+/// `color_*` keys and `timeline`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub path: Option<String>,
+    pub log_path: Option<String>,
+    pub discovery_log_glob_override: Option<String>,
+    pub log_match_strategy: Option<MatchStrategy>,
+    pub log_match_nth: Option<usize>,
+    pub max_log_file_size: Option<u64>,
+    pub interval_ms: Option<u64>,
+    pub totals: Option<TotalsMode>,
+    pub rate_window: Option<usize>,
+    pub short_ids: Option<bool>,
+    pub notation: Option<Notation>,
+    pub units: Option<SizeUnits>,
+    pub chart_marker: Option<ChartMarker>,
+    pub swap_rx_tx_colors: Option<bool>,
+    pub gap_fill: Option<GapFill>,
+    pub sort_secondary: Option<SortKey>,
+    pub storage_gauge: Option<StorageGauge>,
+    pub bw_history_length: Option<usize>,
+    pub cpu_history_length: Option<usize>,
+    pub cpu_avg_window: Option<usize>,
+    pub peers_history_length: Option<usize>,
+    pub state_file: Option<String>,
+    pub log_pane_height: Option<u16>,
+    pub watch_new_nodes: Option<bool>,
+    pub max_concurrent_fetches: Option<usize>,
+    pub max_redirects: Option<usize>,
+    pub bell: Option<bool>,
+    pub pause_on_focus_loss: Option<bool>,
+    pub timeline: Option<bool>,
+    pub tail_mode: Option<bool>,
+    pub group_by_prefix: Option<bool>,
+    pub memory_leak_threshold: Option<f64>,
+    pub stale_threshold: Option<u32>,
+    pub network_interface: Option<String>,
+    pub name_regex: Option<String>,
+    pub color_running: Option<String>,
+    pub color_stopped: Option<String>,
+    pub color_error: Option<String>,
+    pub url_overrides: Option<String>,
+    pub serve: Option<String>,
+    pub alert_email: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_user: Option<String>,
+    pub smtp_password: Option<String>,
+    pub smtp_tls: Option<SmtpTls>,
+    pub influx_url: Option<String>,
+    pub influx_token: Option<String>,
+    pub influx_bucket: Option<String>,
+    pub influx_org: Option<String>,
+    pub snapshot_dir: Option<String>,
+    pub title: Option<String>,
+    pub status_file: Option<String>,
+}
+
+impl Profile {
+    /// Returns a profile where every unset field of `self` is taken from `fallback`.
+    fn or(self, fallback: Profile) -> Profile {
+        Profile {
+            path: self.path.or(fallback.path),
+            log_path: self.log_path.or(fallback.log_path),
+            discovery_log_glob_override: self
+                .discovery_log_glob_override
+                .or(fallback.discovery_log_glob_override),
+            log_match_strategy: self.log_match_strategy.or(fallback.log_match_strategy),
+            log_match_nth: self.log_match_nth.or(fallback.log_match_nth),
+            max_log_file_size: self.max_log_file_size.or(fallback.max_log_file_size),
+            interval_ms: self.interval_ms.or(fallback.interval_ms),
+            totals: self.totals.or(fallback.totals),
+            rate_window: self.rate_window.or(fallback.rate_window),
+            short_ids: self.short_ids.or(fallback.short_ids),
+            notation: self.notation.or(fallback.notation),
+            units: self.units.or(fallback.units),
+            chart_marker: self.chart_marker.or(fallback.chart_marker),
+            swap_rx_tx_colors: self.swap_rx_tx_colors.or(fallback.swap_rx_tx_colors),
+            gap_fill: self.gap_fill.or(fallback.gap_fill),
+            sort_secondary: self.sort_secondary.or(fallback.sort_secondary),
+            storage_gauge: self.storage_gauge.or(fallback.storage_gauge),
+            bw_history_length: self.bw_history_length.or(fallback.bw_history_length),
+            cpu_history_length: self.cpu_history_length.or(fallback.cpu_history_length),
+            cpu_avg_window: self.cpu_avg_window.or(fallback.cpu_avg_window),
+            peers_history_length: self.peers_history_length.or(fallback.peers_history_length),
+            state_file: self.state_file.or(fallback.state_file),
+            log_pane_height: self.log_pane_height.or(fallback.log_pane_height),
+            watch_new_nodes: self.watch_new_nodes.or(fallback.watch_new_nodes),
+            max_concurrent_fetches: self
+                .max_concurrent_fetches
+                .or(fallback.max_concurrent_fetches),
+            max_redirects: self.max_redirects.or(fallback.max_redirects),
+            bell: self.bell.or(fallback.bell),
+            pause_on_focus_loss: self.pause_on_focus_loss.or(fallback.pause_on_focus_loss),
+            timeline: self.timeline.or(fallback.timeline),
+            tail_mode: self.tail_mode.or(fallback.tail_mode),
+            group_by_prefix: self.group_by_prefix.or(fallback.group_by_prefix),
+            memory_leak_threshold: self
+                .memory_leak_threshold
+                .or(fallback.memory_leak_threshold),
+            stale_threshold: self.stale_threshold.or(fallback.stale_threshold),
+            network_interface: self.network_interface.or(fallback.network_interface),
+            name_regex: self.name_regex.or(fallback.name_regex),
+            color_running: self.color_running.or(fallback.color_running),
+            color_stopped: self.color_stopped.or(fallback.color_stopped),
+            color_error: self.color_error.or(fallback.color_error),
+            url_overrides: self.url_overrides.or(fallback.url_overrides),
+            serve: self.serve.or(fallback.serve),
+            alert_email: self.alert_email.or(fallback.alert_email),
+            smtp_host: self.smtp_host.or(fallback.smtp_host),
+            smtp_port: self.smtp_port.or(fallback.smtp_port),
+            smtp_user: self.smtp_user.or(fallback.smtp_user),
+            smtp_password: self.smtp_password.or(fallback.smtp_password),
+            smtp_tls: self.smtp_tls.or(fallback.smtp_tls),
+            influx_url: self.influx_url.or(fallback.influx_url),
+            influx_token: self.influx_token.or(fallback.influx_token),
+            influx_bucket: self.influx_bucket.or(fallback.influx_bucket),
+            influx_org: self.influx_org.or(fallback.influx_org),
+            snapshot_dir: self.snapshot_dir.or(fallback.snapshot_dir),
+            title: self.title.or(fallback.title),
+            status_file: self.status_file.or(fallback.status_file),
+        }
+    }
+}
+
+/// On-disk layout of the config file.
+///
+/// ```toml
+/// interval_ms = 2000               # global defaults
+/// totals = "session"               # Rx/Tx totals: "lifetime" or "session"
+/// rate_window = 5                  # average speeds over the last 5 intervals
+/// short_ids = true                 # show #001, #002... in the Node column
+/// notation = "compact"             # counts as 183K instead of 183,429
+/// units = "binary"                 # sizes in KiB/MiB/GiB instead of kB/MB/GB
+/// chart_marker = "block"           # sparklines drawn with braille, dot or block
+/// swap_rx_tx_colors = true         # Rx in magenta, Tx in cyan
+/// gap_fill = "hold"                # Rx/Tx sparklines on a failed fetch: zero, hold or gap
+/// sort_secondary = "cpu"           # breaks the ties of the `s` sort key (name by default)
+/// storage_gauge = "filesystem"     # storage gauge against the disk instead of 35 GB per node
+/// bw_history_length = 30          # sparkline points for Rx/Tx speeds
+/// cpu_history_length = 300         # sparkline points for CPU usage
+/// cpu_avg_window = 10              # CPU shown as the mean of this many updates
+/// peers_history_length = 300       # sparkline points for live peers
+/// state_file = "~/antop-state.bin" # history kept across restarts
+/// log_pane_height = 15             # rows of the log tail pane (`L`)
+/// watch_new_nodes = true           # pick up new node directories right away
+/// max_concurrent_fetches = 16      # nodes fetched at once (all when unset)
+/// max_redirects = 0                # redirects followed per fetch, 0 = none (default 5)
+/// bell = true                      # ring the terminal bell when a node goes down
+/// pause_on_focus_loss = true       # no sweeps while the terminal is unfocused
+/// timeline = true                  # availability timeline column (`a`)
+/// tail_mode = true                 # most recently updated nodes at the top
+/// group_by_prefix = true           # summary line per node name prefix above the table
+/// memory_leak_threshold = 1.0      # MB/min of steady memory growth flagged as a leak
+/// stale_threshold = 30             # identical updates before a node shows as stale
+/// serve = "0.0.0.0:8080"           # status endpoint (/api/status, /metrics)
+/// color_running = "lightblue"      # status colors: names or "#RRGGBB"
+/// color_error = "#ff8800"
+/// alert_email = "ops@example.com"  # email new alerts, at most every 5 minutes
+/// smtp_host = "smtp.example.com"
+/// smtp_port = 587                  # default port of smtp_tls when unset
+/// smtp_user = "antop@example.com"  # also the sender when it is an address
+/// smtp_password = "secret"         # or ANTOP_SMTP_PASSWORD
+/// smtp_tls = "starttls"            # none, starttls (default) or tls
+/// influx_url = "http://localhost:8086" # write each update to InfluxDB (or file:///path)
+/// influx_bucket = "antop"
+/// influx_org = "home"              # needed by InfluxDB 2
+/// influx_token = "secret"
+/// snapshot_dir = "~/antop-snapshots" # where `W` and `e` write snapshots, default cwd
+/// title = "antop @ {host}"         # header title, {host} = hostname
+/// status_file = "/run/user/1000/antop.json" # fleet summary for widgets, rewritten each update
+/// default_profile = "mainnet"      # used when --profile is not given
+///
+/// [profiles.mainnet]
+/// path = "~/.local/share/autonomi/node/*"
+///
+/// [profiles.local]
+/// path = "/srv/local-testnet/node-*"
+/// log_path = "/var/log/antnode/node-*/antnode.log"
+/// # or, per node: {name} is the basename of its directory
+/// # discovery_log_glob_override = "/var/log/antnode/{name}/current"
+/// log_match_strategy = "first"     # address logged at startup, not the latest one
+/// max_log_file_size = 500000000    # bytes, larger logs are not scanned (default 100 MB)
+/// network_interface = "eth1"       # only nodes bound to this interface (or IP prefix)
+/// name_regex = 'antnode-(\w+)-data' # node names from the first capture group of their path
+/// url_overrides = "~/antop-urls.toml" # metrics URLs of some nodes, instead of their logs
+/// interval_ms = 500
+/// color_running = "green"          # the look: color_* and timeline, no theme or column list
+///
+/// [notifications]                  # JSON POST when a node goes down, recovers or alerts
+/// webhook_url = "https://hooks.slack.com/services/T000/B000/XXXX"
+/// events = ["node_down", "node_recovered"] # also "outlier" and "nat", all by default
+/// rate_limit_secs = 600            # per node and event (default 300)
+///
+/// [hooks]                          # commands run when a node goes down, recovers or alerts
+/// on_node_down = "/usr/local/bin/antnode-recover {node_dir}" # also {node}, {event}, {value}
+/// on_outlier = "/usr/local/bin/page-me {node} {value}" # also on_node_recovered and on_nat
+/// max_concurrent = 4               # hooks running at once (default 4)
+/// cooldown_secs = 600              # per node and event (default 300)
+///
+/// [systemd]                        # unit states and start / stop / restart actions
+/// unit_template = "antnode@{name}.service" # {name} = node directory name (default)
+/// user = false                     # units of `systemctl --user`
+/// [systemd.units]                  # nodes whose unit doesn't follow the template
+/// "node-07" = "antnode-legacy.service"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub default_profile: Option<String>,
+    pub profiles: HashMap<String, Profile>,
+    pub notifications: Option<NotificationsConfig>, // Shared by all the profiles
+    pub hooks: Option<HooksConfig>,                 // Shared by all the profiles
+    pub systemd: Option<SystemdConfig>,             // Shared by all the profiles
+    #[serde(flatten)]
+    pub defaults: Profile,
+}
+
+/// Effective settings after merging CLI flags > selected profile > global defaults.
+#[derive(Debug, Clone, Serialize)]
+pub struct Settings {
+    pub config_file: Option<String>, // Config file that was loaded, if any
+    pub profile: Option<String>,
+    pub path: String,
+    pub log_path: Option<String>,
+    pub discovery_log_glob_override: Option<String>, // Per node log glob, `{name}` = directory basename
+    pub log_match_strategy: MatchStrategy,
+    pub log_match_nth: Option<usize>, // Overrides `log_match_strategy`, 1-indexed
+    pub max_log_file_size: u64,       // Larger log files are skipped by the discovery
+    pub interval_ms: u64,
+    pub totals: TotalsMode,
+    pub rate_window: usize,
+    pub short_ids: bool,
+    pub notation: Notation,
+    pub units: SizeUnits,
+    pub chart_marker: ChartMarker,
+    pub swap_rx_tx_colors: bool,
+    pub gap_fill: GapFill,
+    pub sort_secondary: SortKey,
+    pub storage_gauge: StorageGauge,
+    pub bw_history_length: usize,
+    pub cpu_history_length: usize,
+    pub cpu_avg_window: usize,
+    pub peers_history_length: usize,
+    pub state_file: Option<String>,
+    pub log_pane_height: u16,
+    pub watch_new_nodes: bool,
+    pub max_concurrent_fetches: Option<usize>, // None = all nodes at once
+    pub max_redirects: usize,                  // Per fetch, 0 = not followed
+    pub bell: bool,
+    pub pause_on_focus_loss: bool,
+    pub timeline: bool,
+    pub tail_mode: bool,
+    pub group_by_prefix: bool,
+    pub memory_leak_threshold: f64,        // MB per minute
+    pub stale_threshold: u32,              // Identical updates in a row
+    pub network_interface: Option<String>, // Interface name or IP prefix, None = all nodes
+    pub name_regex: Option<String>,        // Node name pattern, validated in `resolve`
+    pub color_running: Option<String>,     // Status colors, validated in `resolve`
+    pub color_stopped: Option<String>,
+    pub color_error: Option<String>,
+    pub url_overrides: Option<String>, // Node directory -> metrics URL file, see `load_url_overrides`
+    pub serve: Option<String>,         // Address of the JSON status endpoint, None = off
+    pub alert_email: Option<String>,   // Recipient of the alert emails, None = off
+    pub smtp_host: Option<String>,     // Set whenever `alert_email` is, see `resolve`
+    pub smtp_port: Option<u16>,
+    pub smtp_user: Option<String>,
+    #[serde(serialize_with = "redact")]
+    pub smtp_password: Option<String>, // `ANTOP_SMTP_PASSWORD` or the config file
+    pub smtp_tls: SmtpTls,
+    pub influx_url: Option<String>, // Line protocol sink, validated in `resolve`, None = off
+    #[serde(serialize_with = "redact")]
+    pub influx_token: Option<String>,
+    pub influx_bucket: Option<String>,
+    pub influx_org: Option<String>,
+    pub snapshot_dir: Option<String>, // Directory of the `W` and `e` files, None = current directory
+    pub title: String,                // Header title, `{host}` not replaced yet
+    pub status_file: Option<String>,  // JSON summary rewritten after the updates, see `StatusFile`
+    pub notifications: Option<NotificationsConfig>, // Webhook of the config file, None = off
+    pub hooks: Option<HooksConfig>,   // Event commands of the config file, None = off
+    pub systemd: Option<SystemdConfig>, // Unit of each node, None = no unit states or actions
+}
+
+impl Settings {
+    /// Loads the config file (if any) and merges it with the command-line arguments.
+    pub fn load(cli: &Cli) -> Result<Settings> {
+        let (config, config_file) = load_config_file(cli.config.as_deref())?;
+        Settings::resolve(cli, config, config_file)
+    }
+
+    /// Log file glob: `log_path` if set, otherwise derived from the node path glob
+    /// (`<path>/logs/antnode.log`, with a wildcard for the node directory).
+    pub fn effective_log_path(&self) -> String {
+        match self.log_path.as_ref() {
+            Some(log_path) => log_path.clone(), // Already tilde-expanded
+            None => {
+                // Derive log path based on the *original* potentially wildcarded path pattern
+                // A path the glob syntax rejects is used literally for discovery, escape
+                // its brackets so the derived pattern matches it too
+                let path = match glob::Pattern::new(&self.path) {
+                    Ok(_) => self.path.clone(),
+                    Err(_) => glob::Pattern::escape(&self.path),
+                };
+                let mut path_buf = PathBuf::from(&path); // Use original glob pattern
+                if path_buf.file_name().is_some_and(|name| name == "*") {
+                    // If the pattern ends with '*', assume it means node-*/logs/antnode.log
+                    path_buf.pop(); // Remove '*'
+                    path_buf.push("*"); // Add it back (or ensure it's there)
+                } else {
+                    // If it's a specific directory, append /logs/antnode.log
+                    // This derivation logic might need refinement depending on expected usage
+                    // For now, let's keep the wildcard assumption as it was implicitly there before
+                    if !self.path.ends_with('*') {
+                        // Add wildcard if not present, mimicking the old behavior's likely intent
+                        // This part is tricky; ideally, log path derivation is explicit or simpler.
+                        // Let's stick to the original logic's implicit behavior for now.
+                        path_buf.push("*");
+                    }
+                }
+                path_buf.push("logs");
+                path_buf.push("antnode.log");
+                path_buf.to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    /// Renders the effective settings as TOML, with the derived log path filled in.
+    pub fn to_toml(&self) -> Result<String> {
+        let mut effective = self.clone();
+        effective.log_path = Some(self.effective_log_path());
+        toml::to_string_pretty(&effective).context("Failed to serialize settings")
+    }
+
+    /// Names of the settings whose value differs in `other`, as written in the config file.
+    /// The secrets are redacted in the TOML, they are compared directly.
+    pub fn changed_keys(&self, other: &Settings) -> Result<Vec<String>> {
+        let table = |settings: &Settings| match toml::Value::try_from(settings) {
+            Ok(toml::Value::Table(table)) => Ok(table),
+            Ok(_) => Err(anyhow!("Settings are not a table")),
+            Err(e) => Err(e).context("Failed to serialize settings"),
+        };
+        let webhook_url = |settings: &Settings| {
+            settings
+                .notifications
+                .as_ref()
+                .map(|notifications| notifications.webhook_url.clone())
+        };
+        let secrets = [
+            ("smtp_password", self.smtp_password != other.smtp_password),
+            ("influx_token", self.influx_token != other.influx_token),
+            ("notifications", webhook_url(self) != webhook_url(other)),
+        ];
+        let (current, other) = (table(self)?, table(other)?);
+        let mut keys: Vec<String> = current
+            .keys()
+            .chain(other.keys())
+            .filter(|key| current.get(*key) != other.get(*key))
+            .cloned()
+            .chain(
+                secrets
+                    .into_iter()
+                    .filter(|(_, changed)| *changed)
+                    .map(|(key, _)| key.to_string()),
+            )
+            .collect();
+        keys.sort();
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn resolve(
+        cli: &Cli,
+        mut config: ConfigFile,
+        config_file: Option<PathBuf>,
+    ) -> Result<Settings> {
+        // An explicit --profile must exist, the implicit default profile is optional
+        let profile_name = match &cli.profile {
+            Some(name) => {
+                if !config.profiles.contains_key(name) {
+                    let mut available: Vec<&str> =
+                        config.profiles.keys().map(String::as_str).collect();
+                    available.sort();
+                    bail!(
+                        "Profile '{}' not found in config file (available: {})",
+                        name,
+                        if available.is_empty() {
+                            "none".to_string()
+                        } else {
+                            available.join(", ")
+                        }
+                    );
+                }
+                Some(name.clone())
+            }
+            None => config
+                .default_profile
+                .clone()
+                .or_else(|| Some(DEFAULT_PROFILE_NAME.to_string()))
+                .filter(|name| config.profiles.contains_key(name)),
+        };
+
+        let selected = profile_name
+            .as_ref()
+            .and_then(|name| config.profiles.remove(name))
+            .unwrap_or_default();
+
+        let from_cli = Profile {
+            path: cli.path.clone(),
+            log_path: cli.log_path.clone(),
+            discovery_log_glob_override: cli.discovery_log_glob_override.clone(),
+            log_match_strategy: cli.log_match_strategy,
+            log_match_nth: cli.log_match_nth.map(|nth| nth as usize),
+            max_log_file_size: cli.max_log_file_size,
+            interval_ms: cli.interval_ms,
+            totals: None,
+            rate_window: cli.rate_window,
+            short_ids: cli.short_ids.then_some(true),
+            notation: cli.notation,
+            units: cli.units,
+            chart_marker: cli.chart_marker,
+            swap_rx_tx_colors: cli.swap_rx_tx_colors.then_some(true),
+            gap_fill: cli.gap_fill,
+            sort_secondary: cli.sort_secondary,
+            storage_gauge: cli.storage_gauge,
+            bw_history_length: cli.bw_history_length,
+            cpu_history_length: cli.cpu_history_length,
+            cpu_avg_window: cli.cpu_avg_window,
+            peers_history_length: cli.peers_history_length,
+            state_file: cli.state_file.clone(),
+            log_pane_height: cli.log_pane_height,
+            watch_new_nodes: cli.watch_new_nodes.then_some(true),
+            max_concurrent_fetches: cli.max_concurrent_fetches,
+            max_redirects: cli.max_redirects,
+            bell: cli.bell.then_some(true),
+            pause_on_focus_loss: cli.pause_on_focus_loss.then_some(true),
+            timeline: cli.timeline.then_some(true),
+            tail_mode: cli.tail_mode.then_some(true),
+            group_by_prefix: cli.group_by_prefix.then_some(true),
+            memory_leak_threshold: cli.memory_leak_threshold,
+            stale_threshold: cli.stale_threshold,
+            network_interface: cli.network_interface.clone(),
+            name_regex: cli.name_regex.clone(),
+            color_running: cli.color_running.clone(),
+            color_stopped: cli.color_stopped.clone(),
+            color_error: cli.color_error.clone(),
+            url_overrides: cli.url_overrides.clone(),
+            serve: cli.serve.clone(),
+            alert_email: cli.alert_email.clone(),
+            smtp_host: cli.smtp_host.clone(),
+            smtp_port: cli.smtp_port,
+            smtp_user: cli.smtp_user.clone(),
+            smtp_password: env::var(SMTP_PASSWORD_VAR).ok(),
+            smtp_tls: cli.smtp_tls,
+            influx_url: cli.influx_url.clone(),
+            influx_token: cli.influx_token.clone(),
+            influx_bucket: cli.influx_bucket.clone(),
+            influx_org: cli.influx_org.clone(),
+            snapshot_dir: cli.snapshot_dir.clone(),
+            title: cli.title.clone(),
+            status_file: cli.status_file.clone(),
+        };
+        let merged = from_cli.or(selected).or(config.defaults);
+        if let Some(pattern) = &merged.name_regex {
+            Regex::new(pattern).with_context(|| format!("Invalid name regex '{}'", pattern))?;
+        }
+        for (key, color) in [
+            ("color_running", &merged.color_running),
+            ("color_stopped", &merged.color_stopped),
+            ("color_error", &merged.color_error),
+        ] {
+            if let Some(color) = color {
+                color.parse::<Color>().map_err(|_| {
+                    anyhow!(
+                        "Invalid {} '{}': use a color name (green, lightblue, darkgray...), a 256-color index or #RRGGBB",
+                        key,
+                        color
+                    )
+                })?;
+            }
+        }
+        if let Some(email) = &merged.alert_email {
+            email
+                .parse::<Mailbox>()
+                .with_context(|| format!("Invalid alert email address '{}'", email))?;
+            if merged.smtp_host.is_none() {
+                bail!("--alert-email needs an SMTP server to send through, set --smtp-host");
+            }
+        }
+        if let Some(url) = &merged.influx_url {
+            InfluxTarget::parse(
+                url,
+                merged.influx_token.as_deref(),
+                merged.influx_bucket.as_deref(),
+                merged.influx_org.as_deref(),
+            )?;
+        }
+        if let Some(notifications) = &config.notifications {
+            notifications.url()?;
+        }
+        if let Some(hooks) = &config.hooks {
+            hooks.check()?;
+        }
+        // Without it every node would get the same logs
+        if let Some(pattern) = &merged.discovery_log_glob_override
+            && !pattern.contains("{name}")
+        {
+            bail!(
+                "--discovery-log-glob-override '{}' needs a {{name}} placeholder for the node directory name",
+                pattern
+            );
+        }
+        if let Some(systemd) = &config.systemd
+            && !systemd.unit_template.contains("{name}")
+        {
+            bail!(
+                "systemd unit_template '{}' needs a {{name}} placeholder for the node directory name",
+                systemd.unit_template
+            );
+        }
+
+        Ok(Settings {
+            config_file: config_file.map(|path| path.to_string_lossy().into_owned()),
+            profile: profile_name,
+            path: shellexpand::tilde(merged.path.as_deref().unwrap_or(DEFAULT_NODE_PATH))
+                .into_owned(),
+            log_path: merged
+                .log_path
+                .map(|log_path| shellexpand::tilde(&log_path).into_owned()),
+            discovery_log_glob_override: merged
+                .discovery_log_glob_override
+                .map(|pattern| shellexpand::tilde(&pattern).into_owned()),
+            log_match_strategy: merged.log_match_strategy.unwrap_or_default(),
+            log_match_nth: merged.log_match_nth.filter(|&nth| nth > 0),
+            max_log_file_size: merged
+                .max_log_file_size
+                .unwrap_or(DEFAULT_MAX_LOG_FILE_SIZE),
+            interval_ms: merged.interval_ms.unwrap_or(DEFAULT_INTERVAL_MS).max(1),
+            totals: merged.totals.unwrap_or_default(),
+            rate_window: merged.rate_window.unwrap_or(DEFAULT_RATE_WINDOW).max(1),
+            short_ids: merged.short_ids.unwrap_or(false),
+            notation: merged.notation.unwrap_or_default(),
+            units: merged.units.unwrap_or_default(),
+            chart_marker: merged.chart_marker.unwrap_or_default(),
+            swap_rx_tx_colors: merged.swap_rx_tx_colors.unwrap_or(false),
+            gap_fill: merged.gap_fill.unwrap_or_default(),
+            sort_secondary: merged.sort_secondary.unwrap_or_default(),
+            storage_gauge: merged.storage_gauge.unwrap_or_default(),
+            // A sparkline needs at least two points to draw a line
+            bw_history_length: merged
+                .bw_history_length
+                .unwrap_or(DEFAULT_BW_HISTORY_LENGTH)
+                .max(2),
+            cpu_history_length: merged
+                .cpu_history_length
+                .unwrap_or(DEFAULT_CPU_HISTORY_LENGTH)
+                .max(2),
+            cpu_avg_window: merged
+                .cpu_avg_window
+                .unwrap_or(DEFAULT_CPU_AVG_WINDOW)
+                .max(1),
+            peers_history_length: merged
+                .peers_history_length
+                .unwrap_or(DEFAULT_PEERS_HISTORY_LENGTH)
+                .max(2),
+            state_file: merged
+                .state_file
+                .map(|state_file| shellexpand::tilde(&state_file).into_owned())
+                .or_else(default_state_file),
+            // Borders plus at least one line of log
+            log_pane_height: merged
+                .log_pane_height
+                .unwrap_or(DEFAULT_LOG_PANE_HEIGHT)
+                .max(3),
+            watch_new_nodes: merged.watch_new_nodes.unwrap_or(false),
+            max_concurrent_fetches: merged.max_concurrent_fetches.map(|limit| limit.max(1)),
+            max_redirects: merged.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+            bell: merged.bell.unwrap_or(false),
+            pause_on_focus_loss: merged.pause_on_focus_loss.unwrap_or(false),
+            timeline: merged.timeline.unwrap_or(false),
+            tail_mode: merged.tail_mode.unwrap_or(false),
+            group_by_prefix: merged.group_by_prefix.unwrap_or(false),
+            memory_leak_threshold: merged
+                .memory_leak_threshold
+                .unwrap_or(DEFAULT_MEMORY_LEAK_THRESHOLD),
+            stale_threshold: merged
+                .stale_threshold
+                .unwrap_or(DEFAULT_STALE_THRESHOLD)
+                .max(1),
+            network_interface: merged.network_interface,
+            name_regex: merged.name_regex,
+            color_running: merged.color_running,
+            color_stopped: merged.color_stopped,
+            color_error: merged.color_error,
+            url_overrides: merged
+                .url_overrides
+                .map(|path| shellexpand::tilde(&path).into_owned()),
+            serve: merged.serve,
+            alert_email: merged.alert_email,
+            smtp_host: merged.smtp_host,
+            smtp_port: merged.smtp_port,
+            smtp_user: merged.smtp_user,
+            smtp_password: merged.smtp_password,
+            smtp_tls: merged.smtp_tls.unwrap_or_default(),
+            influx_url: merged.influx_url,
+            influx_token: merged.influx_token,
+            influx_bucket: merged.influx_bucket,
+            influx_org: merged.influx_org,
+            snapshot_dir: merged
+                .snapshot_dir
+                .map(|dir| shellexpand::tilde(&dir).into_owned()),
+            title: merged.title.unwrap_or_else(|| DEFAULT_TITLE.to_string()),
+            status_file: merged
+                .status_file
+                .map(|path| shellexpand::tilde(&path).into_owned()),
+            notifications: config.notifications,
+            hooks: config.hooks,
+            // `--systemd` alone goes with the default unit template
+            systemd: config
+                .systemd
+                .or_else(|| cli.systemd.then(SystemdConfig::default)),
+        })
+    }
+}
+
+/// Returns the default config file location (`~/.config/antop/config.toml` on Linux).
+fn default_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("antop").join("config.toml"))
+}
+
+/// Returns the default state file location (`~/.local/share/antop/state.bin` on Linux).
+fn default_state_file() -> Option<String> {
+    dirs::data_local_dir().map(|dir| {
+        dir.join("antop")
+            .join("state.bin")
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+/// Reads and parses the config file, returning it with the path it was read from.
+/// A missing default config file is not an error, but a missing file passed
+/// explicitly with `--config` is.
+fn load_config_file(explicit_path: Option<&str>) -> Result<(ConfigFile, Option<PathBuf>)> {
+    let path = match explicit_path {
+        Some(path) => PathBuf::from(shellexpand::tilde(path).into_owned()),
+        None => match default_config_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok((ConfigFile::default(), None)),
+        },
+    };
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file: {:?}", path))?;
+    let config = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file: {:?}", path))?;
+    Ok((config, Some(path)))
+}
+
+#[cfg(test)]
+impl Settings {
+    /// The settings of `antop` run without flags, config file or environment.
+    pub fn for_tests() -> Settings {
+        use clap::Parser;
+        let mut settings =
+            Settings::resolve(&Cli::parse_from(["antop"]), ConfigFile::default(), None)
+                .expect("Default settings resolve");
+        settings.smtp_password = None;
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notifications(webhook_url: &str) -> NotificationsConfig {
+        toml::from_str(&format!("webhook_url = {:?}", webhook_url)).unwrap()
+    }
+
+    #[test]
+    fn to_toml_redacts_the_secrets() {
+        let mut settings = Settings::for_tests();
+        settings.smtp_password = Some("hunter2".to_string());
+        settings.influx_token = Some("influx-secret".to_string());
+        settings.notifications = Some(notifications("https://hooks.example.com/T000/SECRET"));
+
+        let toml = settings.to_toml().unwrap();
+        assert!(!toml.contains("hunter2"));
+        assert!(!toml.contains("influx-secret"));
+        assert!(!toml.contains("SECRET"));
+        assert!(toml.contains("smtp_password = \"<redacted>\""));
+        assert!(toml.contains("influx_token = \"<redacted>\""));
+        assert!(toml.contains("webhook_url = \"<redacted>\""));
+    }
+
+    #[test]
+    fn to_toml_leaves_unset_secrets_out() {
+        let toml = Settings::for_tests().to_toml().unwrap();
+        assert!(!toml.contains("smtp_password"));
+        assert!(!toml.contains("influx_token"));
+        assert!(!toml.contains("<redacted>"));
+    }
+
+    #[test]
+    fn changed_keys_compares_the_secrets() {
+        let current = Settings::for_tests();
+        let mut other = current.clone();
+        assert!(current.changed_keys(&other).unwrap().is_empty());
+
+        other.smtp_password = Some("one".to_string());
+        other.influx_token = Some("two".to_string());
+        other.notifications = Some(notifications("https://hooks.example.com/a"));
+        assert_eq!(
+            current.changed_keys(&other).unwrap(),
+            ["influx_token", "notifications", "smtp_password"]
+        );
+
+        // Both redacted the same in the TOML, still a change
+        let mut changed_url = other.clone();
+        changed_url.notifications = Some(notifications("https://hooks.example.com/b"));
+        changed_url.smtp_password = Some("three".to_string());
+        assert_eq!(
+            other.changed_keys(&changed_url).unwrap(),
+            ["notifications", "smtp_password"]
+        );
+    }
+}
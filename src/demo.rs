@@ -0,0 +1,229 @@
+use antop::{
+    discovery::{DiscoveredNode, DiscoverySource},
+    fetch::{FetchError, FetchErrorKind, FetchResult},
+};
+use std::time::Instant;
+
+// Every Nth node keeps going down and coming back
+const FLAPPING_NODE_EVERY: usize = 7;
+// Samples a flapping node stays up, then down, per cycle
+const FLAP_UP_SAMPLES: u64 = 20;
+const FLAP_DOWN_SAMPLES: u64 = 6;
+// Memory growth of the leaking node, in MB per minute
+const LEAK_MB_PER_MIN: f64 = 3.0;
+
+/// Fake fleet for `--demo`: plausible, time-varying metrics for a number of nodes,
+/// rendered as the text a real metrics endpoint would serve so they go through the
+/// same parsing and `update_metrics` path. Discovery and fetching are bypassed.
+///
+/// Besides normal nodes the fleet has a CPU hog, a leaking node, a node with few peers, flapping
+/// nodes and, with 5 nodes or more, a stopped one (no metrics URL).
+#[derive(Debug)]
+pub struct DemoFleet {
+    nodes: Vec<DemoNode>,
+    samples: u64,
+    started: Instant,
+    last_sample: Instant,
+}
+
+#[derive(Debug)]
+struct DemoNode {
+    dir_path: String,
+    url: Option<String>, // None = stopped
+    rng: XorShift,
+    started_secs_ago: u64,
+    base_cpu: f64,
+    base_peers: f64,
+    bytes_in: u64,
+    bytes_out: u64,
+    records: u64,
+    rewards: u64,
+    put_errors: u64,
+    connection_errors: u64,
+    dialed: u64,
+    leaking: bool,
+    leaked_mb: f64,
+}
+
+impl DemoFleet {
+    /// Creates `count` fake nodes, the same ones for a given count.
+    pub fn new(count: usize) -> DemoFleet {
+        let nodes = (0..count)
+            .map(|i| {
+                let mut rng = XorShift(0x9E37_79B9_7F4A_7C15 ^ (i as u64 + 1));
+                let stopped = count >= 5 && i == count - 1;
+                DemoNode {
+                    dir_path: format!("/demo/antnode-{}", i + 1),
+                    url: (!stopped).then(|| format!("http://demo-node-{}:{}", i + 1, 13001 + i)),
+                    started_secs_ago: 3600 + rng.below(40 * 24 * 3600),
+                    base_cpu: match i {
+                        2 => 85.0, // CPU hog
+                        _ => 5.0 + rng.below(15) as f64,
+                    },
+                    base_peers: match i {
+                        4 => 4.0, // Poorly connected
+                        _ => 60.0 + rng.below(60) as f64,
+                    },
+                    bytes_in: rng.below(50_000_000_000),
+                    bytes_out: rng.below(50_000_000_000),
+                    records: rng.below(20_000),
+                    rewards: rng.below(500),
+                    put_errors: rng.below(50),
+                    connection_errors: rng.below(2_000),
+                    dialed: 0,
+                    leaking: i == 1,
+                    leaked_mb: 0.0,
+                    rng,
+                }
+            })
+            .collect();
+        DemoFleet {
+            nodes,
+            samples: 0,
+            started: Instant::now(),
+            last_sample: Instant::now(),
+        }
+    }
+
+    /// The fake node directories and the metrics URLs of the nodes that are not stopped.
+    pub fn discovered_nodes(&self) -> (Vec<String>, Vec<DiscoveredNode>) {
+        let dirs = self
+            .nodes
+            .iter()
+            .map(|node| node.dir_path.clone())
+            .collect();
+        let discovered = self
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                Some(DiscoveredNode {
+                    dir_path: node.dir_path.clone(),
+                    url: node.url.clone()?,
+                    source: DiscoverySource::Demo,
+                })
+            })
+            .collect();
+        (dirs, discovered)
+    }
+
+    /// Advances the fleet to now and returns one sweep of results, like
+    /// `fetch_metrics_in_background` does.
+    pub fn sample(&mut self) -> Vec<FetchResult> {
+        let elapsed = self.last_sample.elapsed().as_secs_f64();
+        self.last_sample = Instant::now();
+        self.samples += 1;
+        let samples = self.samples;
+        let running_secs = self.started.elapsed().as_secs();
+
+        self.nodes
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, node)| {
+                let url = node.url.clone()?;
+                let flapping = i % FLAPPING_NODE_EVERY == FLAPPING_NODE_EVERY - 1;
+                let down =
+                    flapping && samples % (FLAP_UP_SAMPLES + FLAP_DOWN_SAMPLES) >= FLAP_UP_SAMPLES;
+                if down {
+                    return Some(FetchResult {
+                        url,
+                        body: Err(FetchError::new(
+                            FetchErrorKind::Connection,
+                            "Network error: demo node down",
+                        )),
+                    });
+                }
+                Some(FetchResult {
+                    url,
+                    body: Ok(node.advance(elapsed, samples, running_secs)),
+                })
+            })
+            .collect()
+    }
+}
+
+impl DemoNode {
+    // Moves the counters forward by `elapsed` seconds and renders the metrics text
+    fn advance(&mut self, elapsed: f64, samples: u64, running_secs: u64) -> String {
+        // Slow waves plus noise, so sparklines have something to show
+        let phase = samples as f64 / 10.0 + self.base_peers;
+        let speed_in =
+            20_000.0 + 150_000.0 * (0.5 + 0.5 * phase.sin()) + self.rng.below(30_000) as f64;
+        let speed_out =
+            10_000.0 + 80_000.0 * (0.5 + 0.5 * (phase * 0.7).cos()) + self.rng.below(20_000) as f64;
+        self.bytes_in += (speed_in * elapsed) as u64;
+        self.bytes_out += (speed_out * elapsed) as u64;
+        if self.rng.below(4) == 0 {
+            self.records += self.rng.below(5);
+        }
+        if self.rng.below(30) == 0 {
+            self.rewards += 1 + self.rng.below(3);
+        }
+        if self.rng.below(10) == 0 {
+            self.put_errors += 1;
+        }
+        self.connection_errors += self.rng.below(3);
+        self.dialed += self.rng.below(4);
+
+        let cpu = (self.base_cpu + 4.0 * (phase * 1.3).sin() + self.rng.below(300) as f64 / 100.0)
+            .clamp(0.0, 100.0);
+        let peers = (self.base_peers + 5.0 * phase.cos()).max(0.0) as u64;
+        let uptime = self.started_secs_ago + running_secs;
+        if self.leaking {
+            self.leaked_mb += LEAK_MB_PER_MIN * elapsed / 60.0;
+        }
+        let memory =
+            180.0 + self.base_peers * 2.0 + self.leaked_mb + self.rng.below(200) as f64 / 100.0;
+
+        format!(
+            "ant_node_uptime {uptime}\n\
+             ant_networking_process_memory_used_mb {memory:.2}\n\
+             ant_networking_process_cpu_usage_percentage {cpu:.2}\n\
+             ant_networking_connected_peers {peers}\n\
+             ant_networking_peers_in_routing_table {routing}\n\
+             ant_networking_estimated_network_size {network_size}\n\
+             ant_networking_records_stored {records}\n\
+             ant_node_put_record_err_total {put_errors}\n\
+             ant_node_current_reward_wallet_balance {rewards}\n\
+             ant_networking_shunned_count_total {shunned}\n\
+             libp2p_bandwidth_bytes_total{{direction=\"Inbound\"}} {bytes_in}\n\
+             libp2p_bandwidth_bytes_total{{direction=\"Outbound\"}} {bytes_out}\n\
+             libp2p_swarm_connections_established_total{{role=\"Dialer\"}} {dialer}\n\
+             libp2p_swarm_connections_established_total{{role=\"Listener\"}} {listener}\n\
+             libp2p_swarm_connections_incoming_error_total{{error=\"Aborted\"}} {incoming_errors}\n\
+             libp2p_swarm_outgoing_connection_error_total{{error=\"Transport\"}} {outgoing_errors}\n\
+             libp2p_kad_query_result_get_closest_peers_error_total{{error=\"Timeout\"}} {kad_errors}\n",
+            routing = peers * 3 + 40,
+            network_size = 250_000 + samples % 1_000,
+            records = self.records,
+            put_errors = self.put_errors,
+            rewards = self.rewards,
+            shunned = self.base_cpu as u64 / 20,
+            bytes_in = self.bytes_in,
+            bytes_out = self.bytes_out,
+            dialer = 500 + self.dialed * 3,
+            // Poorly connected nodes get few inbound connections, like behind a NAT
+            listener = if self.base_peers < 10.0 {
+                20
+            } else {
+                500 + self.base_peers as u64 * 10 + self.dialed
+            },
+            incoming_errors = self.connection_errors / 2,
+            outgoing_errors = self.connection_errors - self.connection_errors / 2,
+            kad_errors = self.connection_errors / 10,
+        )
+    }
+}
+
+// Small deterministic generator, demo data doesn't need a real RNG
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    // Uniform-ish value in 0..bound
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound.max(1)
+    }
+}
@@ -1,32 +1,90 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use glob::glob;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    fmt,
     fs::{self},
-    path::PathBuf,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::LazyLock,
 };
 
+// The line a node logs when its metrics server starts, with the address it listens on
+static METRICS_SERVER_LINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"Metrics server on (\S+)").expect("valid regex"));
+
+// Lines read from the start of a log file, the metrics server starts early
+const LOG_LINES_SCANNED: usize = 50;
+
+/// Where the metrics URL of a node was found. New discovery strategies add a variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoverySource {
+    LogFile(PathBuf),   // "Metrics server on ..." line in this log file
+    Demo,               // Fake node from `--demo`
+    JsonInput(PathBuf), // Node of the JSON snapshot read with `--json-input`
+    History(PathBuf),   // Node of the state file replayed by `antop history`
+    Override(PathBuf),  // Entry of the `--url-overrides` file, wins over the logs
+}
+
+impl fmt::Display for DiscoverySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscoverySource::LogFile(path) => write!(f, "log file {}", path.display()),
+            DiscoverySource::Demo => write!(f, "demo data"),
+            DiscoverySource::JsonInput(path) => write!(f, "JSON snapshot {}", path.display()),
+            DiscoverySource::History(path) => write!(f, "state file {}", path.display()),
+            DiscoverySource::Override(path) => write!(f, "URL override in {}", path.display()),
+        }
+    }
+}
+
+/// Which "Metrics server on" line of a log file gives the metrics URL, when a node
+/// logged several (restarts, port rebinding).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchStrategy {
+    First, // Address from the initial startup
+    #[default]
+    Last, // Latest address, it supersedes the previous ones
+}
+
+/// A node directory with the metrics URL discovered for it.
+#[derive(Debug, Clone)]
+pub struct DiscoveredNode {
+    pub dir_path: String, // Node root directory, the key used throughout the app
+    pub url: String,
+    pub source: DiscoverySource,
+}
+
+/// A log file left out of the discovery, larger than `--max-log-file-size`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OversizedLog {
+    pub path: PathBuf,
+    pub size: u64, // Bytes
+}
+
+/// Result of `find_metrics_nodes`: the nodes found, and the logs too large to read.
+#[derive(Debug, Clone, Default)]
+pub struct Discovery {
+    pub nodes: Vec<DiscoveredNode>, // Sorted by directory, one per metrics URL
+    pub oversized_logs: Vec<OversizedLog>,
+}
+
 /// Finds node root directories matching the provided glob pattern
 /// that also contain an `antnode.pid` file, indicating a potentially running node.
 pub fn find_node_directories(path_glob: &str) -> Result<Vec<String>> {
     let mut directories = Vec::new();
-    for entry in glob(path_glob).context("Failed to read node path glob pattern")? {
-        match entry {
-            Ok(path) => {
-                // Ensure it's a directory
-                if path.is_dir() {
-                    let antnode_pid_path = path.join("antnode.pid");
-
-                    // Check if `antnode.pid` exists and is a file
-                    if antnode_pid_path.is_file() {
-                        // It's a directory containing the PID file
-                        directories.push(path.to_string_lossy().to_string());
-                    }
-                }
-            }
-            Err(e) => {
-                // Log or handle individual glob errors if necessary
-                eprintln!("Warning: Error processing path entry: {}", e);
+    for path in glob_paths(path_glob, "path")? {
+        // Ensure it's a directory
+        if path.is_dir() {
+            let antnode_pid_path = path.join("antnode.pid");
+
+            // Check if `antnode.pid` exists and is a file
+            if antnode_pid_path.is_file() {
+                // It's a directory containing the PID file
+                directories.push(path.to_string_lossy().to_string());
             }
         }
     }
@@ -34,76 +92,276 @@ pub fn find_node_directories(path_glob: &str) -> Result<Vec<String>> {
     Ok(directories)
 }
 
+/// Paths matching the glob `pattern`. A pattern the glob syntax rejects, like a
+/// directory name with an unbalanced `[`, is taken as a literal path when it has no
+/// `*` or `?` and exists, otherwise the error points at the offending character.
+/// `what` names the pattern in the error ("path", "log path").
+fn glob_paths(pattern: &str, what: &str) -> Result<Vec<PathBuf>> {
+    let paths = match glob(pattern) {
+        Ok(paths) => paths,
+        Err(_) if !pattern.contains(['*', '?']) && Path::new(pattern).exists() => {
+            return Ok(vec![PathBuf::from(pattern)]);
+        }
+        Err(e) => {
+            bail!(
+                "Invalid {} pattern: {} at position {}\n  {}\n  {}^\n\
+                 Wildcards are `*`, `?` and `[...]`, match a literal `[` or `]` with `[[]` or `[]]`",
+                what,
+                e.msg,
+                e.pos,
+                pattern,
+                " ".repeat(e.pos)
+            );
+        }
+    };
+    Ok(paths
+        .filter_map(|entry| {
+            entry
+                .map_err(|e| eprintln!("Warning: Error processing path entry: {}", e))
+                .ok()
+        })
+        .collect())
+}
+
+/// Reads a `--url-overrides` file: a TOML table mapping node directories to the
+/// metrics URL to use for them instead of the one found in their logs.
+///
+/// ```toml
+/// "~/.local/share/autonomi/node/antnode-3" = "http://10.0.0.5:8080/antnode-3/metrics"
+/// ```
+pub fn load_url_overrides(path: &str) -> Result<Vec<DiscoveredNode>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read URL overrides file: {}", path))?;
+    let overrides: BTreeMap<String, String> = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse URL overrides file: {}", path))?;
+    Ok(overrides
+        .into_iter()
+        .map(|(dir_path, url)| DiscoveredNode {
+            // Same form as the discovered directories: expanded, no trailing slash
+            dir_path: shellexpand::tilde(dir_path.trim_end_matches('/')).into_owned(),
+            url,
+            source: DiscoverySource::Override(PathBuf::from(path)),
+        })
+        .collect())
+}
+
 /// Finds metrics node addresses by scanning log files specified by the glob pattern.
 /// Extracts node name from the parent directory of the log file.
-pub async fn find_metrics_nodes(log_path_glob: PathBuf) -> Result<Vec<(String, String)>> {
-    let re = Regex::new(r"Metrics server on (\S+)")?;
-    let mut nodes: Vec<(String, String)> = Vec::new();
-
-    // Convert PathBuf to string for glob, handle potential errors
-    let glob_str = log_path_glob
-        .to_str()
-        .ok_or_else(|| anyhow::anyhow!("Log path is not valid UTF-8"))?;
-
-    for entry in glob(glob_str).context("Failed to read log path glob pattern")? {
-        match entry {
-            Ok(log_file_path) => {
-                if log_file_path.is_file() {
-                    // Try to get the parent directory of the log file
-                    if let Some(log_parent_dir) = log_file_path.parent() {
-                        // Now, get the parent of the log's parent directory (the node's root)
-                        if let Some(node_root_dir) = log_parent_dir.parent() {
-                            // Use the full path of the node's root directory as the identifier
-                            let root_path = node_root_dir.to_string_lossy().to_string();
-
-                            match process_log_file(&log_file_path, &re) {
-                                Ok(Some(address)) => {
-                                    // Push the root_path and address
-                                    nodes.push((root_path, address));
-                                }
-                                Ok(None) => {
-                                    // Log file processed, but no metrics address found
-                                }
-                                Err(_err) => {
-                                    // Error reading or processing this specific log file
-                                    // Optionally log this error
-                                }
-                            }
-                        } else {
-                            // Could not get parent of parent (e.g., log file is not in a 'logs' subdir?)
-                            // Optionally log this, or perhaps fallback to log_parent_dir?
-                            // For now, just skip if we can't get the node root dir this way.
-                        }
-                    } else {
-                        // Could not get parent directory for the log file
-                        // Optionally log this
-                    }
+/// With `per_node_log_glob_template`, the logs of each of `node_dirs` are found by
+/// replacing `{name}` with the basename of its directory instead, for logs kept away
+/// from the node directories, and `log_path_glob` is not used.
+/// `strategy` and `nth` pick the address of logs with several, see `process_log_file`.
+/// Log files larger than `max_log_file_size` bytes are not read, they are returned
+/// along with the nodes so the caller can tell the user.
+///
+/// ```no_run
+/// use antop::discovery::{MatchStrategy, find_metrics_nodes};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let logs = shellexpand::tilde("~/.local/share/autonomi/node/*/logs/antnode.log");
+/// let discovery = find_metrics_nodes(
+///     logs.as_ref().into(),
+///     None,
+///     &[],
+///     MatchStrategy::Last,
+///     None,
+///     50 * 1024 * 1024,
+/// )
+/// .await?;
+/// for node in &discovery.nodes {
+///     println!("{} -> {} (from {})", node.dir_path, node.url, node.source);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn find_metrics_nodes(
+    log_path_glob: PathBuf,
+    per_node_log_glob_template: Option<&str>,
+    node_dirs: &[String],
+    strategy: MatchStrategy,
+    nth: Option<usize>,
+    max_log_file_size: u64,
+) -> Result<Discovery> {
+    let mut nodes: Vec<DiscoveredNode> = Vec::new();
+    let mut oversized = Vec::new();
+    let mut scan = |log_file_path: &PathBuf| -> Option<String> {
+        // Logs of nodes running for months can be gigabytes, read whole they would
+        // stall the discovery. A file whose size can't be read is skipped like one
+        // that can't be read.
+        let size = fs::metadata(log_file_path).ok()?.len();
+        if size > max_log_file_size {
+            oversized.push(OversizedLog {
+                path: log_file_path.clone(),
+                size,
+            });
+            return None;
+        }
+        // A file that can't be read or has no metrics address gives nothing
+        process_log_file(log_file_path, strategy, nth)
+            .ok()
+            .flatten()
+    };
+
+    if let Some(template) = per_node_log_glob_template {
+        for dir_path in node_dirs {
+            let Some(name) = Path::new(dir_path).file_name() else {
+                continue;
+            };
+            let pattern = template.replace("{name}", &name.to_string_lossy());
+            // The last matching file with an address wins
+            let mut found = None;
+            for log_file_path in glob_paths(&pattern, "log glob override")? {
+                if log_file_path.is_file()
+                    && let Some(address) = scan(&log_file_path)
+                {
+                    found = Some((address, log_file_path));
                 }
             }
-            Err(_e) => { /* Optionally log glob pattern error */ }
+            if let Some((address, log_file_path)) = found {
+                nodes.push(DiscoveredNode {
+                    dir_path: dir_path.clone(),
+                    url: address,
+                    source: DiscoverySource::LogFile(log_file_path),
+                });
+            }
+        }
+    } else {
+        // Convert PathBuf to string for glob, handle potential errors
+        let glob_str = log_path_glob
+            .to_str()
+            .ok_or_else(|| anyhow::anyhow!("Log path is not valid UTF-8"))?;
+
+        for log_file_path in glob_paths(glob_str, "log path")? {
+            if !log_file_path.is_file() {
+                continue;
+            }
+            // The node's root is the parent of the log's directory (`<root>/logs/antnode.log`),
+            // logs elsewhere are skipped
+            let Some(node_root_dir) = log_file_path.parent().and_then(Path::parent) else {
+                continue;
+            };
+            // Use the full path of the node's root directory as the identifier
+            let root_path = node_root_dir.to_string_lossy().to_string();
+            if let Some(address) = scan(&log_file_path) {
+                // Push the root_path and address, tagged with the log file
+                nodes.push(DiscoveredNode {
+                    dir_path: root_path,
+                    url: address,
+                    source: DiscoverySource::LogFile(log_file_path.clone()),
+                });
+            }
         }
     }
 
-    nodes.sort_by(|a, b| a.0.cmp(&b.0));
+    nodes.sort_by(|a, b| a.dir_path.cmp(&b.dir_path));
     // Note: Deduping by address might hide multiple nodes reporting the same address.
     // Consider if this is the desired behavior.
-    nodes.dedup_by(|a, b| a.1 == b.1);
-    Ok(nodes)
+    nodes.dedup_by(|a, b| a.url == b.url);
+    Ok(Discovery {
+        nodes,
+        oversized_logs: oversized,
+    })
 }
 
-/// Reads a single log file and extracts the last metrics node address.
-fn process_log_file(path: &PathBuf, re: &Regex) -> Result<Option<String>> {
+/// Reads a single log file and extracts a metrics node address, see
+/// `find_metrics_address`.
+fn process_log_file(
+    path: &PathBuf,
+    strategy: MatchStrategy,
+    nth: Option<usize>,
+) -> Result<Option<String>> {
     let content =
         fs::read_to_string(path).with_context(|| format!("Failed to read log file: {:?}", path))?;
+    Ok(find_metrics_address(&content, strategy, nth))
+}
+
+/// Metrics address in the text of a node log: the first or last one found, per
+/// `strategy`, or the `nth` one (1-indexed) when given. A log with fewer than `nth`
+/// addresses gives its last one. Only the first lines are looked at.
+///
+/// ```
+/// use antop::discovery::{MatchStrategy, find_metrics_address};
+///
+/// let log = "[2025-05-01T10:00:00Z INFO] Metrics server on http://127.0.0.1:13001\n\
+///            [2025-05-02T08:30:00Z INFO] Metrics server on http://127.0.0.1:13002\n";
+/// let last = find_metrics_address(log, MatchStrategy::Last, None);
+/// assert_eq!(last.as_deref(), Some("http://127.0.0.1:13002"));
+/// let first = find_metrics_address(log, MatchStrategy::Last, Some(1));
+/// assert_eq!(first.as_deref(), Some("http://127.0.0.1:13001"));
+/// ```
+pub fn find_metrics_address(
+    log: &str,
+    strategy: MatchStrategy,
+    nth: Option<usize>,
+) -> Option<String> {
     let mut last_match: Option<String> = None;
-    // Limit lines read for performance, especially on large logs.
-    // Increased slightly from 40, just in case.
-    for line in content.lines().take(50) {
-        if let Some(caps) = re.captures(line) {
-            if let Some(address) = caps.get(1) {
-                last_match = Some(address.as_str().to_string());
+    let mut matches = 0;
+    for line in log.lines().take(LOG_LINES_SCANNED) {
+        if let Some(caps) = METRICS_SERVER_LINE.captures(line)
+            && let Some(address) = caps.get(1)
+        {
+            last_match = Some(address.as_str().to_string());
+            matches += 1;
+            let done = match nth {
+                Some(nth) => matches >= nth,
+                None => strategy == MatchStrategy::First,
+            };
+            if done {
+                break;
             }
         }
     }
-    Ok(last_match)
+    last_match
+}
+
+/// Whether the metrics server at `url` is bound to `filter`, either the name of a
+/// network interface (`eth1`) or a prefix of the IP address (`192.168.1.`, `fe80:`).
+/// A server bound to all addresses (`0.0.0.0`, `::`) is on every interface.
+pub fn matches_interface(url: &str, filter: &str) -> bool {
+    let Some(host) = url_host_ip(url) else {
+        return false;
+    };
+    match interface_addresses(filter) {
+        Some(addresses) => host.is_unspecified() || addresses.contains(&host),
+        None => host.to_string().starts_with(filter),
+    }
+}
+
+// IP address of the host of a metrics URL, None for a host name or an unparsable URL
+fn url_host_ip(url: &str) -> Option<IpAddr> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    // IPv6 hosts come back in brackets
+    let host = parsed
+        .host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    host.parse().ok()
+}
+
+// Addresses of the interface called `name`, None when there is no such interface
+#[cfg(unix)]
+fn interface_addresses(name: &str) -> Option<Vec<IpAddr>> {
+    let mut found = false;
+    let mut addresses = Vec::new();
+    for interface in nix::ifaddrs::getifaddrs().ok()? {
+        if interface.interface_name != name {
+            continue;
+        }
+        found = true;
+        let Some(address) = interface.address else {
+            continue;
+        };
+        if let Some(v4) = address.as_sockaddr_in() {
+            addresses.push(IpAddr::V4(v4.ip()));
+        } else if let Some(v6) = address.as_sockaddr_in6() {
+            addresses.push(IpAddr::V6(v6.ip()));
+        }
+    }
+    found.then_some(addresses)
+}
+
+// Interface names are only resolved on Unix, elsewhere the filter is an IP prefix
+#[cfg(not(unix))]
+fn interface_addresses(_name: &str) -> Option<Vec<IpAddr>> {
+    None
 }
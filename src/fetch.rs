@@ -1,39 +1,235 @@
 use anyhow::Result; // Keep Result for potential internal errors, though return type is specific
-use futures::future::join_all;
-use std::time::Duration;
+use futures::{StreamExt, stream};
+use std::{fmt, time::Duration};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::metrics::check_prometheus_format;
 
 // Add the specific import instead of the crate import
-use reqwest::Client;
+use reqwest::{Client, header::LOCATION, redirect::Policy};
+
+/// Why a metrics fetch failed. A node answering with an HTTP error is up, its metrics
+/// handler (or a proxy in front of it) failed, unlike one that can't be reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    Connection,  // Refused, reset or unresolvable, the node is likely down
+    Timeout,     // No answer in time, an overloaded node or host
+    Client(u16), // 4xx, usually a wrong metrics URL
+    Server(u16), // 5xx, the node is up but its metrics handler failed
+    Redirect,    // Redirect loop, too many redirects or one not followed
+    Body,        // Body unreadable or not Prometheus text
+}
+
+impl FetchErrorKind {
+    /// Short label shown in the Status column.
+    pub fn label(self) -> String {
+        match self {
+            FetchErrorKind::Connection => "Conn error".to_string(),
+            FetchErrorKind::Timeout => "Timeout".to_string(),
+            FetchErrorKind::Client(code) | FetchErrorKind::Server(code) => {
+                format!("HTTP {}", code)
+            }
+            FetchErrorKind::Redirect => "Redirect".to_string(),
+            FetchErrorKind::Body => "Bad body".to_string(),
+        }
+    }
+}
+
+/// A failed fetch: its category and the full message, shown in the detail popup.
+#[derive(Debug, Clone)]
+pub struct FetchError {
+    pub kind: FetchErrorKind,
+    pub message: String,
+}
+
+impl FetchError {
+    /// A failed fetch of `kind`, `message` says what happened.
+    pub fn new(kind: FetchErrorKind, message: impl Into<String>) -> FetchError {
+        FetchError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// The outcome of fetching the metrics of one node.
+#[derive(Debug, Clone)]
+pub struct FetchResult {
+    pub url: String,                      // Metrics URL the node was fetched from
+    pub body: Result<String, FetchError>, // Raw metrics text, see `parse_metrics`
+}
+
+/// Progress of a background metrics sweep, sent from the fetch task to the UI loop.
+#[derive(Debug)]
+pub enum FetchEvent {
+    Progress { done: usize, total: usize }, // One more node answered (or failed)
+    Finished(Vec<FetchResult>),             // All nodes done
+}
+
+/// Fetches metrics data from a list of server addresses concurrently, at most
+/// `max_concurrent` at a time (None = all at once), following up to `max_redirects`.
+/// Reports each completed node on `events`, then sends all the results as
+/// `FetchEvent::Finished`, in the order the nodes answered.
+/// Meant to be spawned so the UI keeps drawing while a sweep is in flight.
+pub async fn fetch_metrics_in_background(
+    addresses: Vec<String>,
+    max_concurrent: Option<usize>,
+    max_redirects: usize,
+    events: UnboundedSender<FetchEvent>,
+) {
+    let client = build_client(max_redirects);
+    let total = addresses.len();
+    let limit = max_concurrent.unwrap_or(total).max(1);
+    let mut pending = stream::iter(addresses)
+        .map(|addr| fetch_one(client.clone(), addr))
+        .buffer_unordered(limit);
+
+    let mut results = Vec::with_capacity(total);
+    while let Some(result) = pending.next().await {
+        results.push(result);
+        // The receiver only goes away when the app exits, nothing left to do then
+        if events
+            .send(FetchEvent::Progress {
+                done: results.len(),
+                total,
+            })
+            .is_err()
+        {
+            return;
+        }
+    }
+    let _ = events.send(FetchEvent::Finished(results));
+}
+
+/// Fetches the raw metrics text of a single node, to show it as served.
+pub async fn fetch_raw_metrics(addr: String, max_redirects: usize) -> Result<String, String> {
+    fetch_one(build_client(max_redirects), addr)
+        .await
+        .body
+        .map_err(|e| e.message)
+}
+
+/// Fetches the metrics of a single node within `timeout` and checks they are
+/// Prometheus text, for `antop validate`.
+pub async fn probe_metrics(
+    addr: String,
+    timeout: Duration,
+    max_redirects: usize,
+) -> Result<(), String> {
+    let client = Client::builder()
+        .timeout(timeout)
+        .redirect(redirect_policy(max_redirects))
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    let body = fetch_one(client, addr).await.body.map_err(|e| e.message)?;
+    check_prometheus_format(&body)
+}
 
-/// Fetches metrics data from a list of server addresses concurrently.
-/// Returns a vector of tuples: (address, Result<raw_metrics_string, error_string>).
-pub async fn fetch_metrics(addresses: &[String]) -> Vec<(String, Result<String, String>)> {
-    // Using Result<String, String> as per original design
-    let client = Client::builder() // Use Client directly
+// Shared HTTP client settings for metrics requests
+fn build_client(max_redirects: usize) -> Client {
+    Client::builder() // Use Client directly
         .timeout(Duration::from_secs(2)) // Shorter timeout for TUI responsiveness
+        .redirect(redirect_policy(max_redirects))
         .build()
         // Consider proper error handling instead of unwrap_or_else
-        .unwrap_or_else(|_| Client::new()); // Use Client directly
-
-    let futures = addresses.iter().map(|addr| {
-        let client = client.clone();
-        let addr = addr.clone();
-        async move {
-            let url = format!("{}/metrics", addr);
-            let result = client.get(&url).send().await;
-
-            match result {
-                Ok(response) => match response.error_for_status() {
-                    Ok(successful_response) => match successful_response.text().await {
-                        Ok(text) => (addr, Ok(text)),
-                        Err(e) => (addr, Err(format!("Read body error: {}", e))),
+        .unwrap_or_else(|_| Client::new()) // Use Client directly
+}
+
+// Follows up to `max_redirects` redirects, like some proxies do from `/metrics` to
+// `/metrics/`. A URL seen before in the chain is a loop, reported as such
+fn redirect_policy(max_redirects: usize) -> Policy {
+    if max_redirects == 0 {
+        return Policy::none();
+    }
+    Policy::custom(move |attempt| {
+        if attempt.previous().contains(attempt.url()) {
+            let loop_error = format!("redirect loop back to {}", attempt.url());
+            attempt.error(loop_error)
+        } else if attempt.previous().len() > max_redirects {
+            let limit_error = format!("more than {} redirects (--max-redirects)", max_redirects);
+            attempt.error(limit_error)
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+// Fetches the raw metrics text of a single node
+async fn fetch_one(client: Client, addr: String) -> FetchResult {
+    // A trailing slash would make it `//metrics`, which not every server takes
+    let url = format!("{}/metrics", addr.trim_end_matches('/'));
+    let result = client.get(&url).send().await;
+
+    let result = match result {
+        // Only answered with redirects when they are not followed, `--max-redirects 0`
+        Ok(response) if response.status().is_redirection() => {
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .unwrap_or("an unknown location");
+            Err(FetchError::new(
+                FetchErrorKind::Redirect,
+                format!(
+                    "Redirect error: {} to {}, not followed (--max-redirects 0)",
+                    response.status(),
+                    location
+                ),
+            ))
+        }
+        Ok(response) => match response.error_for_status() {
+            Ok(successful_response) => match successful_response.text().await {
+                Ok(text) => Ok(text),
+                Err(e) => Err(FetchError::new(
+                    if e.is_timeout() {
+                        FetchErrorKind::Timeout
+                    } else {
+                        FetchErrorKind::Body
                     },
-                    Err(status_error) => (addr, Err(format!("HTTP error: {}", status_error))),
-                },
-                Err(network_error) => (addr, Err(format!("Network error: {}", network_error))),
+                    format!("Read body error: {}", e),
+                )),
+            },
+            Err(status_error) => {
+                let code = status_error.status().map_or(0, |status| status.as_u16());
+                let kind = if code >= 500 {
+                    FetchErrorKind::Server(code)
+                } else {
+                    FetchErrorKind::Client(code)
+                };
+                Err(FetchError::new(
+                    kind,
+                    format!("HTTP error: {}", status_error),
+                ))
             }
+        },
+        // The policy's own message is the cause of the reqwest error
+        Err(redirect_error) if redirect_error.is_redirect() => {
+            let reason = std::error::Error::source(&redirect_error)
+                .map_or_else(|| redirect_error.to_string(), |cause| cause.to_string());
+            Err(FetchError::new(
+                FetchErrorKind::Redirect,
+                format!("Redirect error: {}", reason),
+            ))
         }
-    });
-
-    join_all(futures).await
+        Err(network_error) => Err(FetchError::new(
+            if network_error.is_timeout() {
+                FetchErrorKind::Timeout
+            } else {
+                FetchErrorKind::Connection
+            },
+            format!("Network error: {}", network_error),
+        )),
+    };
+    FetchResult {
+        url: addr,
+        body: result,
+    }
 }
@@ -0,0 +1,448 @@
+use crate::webhook::WebhookEvent;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io,
+    process::Stdio,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    process::Command,
+    sync::{Semaphore, mpsc},
+};
+
+// Default time between two runs of the hook of the same event for the same node
+pub const DEFAULT_HOOK_COOLDOWN_SECS: u64 = 300;
+// Default hooks running at once, the others wait for their turn
+pub const DEFAULT_MAX_CONCURRENT_HOOKS: usize = 4;
+// A hook still running after this long is killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+// Hooks waiting to run, the ones arriving while the queue is full are dropped
+const MAX_QUEUED_HOOKS: usize = 64;
+
+/// The `[hooks]` section of the config file: a command per event, run with the node
+/// of the event. The command is split on spaces and run without a shell, `{node}`,
+/// `{node_dir}`, `{event}` and `{value}` are replaced in each argument.
+///
+/// ```toml
+/// [hooks]
+/// on_node_down = "/usr/local/bin/antnode-recover {node_dir}"
+/// on_node_recovered = "logger antop: {node} is back up"
+/// on_outlier = "/usr/local/bin/page-me {node} {value}"
+/// on_nat = "/usr/local/bin/page-me {node} {value}"
+/// max_concurrent = 4  # hooks running at once (default 4)
+/// cooldown_secs = 300 # per node and event (default 300)
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HooksConfig {
+    pub on_node_down: Option<String>,
+    pub on_node_recovered: Option<String>,
+    pub on_outlier: Option<String>,
+    pub on_nat: Option<String>,
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_max_concurrent() -> usize {
+    DEFAULT_MAX_CONCURRENT_HOOKS
+}
+
+fn default_cooldown_secs() -> u64 {
+    DEFAULT_HOOK_COOLDOWN_SECS
+}
+
+impl HooksConfig {
+    /// Command template of `event`, None when it has no hook.
+    pub fn command(&self, event: WebhookEvent) -> Option<&str> {
+        match event {
+            WebhookEvent::NodeDown => self.on_node_down.as_deref(),
+            WebhookEvent::NodeRecovered => self.on_node_recovered.as_deref(),
+            WebhookEvent::Outlier => self.on_outlier.as_deref(),
+            WebhookEvent::Nat => self.on_nat.as_deref(),
+        }
+    }
+
+    /// Fails on an empty command or a `max_concurrent` of 0.
+    pub fn check(&self) -> Result<()> {
+        if self.max_concurrent == 0 {
+            bail!("Invalid [hooks] max_concurrent 0: at least 1 hook must run at once");
+        }
+        for event in WebhookEvent::ALL {
+            if self
+                .command(event)
+                .is_some_and(|command| command.trim().is_empty())
+            {
+                bail!("Invalid [hooks] on_{}: the command is empty", event.name());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Arguments of the hook command `template` for `event` of a node: the template split
+/// on spaces, the placeholders replaced in each part. A value with spaces stays one
+/// argument and is never seen by a shell.
+pub fn expand_command(
+    template: &str,
+    node: &str,
+    node_dir: &str,
+    event: WebhookEvent,
+    value: Option<&str>,
+) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|part| {
+            part.replace("{node}", node)
+                .replace("{node_dir}", node_dir)
+                .replace("{event}", event.name())
+                .replace("{value}", value.unwrap_or_default())
+        })
+        .collect()
+}
+
+/// A hook to run: the expanded command, and the event for its environment and report.
+#[derive(Debug, Clone)]
+pub struct HookRun {
+    pub event: WebhookEvent,
+    pub node: String,
+    pub node_dir: String,
+    pub value: Option<String>,
+    pub command: Vec<String>,
+}
+
+/// Hooks of `[hooks]`: `notify` drops the events without a hook or whose hook ran too
+/// recently for the same node, `take` hands the others to `run_app`, which runs them
+/// in the background.
+#[derive(Debug)]
+pub struct HookRunner {
+    config: HooksConfig,
+    cooldown: Duration,
+    last_run: HashMap<(String, WebhookEvent), Instant>,
+    pending: Vec<HookRun>,
+}
+
+impl HookRunner {
+    /// None without a `[hooks]` section.
+    pub fn new(config: Option<&HooksConfig>) -> Option<HookRunner> {
+        let config = config?.clone();
+        Some(HookRunner {
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            config,
+            last_run: HashMap::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Most hooks running at once.
+    pub fn max_concurrent(&self) -> usize {
+        self.config.max_concurrent
+    }
+
+    /// Queues the hook of `event` for the node in `node_dir` unless the same hook ran
+    /// for it less than the cooldown ago, so a flapping node runs it once per cooldown.
+    pub fn notify(
+        &mut self,
+        node: &str,
+        node_dir: &str,
+        event: WebhookEvent,
+        value: Option<String>,
+        now: Instant,
+    ) {
+        let Some(template) = self.config.command(event) else {
+            return;
+        };
+        let key = (node_dir.to_string(), event);
+        if self
+            .last_run
+            .get(&key)
+            .is_some_and(|ran| now.saturating_duration_since(*ran) < self.cooldown)
+        {
+            return;
+        }
+        self.last_run.insert(key, now);
+        self.pending.push(HookRun {
+            event,
+            node: node.to_string(),
+            node_dir: node_dir.to_string(),
+            command: expand_command(template, node, node_dir, event, value.as_deref()),
+            value,
+        });
+    }
+
+    /// The hooks queued since the last call, oldest first.
+    pub fn take(&mut self) -> Vec<HookRun> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// How a hook run ended, kept per node in `App::hook_log`.
+#[derive(Debug, Clone)]
+pub struct HookOutcome {
+    pub event: WebhookEvent,
+    pub node: String,
+    pub node_dir: String,
+    pub exit_code: Option<i32>, // None when it didn't start, timed out or was killed
+    pub result: String,         // "exited with 1: disk full", "killed after 60s"...
+    pub at: DateTime<Local>,
+}
+
+impl HookOutcome {
+    /// Whether the hook exited with 0.
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    /// The outcome as a sentence for the status bar.
+    pub fn text(&self) -> String {
+        format!(
+            "Hook on_{} of {} {}",
+            self.event.name(),
+            self.node,
+            self.result
+        )
+    }
+}
+
+/// Starts the task running the hooks sent to the returned channel, up to
+/// `max_concurrent` at once. The outcome of each goes to `outcomes`.
+pub fn start_hooks(
+    max_concurrent: usize,
+    outcomes: mpsc::Sender<HookOutcome>,
+) -> mpsc::Sender<HookRun> {
+    let (runs, mut queued) = mpsc::channel::<HookRun>(MAX_QUEUED_HOOKS);
+    let slots = Arc::new(Semaphore::new(max_concurrent));
+    tokio::spawn(async move {
+        while let Some(run) = queued.recv().await {
+            let Ok(slot) = slots.clone().acquire_owned().await else {
+                return;
+            };
+            let outcomes = outcomes.clone();
+            tokio::spawn(async move {
+                let outcome = run_hook(&run).await;
+                drop(slot);
+                let _ = outcomes.send(outcome).await;
+            });
+        }
+    });
+    runs
+}
+
+// Runs the command of `run` with the event in ANTOP_* variables
+async fn run_hook(run: &HookRun) -> HookOutcome {
+    let outcome = |exit_code, result: String| HookOutcome {
+        event: run.event,
+        node: run.node.clone(),
+        node_dir: run.node_dir.clone(),
+        exit_code,
+        result,
+        at: Local::now(),
+    };
+    let Some((program, args)) = run.command.split_first() else {
+        return outcome(None, "has no command".to_string());
+    };
+    let output = Command::new(program)
+        .args(args)
+        .env("ANTOP_EVENT", run.event.name())
+        .env("ANTOP_NODE", &run.node)
+        .env("ANTOP_NODE_DIR", &run.node_dir)
+        .env("ANTOP_VALUE", run.value.as_deref().unwrap_or_default())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+    let output = match tokio::time::timeout(HOOK_TIMEOUT, output).await {
+        Err(_) => return outcome(None, format!("killed after {:?}", HOOK_TIMEOUT)),
+        Ok(Err(e)) if e.kind() == io::ErrorKind::NotFound => {
+            return outcome(None, format!("failed to start: {} not found", program));
+        }
+        Ok(Err(e)) => return outcome(None, format!("failed to start: {}", e)),
+        Ok(Ok(output)) => output,
+    };
+    // Scripts tend to end with what went wrong
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .map(|line| format!(": {}", line))
+        .unwrap_or_default();
+    let result = match output.status.code() {
+        Some(code) => format!("exited with {}{}", code, stderr),
+        None => format!("was killed by a signal{}", stderr),
+    };
+    outcome(output.status.code(), result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> HooksConfig {
+        HooksConfig {
+            on_node_down: Some("/bin/recover {node_dir} --event={event}".to_string()),
+            on_node_recovered: None,
+            on_outlier: Some("page-me {node} {value}".to_string()),
+            on_nat: None,
+            max_concurrent: DEFAULT_MAX_CONCURRENT_HOOKS,
+            cooldown_secs: 60,
+        }
+    }
+
+    fn run(command: &[&str]) -> HookRun {
+        HookRun {
+            event: WebhookEvent::NodeDown,
+            node: "antnode-1".to_string(),
+            node_dir: "/srv/antnode-1".to_string(),
+            value: None,
+            command: command.iter().map(|part| part.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn placeholders_are_replaced_in_each_argument() {
+        let command = expand_command(
+            "/bin/hook {node} dir={node_dir} {event}:{value}",
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::Outlier,
+            Some("cpu"),
+        );
+        assert_eq!(
+            command,
+            [
+                "/bin/hook",
+                "antnode-1",
+                "dir=/srv/antnode-1",
+                "outlier:cpu"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_template_is_split_on_whitespace_before_the_replacement() {
+        // Runs of spaces and tabs make no empty arguments, a value with spaces stays one
+        let command = expand_command(
+            "  logger\t -t antop   {value} ",
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::Nat,
+            Some("only 2 inbound; rm -rf /"),
+        );
+        assert_eq!(
+            command,
+            ["logger", "-t", "antop", "only 2 inbound; rm -rf /"]
+        );
+    }
+
+    #[test]
+    fn a_missing_value_is_replaced_with_nothing() {
+        let command = expand_command("hook {value}", "n", "/n", WebhookEvent::NodeRecovered, None);
+        assert_eq!(command, ["hook", ""]);
+    }
+
+    #[test]
+    fn events_without_a_hook_are_dropped() {
+        let mut runner = HookRunner::new(Some(&config())).unwrap();
+        runner.notify(
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::Nat,
+            None,
+            Instant::now(),
+        );
+        assert!(runner.take().is_empty());
+    }
+
+    #[test]
+    fn each_node_and_event_has_its_own_cooldown() {
+        let mut runner = HookRunner::new(Some(&config())).unwrap();
+        let start = Instant::now();
+        runner.notify(
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::NodeDown,
+            None,
+            start,
+        );
+        // Same node and event within the cooldown, dropped
+        let later = start + Duration::from_secs(59);
+        runner.notify(
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::NodeDown,
+            None,
+            later,
+        );
+        // Another event of the node, or the same event of another node, run
+        let value = Some("cpu".to_string());
+        runner.notify(
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::Outlier,
+            value,
+            later,
+        );
+        runner.notify(
+            "antnode-2",
+            "/srv/antnode-2",
+            WebhookEvent::NodeDown,
+            None,
+            later,
+        );
+        let queued: Vec<Vec<String>> = runner.take().into_iter().map(|run| run.command).collect();
+        assert_eq!(
+            queued,
+            [
+                vec!["/bin/recover", "/srv/antnode-1", "--event=node_down"],
+                vec!["page-me", "antnode-1", "cpu"],
+                vec!["/bin/recover", "/srv/antnode-2", "--event=node_down"],
+            ]
+        );
+
+        // Once the cooldown is over, the hook runs again
+        let over = start + Duration::from_secs(60);
+        runner.notify(
+            "antnode-1",
+            "/srv/antnode-1",
+            WebhookEvent::NodeDown,
+            None,
+            over,
+        );
+        assert_eq!(runner.take().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn the_exit_code_and_last_stderr_line_are_kept() {
+        let script = "echo starting >&2; echo \"$ANTOP_NODE: disk full\" >&2; exit 3";
+        let outcome = run_hook(&run(&["sh", "-c", script])).await;
+        assert_eq!(outcome.exit_code, Some(3));
+        assert!(!outcome.succeeded());
+        assert_eq!(outcome.result, "exited with 3: antnode-1: disk full");
+        assert_eq!(
+            outcome.text(),
+            "Hook on_node_down of antnode-1 exited with 3: antnode-1: disk full"
+        );
+
+        let outcome = run_hook(&run(&["true"])).await;
+        assert!(outcome.succeeded());
+        assert_eq!(outcome.result, "exited with 0");
+    }
+
+    #[tokio::test]
+    async fn a_missing_program_has_no_exit_code() {
+        let outcome = run_hook(&run(&["/nonexistent/antop-hook"])).await;
+        assert_eq!(outcome.exit_code, None);
+        assert_eq!(
+            outcome.result,
+            "failed to start: /nonexistent/antop-hook not found"
+        );
+    }
+}
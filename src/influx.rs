@@ -0,0 +1,398 @@
+use crate::{app::App, config::Settings, output::fleet_snapshot};
+use antop::{metrics::NodeMetrics, state::NodeStatus};
+use anyhow::{Context, Result, bail};
+use reqwest::{Client, Url};
+use std::{path::PathBuf, time::Duration};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, sync::mpsc};
+
+// Updates waiting to be written, the ones arriving while the queue is full are dropped
+const MAX_QUEUED_BATCHES: usize = 16;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where the line protocol of `--influx-url` goes.
+#[derive(Debug, Clone)]
+pub enum InfluxTarget {
+    // `/api/v2/write` of an InfluxDB server, bucket and precision in the query
+    Http {
+        write_url: Url,
+        token: Option<String>,
+    },
+    // `file:///path`, points appended to a local file, for testing
+    File(PathBuf),
+}
+
+impl InfluxTarget {
+    /// Parses `--influx-url`: an `http(s)://` server, which needs a bucket, or a
+    /// `file://` path.
+    pub fn parse(
+        url: &str,
+        token: Option<&str>,
+        bucket: Option<&str>,
+        org: Option<&str>,
+    ) -> Result<InfluxTarget> {
+        if let Some(path) = url.strip_prefix("file://") {
+            return Ok(InfluxTarget::File(PathBuf::from(
+                shellexpand::tilde(path).into_owned(),
+            )));
+        }
+        let write_url = format!("{}/api/v2/write", url.trim_end_matches('/'));
+        let mut write_url =
+            Url::parse(&write_url).with_context(|| format!("Invalid InfluxDB URL '{}'", url))?;
+        if !matches!(write_url.scheme(), "http" | "https") {
+            bail!(
+                "Invalid InfluxDB URL '{}': use http://, https:// or file://",
+                url
+            );
+        }
+        let Some(bucket) = bucket else {
+            bail!("--influx-url needs the bucket to write to, set --influx-bucket");
+        };
+        {
+            let mut query = write_url.query_pairs_mut();
+            query
+                .append_pair("bucket", bucket)
+                .append_pair("precision", "s");
+            if let Some(org) = org {
+                query.append_pair("org", org);
+            }
+        }
+        Ok(InfluxTarget::Http {
+            write_url,
+            token: token.map(str::to_string),
+        })
+    }
+
+    async fn write(&self, client: &Client, lines: &str) -> Result<()> {
+        match self {
+            InfluxTarget::Http { write_url, token } => {
+                let mut request = client.post(write_url.clone()).body(lines.to_string());
+                if let Some(token) = token {
+                    request = request.header("Authorization", format!("Token {}", token));
+                }
+                request
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .context("InfluxDB write failed")?; // The error names the URL
+            }
+            InfluxTarget::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .with_context(|| format!("Failed to open {}", path.display()))?;
+                file.write_all(lines.as_bytes())
+                    .await
+                    .with_context(|| format!("Failed to write to {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the background writer reports, only when the sink starts or stops failing.
+#[derive(Debug)]
+pub enum InfluxEvent {
+    Failing(String), // Points are dropped until the sink answers again
+    Recovered,
+}
+
+/// Line protocol sink of `--influx-url`. `write` queues the points of an update and
+/// returns at once, a background task writes them, so a slow or unreachable sink
+/// never holds up the UI. What can't be written is dropped, not retried.
+pub struct InfluxSink {
+    batches: mpsc::Sender<String>,
+    host: String, // `host` tag of every point
+}
+
+impl InfluxSink {
+    /// Starts the writer, None without `--influx-url`. `events` gets the state changes
+    /// of the sink, to show in the status bar.
+    pub fn start(
+        settings: &Settings,
+        events: mpsc::Sender<InfluxEvent>,
+    ) -> Result<Option<InfluxSink>> {
+        let Some(url) = &settings.influx_url else {
+            return Ok(None);
+        };
+        let target = InfluxTarget::parse(
+            url,
+            settings.influx_token.as_deref(),
+            settings.influx_bucket.as_deref(),
+            settings.influx_org.as_deref(),
+        )?;
+        let (batches, queued) = mpsc::channel(MAX_QUEUED_BATCHES);
+        tokio::spawn(write_batches(target, queued, events));
+        let host = hostname::get()
+            .map(|host| host.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "unknown".to_string());
+        Ok(Some(InfluxSink { batches, host }))
+    }
+
+    /// Queues one point per node for the current state of `app`. Returns false when
+    /// the writer is too far behind and the points were dropped.
+    pub fn write(&self, app: &App) -> bool {
+        let timestamp = chrono::Utc::now().timestamp();
+        self.batches
+            .try_send(node_points(app, &self.host, timestamp))
+            .is_ok()
+    }
+}
+
+// Writes the queued batches one request at a time, those queued during a request
+// go together in the next one
+async fn write_batches(
+    target: InfluxTarget,
+    mut queued: mpsc::Receiver<String>,
+    events: mpsc::Sender<InfluxEvent>,
+) {
+    let client = Client::builder()
+        .timeout(WRITE_TIMEOUT)
+        .build()
+        .unwrap_or_else(|_| Client::new());
+    let mut failing = false;
+    while let Some(mut lines) = queued.recv().await {
+        while let Ok(more) = queued.try_recv() {
+            lines.push_str(&more);
+        }
+        let event = match target.write(&client, &lines).await {
+            Err(e) if !failing => Some(InfluxEvent::Failing(format!("{:#}", e))),
+            Ok(()) if failing => Some(InfluxEvent::Recovered),
+            _ => None,
+        };
+        if let Some(event) = event {
+            failing = !failing;
+            let _ = events.send(event).await;
+        }
+    }
+}
+
+/// Line protocol of the current state of `app`: one `antnode` point per node, tagged
+/// with its name and `host`. A node answering with fresh metrics gets `up=1i` and its
+/// values, the others (stopped, failing or stale) `up=0i` alone.
+pub fn node_points(app: &App, host: &str, timestamp: i64) -> String {
+    let host = escape_tag(host);
+    let mut lines = String::new();
+    for node in fleet_snapshot(app).nodes {
+        let name = node.name.unwrap_or(node.dir_path);
+        let up = node.status.as_deref() == Some(NodeStatus::Running.label());
+        let mut fields = vec![format!("up={}i", u8::from(up))];
+        if up {
+            fields.extend(metric_fields(&node.metrics));
+        }
+        lines.push_str(&format!(
+            "antnode,node={},host={} {} {}\n",
+            escape_tag(&name),
+            host,
+            fields.join(","),
+            timestamp
+        ));
+    }
+    lines
+}
+
+// Fields of the core metrics a node reported, integers with the `i` suffix
+fn metric_fields(metrics: &NodeMetrics) -> Vec<String> {
+    let integers = [
+        ("uptime_seconds", metrics.uptime_seconds),
+        ("connected_peers", metrics.connected_peers),
+        ("peers_in_routing_table", metrics.peers_in_routing_table),
+        ("estimated_network_size", metrics.estimated_network_size),
+        ("records_stored", metrics.records_stored),
+        ("reward_wallet_balance", metrics.reward_wallet_balance),
+        ("bandwidth_inbound_bytes", metrics.bandwidth_inbound_bytes),
+        ("bandwidth_outbound_bytes", metrics.bandwidth_outbound_bytes),
+        ("shunned_count", metrics.shunned_count),
+        ("relay_hop_requests", metrics.relay_hop_requests),
+        (
+            "relay_hop_circuits_accepted",
+            metrics.relay_hop_circuits_accepted,
+        ),
+        ("total_errors", Some(metrics.total_errors())),
+    ];
+    let floats = [
+        ("memory_used_mb", metrics.memory_used_mb),
+        ("cpu_usage_percentage", metrics.cpu_usage_percentage),
+        ("speed_in_bps", metrics.speed_in_bps),
+        ("speed_out_bps", metrics.speed_out_bps),
+    ];
+    integers
+        .into_iter()
+        .filter_map(|(key, value)| Some(format!("{}={}i", key, value?)))
+        .chain(
+            floats
+                .into_iter()
+                // NaN and infinities have no line protocol representation
+                .filter_map(|(key, value)| {
+                    Some(format!("{}={}", key, value.filter(|v| v.is_finite())?))
+                }),
+        )
+        .collect()
+}
+
+// Tag values escape commas, spaces and equal signs
+fn escape_tag(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(' ', "\\ ")
+        .replace('=', "\\=")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{app::test_metrics_body, serve::read_test_request_body};
+    use antop::fetch::{FetchError, FetchErrorKind};
+    use std::time::Instant;
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    // Splits `text` on the `separator`s not escaped with a backslash
+    fn split_unescaped(text: &str, separator: char) -> Vec<String> {
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for c in text.chars() {
+            if c == separator && !escaped {
+                parts.push(String::new());
+                continue;
+            }
+            escaped = c == '\\' && !escaped;
+            parts.last_mut().unwrap().push(c);
+        }
+        parts
+    }
+
+    fn unescape_tag(value: &str) -> String {
+        let mut unescaped = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            unescaped.push(if c == '\\' { chars.next().unwrap() } else { c });
+        }
+        unescaped
+    }
+
+    // Line protocol server writing each request body to the returned channel
+    async fn mock_influx() -> (InfluxTarget, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let target = InfluxTarget::parse(&url, Some("token"), Some("antop"), None).unwrap();
+        let (requests, received) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let body = read_test_request_body(&mut stream).await;
+                let _ = requests.send(String::from_utf8(body).unwrap());
+                let _ = stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                    .await;
+            }
+        });
+        (target, received)
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_drops_the_update_without_waiting() {
+        let app = App::for_tests(&["/srv/antnode-1"]);
+        // Nothing takes the batches off the queue, as with a hung writer
+        let (batches, _queued) = mpsc::channel(MAX_QUEUED_BATCHES);
+        let sink = InfluxSink {
+            batches,
+            host: "host".to_string(),
+        };
+        for _ in 0..MAX_QUEUED_BATCHES {
+            assert!(sink.write(&app));
+        }
+        let start = Instant::now();
+        assert!(!sink.write(&app));
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn batches_queued_during_a_write_go_in_one_request() {
+        let (target, mut received) = mock_influx().await;
+        let (batches, queued) = mpsc::channel(MAX_QUEUED_BATCHES);
+        let (events, mut events_rx) = mpsc::channel(4);
+        for i in 0..3 {
+            batches
+                .send(format!("antnode,node=n{} up=1i {}\n", i, i))
+                .await
+                .unwrap();
+        }
+        drop(batches);
+        // Returns once the queue is drained and closed
+        write_batches(target, queued, events).await;
+
+        assert_eq!(
+            received.recv().await.unwrap(),
+            "antnode,node=n0 up=1i 0\nantnode,node=n1 up=1i 1\nantnode,node=n2 up=1i 2\n"
+        );
+        assert!(received.try_recv().is_err());
+        assert!(events_rx.try_recv().is_err()); // Never failed
+    }
+
+    #[test]
+    fn tags_escape_spaces_commas_and_equal_signs() {
+        assert_eq!(escape_tag("plain-name"), "plain-name");
+        assert_eq!(escape_tag("node 1,a=b"), "node\\ 1\\,a\\=b");
+        assert_eq!(escape_tag("back\\slash"), "back\\\\slash");
+    }
+
+    #[test]
+    fn node_points_are_valid_line_protocol_whatever_the_names() {
+        let names = ["node 1", "node,2", "node=3", "plain-4"];
+        let dirs: Vec<String> = names.iter().map(|name| format!("/srv/{}", name)).collect();
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+        let mut app = App::for_tests(&dirs);
+        app.answer_all(|i| match i {
+            3 => Err(FetchError::new(FetchErrorKind::Connection, "refused")),
+            _ => Ok(test_metrics_body(100 + i as u64, 7)),
+        });
+
+        let points = node_points(&app, "my host,1", 1_700_000_000);
+        let lines: Vec<&str> = points.lines().collect();
+        assert_eq!(lines.len(), names.len());
+        let mut seen = Vec::new();
+        for line in lines {
+            // Measurement and tags, fields, timestamp
+            let parts = split_unescaped(line, ' ');
+            assert_eq!(parts.len(), 3, "{}", line);
+            assert_eq!(parts[2], "1700000000");
+
+            let series = split_unescaped(&parts[0], ',');
+            assert_eq!(series[0], "antnode");
+            let tags: Vec<(String, String)> = series[1..]
+                .iter()
+                .map(|tag| {
+                    let pair = split_unescaped(tag, '=');
+                    assert_eq!(pair.len(), 2, "{}", tag);
+                    (pair[0].clone(), unescape_tag(&pair[1]))
+                })
+                .collect();
+            assert_eq!(tags[0].0, "node");
+            assert_eq!(tags[1], ("host".to_string(), "my host,1".to_string()));
+
+            let fields = split_unescaped(&parts[1], ',');
+            for field in &fields {
+                let (key, value) = field.split_once('=').unwrap();
+                assert!(!key.is_empty());
+                let number = value.strip_suffix('i').unwrap_or(value);
+                assert!(number.parse::<f64>().is_ok(), "{}", field);
+            }
+            let up = fields[0].as_str();
+            match tags[0].1.as_str() {
+                "plain-4" => assert_eq!(fields, ["up=0i"]),
+                _ => {
+                    assert_eq!(up, "up=1i");
+                    assert!(
+                        fields
+                            .iter()
+                            .any(|field| field.starts_with("records_stored="))
+                    );
+                }
+            }
+            seen.push(tags[0].1.clone());
+        }
+        seen.sort();
+        assert_eq!(seen, names);
+    }
+}
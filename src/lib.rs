@@ -0,0 +1,81 @@
+//! Discovery and metrics of antnode instances, the engine of the `antop` dashboard.
+//!
+//! - [`discovery`] finds the nodes of a machine and their metrics URL, from the
+//!   "Metrics server on" line of their logs.
+//! - [`fetch`] fetches their Prometheus metrics concurrently.
+//! - [`metrics`] parses the metrics text into a [`metrics::NodeMetrics`].
+//! - [`state`] has the node statuses and the history format of the state file.
+//!
+//! The dashboard itself stays in the `antop` binary, which uses this crate like any
+//! other user. A node found in its logs, fetched and parsed:
+//!
+//! ```
+//! use antop::discovery::{MatchStrategy, find_metrics_nodes};
+//! use antop::fetch::{FetchEvent, fetch_metrics_in_background};
+//! use antop::metrics::{check_prometheus_format, parse_metrics};
+//! use antop::state::NodeStatus;
+//! use std::io::{Read, Write};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! // A node answering with fixture metrics
+//! let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+//! let url = format!("http://{}", listener.local_addr()?);
+//! std::thread::spawn(move || {
+//!     let (mut stream, _) = listener.accept().unwrap();
+//!     stream.read(&mut [0; 1024]).unwrap();
+//!     let body = "# HELP ant_networking_connected_peers Connected peers\n\
+//!                 ant_networking_connected_peers 42\n\
+//!                 ant_node_uptime 3600\n\
+//!                 libp2p_bandwidth_bytes_total{direction=\"Inbound\"} 1000\n";
+//!     let response = format!(
+//!         "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+//!         body.len(),
+//!         body
+//!     );
+//!     stream.write_all(response.as_bytes()).unwrap();
+//! });
+//! // Its directory, with the log line giving its metrics URL
+//! let root = std::env::temp_dir().join(format!("antop-doc-{}", std::process::id()));
+//! let node_dir = root.join("antnode-1");
+//! std::fs::create_dir_all(node_dir.join("logs"))?;
+//! std::fs::write(
+//!     node_dir.join("logs/antnode.log"),
+//!     format!("[2025-05-01T10:00:00Z INFO] Metrics server on {}\n", url),
+//! )?;
+//!
+//! let discovery = find_metrics_nodes(
+//!     root.join("*/logs/antnode.log"),
+//!     None,
+//!     &[],
+//!     MatchStrategy::Last,
+//!     None,
+//!     u64::MAX,
+//! )
+//! .await?;
+//! assert_eq!(discovery.nodes[0].url, url);
+//!
+//! let (events, mut received) = tokio::sync::mpsc::unbounded_channel();
+//! let urls = discovery.nodes.iter().map(|node| node.url.clone()).collect();
+//! tokio::spawn(fetch_metrics_in_background(urls, None, 5, events));
+//! while let Some(event) = received.recv().await {
+//!     let FetchEvent::Finished(results) = event else {
+//!         continue;
+//!     };
+//!     assert_eq!(NodeStatus::from_fetch(true, Some(&results[0].body)), NodeStatus::Running);
+//!     let body = results[0].body.clone()?;
+//!     check_prometheus_format(&body).map_err(anyhow::Error::msg)?;
+//!     let metrics = parse_metrics(&body);
+//!     assert_eq!(metrics.connected_peers, Some(42));
+//!     assert_eq!(metrics.uptime_seconds, Some(3600));
+//!     assert_eq!(metrics.bandwidth_inbound_bytes, Some(1000));
+//! }
+//! # std::fs::remove_dir_all(&root)?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod discovery;
+pub mod fetch;
+pub mod metrics;
+pub mod state;
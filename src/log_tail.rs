@@ -0,0 +1,100 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+// Bytes read from the end of the file when the tail is opened
+const INITIAL_TAIL_BYTES: u64 = 16 * 1024;
+// Lines kept in memory, older ones are dropped
+const MAX_TAIL_LINES: usize = 1000;
+
+/// Follows the end of a log file, like `tail -f`. Polled on every update tick.
+#[derive(Debug)]
+pub struct LogTail {
+    pub path: PathBuf,
+    pub lines: VecDeque<String>,
+    pub error: Option<String>, // Last read error, shown in the pane instead of the lines
+    pub scroll: usize,         // Rows scrolled up from the end, 0 follows new lines
+    offset: u64,               // Bytes of the file consumed so far
+    partial: String,           // Last line when it has no trailing newline yet
+}
+
+impl LogTail {
+    /// Opens the tail of `path`, reading its last few KB right away.
+    pub fn open(path: PathBuf) -> LogTail {
+        let mut tail = LogTail {
+            path,
+            lines: VecDeque::new(),
+            error: None,
+            scroll: 0,
+            offset: 0,
+            partial: String::new(),
+        };
+        tail.poll();
+        tail
+    }
+
+    /// Reads whatever was appended since the last poll. A file that shrank was
+    /// truncated or rotated, it is read again from its last few KB.
+    pub fn poll(&mut self) {
+        match self.read_appended() {
+            Ok(()) => self.error = None,
+            Err(e) => self.error = Some(format!("Cannot read the log: {}", e)),
+        }
+    }
+
+    /// Lines to display, including a last line still being written.
+    pub fn display_lines(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .map(String::as_str)
+            .chain((!self.partial.is_empty()).then_some(self.partial.as_str()))
+    }
+
+    fn read_appended(&mut self) -> io::Result<()> {
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            self.lines.clear();
+            self.partial.clear();
+            self.offset = 0;
+        }
+
+        // Jump straight to the end when opening, or when too much was written to catch up
+        let start = if len - self.offset > INITIAL_TAIL_BYTES {
+            self.lines.clear();
+            self.partial.clear();
+            len - INITIAL_TAIL_BYTES
+        } else {
+            self.offset
+        };
+        if start == len {
+            return Ok(());
+        }
+        // Starting mid-file, the first line read is cut
+        let skip_first_line = start > 0 && start != self.offset;
+
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::with_capacity((len - start) as usize);
+        file.take(len - start).read_to_end(&mut buf)?;
+        self.offset = start + buf.len() as u64;
+
+        let mut text = std::mem::take(&mut self.partial);
+        text.push_str(&String::from_utf8_lossy(&buf));
+        let mut chunks: Vec<&str> = text.split('\n').collect();
+        if skip_first_line && chunks.len() > 1 {
+            chunks.remove(0);
+        }
+        self.partial = chunks.pop().unwrap_or_default().to_string();
+        for line in chunks {
+            self.lines
+                .push_back(line.trim_end_matches('\r').to_string());
+        }
+        while self.lines.len() > MAX_TAIL_LINES {
+            self.lines.pop_front();
+        }
+        Ok(())
+    }
+}
@@ -1,31 +1,374 @@
+mod alert;
 mod app;
 mod cli;
-mod discovery;
-mod fetch;
-mod metrics;
+mod clipboard;
+mod config;
+mod demo;
+mod hooks;
+mod influx;
+mod log_tail;
+mod output;
+mod process;
+mod recording;
+mod replay;
+mod serve;
+mod systemd;
 mod ui;
+mod watcher;
+mod webhook;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use clap::Parser;
-use std::path::PathBuf;
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
     app::App,
-    cli::Cli,
-    discovery::{find_metrics_nodes, find_node_directories},
-    ui::{restore_terminal, run_app, setup_terminal},
+    cli::{Cli, Command},
+    config::Settings,
+    demo::DemoFleet,
+    replay::HistoricState,
+    serve::StatusServer,
+    ui::{formatters::format_option_u64_bytes, restore_terminal, run_app, setup_terminal},
+    webhook::WebhookNotifier,
+};
+use antop::{
+    discovery::{
+        DiscoveredNode, Discovery, find_metrics_nodes, find_node_directories, load_url_overrides,
+        matches_interface,
+    },
+    fetch::probe_metrics,
 };
 
+// How long `antop validate` waits for the metrics of each node
+const VALIDATE_FETCH_TIMEOUT: Duration = Duration::from_secs(1);
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     let cli = Cli::parse();
 
-    // Expand the tilde in the path provided by the user
-    let expanded_path_glob = shellexpand::tilde(&cli.path).into_owned();
+    // Reports a broken config file itself, it runs before the settings are loaded
+    if let Some(Command::Validate) = &cli.command {
+        let valid = validate(&cli).await;
+        std::process::exit(if valid { 0 } else { 1 });
+    }
+
+    // Merge CLI flags, the selected config profile and the global config defaults
+    let mut settings = Settings::load(&cli).context("Failed to load configuration")?;
+
+    if cli.dump_config {
+        print!("{}", settings.to_toml()?);
+        return Ok(());
+    }
+
+    match &cli.command {
+        Some(Command::History {
+            state_file,
+            replay_speed,
+        }) => {
+            return replay_history(&cli, &settings, state_file.as_deref(), *replay_speed).await;
+        }
+        Some(Command::Validate) | None => {}
+    }
+
+    // Fake nodes instead of the real ones, see `--demo`, a snapshot, see `--json-input`,
+    // or a recording, see `--replay`. Their history must not end up in the real state file.
+    let demo = cli.demo.map(DemoFleet::new);
+    if demo.is_some() || cli.json_input.is_some() || cli.replay.is_some() {
+        settings.state_file = None;
+        // Nor should their nodes be started or stopped, or their events run hooks
+        settings.systemd = None;
+        settings.hooks = None;
+    }
+
+    let mut app = match (&cli.json_input, &cli.replay) {
+        (Some(path), _) => {
+            let nodes = output::read_json_snapshot(path)?;
+            App::from_snapshot(nodes, path, &settings)
+        }
+        (None, Some(path)) => recording::replay_app(path, &settings)?,
+        (None, None) => {
+            let (discovered_node_dirs, initial_node_urls) = match &demo {
+                Some(fleet) => fleet.discovered_nodes(),
+                None => discover_nodes(&settings).await?,
+            };
+
+            // Create the App state
+            // Pass the discovered directories *and* the initial URLs, restoring the history
+            // saved by a previous run when there is one
+            let saved_state = settings
+                .state_file
+                .as_ref()
+                .and_then(|path| std::fs::read(path).ok());
+            match saved_state {
+                Some(data) => App::deserialize_state(
+                    &data,
+                    discovered_node_dirs.clone(),
+                    initial_node_urls.clone(),
+                    &settings,
+                )
+                .unwrap_or_else(|e| {
+                    eprintln!("Warning: Ignoring unreadable state file: {}", e);
+                    App::new(discovered_node_dirs, initial_node_urls, &settings)
+                }),
+                None => App::new(discovered_node_dirs, initial_node_urls, &settings),
+            }
+        }
+    };
+
+    // One-shot export, no TUI
+    if let Some(path) = &cli.export_grafana_dashboard {
+        output::write_grafana_dashboard(&app, path)?;
+        println!("Grafana dashboard written to {}", path);
+        return Ok(());
+    }
+
+    // Webhook of `[notifications]`, an invalid URL stops here rather than turning it off
+    if let Some(notifications) = &settings.notifications {
+        app.webhook = Some(WebhookNotifier::new(notifications)?);
+    }
+
+    // JSON status endpoint, started before the terminal is taken so bind errors show
+    if let Some(addr) = &settings.serve {
+        app.status_server = Some(StatusServer::start(addr).await?);
+        app.publish_status();
+    }
+
+    // Setup terminal
+    let alt_screen = !cli.no_altscreen;
+    let mut terminal = setup_terminal(alt_screen)?;
+
+    // Run the main application loop using .await
+    let app_result = run_app(&mut terminal, app, &cli, &settings, demo).await;
+
+    // Restore terminal state
+    restore_terminal(&mut terminal, alt_screen)?;
+
+    // Print any errors that occurred during the app run
+    if let Err(err) = app_result {
+        eprintln!("Error running application: {}", err);
+        // Optionally, return a non-zero exit code here if desired
+        // std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `antop history`: replays the histories of a state file, the configured one by default.
+async fn replay_history(
+    cli: &Cli,
+    settings: &Settings,
+    state_file: Option<&str>,
+    speed: f64,
+) -> Result<()> {
+    if !replay::REPLAY_SPEEDS.contains(&speed) {
+        bail!(
+            "--replay-speed must be between {} and {}, got {}",
+            replay::REPLAY_SPEEDS.start(),
+            replay::REPLAY_SPEEDS.end(),
+            speed
+        );
+    }
+    let Some(path) = state_file.or(settings.state_file.as_deref()) else {
+        bail!("No state file to replay, set --state-file");
+    };
+    let history = HistoricState::load(path)?;
+    // Its nodes are named after their metrics URLs, they have no units
+    let settings = &Settings {
+        systemd: None,
+        ..settings.clone()
+    };
+    let alt_screen = !cli.no_altscreen;
+    let mut terminal = setup_terminal(alt_screen)?;
+    let result = replay::run_replay(&mut terminal, history, speed, settings).await;
+    restore_terminal(&mut terminal, alt_screen)?;
+    result
+}
+
+// Prints one check of `antop validate` and returns whether it passed
+fn check(passed: bool, text: impl Display) -> bool {
+    println!("{} {}", if passed { "✓" } else { "✗" }, text);
+    passed
+}
+
+/// `antop validate`: checks the config file, the flags, the node directories and the
+/// metrics URLs found in their logs like the dashboard would, printing one line per
+/// check. Returns whether they all passed.
+async fn validate(cli: &Cli) -> bool {
+    let settings = match Settings::load(cli) {
+        Ok(settings) => {
+            check(
+                true,
+                match &settings.config_file {
+                    Some(path) => format!("Config file {} is valid", path),
+                    None => "No config file, flags and defaults only".to_string(),
+                },
+            );
+            settings
+        }
+        Err(e) => return check(false, format!("Configuration: {:#}", e)),
+    };
+    let mut valid = true;
+
+    // Values the dashboard raises to their minimum without a word
+    let mut flags_valid = true;
+    let as_u64 = |value: Option<usize>| value.map(|value| value as u64);
+    for (flag, value, min) in [
+        ("--interval-ms", cli.interval_ms, 1),
+        (
+            "--max-concurrent-fetches",
+            as_u64(cli.max_concurrent_fetches),
+            1,
+        ),
+        ("--rate-window", as_u64(cli.rate_window), 1),
+        ("--bw-history-length", as_u64(cli.bw_history_length), 2),
+        ("--cpu-history-length", as_u64(cli.cpu_history_length), 2),
+        ("--cpu-avg-window", as_u64(cli.cpu_avg_window), 1),
+        (
+            "--peers-history-length",
+            as_u64(cli.peers_history_length),
+            2,
+        ),
+        ("--log-pane-height", cli.log_pane_height.map(u64::from), 3),
+        ("--stale-threshold", cli.stale_threshold.map(u64::from), 1),
+    ] {
+        if let Some(value) = value
+            && value < min
+        {
+            flags_valid &= check(
+                false,
+                format!("{} {} is below {}, {} is used", flag, value, min, min),
+            );
+        }
+    }
+    if let Some(threshold) = cli.memory_leak_threshold
+        && !(threshold.is_finite() && threshold > 0.0)
+    {
+        flags_valid &= check(
+            false,
+            format!(
+                "--memory-leak-threshold {} is not a positive number",
+                threshold
+            ),
+        );
+    }
+    if flags_valid {
+        check(true, "Flags within their ranges");
+    }
+    valid &= flags_valid;
+
+    let node_dirs = match find_node_directories(&settings.path) {
+        Ok(dirs) if dirs.is_empty() => {
+            valid &= check(
+                false,
+                format!("No node directories match {}", settings.path),
+            );
+            return valid;
+        }
+        Ok(dirs) => dirs,
+        Err(e) => return check(false, format!("{:#}", e)),
+    };
+    let without_store: Vec<&String> = node_dirs
+        .iter()
+        .filter(|dir| !Path::new(dir).join("record_store").is_dir())
+        .collect();
+    check(
+        true,
+        format!(
+            "{} node directories match {}, {} with a record store",
+            node_dirs.len(),
+            settings.path,
+            node_dirs.len() - without_store.len()
+        ),
+    );
+    for dir in without_store {
+        valid &= check(false, format!("{}: no record_store directory", dir));
+    }
+
+    let mut nodes = match find_metrics_nodes(
+        PathBuf::from(settings.effective_log_path()),
+        settings.discovery_log_glob_override.as_deref(),
+        &node_dirs,
+        settings.log_match_strategy,
+        settings.log_match_nth,
+        settings.max_log_file_size,
+    )
+    .await
+    {
+        Ok(Discovery {
+            nodes,
+            oversized_logs,
+        }) => {
+            for log in oversized_logs {
+                valid &= check(
+                    false,
+                    format!(
+                        "{}: log file of {} not scanned, larger than --max-log-file-size",
+                        log.path.display(),
+                        format_option_u64_bytes(Some(log.size), settings.units)
+                    ),
+                );
+            }
+            nodes
+        }
+        Err(e) => {
+            valid &= check(false, format!("Metrics URL discovery: {:#}", e));
+            Vec::new()
+        }
+    };
+    if let Some(path) = &settings.url_overrides {
+        match load_url_overrides(path) {
+            Ok(overrides) => {
+                for node in overrides {
+                    nodes.retain(|discovered| discovered.dir_path != node.dir_path);
+                    nodes.push(node);
+                }
+            }
+            Err(e) => valid &= check(false, format!("{:#}", e)),
+        }
+    }
+
+    // Every node at once, each within the timeout
+    let max_redirects = settings.max_redirects;
+    let probes = node_dirs.iter().map(|dir| {
+        let url = nodes
+            .iter()
+            .find(|node| &node.dir_path == dir)
+            .map(|node| node.url.clone());
+        async move {
+            let result = match url {
+                Some(url) => {
+                    match probe_metrics(url.clone(), VALIDATE_FETCH_TIMEOUT, max_redirects).await {
+                        Ok(()) => Ok(url),
+                        Err(e) => Err(format!("{}: {}", url, e)),
+                    }
+                }
+                None => Err("no metrics URL found in its log".to_string()),
+            };
+            (dir, result)
+        }
+    });
+    for (dir, result) in futures::future::join_all(probes).await {
+        valid &= match result {
+            Ok(url) => check(true, format!("{}: metrics at {}", dir, url)),
+            Err(e) => check(false, format!("{}: {}", dir, e)),
+        };
+    }
+    valid
+}
+
+/// Finds the node directories and the metrics URLs in their logs, warning about
+/// anything missing rather than failing: nodes are shown as stopped until found.
+async fn discover_nodes(settings: &Settings) -> Result<(Vec<String>, Vec<DiscoveredNode>)> {
+    // The path from the settings is already tilde-expanded
+    let expanded_path_glob = settings.path.clone();
 
     // --- New: Find all node directories first ---
-    let discovered_node_dirs = find_node_directories(&expanded_path_glob)
-        .context("Failed to find node directories based on the provided path pattern")?;
+    // A malformed pattern fails with its own message, pointing at the bad character
+    let discovered_node_dirs = find_node_directories(&expanded_path_glob)?;
 
     if discovered_node_dirs.is_empty() {
         eprintln!(
@@ -38,53 +381,53 @@ async fn main() -> Result<(), anyhow::Error> {
     // --- End New ---
 
     // Determine the log path: use provided or derive from node path
-    let effective_log_path = match cli.log_path.as_ref() {
-        Some(log_path) => shellexpand::tilde(log_path).into_owned(), // Expand tilde if provided
-        None => {
-            // Derive log path based on the *original* potentially wildcarded path pattern
-            let mut path_buf = PathBuf::from(&expanded_path_glob); // Use original glob pattern
-            if path_buf.file_name().is_some_and(|name| name == "*") {
-                // If the pattern ends with '*', assume it means node-*/logs/antnode.log
-                path_buf.pop(); // Remove '*'
-                path_buf.push("*"); // Add it back (or ensure it's there)
-            } else {
-                // If it's a specific directory, append /logs/antnode.log
-                // This derivation logic might need refinement depending on expected usage
-                // For now, let's keep the wildcard assumption as it was implicitly there before
-                if !expanded_path_glob.ends_with('*') {
-                    // Add wildcard if not present, mimicking the old behavior's likely intent
-                    // This part is tricky; ideally, log path derivation is explicit or simpler.
-                    // Let's stick to the original logic's implicit behavior for now.
-                    path_buf.push("*");
-                }
-            }
-            path_buf.push("logs");
-            path_buf.push("antnode.log");
-            path_buf.to_string_lossy().into_owned()
-        }
-    };
+    let effective_log_path = settings.effective_log_path();
 
     // Convert the effective_log_path String to PathBuf
     let log_path_buf = PathBuf::from(effective_log_path.clone());
+    // What the messages below name, the per node pattern when there is one
+    let log_pattern = settings
+        .discovery_log_glob_override
+        .as_deref()
+        .unwrap_or(&effective_log_path);
 
     // Find initial metrics URLs
-    let initial_node_urls = match find_metrics_nodes(log_path_buf).await {
-        Ok(nodes) => {
+    let initial_node_urls = match find_metrics_nodes(
+        log_path_buf,
+        settings.discovery_log_glob_override.as_deref(),
+        &discovered_node_dirs,
+        settings.log_match_strategy,
+        settings.log_match_nth,
+        settings.max_log_file_size,
+    )
+    .await
+    {
+        Ok(Discovery {
+            nodes,
+            oversized_logs,
+        }) => {
+            for log in oversized_logs {
+                eprintln!(
+                    "Warning: Skipped log file {} ({}), larger than --max-log-file-size",
+                    log.path.display(),
+                    format_option_u64_bytes(Some(log.size), settings.units)
+                );
+            }
             if nodes.is_empty() && !discovered_node_dirs.is_empty() {
                 // Only warn if we found directories but no metrics URLs
                 eprintln!(
                     "Warning: Found {} node directories but no metrics servers via logs: {}",
                     discovered_node_dirs.len(),
-                    effective_log_path
+                    log_pattern
                 );
                 eprintln!("Nodes will be shown as stopped/pending until metrics are found.");
             }
-            nodes // This is Vec<(root_path, url)>
+            nodes // This is Vec<DiscoveredNode>
         }
         Err(e) => {
             eprintln!(
                 "Error during initial metrics server discovery using pattern '{}': {}",
-                effective_log_path, e
+                log_pattern, e
             );
             eprintln!(
                 "Proceeding without initial servers. Discovery will be attempted periodically."
@@ -93,29 +436,45 @@ async fn main() -> Result<(), anyhow::Error> {
         }
     };
 
-    // Create the App state
-    // Pass the discovered directories *and* the initial URLs
-    let app = App::new(
-        discovered_node_dirs,
-        initial_node_urls,
-        expanded_path_glob.clone(),
-    );
-
-    // Setup terminal
-    let mut terminal = setup_terminal()?;
-
-    // Run the main application loop using .await
-    let app_result = run_app(&mut terminal, app, &cli, &effective_log_path).await;
-
-    // Restore terminal state
-    restore_terminal(&mut terminal)?;
-
-    // Print any errors that occurred during the app run
-    if let Err(err) = app_result {
-        eprintln!("Error running application: {}", err);
-        // Optionally, return a non-zero exit code here if desired
-        // std::process::exit(1);
+    // Overridden URLs replace the ones found in the logs, for known node directories.
+    // They go through the `--network-interface` filter below like the others
+    let mut initial_node_urls = initial_node_urls;
+    if let Some(path) = &settings.url_overrides {
+        for node in load_url_overrides(path)? {
+            if !discovered_node_dirs.contains(&node.dir_path) {
+                eprintln!(
+                    "Warning: URL override for '{}' ignored, it is not a discovered node directory",
+                    node.dir_path
+                );
+                continue;
+            }
+            initial_node_urls.retain(|discovered| discovered.dir_path != node.dir_path);
+            initial_node_urls.push(node);
+        }
     }
 
-    Ok(())
+    // With `--network-interface`, nodes bound elsewhere are left out entirely,
+    // directory included. Nodes without a URL yet are kept.
+    let (discovered_node_dirs, initial_node_urls) = match &settings.network_interface {
+        Some(filter) => {
+            let (kept, dropped): (Vec<_>, Vec<_>) = initial_node_urls
+                .into_iter()
+                .partition(|node| matches_interface(&node.url, filter));
+            if kept.is_empty() && !dropped.is_empty() {
+                eprintln!(
+                    "Warning: None of the {} discovered metrics servers is bound to '{}'",
+                    dropped.len(),
+                    filter
+                );
+            }
+            let discovered_node_dirs = discovered_node_dirs
+                .into_iter()
+                .filter(|dir| !dropped.iter().any(|node| &node.dir_path == dir))
+                .collect();
+            (discovered_node_dirs, kept)
+        }
+        None => (discovered_node_dirs, initial_node_urls),
+    };
+
+    Ok((discovered_node_dirs, initial_node_urls))
 }
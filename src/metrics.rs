@@ -1,7 +1,10 @@
-use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, str::FromStr};
 
 /// Structure to hold parsed metrics from an antnode.
-#[derive(Debug, Default, Clone)]
+/// Serialized as is in JSON snapshots, without the chart data.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NodeMetrics {
     pub uptime_seconds: Option<u64>,
     pub memory_used_mb: Option<f64>,
@@ -17,19 +20,292 @@ pub struct NodeMetrics {
     pub incoming_connection_errors: Option<u64>,
     pub outgoing_connection_errors: Option<u64>,
     pub kad_get_closest_peers_errors: Option<u64>,
+    pub shunned_count: Option<u64>, // Times this node was shunned by other peers
+    pub connections_dialer_total: Option<u64>, // Connections this node initiated
+    pub connections_listener_total: Option<u64>, // Connections other peers initiated
+    pub relay_hop_requests: Option<u64>, // Circuits other peers asked this node to relay
+    pub relay_hop_circuits_accepted: Option<u64>, // Of those, circuits it accepted
     pub speed_in_bps: Option<f64>,
     pub speed_out_bps: Option<f64>,
+    pub peer_count_rate: Option<f64>, // Live peers gained per second, negative when losing them
+    // Mean of the last `--cpu-avg-window` CPU samples, the reported value is a snapshot that spikes
+    pub cpu_smoothed_percent: Option<f64>,
     // Pre-processed data for the speed chart
+    #[serde(skip)]
     pub chart_data_in: Option<Vec<(f64, f64)>>,
+    #[serde(skip)]
     pub chart_data_out: Option<Vec<(f64, f64)>>,
+    #[serde(skip)]
+    pub chart_data_errors: Option<Vec<(f64, f64)>>,
+    #[serde(skip)]
+    pub chart_data_cpu: Option<Vec<(f64, f64)>>,
+    #[serde(skip)]
+    pub chart_data_peers: Option<Vec<(f64, f64)>>,
+    // Memory growth in MB per minute, when steep enough to suspect a leak, see `detect_memory_leak`
+    pub memory_leak_mb_per_min: Option<f64>,
+}
+
+impl NodeMetrics {
+    /// Sum of all error counters reported by the node.
+    pub fn total_errors(&self) -> u64 {
+        self.put_record_errors.unwrap_or(0)
+            + self.incoming_connection_errors.unwrap_or(0)
+            + self.outgoing_connection_errors.unwrap_or(0)
+            + self.kad_get_closest_peers_errors.unwrap_or(0)
+    }
+
+    /// Inbound plus outbound speed in bytes per second, a missing one counts as 0.
+    pub fn total_speed_bps(&self) -> f64 {
+        self.speed_in_bps.unwrap_or(0.0) + self.speed_out_bps.unwrap_or(0.0)
+    }
+
+    /// CPU usage shown and checked for outliers: the smoothed value, or the reported one
+    /// where there is none (e.g. snapshots taken before it existed).
+    pub fn cpu_percent(&self) -> Option<f64> {
+        self.cpu_smoothed_percent.or(self.cpu_usage_percentage)
+    }
+
+    /// Share of established connections initiated by other peers, in percent.
+    /// A low ratio hints at a node behind a restrictive NAT.
+    pub fn inbound_ratio(&self) -> Option<f64> {
+        let listener = self.connections_listener_total?;
+        let total = listener + self.connections_dialer_total?;
+        (total > 0).then(|| listener as f64 / total as f64 * 100.0)
+    }
+
+    /// Whether other peers use the node as a relay, it then spends bandwidth and CPU
+    /// on their circuits.
+    pub fn is_relay(&self) -> bool {
+        self.relay_hop_requests.is_some_and(|requests| requests > 0)
+    }
+
+    /// Share of the relay requests the node accepted, in percent.
+    pub fn relay_acceptance_rate(&self) -> Option<f64> {
+        let requests = self.relay_hop_requests.filter(|&requests| requests > 0)?;
+        let accepted = self.relay_hop_circuits_accepted.unwrap_or(0);
+        Some(accepted as f64 / requests as f64 * 100.0)
+    }
+}
+
+/// Inbound ratio (percent) below which a node is flagged as possibly behind a NAT.
+pub const NAT_SUSPECT_INBOUND_RATIO: f64 = 20.0;
+
+/// Whether two fetches of a node returned the same values for every parsed metric.
+/// Derived fields (speeds, rates, chart data) are left out, they move on their own.
+pub fn is_metrics_identical(a: &NodeMetrics, b: &NodeMetrics) -> bool {
+    a.uptime_seconds == b.uptime_seconds
+        && a.memory_used_mb == b.memory_used_mb
+        && a.cpu_usage_percentage == b.cpu_usage_percentage
+        && a.connected_peers == b.connected_peers
+        && a.peers_in_routing_table == b.peers_in_routing_table
+        && a.estimated_network_size == b.estimated_network_size
+        && a.bandwidth_inbound_bytes == b.bandwidth_inbound_bytes
+        && a.bandwidth_outbound_bytes == b.bandwidth_outbound_bytes
+        && a.records_stored == b.records_stored
+        && a.put_record_errors == b.put_record_errors
+        && a.reward_wallet_balance == b.reward_wallet_balance
+        && a.incoming_connection_errors == b.incoming_connection_errors
+        && a.outgoing_connection_errors == b.outgoing_connection_errors
+        && a.kad_get_closest_peers_errors == b.kad_get_closest_peers_errors
+        && a.shunned_count == b.shunned_count
+        && a.connections_dialer_total == b.connections_dialer_total
+        && a.connections_listener_total == b.connections_listener_total
+        && a.relay_hop_requests == b.relay_hop_requests
+        && a.relay_hop_circuits_accepted == b.relay_hop_circuits_accepted
+}
+
+/// `NodeMetrics` fields filled from the metrics text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum MetricField {
+    Uptime,
+    MemoryUsedMb,
+    CpuUsagePercentage,
+    ConnectedPeers,
+    PeersInRoutingTable,
+    EstimatedNetworkSize,
+    RecordsStored,
+    PutRecordErrors,
+    RewardWalletBalance,
+    ShunnedCount,
+    Bandwidth,                // Split into in/out by the `direction` label
+    ConnectionsEstablished,   // Split into dialer/listener by the `role` label
+    IncomingConnectionErrors, // Summed over all label sets
+    OutgoingConnectionErrors, // Summed over all label sets
+    KadGetClosestPeersErrors, // Summed over all label sets
+    RelayHopRequests,         // Summed over all label sets
+    RelayHopCircuitsAccepted, // Summed over all label sets
+}
+
+/// Accepted metric names (without labels) for each field, in order of preference.
+/// Metric names changed across antnode versions, e.g. nodes from before the Autonomi
+/// rename use the `sn_` prefix instead of `ant_`. When a node exposes several names
+/// for the same field, the earliest one in the list wins.
+const METRIC_ALIASES: [(MetricField, &[&str]); 17] = [
+    (MetricField::Uptime, &["ant_node_uptime", "sn_node_uptime"]),
+    (
+        MetricField::MemoryUsedMb,
+        &[
+            "ant_networking_process_memory_used_mb",
+            "sn_networking_process_memory_used_mb",
+        ],
+    ),
+    (
+        MetricField::CpuUsagePercentage,
+        &[
+            "ant_networking_process_cpu_usage_percentage",
+            "sn_networking_process_cpu_usage_percentage",
+        ],
+    ),
+    (
+        MetricField::ConnectedPeers,
+        &[
+            "ant_networking_connected_peers",
+            "sn_networking_connected_peers",
+        ],
+    ),
+    (
+        MetricField::PeersInRoutingTable,
+        &[
+            "ant_networking_peers_in_routing_table",
+            "sn_networking_peers_in_routing_table",
+        ],
+    ),
+    (
+        MetricField::EstimatedNetworkSize,
+        &[
+            "ant_networking_estimated_network_size",
+            "sn_networking_estimated_network_size",
+        ],
+    ),
+    (
+        MetricField::RecordsStored,
+        &[
+            "ant_networking_records_stored",
+            "sn_networking_records_stored",
+        ],
+    ),
+    (
+        MetricField::PutRecordErrors,
+        &[
+            "ant_node_put_record_err_total",
+            "sn_node_put_record_err_total",
+        ],
+    ),
+    (
+        MetricField::RewardWalletBalance,
+        &[
+            "ant_node_current_reward_wallet_balance",
+            "sn_node_current_reward_wallet_balance",
+        ],
+    ),
+    (
+        MetricField::ShunnedCount,
+        &[
+            "ant_networking_shunned_count_total",
+            "sn_networking_shunned_count_total",
+        ],
+    ),
+    (MetricField::Bandwidth, &["libp2p_bandwidth_bytes_total"]),
+    (
+        MetricField::ConnectionsEstablished,
+        &["libp2p_swarm_connections_established_total"],
+    ),
+    (
+        MetricField::IncomingConnectionErrors,
+        &["libp2p_swarm_connections_incoming_error_total"],
+    ),
+    (
+        MetricField::OutgoingConnectionErrors,
+        &["libp2p_swarm_outgoing_connection_error_total"],
+    ),
+    (
+        MetricField::KadGetClosestPeersErrors,
+        &["libp2p_kad_query_result_get_closest_peers_error_total"],
+    ),
+    (
+        MetricField::RelayHopRequests,
+        &["libp2p_relay_hop_requests_total"],
+    ),
+    (
+        MetricField::RelayHopCircuitsAccepted,
+        &["libp2p_relay_hop_circuits_accepted_total"],
+    ),
+];
+
+/// Looks up the field a metric name maps to, with the alias rank (0 = preferred name).
+fn lookup_alias(name: &str) -> Option<(MetricField, usize)> {
+    METRIC_ALIASES.iter().find_map(|(field, aliases)| {
+        aliases
+            .iter()
+            .position(|alias| *alias == name)
+            .map(|rank| (*field, rank))
+    })
+}
+
+impl NodeMetrics {
+    // Resets a field, used when a more preferred alias shows up after a less preferred one
+    fn clear_field(&mut self, field: MetricField) {
+        match field {
+            MetricField::Uptime => self.uptime_seconds = None,
+            MetricField::MemoryUsedMb => self.memory_used_mb = None,
+            MetricField::CpuUsagePercentage => self.cpu_usage_percentage = None,
+            MetricField::ConnectedPeers => self.connected_peers = None,
+            MetricField::PeersInRoutingTable => self.peers_in_routing_table = None,
+            MetricField::EstimatedNetworkSize => self.estimated_network_size = None,
+            MetricField::RecordsStored => self.records_stored = None,
+            MetricField::PutRecordErrors => self.put_record_errors = None,
+            MetricField::RewardWalletBalance => self.reward_wallet_balance = None,
+            MetricField::ShunnedCount => self.shunned_count = None,
+            MetricField::Bandwidth => {
+                self.bandwidth_inbound_bytes = None;
+                self.bandwidth_outbound_bytes = None;
+            }
+            MetricField::ConnectionsEstablished => {
+                self.connections_dialer_total = None;
+                self.connections_listener_total = None;
+            }
+            MetricField::IncomingConnectionErrors => self.incoming_connection_errors = None,
+            MetricField::OutgoingConnectionErrors => self.outgoing_connection_errors = None,
+            MetricField::KadGetClosestPeersErrors => self.kad_get_closest_peers_errors = None,
+            MetricField::RelayHopRequests => self.relay_hop_requests = None,
+            MetricField::RelayHopCircuitsAccepted => self.relay_hop_circuits_accepted = None,
+        }
+    }
+}
+
+/// Checks that a fetched body is Prometheus metrics text before it is parsed: it has
+/// a `# HELP` line or at least one metric we know. Anything else, like an HTML page
+/// or JSON from a misconfigured endpoint, would parse to a row of blanks that looks
+/// healthy. The error names what the body looks like instead.
+pub fn check_prometheus_format(body: &str) -> Result<(), String> {
+    let recognized = body.lines().any(|line| {
+        line.starts_with("# HELP")
+            || line
+                .split(['{', ' '])
+                .next()
+                .is_some_and(|name| lookup_alias(name).is_some())
+    });
+    if recognized {
+        return Ok(());
+    }
+    let looks_like = match body.trim_start().chars().next() {
+        None => "an empty body",
+        Some('<') => "HTML",
+        Some('{' | '[') => "JSON",
+        Some(_) => "no known metrics",
+    };
+    Err(format!(
+        "Format error: not Prometheus text ({})",
+        looks_like
+    ))
 }
 
 /// Parses the raw metrics text into a NodeMetrics struct.
+/// Metric names are resolved through `METRIC_ALIASES`, unknown metrics are ignored.
 pub fn parse_metrics(metrics_data: &str) -> NodeMetrics {
     let mut metrics = NodeMetrics::default();
-    let mut outgoing_connection_errors_sum: u64 = 0;
-    let mut incoming_connection_errors_sum: u64 = 0;
-    let mut kad_get_closest_peers_errors_sum: u64 = 0;
+    // Rank of the alias each field was filled from
+    let mut field_ranks: HashMap<MetricField, usize> = HashMap::new();
 
     for line in metrics_data.lines() {
         if line.starts_with('#') || line.is_empty() {
@@ -43,80 +319,80 @@ pub fn parse_metrics(metrics_data: &str) -> NodeMetrics {
 
         let metric_name = parts[0];
         let value_str = parts[parts.len() - 1]; // Value is usually the last part
+        // Strip the labels: `name{label="value"}` -> `name`
+        let base_name = metric_name.split('{').next().unwrap_or(metric_name);
+
+        let Some((field, rank)) = lookup_alias(base_name) else {
+            continue;
+        };
+        match field_ranks.get(&field) {
+            Some(&best) if rank > best => continue, // A preferred alias already filled it
+            Some(&best) if rank < best => metrics.clear_field(field),
+            _ => {}
+        }
+        field_ranks.insert(field, rank);
 
         fn parse_value<T: FromStr>(s: &str) -> Option<T> {
             s.parse::<T>().ok()
         }
-
-        match metric_name {
-            "ant_node_uptime" => metrics.uptime_seconds = parse_value::<u64>(value_str),
-            "ant_networking_process_memory_used_mb" => {
-                metrics.memory_used_mb = parse_value::<f64>(value_str)
+        // Adds a labeled counter to its running sum
+        fn add_value(sum: &mut Option<u64>, s: &str) {
+            if let Some(val) = parse_value::<u64>(s) {
+                *sum.get_or_insert(0) += val;
             }
-            "ant_networking_process_cpu_usage_percentage" => {
+        }
+
+        match field {
+            MetricField::Uptime => metrics.uptime_seconds = parse_value::<u64>(value_str),
+            MetricField::MemoryUsedMb => metrics.memory_used_mb = parse_value::<f64>(value_str),
+            MetricField::CpuUsagePercentage => {
                 metrics.cpu_usage_percentage = parse_value::<f64>(value_str)
             }
-            "ant_networking_connected_peers" => {
-                metrics.connected_peers = parse_value::<u64>(value_str)
-            }
-            "ant_networking_peers_in_routing_table" => {
+            MetricField::ConnectedPeers => metrics.connected_peers = parse_value::<u64>(value_str),
+            MetricField::PeersInRoutingTable => {
                 metrics.peers_in_routing_table = parse_value::<u64>(value_str)
             }
-            "ant_networking_estimated_network_size" => {
+            MetricField::EstimatedNetworkSize => {
                 metrics.estimated_network_size = parse_value::<u64>(value_str)
             }
-            "ant_networking_records_stored" => {
-                metrics.records_stored = parse_value::<u64>(value_str)
-            }
-            "ant_node_put_record_err_total" => {
+            MetricField::RecordsStored => metrics.records_stored = parse_value::<u64>(value_str),
+            MetricField::PutRecordErrors => {
                 metrics.put_record_errors = parse_value::<u64>(value_str)
             }
-            "ant_node_current_reward_wallet_balance" => {
+            MetricField::RewardWalletBalance => {
                 metrics.reward_wallet_balance = parse_value::<u64>(value_str)
             }
+            MetricField::ShunnedCount => metrics.shunned_count = parse_value::<u64>(value_str),
             // Handle metrics with labels
-            name if name.starts_with("libp2p_bandwidth_bytes_total") => {
+            MetricField::Bandwidth => {
                 if line.contains(r#"direction="Inbound""#) {
                     metrics.bandwidth_inbound_bytes = parse_value::<u64>(value_str);
                 } else if line.contains(r#"direction="Outbound""#) {
                     metrics.bandwidth_outbound_bytes = parse_value::<u64>(value_str);
                 }
             }
-            name if name.starts_with("libp2p_swarm_connections_incoming_error_total") => {
-                if let Some(val) = parse_value::<u64>(value_str) {
-                    incoming_connection_errors_sum += val;
+            MetricField::ConnectionsEstablished => {
+                if line.contains(r#"role="Dialer""#) {
+                    add_value(&mut metrics.connections_dialer_total, value_str);
+                } else if line.contains(r#"role="Listener""#) {
+                    add_value(&mut metrics.connections_listener_total, value_str);
                 }
             }
-            name if name.starts_with("libp2p_swarm_outgoing_connection_error_total") => {
-                if let Some(val) = parse_value::<u64>(value_str) {
-                    outgoing_connection_errors_sum += val;
-                }
+            MetricField::IncomingConnectionErrors => {
+                add_value(&mut metrics.incoming_connection_errors, value_str)
             }
-            name if name.starts_with("libp2p_kad_query_result_get_closest_peers_error_total") => {
-                if let Some(val) = parse_value::<u64>(value_str) {
-                    kad_get_closest_peers_errors_sum += val;
-                }
+            MetricField::OutgoingConnectionErrors => {
+                add_value(&mut metrics.outgoing_connection_errors, value_str)
+            }
+            MetricField::KadGetClosestPeersErrors => {
+                add_value(&mut metrics.kad_get_closest_peers_errors, value_str)
+            }
+            MetricField::RelayHopRequests => add_value(&mut metrics.relay_hop_requests, value_str),
+            MetricField::RelayHopCircuitsAccepted => {
+                add_value(&mut metrics.relay_hop_circuits_accepted, value_str)
             }
-            _ => {}
         }
     }
 
-    // Assign summed errors if they were found or the metric name exists at all
-    if incoming_connection_errors_sum > 0
-        || metrics_data.contains("libp2p_swarm_connections_incoming_error_total")
-    {
-        metrics.incoming_connection_errors = Some(incoming_connection_errors_sum);
-    }
-    if outgoing_connection_errors_sum > 0
-        || metrics_data.contains("libp2p_swarm_outgoing_connection_error_total")
-    {
-        metrics.outgoing_connection_errors = Some(outgoing_connection_errors_sum);
-    }
-    if kad_get_closest_peers_errors_sum > 0
-        || metrics_data.contains("libp2p_kad_query_result_get_closest_peers_error_total")
-    {
-        metrics.kad_get_closest_peers_errors = Some(kad_get_closest_peers_errors_sum);
-    }
-
     metrics
 }
@@ -0,0 +1,943 @@
+use crate::{
+    app::App,
+    ui::{
+        formatters::{
+            create_list_item_cells, create_placeholder_cells, format_count,
+            format_option_u64_bytes, format_speed_bps,
+        },
+        widgets::HEADER_TITLES,
+    },
+};
+use antop::{metrics::NodeMetrics, state::NodeStatus};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{fs, path::Path};
+use unicode_width::UnicodeWidthStr;
+
+// Datasource input, filled in by Grafana when the dashboard is imported
+const DATASOURCE_INPUT: &str = "${DS_PROMETHEUS}";
+
+// --- Grafana Dashboard ---
+
+/// Builds a Grafana dashboard model for the fleet.
+///
+/// The panels query the metrics exposed by each node's own metrics server, so they work
+/// with any Prometheus scraping the nodes directly. The `instance` variable is
+/// pre-filled with the `host:port` of every node antop currently knows about.
+pub fn generate_grafana_dashboard(app: &App) -> Value {
+    let mut instances: Vec<String> = app
+        .node_urls
+        .values()
+        .filter_map(|url| url_instance(url))
+        .collect();
+    instances.sort();
+    instances.dedup();
+
+    let panels = vec![
+        timeseries_panel(
+            1,
+            "Total bandwidth",
+            "Bps",
+            grid(0, 0, 16, 8),
+            vec![
+                target(
+                    "A",
+                    r#"sum(rate(libp2p_bandwidth_bytes_total{direction="Inbound", instance=~"$instance"}[1m]))"#,
+                    "In",
+                ),
+                target(
+                    "B",
+                    r#"sum(rate(libp2p_bandwidth_bytes_total{direction="Outbound", instance=~"$instance"}[1m]))"#,
+                    "Out",
+                ),
+            ],
+        ),
+        json!({
+            "id": 2,
+            "type": "gauge",
+            "title": "Nodes up",
+            "datasource": datasource(),
+            "gridPos": grid(16, 0, 8, 8),
+            "fieldConfig": {
+                "defaults": {
+                    "min": 0,
+                    "max": app.nodes.len(), // Nodes discovered when the dashboard was generated
+                    "thresholds": {
+                        "mode": "percentage",
+                        "steps": [
+                            { "color": "red", "value": null },
+                            { "color": "orange", "value": 50 },
+                            { "color": "green", "value": 90 }
+                        ]
+                    }
+                },
+                "overrides": []
+            },
+            "targets": [target("A", r#"count(up{instance=~"$instance"} == 1)"#, "Up")]
+        }),
+        json!({
+            "id": 3,
+            "type": "heatmap",
+            "title": "CPU usage per node",
+            "datasource": datasource(),
+            "gridPos": grid(0, 8, 24, 10),
+            "options": {
+                "calculate": false, // One row per node, the series are already buckets
+                "yAxis": { "axisPlacement": "left" },
+                "color": { "mode": "scheme", "scheme": "Oranges" },
+                "cellValues": { "unit": "percent" }
+            },
+            "targets": [target(
+                "A",
+                r#"ant_networking_process_cpu_usage_percentage{instance=~"$instance"}"#,
+                "{{instance}}",
+            )]
+        }),
+        timeseries_panel(
+            4,
+            "Records stored per node",
+            "short",
+            grid(0, 18, 12, 8),
+            vec![target(
+                "A",
+                r#"ant_networking_records_stored{instance=~"$instance"}"#,
+                "{{instance}}",
+            )],
+        ),
+        timeseries_panel(
+            5,
+            "Error rate",
+            "cps",
+            grid(12, 18, 12, 8),
+            vec![
+                target(
+                    "A",
+                    r#"sum(rate(ant_node_put_record_err_total{instance=~"$instance"}[5m]))"#,
+                    "Put record",
+                ),
+                target(
+                    "B",
+                    r#"sum(rate(libp2p_swarm_connections_incoming_error_total{instance=~"$instance"}[5m]))"#,
+                    "Incoming connection",
+                ),
+                target(
+                    "C",
+                    r#"sum(rate(libp2p_swarm_outgoing_connection_error_total{instance=~"$instance"}[5m]))"#,
+                    "Outgoing connection",
+                ),
+                target(
+                    "D",
+                    r#"sum(rate(libp2p_kad_query_result_get_closest_peers_error_total{instance=~"$instance"}[5m]))"#,
+                    "Kad get closest peers",
+                ),
+            ],
+        ),
+    ];
+
+    json!({
+        "__inputs": [{
+            "name": "DS_PROMETHEUS",
+            "label": "Prometheus",
+            "type": "datasource",
+            "pluginId": "prometheus",
+            "pluginName": "Prometheus"
+        }],
+        "title": "Autonomi Nodes (antop)",
+        "uid": "antop-fleet",
+        "tags": ["antop", "autonomi"],
+        "timezone": "browser",
+        "schemaVersion": 39,
+        "refresh": "30s",
+        "time": { "from": "now-6h", "to": "now" },
+        "templating": {
+            "list": [{
+                "name": "instance",
+                "label": "Node",
+                "type": "custom",
+                "query": instances.join(","),
+                "multi": true,
+                "includeAll": true,
+                "allValue": ".*",
+                "current": { "text": "All", "value": "$__all" },
+                "options": []
+            }]
+        },
+        "panels": panels
+    })
+}
+
+/// Writes the Grafana dashboard JSON for `app` to `path`.
+pub fn write_grafana_dashboard(app: &App, path: &str) -> Result<()> {
+    let dashboard = generate_grafana_dashboard(app);
+    let content = serde_json::to_string_pretty(&dashboard)
+        .context("Failed to serialize Grafana dashboard")?;
+    fs::write(path, content).with_context(|| format!("Failed to write Grafana dashboard: {}", path))
+}
+
+// --- Helper Functions ---
+
+/// Prometheus `instance` label (host:port) of a metrics URL.
+pub fn url_instance(url: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    Some(format!(
+        "{}:{}",
+        parsed.host_str()?,
+        parsed.port_or_known_default()?
+    ))
+}
+
+fn datasource() -> Value {
+    json!({ "type": "prometheus", "uid": DATASOURCE_INPUT })
+}
+
+fn grid(x: u32, y: u32, w: u32, h: u32) -> Value {
+    json!({ "x": x, "y": y, "w": w, "h": h })
+}
+
+fn target(ref_id: &str, expr: &str, legend: &str) -> Value {
+    json!({
+        "refId": ref_id,
+        "datasource": datasource(),
+        "expr": expr,
+        "legendFormat": legend
+    })
+}
+
+fn timeseries_panel(
+    id: u32,
+    title: &str,
+    unit: &str,
+    grid_pos: Value,
+    targets: Vec<Value>,
+) -> Value {
+    json!({
+        "id": id,
+        "type": "timeseries",
+        "title": title,
+        "datasource": datasource(),
+        "gridPos": grid_pos,
+        "fieldConfig": { "defaults": { "unit": unit }, "overrides": [] },
+        "targets": targets
+    })
+}
+
+// --- JSON Snapshot ---
+
+/// One node of a JSON snapshot, the file `--json-input` reads: a JSON array with an
+/// object per node holding its directory, its metrics URL and every `NodeMetrics` field.
+///
+/// ```json
+/// [{ "dir_path": "/var/antctl/services/antnode1", "url": "http://127.0.0.1:13001",
+///    "uptime_seconds": 5400, "connected_peers": 80, "records_stored": 1234 }]
+/// ```
+///
+/// A node without `url` is stopped, one with an `error` failed its last fetch.
+/// Missing metrics are left blank. `name` and `status` are written for readers of
+/// the snapshot, `--json-input` ignores them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeSnapshot {
+    pub dir_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(flatten)]
+    pub metrics: NodeMetrics,
+}
+
+/// Reads the nodes of a JSON snapshot file.
+pub fn read_json_snapshot(path: &str) -> Result<Vec<NodeSnapshot>> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read JSON snapshot {}", path))?;
+    serde_json::from_str(&data).with_context(|| format!("Invalid JSON snapshot {}", path))
+}
+
+/// Fleet totals of a `FleetSnapshot`, like the summary area of the TUI.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FleetSummary {
+    pub nodes: usize,
+    pub running: usize,
+    pub initializing: usize,
+    pub error: usize,
+    pub stopped: usize,
+    pub stale: usize,
+    pub cpu_usage_percentage: f64,
+    pub memory_used_mb: f64,
+    pub connected_peers: u64,
+    pub records_stored: u64,
+    pub reward_wallet_balance: u64,
+    pub bandwidth_inbound_bytes: u64,
+    pub bandwidth_outbound_bytes: u64,
+    pub speed_in_bps: f64,
+    pub speed_out_bps: f64,
+}
+
+/// The whole fleet at one update: when it was taken, the fleet totals and every node
+/// in display order. Served by `--serve`, its `nodes` can be read back with `--json-input`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FleetSnapshot {
+    pub timestamp: Option<DateTime<Local>>, // None before the first update
+    pub summary: FleetSummary,
+    pub nodes: Vec<NodeSnapshot>,
+}
+
+/// Takes a snapshot of the fleet from the current state of `app`.
+pub fn fleet_snapshot(app: &App) -> FleetSnapshot {
+    let counts = app.status_counts();
+    let nodes = app
+        .sorted_nodes()
+        .iter()
+        .map(|dir_path| {
+            let url = app.node_urls.get(dir_path).cloned();
+            let result = url.as_ref().and_then(|url| app.node_metrics.get(url));
+            let status = match app.node_status(dir_path) {
+                NodeStatus::Running if url.as_deref().is_some_and(|url| app.is_stale(url)) => {
+                    "Stale"
+                }
+                status => status.label(),
+            };
+            NodeSnapshot {
+                dir_path: dir_path.clone(),
+                name: Some(app.node_name(dir_path)),
+                status: Some(status.to_string()),
+                url,
+                error: result.and_then(|result| result.as_ref().err().cloned()),
+                metrics: result
+                    .and_then(|result| result.as_ref().ok().cloned())
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+    FleetSnapshot {
+        timestamp: Some(Local::now()),
+        summary: FleetSummary {
+            nodes: app.nodes.len(),
+            running: counts.running,
+            initializing: counts.initializing,
+            error: counts.error,
+            stopped: counts.stopped,
+            stale: app.summary_stale_count,
+            cpu_usage_percentage: app.total_cpu_usage,
+            memory_used_mb: app.total_memory_used_mb,
+            connected_peers: app.summary_total_live_peers,
+            records_stored: app.summary_total_records,
+            reward_wallet_balance: app.summary_total_rewards,
+            bandwidth_inbound_bytes: app.summary_total_data_in_bytes,
+            bandwidth_outbound_bytes: app.summary_total_data_out_bytes,
+            speed_in_bps: app.summary_total_in_speed,
+            speed_out_bps: app.summary_total_out_speed,
+        },
+        nodes,
+    }
+}
+
+// --- Prometheus Exposition ---
+
+// Per-node value series: name, type, help and value. Only exported for running nodes.
+type NodeSeries = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&NodeMetrics) -> Option<f64>,
+);
+
+const NODE_SERIES: [NodeSeries; 16] = [
+    ("antop_node_uptime_seconds", "gauge", "Node uptime", |m| {
+        m.uptime_seconds.map(|v| v as f64)
+    }),
+    (
+        "antop_node_memory_used_mb",
+        "gauge",
+        "Node process memory in MB",
+        |m| m.memory_used_mb,
+    ),
+    (
+        "antop_node_cpu_percent",
+        "gauge",
+        "Node process CPU usage",
+        |m| m.cpu_usage_percentage,
+    ),
+    ("antop_node_connected_peers", "gauge", "Live peers", |m| {
+        m.connected_peers.map(|v| v as f64)
+    }),
+    (
+        "antop_node_routing_table_peers",
+        "gauge",
+        "Peers in the routing table",
+        |m| m.peers_in_routing_table.map(|v| v as f64),
+    ),
+    (
+        "antop_node_estimated_network_size",
+        "gauge",
+        "Network size estimated by the node",
+        |m| m.estimated_network_size.map(|v| v as f64),
+    ),
+    (
+        "antop_node_records_stored",
+        "gauge",
+        "Records stored",
+        |m| m.records_stored.map(|v| v as f64),
+    ),
+    (
+        "antop_node_reward_wallet_balance",
+        "gauge",
+        "Reward wallet balance",
+        |m| m.reward_wallet_balance.map(|v| v as f64),
+    ),
+    (
+        "antop_node_received_bytes_total",
+        "counter",
+        "Bytes received",
+        |m| m.bandwidth_inbound_bytes.map(|v| v as f64),
+    ),
+    (
+        "antop_node_sent_bytes_total",
+        "counter",
+        "Bytes sent",
+        |m| m.bandwidth_outbound_bytes.map(|v| v as f64),
+    ),
+    (
+        "antop_node_receive_bytes_per_second",
+        "gauge",
+        "Receive speed",
+        |m| m.speed_in_bps,
+    ),
+    (
+        "antop_node_send_bytes_per_second",
+        "gauge",
+        "Send speed",
+        |m| m.speed_out_bps,
+    ),
+    (
+        "antop_node_put_record_errors_total",
+        "counter",
+        "PUT record errors",
+        |m| m.put_record_errors.map(|v| v as f64),
+    ),
+    (
+        "antop_node_incoming_connection_errors_total",
+        "counter",
+        "Incoming connection errors",
+        |m| m.incoming_connection_errors.map(|v| v as f64),
+    ),
+    (
+        "antop_node_outgoing_connection_errors_total",
+        "counter",
+        "Outgoing connection errors",
+        |m| m.outgoing_connection_errors.map(|v| v as f64),
+    ),
+    (
+        "antop_node_shunned_total",
+        "counter",
+        "Times the node was shunned by other peers",
+        |m| m.shunned_count.map(|v| v as f64),
+    ),
+];
+
+/// Renders `snapshot` as Prometheus text, for `GET /metrics` of `--serve`.
+///
+/// Every node gets `antop_node_up{node="<name>"}`, 1 when it is running and answering
+/// with fresh metrics. Its value series are only exported while it is up, a stopped,
+/// failing or stale node exports `antop_node_up 0` alone. The fleet totals follow as
+/// `antop_fleet_*` gauges and `antop_nodes{status="..."}`.
+pub fn prometheus_exposition(snapshot: &FleetSnapshot) -> String {
+    let mut out = String::new();
+    // Stale nodes have their own status, "Running" means fresh metrics
+    let is_up = |node: &NodeSnapshot| node.status.as_deref() == Some(NodeStatus::Running.label());
+    let up_nodes: Vec<(String, &NodeMetrics)> = snapshot
+        .nodes
+        .iter()
+        .filter(|node| is_up(node))
+        .map(|node| (node_label(node), &node.metrics))
+        .collect();
+
+    write_family(
+        &mut out,
+        "antop_node_up",
+        "gauge",
+        "1 when the node answers with fresh metrics, 0 when stopped, failing or stale",
+    );
+    for node in &snapshot.nodes {
+        let up = if is_up(node) { 1.0 } else { 0.0 };
+        write_sample(&mut out, "antop_node_up", &node_label(node), up);
+    }
+
+    for (name, kind, help, value) in NODE_SERIES {
+        let samples: Vec<(&str, f64)> = up_nodes
+            .iter()
+            .filter_map(|(label, metrics)| value(metrics).map(|value| (label.as_str(), value)))
+            .collect();
+        if samples.is_empty() {
+            continue;
+        }
+        write_family(&mut out, name, kind, help);
+        for (label, value) in samples {
+            write_sample(&mut out, name, label, value);
+        }
+    }
+
+    let summary = &snapshot.summary;
+    write_family(&mut out, "antop_nodes", "gauge", "Nodes per status");
+    for (status, count) in [
+        ("running", summary.running),
+        ("initializing", summary.initializing),
+        ("error", summary.error),
+        ("stopped", summary.stopped),
+        ("stale", summary.stale),
+    ] {
+        out.push_str(&format!("antop_nodes{{status=\"{}\"}} {}\n", status, count));
+    }
+    for (name, help, value) in [
+        (
+            "antop_fleet_cpu_percent",
+            "CPU usage summed over the nodes",
+            summary.cpu_usage_percentage,
+        ),
+        (
+            "antop_fleet_memory_used_mb",
+            "Memory in MB summed over the nodes",
+            summary.memory_used_mb,
+        ),
+        (
+            "antop_fleet_connected_peers",
+            "Live peers summed over the nodes",
+            summary.connected_peers as f64,
+        ),
+        (
+            "antop_fleet_records_stored",
+            "Records stored by the fleet",
+            summary.records_stored as f64,
+        ),
+        (
+            "antop_fleet_reward_wallet_balance",
+            "Rewards of the fleet",
+            summary.reward_wallet_balance as f64,
+        ),
+        (
+            "antop_fleet_received_bytes",
+            "Bytes received by the fleet",
+            summary.bandwidth_inbound_bytes as f64,
+        ),
+        (
+            "antop_fleet_sent_bytes",
+            "Bytes sent by the fleet",
+            summary.bandwidth_outbound_bytes as f64,
+        ),
+        (
+            "antop_fleet_receive_bytes_per_second",
+            "Fleet receive speed",
+            summary.speed_in_bps,
+        ),
+        (
+            "antop_fleet_send_bytes_per_second",
+            "Fleet send speed",
+            summary.speed_out_bps,
+        ),
+    ] {
+        write_family(&mut out, name, "gauge", help);
+        out.push_str(&format!("{} {}\n", name, prometheus_value(value)));
+    }
+    out
+}
+
+// `# HELP` and `# TYPE` lines heading the samples of a metric family
+fn write_family(out: &mut String, name: &str, kind: &str, help: &str) {
+    out.push_str(&format!(
+        "# HELP {} {}\n# TYPE {} {}\n",
+        name, help, name, kind
+    ));
+}
+
+fn write_sample(out: &mut String, name: &str, node: &str, value: f64) {
+    out.push_str(&format!(
+        "{}{{node=\"{}\"}} {}\n",
+        name,
+        escape_label_value(node),
+        prometheus_value(value)
+    ));
+}
+
+// Node column name, or the directory path for snapshots taken without names
+fn node_label(node: &NodeSnapshot) -> String {
+    node.name.clone().unwrap_or_else(|| node.dir_path.clone())
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Rust prints infinities as `inf`, Prometheus wants `+Inf`
+fn prometheus_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// --- Table Snapshot ---
+
+/// Plain text of `snapshot` as the node table shows it: the summary line, then one
+/// aligned row per node with the same cells as the TUI. Values come from `app` where
+/// the snapshot has none, like the units or the zeroed error counts.
+pub fn snapshot_table(app: &App, snapshot: &FleetSnapshot) -> String {
+    let summary = &snapshot.summary;
+    let mut counts = vec![format!("{} running", summary.running)];
+    for (count, label) in [
+        (summary.initializing, "initializing"),
+        (summary.error, "error"),
+        (summary.stopped, "stopped"),
+        (summary.stale, "stale"),
+    ] {
+        if count > 0 {
+            counts.push(format!("{} {}", count, label));
+        }
+    }
+    let summary_line = format!(
+        "{} nodes ({}) | CPU {:.2}% | Mem {} | Peers {} | Recs {} | Rwds {} | Rx {} ({}) | Tx {} ({})",
+        summary.nodes,
+        counts.join(", "),
+        summary.cpu_usage_percentage,
+        format_option_u64_bytes(
+            Some((summary.memory_used_mb * 1_000_000.0) as u64), // Nodes report decimal MB
+            app.size_units
+        ),
+        format_count(Some(summary.connected_peers), app.notation),
+        format_count(Some(summary.records_stored), app.notation),
+        format_count(Some(summary.reward_wallet_balance), app.notation),
+        format_option_u64_bytes(Some(summary.bandwidth_inbound_bytes), app.size_units),
+        format_speed_bps(Some(summary.speed_in_bps), app.size_units),
+        format_option_u64_bytes(Some(summary.bandwidth_outbound_bytes), app.size_units),
+        format_speed_bps(Some(summary.speed_out_bps), app.size_units),
+    );
+
+    let mut rows: Vec<Vec<String>> = vec![
+        HEADER_TITLES
+            .iter()
+            .chain(&["Status"])
+            .map(|title| title.to_string())
+            .collect(),
+    ];
+    for node in &snapshot.nodes {
+        let name = node.name.clone().unwrap_or_else(|| node.dir_path.clone());
+        let status = node.status.clone().unwrap_or_default();
+        // Running and stale nodes have metrics, the others placeholders like in the TUI
+        let has_metrics = node.error.is_none() && matches!(status.as_str(), "Running" | "Stale");
+        let mut cells = match &node.url {
+            Some(url) if has_metrics => create_list_item_cells(
+                &name,
+                &node.metrics,
+                app.notation,
+                app.error_baseline.get(url),
+            ),
+            _ => create_placeholder_cells(&name),
+        };
+        cells.push(match &node.error {
+            Some(e) => format!("{} ({})", status, e),
+            None => status,
+        });
+        rows.push(
+            cells
+                .into_iter()
+                .map(|cell| cell.trim().to_string())
+                .collect(),
+        );
+    }
+
+    let last = rows[0].len() - 1;
+    let widths: Vec<usize> = (0..=last)
+        .map(|i| rows.iter().map(|row| row[i].width()).max().unwrap_or(0))
+        .collect();
+    let mut text = format!(
+        "antop snapshot, {}\n{}\n\n",
+        snapshot
+            .timestamp
+            .unwrap_or_else(Local::now)
+            .format("%Y-%m-%d %H:%M:%S"),
+        summary_line
+    );
+    for row in rows {
+        let line: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                let padding = " ".repeat(widths[i] - cell.width());
+                match i {
+                    0 => format!("{}{}", cell, padding), // Node, left aligned
+                    i if i == last => cell.clone(),      // Status, not padded
+                    _ => format!("{}{}", padding, cell), // Values, right aligned
+                }
+            })
+            .collect();
+        text.push_str(line.join("  ").trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+/// Writes the table of `snapshot_table` to `<stem>.txt` and the snapshot itself to
+/// `<stem>.json`, for `W`. Returns the path of the text file.
+pub fn write_table_snapshot(stem: &Path, table: &str, snapshot: &FleetSnapshot) -> Result<String> {
+    let text_path = stem.with_extension("txt");
+    let json_path = stem.with_extension("json");
+    let json =
+        serde_json::to_string_pretty(snapshot).context("Failed to serialize the snapshot")?;
+    fs::write(&text_path, table)
+        .with_context(|| format!("Failed to write {}", text_path.display()))?;
+    fs::write(&json_path, json)
+        .with_context(|| format!("Failed to write {}", json_path.display()))?;
+    Ok(text_path.display().to_string())
+}
+
+// --- Status File ---
+
+/// Contents of `--status-file`, a small JSON file for status bar widgets (conky,
+/// polybar...), rewritten after the updates, at most once per second:
+///
+/// ```json
+/// {
+///   "timestamp": "2025-05-01T12:00:00.000000+02:00",
+///   "summary": { "nodes": 4, "running": 2, "initializing": 0, "error": 1, "stopped": 1,
+///                "stale": 0, "cpu_usage_percentage": 12.5, "memory_used_mb": 230.1,
+///                "connected_peers": 32, "records_stored": 3703, "reward_wallet_balance": 0,
+///                "bandwidth_inbound_bytes": 3070000000, "bandwidth_outbound_bytes": 1530000000,
+///                "speed_in_bps": 311680.0, "speed_out_bps": 143930.0 },
+///   "alert_count": 1,
+///   "top_alert": { "node": "antnode-3", "alert": "Down: HTTP error: ..." }
+/// }
+/// ```
+///
+/// `summary` has the fields of `FleetSummary`, `top_alert` is null without alerts,
+/// see `App::top_alert`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusFile {
+    pub timestamp: DateTime<Local>,
+    pub summary: FleetSummary,
+    pub alert_count: usize,
+    pub top_alert: Option<TopAlert>,
+}
+
+/// The most pressing alert of a `StatusFile`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TopAlert {
+    pub node: String,
+    pub alert: String,
+}
+
+/// Writes the status file of the current state of `app` to `path`. It is written next
+/// to it first and renamed over it, so readers never see a partial file.
+pub fn write_status_file(app: &App, path: &Path) -> Result<()> {
+    let (alert_count, top_alert) = app.top_alert();
+    let status = StatusFile {
+        timestamp: Local::now(),
+        summary: fleet_snapshot(app).summary,
+        alert_count,
+        top_alert: top_alert.map(|(node, alert)| TopAlert { node, alert }),
+    };
+    let json = serde_json::to_string_pretty(&status)?;
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+    fs::write(&temp_path, json)
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+// --- History CSV ---
+
+/// The sparkline histories of the node behind `url` as CSV, for `e`: one row per
+/// sample, `series,samples_ago,value`, the latest sample of each series being 0 samples
+/// ago. Speeds, errors, CPU and peers have a sample per update, memory one every 30
+/// seconds. Speeds of failed fetches are left empty. None when the node has no history.
+pub fn history_csv(app: &App, url: &str) -> Option<String> {
+    fn rows<T: ToString>(
+        csv: &mut String,
+        series: &str,
+        history: Option<&std::collections::VecDeque<Option<T>>>,
+    ) {
+        let Some(history) = history else {
+            return;
+        };
+        for (samples_ago, value) in history.iter().rev().enumerate() {
+            let value = value.as_ref().map(T::to_string).unwrap_or_default();
+            csv.push_str(&format!("{},{},{}\n", series, samples_ago, value));
+        }
+    }
+    let wrap = |history: Option<&std::collections::VecDeque<u64>>| {
+        history.map(|history| history.iter().copied().map(Some).collect())
+    };
+
+    let mut csv = String::from("series,samples_ago,value\n");
+    rows(&mut csv, "rx_bps", app.speed_in_history.get(url));
+    rows(&mut csv, "tx_bps", app.speed_out_history.get(url));
+    rows(
+        &mut csv,
+        "errors",
+        wrap(app.errors_history.get(url)).as_ref(),
+    );
+    let cpu = app.cpu_history.get(url).map(|history| {
+        history
+            .iter()
+            .map(|cpu| Some(format!("{:.2}", cpu)))
+            .collect()
+    });
+    rows(&mut csv, "cpu_percent", cpu.as_ref());
+    rows(
+        &mut csv,
+        "memory_mb",
+        wrap(app.memory_history.get(url)).as_ref(),
+    );
+    rows(
+        &mut csv,
+        "live_peers",
+        wrap(app.peers_history.get(url)).as_ref(),
+    );
+    (csv.lines().count() > 1).then_some(csv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_metrics_body;
+    use antop::{
+        fetch::{FetchError, FetchErrorKind},
+        metrics::check_prometheus_format,
+    };
+    use prometheus_parse::{Scrape, Value};
+
+    fn fleet() -> App {
+        let mut app = App::for_tests(&["/nodes/antnode-1", "/nodes/antnode-2", "/nodes/antnode-3"]);
+        app.answer_all(|i| match i {
+            2 => Err(FetchError::new(FetchErrorKind::Server(500), "HTTP 500")),
+            _ => Ok(test_metrics_body(1000 * (i as u64 + 1), 7)),
+        });
+        app
+    }
+
+    fn parse(text: &str) -> Scrape {
+        Scrape::parse(text.lines().map(|line| Ok(line.to_string()))).unwrap()
+    }
+
+    fn value(scrape: &Scrape, metric: &str, label: (&str, &str)) -> Option<f64> {
+        scrape
+            .samples
+            .iter()
+            .find(|sample| sample.metric == metric && sample.labels.get(label.0) == Some(label.1))
+            .map(|sample| match sample.value {
+                Value::Counter(value) | Value::Gauge(value) | Value::Untyped(value) => value,
+                _ => panic!("{} is not a single value", metric),
+            })
+    }
+
+    #[test]
+    fn exposition_parses_as_prometheus_text() {
+        let text = prometheus_exposition(&fleet_snapshot(&fleet()));
+        assert_eq!(check_prometheus_format(&text), Ok(()));
+
+        let scrape = parse(&text);
+        // Every sample line was understood, and every family documented
+        let sample_lines = text
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .count();
+        assert_eq!(scrape.samples.len(), sample_lines);
+        for sample in &scrape.samples {
+            assert!(
+                scrape.docs.contains_key(&sample.metric),
+                "{}",
+                sample.metric
+            );
+        }
+    }
+
+    #[test]
+    fn exposition_has_the_node_and_fleet_values() {
+        let scrape = parse(&prometheus_exposition(&fleet_snapshot(&fleet())));
+        let up = |node| value(&scrape, "antop_node_up", ("node", node));
+        assert_eq!(up("antnode-1"), Some(1.0));
+        assert_eq!(up("antnode-2"), Some(1.0));
+        assert_eq!(up("antnode-3"), Some(0.0));
+        assert_eq!(
+            value(&scrape, "antop_node_records_stored", ("node", "antnode-2")),
+            Some(2000.0)
+        );
+        // A failing node only exports antop_node_up
+        assert!(
+            scrape
+                .samples
+                .iter()
+                .all(|sample| sample.labels.get("node") != Some("antnode-3")
+                    || sample.metric == "antop_node_up")
+        );
+        assert_eq!(
+            value(&scrape, "antop_nodes", ("status", "running")),
+            Some(2.0)
+        );
+        assert_eq!(
+            value(&scrape, "antop_nodes", ("status", "error")),
+            Some(1.0)
+        );
+        let records = scrape
+            .samples
+            .iter()
+            .find(|sample| sample.metric == "antop_fleet_records_stored")
+            .map(|sample| &sample.value);
+        assert!(matches!(records, Some(Value::Gauge(records)) if *records == 3000.0));
+    }
+
+    #[test]
+    fn exposition_escapes_the_node_names() {
+        let mut snapshot = fleet_snapshot(&fleet());
+        snapshot.nodes[0].name = Some("node \"a\"\\b\nc".to_string());
+        let text = prometheus_exposition(&snapshot);
+        assert!(text.contains(r#"antop_node_up{node="node \"a\"\\b\nc"} 1"#));
+        assert_eq!(text.lines().filter(|line| line.starts_with('c')).count(), 0);
+    }
+
+    #[test]
+    fn status_file_parses_back() {
+        let app = fleet();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("antop-status.json");
+        write_status_file(&app, &path).unwrap();
+        // Written a second time over the first, as at each update
+        write_status_file(&app, &path).unwrap();
+
+        let status: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(status["summary"]["nodes"], 3);
+        assert_eq!(status["summary"]["running"], 2);
+        assert_eq!(status["summary"]["error"], 1);
+        assert_eq!(status["summary"]["records_stored"], 3000);
+        let (alert_count, _) = app.top_alert();
+        assert!(alert_count >= 1);
+        assert_eq!(status["alert_count"], alert_count);
+        assert_eq!(status["top_alert"]["node"], "antnode-3");
+        assert!(
+            status["top_alert"]["alert"]
+                .as_str()
+                .unwrap()
+                .starts_with("Down")
+        );
+
+        // Only the status file is left, not its temporary file
+        let files: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["antop-status.json"]);
+    }
+}
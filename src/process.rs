@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    thread,
+    time::Duration,
+};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+use tokio::sync::mpsc;
+
+// How often the processes of the nodes without metrics are looked at
+pub const PROCESS_STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// CPU and memory of a node process, read from the system for nodes whose metrics
+/// endpoint doesn't answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub cpu_percent: f32,  // Since the previous sample, 0 at the first one
+    pub memory_bytes: u64, // Resident set size
+}
+
+impl ProcessStats {
+    /// Resident memory in MB, like the `memory_used_mb` metric.
+    pub fn memory_mb(&self) -> f64 {
+        self.memory_bytes as f64 / 1_000_000.0
+    }
+}
+
+/// PID in the `antnode.pid` file of the node in `dir_path`, if it has a readable one.
+pub fn read_pid(dir_path: &str) -> Option<u32> {
+    fs::read_to_string(Path::new(dir_path).join("antnode.pid"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Samples node processes off the UI loop: `sample` hands the node directories to a
+/// background thread, which reads their PID files and the processes, and sends the
+/// stats of those still running back to the loop. A PID reused by a process other
+/// than an antnode gives no stats.
+pub struct ProcessSampler {
+    requests: SyncSender<Vec<String>>,
+}
+
+impl ProcessSampler {
+    /// Starts the sampling thread, its results go to `stats`, keyed by node directory.
+    pub fn start(stats: mpsc::Sender<HashMap<String, ProcessStats>>) -> ProcessSampler {
+        let (requests, pending) = sync_channel(1);
+        thread::spawn(move || sample_processes(pending, stats));
+        ProcessSampler { requests }
+    }
+
+    /// Queues a sample of the processes of `dir_paths`. Skipped while the previous one
+    /// is still running.
+    pub fn sample(&self, dir_paths: Vec<String>) {
+        let _ = self.requests.try_send(dir_paths);
+    }
+}
+
+fn sample_processes(
+    pending: Receiver<Vec<String>>,
+    stats: mpsc::Sender<HashMap<String, ProcessStats>>,
+) {
+    // Kept across samples, the CPU usage is measured between two refreshes
+    let mut system = System::new();
+    while let Ok(dir_paths) = pending.recv() {
+        let pids: Vec<(String, Pid)> = dir_paths
+            .into_iter()
+            .filter_map(|dir_path| {
+                let pid = Pid::from_u32(read_pid(&dir_path)?);
+                Some((dir_path, pid))
+            })
+            .collect();
+        let to_update: Vec<Pid> = pids.iter().map(|(_, pid)| *pid).collect();
+        system.refresh_processes_specifics(
+            ProcessesToUpdate::Some(&to_update),
+            true,
+            ProcessRefreshKind::nothing().with_cpu().with_memory(),
+        );
+        let sampled = pids
+            .into_iter()
+            .filter_map(|(dir_path, pid)| {
+                let process = system.process(pid)?;
+                if !process.name().to_string_lossy().contains("antnode") {
+                    return None;
+                }
+                let stats = ProcessStats {
+                    pid: pid.as_u32(),
+                    cpu_percent: process.cpu_usage(),
+                    memory_bytes: process.memory(),
+                };
+                Some((dir_path, stats))
+            })
+            .collect();
+        if stats.blocking_send(sampled).is_err() {
+            return;
+        }
+    }
+}
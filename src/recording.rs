@@ -0,0 +1,536 @@
+use crate::{
+    app::App,
+    config::Settings,
+    output::{NodeSnapshot, fleet_snapshot},
+};
+use antop::metrics::NodeMetrics;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, params_from_iter, types::Value};
+use std::{
+    path::Path,
+    sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    thread,
+};
+use tokio::sync::mpsc;
+
+// Updates waiting to be written, the ones arriving while the queue is full are dropped
+const MAX_QUEUED_BATCHES: usize = 16;
+
+type IntegerColumn = (
+    &'static str,
+    fn(&NodeMetrics) -> Option<u64>,
+    fn(&mut NodeMetrics, Option<u64>),
+);
+type RealColumn = (
+    &'static str,
+    fn(&NodeMetrics) -> Option<f64>,
+    fn(&mut NodeMetrics, Option<f64>),
+);
+
+// Core metrics recorded per node, one column each
+const INTEGER_COLUMNS: [IntegerColumn; 13] = [
+    (
+        "uptime_seconds",
+        |m| m.uptime_seconds,
+        |m, v| m.uptime_seconds = v,
+    ),
+    (
+        "connected_peers",
+        |m| m.connected_peers,
+        |m, v| m.connected_peers = v,
+    ),
+    (
+        "peers_in_routing_table",
+        |m| m.peers_in_routing_table,
+        |m, v| m.peers_in_routing_table = v,
+    ),
+    (
+        "estimated_network_size",
+        |m| m.estimated_network_size,
+        |m, v| m.estimated_network_size = v,
+    ),
+    (
+        "records_stored",
+        |m| m.records_stored,
+        |m, v| m.records_stored = v,
+    ),
+    (
+        "reward_wallet_balance",
+        |m| m.reward_wallet_balance,
+        |m, v| m.reward_wallet_balance = v,
+    ),
+    (
+        "bandwidth_inbound_bytes",
+        |m| m.bandwidth_inbound_bytes,
+        |m, v| m.bandwidth_inbound_bytes = v,
+    ),
+    (
+        "bandwidth_outbound_bytes",
+        |m| m.bandwidth_outbound_bytes,
+        |m, v| m.bandwidth_outbound_bytes = v,
+    ),
+    (
+        "put_record_errors",
+        |m| m.put_record_errors,
+        |m, v| m.put_record_errors = v,
+    ),
+    (
+        "incoming_connection_errors",
+        |m| m.incoming_connection_errors,
+        |m, v| m.incoming_connection_errors = v,
+    ),
+    (
+        "outgoing_connection_errors",
+        |m| m.outgoing_connection_errors,
+        |m, v| m.outgoing_connection_errors = v,
+    ),
+    (
+        "kad_get_closest_peers_errors",
+        |m| m.kad_get_closest_peers_errors,
+        |m, v| m.kad_get_closest_peers_errors = v,
+    ),
+    (
+        "shunned_count",
+        |m| m.shunned_count,
+        |m, v| m.shunned_count = v,
+    ),
+];
+const REAL_COLUMNS: [RealColumn; 4] = [
+    (
+        "memory_used_mb",
+        |m| m.memory_used_mb,
+        |m, v| m.memory_used_mb = v,
+    ),
+    (
+        "cpu_usage_percentage",
+        |m| m.cpu_usage_percentage,
+        |m, v| m.cpu_usage_percentage = v,
+    ),
+    (
+        "speed_in_bps",
+        |m| m.speed_in_bps,
+        |m, v| m.speed_in_bps = v,
+    ),
+    (
+        "speed_out_bps",
+        |m| m.speed_out_bps,
+        |m, v| m.speed_out_bps = v,
+    ),
+];
+// Columns before the metrics, in the order of `row_values`
+const NODE_COLUMNS: [&str; 6] = ["timestamp", "dir_path", "name", "status", "url", "error"];
+
+fn column_names() -> Vec<&'static str> {
+    NODE_COLUMNS
+        .into_iter()
+        .chain(INTEGER_COLUMNS.iter().map(|(name, _, _)| *name))
+        .chain(REAL_COLUMNS.iter().map(|(name, _, _)| *name))
+        .collect()
+}
+
+fn create_schema(conn: &Connection) -> rusqlite::Result<()> {
+    let metric_columns: Vec<String> = INTEGER_COLUMNS
+        .iter()
+        .map(|(name, _, _)| format!("{} INTEGER", name))
+        .chain(
+            REAL_COLUMNS
+                .iter()
+                .map(|(name, _, _)| format!("{} REAL", name)),
+        )
+        .collect();
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS samples (
+            timestamp INTEGER NOT NULL,
+            dir_path TEXT NOT NULL,
+            name TEXT,
+            status TEXT,
+            url TEXT,
+            error TEXT,
+            {}
+        );
+        CREATE INDEX IF NOT EXISTS samples_timestamp ON samples (timestamp);",
+        metric_columns.join(",\n            ")
+    ))
+}
+
+// Values of one `samples` row, in the order of `column_names`
+fn row_values(timestamp: i64, node: NodeSnapshot) -> Vec<Value> {
+    let mut values = vec![
+        Value::Integer(timestamp),
+        Value::Text(node.dir_path),
+        node.name.map_or(Value::Null, Value::Text),
+        node.status.map_or(Value::Null, Value::Text),
+        node.url.map_or(Value::Null, Value::Text),
+        node.error.map_or(Value::Null, Value::Text),
+    ];
+    values.extend(INTEGER_COLUMNS.iter().map(|(_, get, _)| {
+        get(&node.metrics)
+            .and_then(|v| i64::try_from(v).ok())
+            .map_or(Value::Null, Value::Integer)
+    }));
+    values.extend(
+        REAL_COLUMNS
+            .iter()
+            .map(|(_, get, _)| get(&node.metrics).map_or(Value::Null, Value::Real)),
+    );
+    values
+}
+
+/// History database of `--record`: `record` queues one row per node for the current
+/// state of `app` and returns at once, a background thread writes the rows, those
+/// queued meanwhile in the same transaction. The first failed write (full disk,
+/// database removed...) stops the recording, the error goes to the status bar.
+pub struct Recorder {
+    batches: SyncSender<Vec<Vec<Value>>>,
+}
+
+impl Recorder {
+    /// Opens or creates the database at `path`, so a wrong path fails at startup, and
+    /// starts the writer. `stopped` gets the error that stopped the recording.
+    pub fn start(path: &str, stopped: mpsc::Sender<String>) -> Result<Recorder> {
+        let mut conn = Connection::open(path)
+            .with_context(|| format!("Failed to open the history database {}", path))?;
+        create_schema(&conn)
+            .with_context(|| format!("Failed to create the history table in {}", path))?;
+        let (batches, queued) = sync_channel(MAX_QUEUED_BATCHES);
+        let path = path.to_string();
+        thread::spawn(move || {
+            if let Err(e) = write_batches(&mut conn, queued) {
+                let _ = stopped.blocking_send(format!(
+                    "Recording to {} stopped: {}, monitoring goes on",
+                    path, e
+                ));
+            }
+        });
+        Ok(Recorder { batches })
+    }
+
+    /// Queues the rows of the current state of `app`. Returns false when the writer is
+    /// too far behind and they were dropped. Once the recording stopped nothing is
+    /// queued anymore, and this returns true.
+    pub fn record(&self, app: &App) -> bool {
+        let timestamp = Local::now().timestamp();
+        let rows = fleet_snapshot(app)
+            .nodes
+            .into_iter()
+            .map(|node| row_values(timestamp, node))
+            .collect();
+        !matches!(self.batches.try_send(rows), Err(TrySendError::Full(_)))
+    }
+}
+
+fn write_batches(conn: &mut Connection, queued: Receiver<Vec<Vec<Value>>>) -> rusqlite::Result<()> {
+    let columns = column_names();
+    let insert = format!(
+        "INSERT INTO samples ({}) VALUES ({})",
+        columns.join(", "),
+        vec!["?"; columns.len()].join(", ")
+    );
+    while let Ok(mut rows) = queued.recv() {
+        while let Ok(more) = queued.try_recv() {
+            rows.extend(more);
+        }
+        let transaction = conn.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(&insert)?;
+            for row in rows {
+                statement.execute(params_from_iter(row))?;
+            }
+        }
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// History database of `--replay`, stepped through one recorded update at a time.
+pub struct Replay {
+    conn: Connection,
+    pub file: String,         // File name, for the top bar
+    pub timestamps: Vec<i64>, // Recorded updates, oldest first
+    pub position: usize,      // Index in `timestamps` of the update shown
+}
+
+impl Replay {
+    /// Opens a database written by `--record`, starting at its first update.
+    pub fn open(path: &str) -> Result<Replay> {
+        if !Path::new(path).is_file() {
+            bail!("History database {} not found", path);
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open the history database {}", path))?;
+        Replay::load(conn, path)
+    }
+
+    // Reads the recorded updates of the database at `path`, opened as `conn`
+    fn load(conn: Connection, path: &str) -> Result<Replay> {
+        let timestamps = conn
+            .prepare("SELECT DISTINCT timestamp FROM samples ORDER BY timestamp")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<Vec<i64>>>()
+            })
+            .with_context(|| format!("{} is not a history database of --record", path))?;
+        if timestamps.is_empty() {
+            bail!("History database {} has no recorded updates", path);
+        }
+        Ok(Replay {
+            conn,
+            file: Path::new(path)
+                .file_name()
+                .map_or(path.to_string(), |name| name.to_string_lossy().into_owned()),
+            timestamps,
+            position: 0,
+        })
+    }
+
+    /// When the update shown was recorded.
+    pub fn timestamp(&self) -> Option<DateTime<Local>> {
+        DateTime::from_timestamp(self.timestamps[self.position], 0)
+            .map(|time| time.with_timezone(&Local))
+    }
+
+    /// Moves `delta` updates forward (or back), staying within the recording. Returns
+    /// false when already at its end.
+    pub fn step(&mut self, delta: isize) -> bool {
+        let position = self
+            .position
+            .saturating_add_signed(delta)
+            .min(self.timestamps.len() - 1);
+        let moved = position != self.position;
+        self.position = position;
+        moved
+    }
+
+    /// Every node of the recording, with the last URL it had, so the node list
+    /// doesn't change while stepping.
+    pub fn nodes(&self) -> Result<Vec<NodeSnapshot>> {
+        let mut statement = self.conn.prepare(
+            "SELECT dir_path, name, url FROM samples WHERE rowid IN
+                (SELECT MAX(rowid) FROM samples GROUP BY dir_path)",
+        )?;
+        let nodes = statement
+            .query_map([], |row| {
+                Ok(NodeSnapshot {
+                    dir_path: row.get(0)?,
+                    name: row.get(1)?,
+                    status: None,
+                    url: row.get(2)?,
+                    error: None,
+                    metrics: NodeMetrics::default(),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(nodes)
+    }
+
+    /// The nodes recorded at the update shown.
+    pub fn snapshot(&self) -> Result<Vec<NodeSnapshot>> {
+        let columns = column_names();
+        let mut statement = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM samples WHERE timestamp = ?1",
+            columns.join(", ")
+        ))?;
+        let metrics_start = NODE_COLUMNS.len();
+        let nodes = statement
+            .query_map([self.timestamps[self.position]], |row| {
+                let mut metrics = NodeMetrics::default();
+                for (i, (_, _, set)) in INTEGER_COLUMNS.iter().enumerate() {
+                    let value: Option<i64> = row.get(metrics_start + i)?;
+                    set(&mut metrics, value.and_then(|v| u64::try_from(v).ok()));
+                }
+                for (i, (_, _, set)) in REAL_COLUMNS.iter().enumerate() {
+                    set(
+                        &mut metrics,
+                        row.get(metrics_start + INTEGER_COLUMNS.len() + i)?,
+                    );
+                }
+                Ok(NodeSnapshot {
+                    dir_path: row.get(1)?,
+                    name: row.get(2)?,
+                    status: row.get(3)?,
+                    url: row.get(4)?,
+                    error: row.get(5)?,
+                    metrics,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(nodes)
+    }
+}
+
+/// Loads `--replay`: an App with every recorded node, showing the first update.
+pub fn replay_app(path: &str, settings: &Settings) -> Result<App> {
+    let replay = Replay::open(path)?;
+    let mut app = App::from_snapshot(replay.nodes()?, path, settings);
+    app.replay = Some(replay);
+    app.step_replay(0)?;
+    Ok(app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_metrics_body;
+    use antop::fetch::{FetchError, FetchErrorKind};
+
+    const DIRS: [&str; 2] = ["/srv/antnode-1", "/srv/antnode-2"];
+
+    fn memory_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn
+    }
+
+    // Rows of three updates at 1000, 1001 and 1002: the records grow by 10 each
+    // time, and antnode-2 fails at the last one
+    fn recorded_updates() -> Vec<Vec<Vec<Value>>> {
+        let mut app = App::for_tests(&DIRS);
+        (0..3u64)
+            .map(|update| {
+                app.answer_all(|i| match (update, i) {
+                    (2, 1) => Err(FetchError::new(FetchErrorKind::Connection, "refused")),
+                    _ => Ok(test_metrics_body(100 * (i as u64 + 1) + 10 * update, 5)),
+                });
+                fleet_snapshot(&app)
+                    .nodes
+                    .into_iter()
+                    .map(|node| row_values(1000 + update as i64, node))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // `conn` with the updates written as the recorder does, queued before the
+    // writer gets to them
+    fn record(conn: &mut Connection, updates: Vec<Vec<Vec<Value>>>) {
+        let (batches, queued) = sync_channel(MAX_QUEUED_BATCHES);
+        for rows in updates {
+            batches.try_send(rows).unwrap();
+        }
+        drop(batches);
+        write_batches(conn, queued).unwrap();
+    }
+
+    fn records(nodes: &[NodeSnapshot]) -> Vec<(String, Option<u64>)> {
+        let mut records: Vec<(String, Option<u64>)> = nodes
+            .iter()
+            .map(|node| (node.dir_path.clone(), node.metrics.records_stored))
+            .collect();
+        records.sort();
+        records
+    }
+
+    #[test]
+    fn the_schema_has_a_column_per_recorded_value() {
+        let conn = memory_database();
+        // Opening an existing database creates nothing twice
+        create_schema(&conn).unwrap();
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('samples')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(columns, column_names());
+    }
+
+    #[test]
+    fn queued_updates_are_all_written() {
+        let mut conn = memory_database();
+        record(&mut conn, recorded_updates());
+        let rows_per_update: Vec<(i64, i64)> = conn
+            .prepare(
+                "SELECT timestamp, COUNT(*) FROM samples GROUP BY timestamp ORDER BY timestamp",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows_per_update, [(1000, 2), (1001, 2), (1002, 2)]);
+    }
+
+    #[test]
+    fn an_empty_database_has_nothing_to_replay() {
+        let conn = memory_database();
+        assert!(Replay::load(conn, "empty.sqlite").is_err());
+    }
+
+    #[test]
+    fn the_replay_steps_through_the_recorded_updates() {
+        let mut conn = memory_database();
+        record(&mut conn, recorded_updates());
+        let mut replay = Replay::load(conn, "history.sqlite").unwrap();
+        assert_eq!(replay.timestamps, [1000, 1001, 1002]);
+        assert_eq!(replay.position, 0);
+        assert_eq!(replay.timestamp().unwrap().timestamp(), 1000);
+
+        let nodes = replay.nodes().unwrap();
+        let mut dirs: Vec<&str> = nodes.iter().map(|node| node.dir_path.as_str()).collect();
+        dirs.sort();
+        assert_eq!(dirs, DIRS);
+
+        let first = replay.snapshot().unwrap();
+        assert_eq!(
+            records(&first),
+            [
+                (DIRS[0].to_string(), Some(100)),
+                (DIRS[1].to_string(), Some(200))
+            ]
+        );
+        assert!(
+            first
+                .iter()
+                .all(|node| node.metrics.uptime_seconds == Some(93784))
+        );
+        assert!(first.iter().all(|node| node.error.is_none()));
+
+        // Forward past the end stops at the last update, where antnode-2 failed
+        assert!(replay.step(5));
+        assert_eq!(replay.position, 2);
+        assert!(!replay.step(1));
+        let last = replay.snapshot().unwrap();
+        assert_eq!(
+            records(&last),
+            [
+                (DIRS[0].to_string(), Some(120)),
+                (DIRS[1].to_string(), None)
+            ]
+        );
+        let failed = last.iter().find(|node| node.dir_path == DIRS[1]).unwrap();
+        assert!(
+            failed
+                .error
+                .as_deref()
+                .is_some_and(|e| e.contains("refused"))
+        );
+
+        // And back, before the start stops at the first one
+        assert!(replay.step(-1));
+        assert_eq!(records(&replay.snapshot().unwrap())[0].1, Some(110));
+        assert!(replay.step(-10));
+        assert_eq!(replay.position, 0);
+        assert!(!replay.step(-1));
+    }
+
+    #[test]
+    fn group_summaries_follow_the_replay() {
+        let mut conn = memory_database();
+        record(&mut conn, recorded_updates());
+        let replay = Replay::load(conn, "history.sqlite").unwrap();
+        let mut settings = Settings::for_tests();
+        settings.group_by_prefix = true;
+        let mut app = App::from_snapshot(replay.nodes().unwrap(), "history.sqlite", &settings);
+        app.replay = Some(replay);
+        app.step_replay(0).unwrap();
+        let group = |app: &App| *app.node_group_summary("antnode").unwrap();
+        assert_eq!((group(&app).running, group(&app).records), (2, 300));
+
+        app.step_replay(2).unwrap();
+        assert_eq!((group(&app).running, group(&app).errors), (1, 1));
+        assert_eq!(group(&app).records, 120);
+    }
+}
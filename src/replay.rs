@@ -0,0 +1,280 @@
+use crate::{
+    app::{App, UiMode},
+    config::Settings,
+    output::url_instance,
+    ui::ui,
+};
+use antop::{
+    discovery::{DiscoveredNode, DiscoverySource},
+    metrics::NodeMetrics,
+    state::PersistedState,
+};
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode};
+use ratatui::{Terminal, backend::Backend};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// Time between two steps of `antop history` at `--replay-speed 1`
+pub const REPLAY_STEP: Duration = Duration::from_millis(500);
+// Accepted `--replay-speed` factors, a step every 50 seconds to every 5 milliseconds
+pub const REPLAY_SPEEDS: RangeInclusive<f64> = 0.01..=100.0;
+// How often the loop looks for key presses
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The sparkline histories of a state file, replayed by `antop history`.
+pub struct HistoricState {
+    pub path: PathBuf,
+    pub state: PersistedState,
+    pub steps: usize, // Values in the longest history, the last one is the latest update
+}
+
+impl HistoricState {
+    /// Reads the state file at `path`, as written with `--state-file`.
+    pub fn load(path: &str) -> Result<HistoricState> {
+        let data = fs::read(path).with_context(|| format!("Failed to read state file {}", path))?;
+        let state: PersistedState = bincode::deserialize(&data)
+            .with_context(|| format!("{} is not an antop state file", path))?;
+        let longest = |lengths: &mut dyn Iterator<Item = usize>| lengths.max().unwrap_or(0);
+        let steps = [
+            longest(&mut state.speed_in_history.values().map(VecDeque::len)),
+            longest(&mut state.speed_out_history.values().map(VecDeque::len)),
+            longest(&mut state.errors_history.values().map(VecDeque::len)),
+            longest(&mut state.cpu_history.values().map(VecDeque::len)),
+            longest(&mut state.memory_history.values().map(VecDeque::len)),
+            longest(&mut state.peers_history.values().map(VecDeque::len)),
+            state.total_speed_in_history.len(),
+            state.total_speed_out_history.len(),
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+        Ok(HistoricState {
+            path: PathBuf::from(path),
+            state,
+            steps,
+        })
+    }
+
+    // Every metrics URL with a history
+    fn urls(&self) -> Vec<String> {
+        let state = &self.state;
+        let mut urls: Vec<String> = state
+            .speed_in_history
+            .keys()
+            .chain(state.speed_out_history.keys())
+            .chain(state.errors_history.keys())
+            .chain(state.cpu_history.keys())
+            .chain(state.memory_history.keys())
+            .chain(state.peers_history.keys())
+            .cloned()
+            .collect();
+        urls.sort();
+        urls.dedup();
+        urls
+    }
+}
+
+/// Where `antop history` is in the replayed state file, for the top and bottom bars.
+#[derive(Debug, Clone)]
+pub struct HistoryPlayback {
+    pub file: String,
+    pub step: usize,  // Updates shown, 1 to `steps`
+    pub steps: usize, // Updates in the state file
+    pub playing: bool,
+}
+
+// The values of `history` up to update `step` of `steps`. Histories end with the
+// latest update, shorter ones started later
+fn history_until<T: Clone>(history: &VecDeque<T>, steps: usize, step: usize) -> VecDeque<T> {
+    let start = steps.saturating_sub(history.len());
+    history
+        .iter()
+        .take(step.saturating_sub(start))
+        .cloned()
+        .collect()
+}
+
+// Histories of `all` cut at `step`, keyed by the same URLs
+fn histories_until<T: Clone>(
+    all: &HashMap<String, VecDeque<T>>,
+    steps: usize,
+    step: usize,
+) -> HashMap<String, VecDeque<T>> {
+    all.iter()
+        .map(|(url, history)| (url.clone(), history_until(history, steps, step)))
+        .collect()
+}
+
+/// Shows `app` as it was at update `step` of `history`: the sparklines up to then, the
+/// metrics of each node from their last values. Nodes without CPU, memory or peers
+/// values yet have no metrics, the state file doesn't say whether a node was up.
+fn show_step(app: &mut App, history: &HistoricState, step: usize) {
+    let (state, steps) = (&history.state, history.steps);
+    app.speed_in_history = histories_until(&state.speed_in_history, steps, step);
+    app.speed_out_history = histories_until(&state.speed_out_history, steps, step);
+    app.errors_history = histories_until(&state.errors_history, steps, step);
+    app.cpu_history = histories_until(&state.cpu_history, steps, step);
+    app.memory_history = histories_until(&state.memory_history, steps, step);
+    app.peers_history = histories_until(&state.peers_history, steps, step);
+    app.total_speed_in_history = history_until(&state.total_speed_in_history, steps, step);
+    app.total_speed_out_history = history_until(&state.total_speed_out_history, steps, step);
+
+    app.node_metrics.clear();
+    for url in app.node_urls.values() {
+        let last = |histories: &HashMap<String, VecDeque<u64>>| {
+            histories
+                .get(url)
+                .and_then(|history| history.back().copied())
+        };
+        let last_speed = |histories: &HashMap<String, VecDeque<Option<u64>>>| {
+            histories
+                .get(url)
+                .and_then(|history| history.back().copied().flatten())
+                .map(|speed| speed as f64)
+        };
+        let speed_points = |histories: &HashMap<String, VecDeque<Option<u64>>>| {
+            histories.get(url).map(|history| {
+                history
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, &val)| Some((i as f64, val? as f64)))
+                    .collect()
+            })
+        };
+        let metrics = NodeMetrics {
+            cpu_usage_percentage: app
+                .cpu_history
+                .get(url)
+                .and_then(|history| history.back().copied()),
+            memory_used_mb: last(&app.memory_history).map(|memory| memory as f64),
+            connected_peers: last(&app.peers_history),
+            // The state file keeps the error total only, it stands for all the counters
+            put_record_errors: last(&app.errors_history),
+            speed_in_bps: last_speed(&app.speed_in_history),
+            speed_out_bps: last_speed(&app.speed_out_history),
+            chart_data_in: speed_points(&app.speed_in_history),
+            chart_data_out: speed_points(&app.speed_out_history),
+            chart_data_errors: app.errors_history.get(url).map(|history| {
+                history
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &val)| (i as f64, val as f64))
+                    .collect()
+            }),
+            ..NodeMetrics::default()
+        };
+        let answered = metrics.cpu_usage_percentage.is_some()
+            || metrics.memory_used_mb.is_some()
+            || metrics.connected_peers.is_some();
+        if answered {
+            app.node_metrics.insert(url.clone(), Ok(metrics));
+        }
+    }
+    app.update_totals();
+    app.regroup_nodes(); // All the metrics were replaced
+    app.refresh_node_order();
+    if let Some(playback) = &mut app.history_playback {
+        playback.step = step;
+    }
+}
+
+/// Replays the histories of a state file in the TUI, `antop history`: one update every
+/// `REPLAY_STEP` divided by `speed`, from the oldest to the latest. Space pauses and
+/// resumes, Left / Right step back and forth (pausing), `?` shows the help, q or Esc
+/// quits.
+pub async fn run_replay<B: Backend>(
+    terminal: &mut Terminal<B>,
+    history: HistoricState,
+    speed: f64,
+    settings: &Settings,
+) -> Result<()> {
+    // One node per metrics URL, named after its host and port: the state file has no
+    // node directories
+    let discovered: Vec<DiscoveredNode> = history
+        .urls()
+        .into_iter()
+        .map(|url| DiscoveredNode {
+            dir_path: url_instance(&url).unwrap_or_else(|| url.clone()),
+            url,
+            source: DiscoverySource::History(history.path.clone()),
+        })
+        .collect();
+    let dirs = discovered
+        .iter()
+        .map(|node| node.dir_path.clone())
+        .collect();
+    let mut app = App::new(dirs, discovered, settings);
+    app.history_playback = Some(HistoryPlayback {
+        file: Path::new(&history.path)
+            .file_name()
+            .map_or(history.path.display().to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            }),
+        step: 1,
+        steps: history.steps,
+        playing: true,
+    });
+
+    let step_interval = REPLAY_STEP.div_f64(speed);
+    let mut step = 1.min(history.steps);
+    let mut playing = true;
+    let mut last_step = Instant::now();
+    show_step(&mut app, &history, step);
+    loop {
+        if let Some(playback) = &mut app.history_playback {
+            playback.playing = playing;
+        }
+        terminal.draw(|f| ui(f, &mut app, Instant::now()))?;
+
+        let ready = tokio::task::spawn_blocking(|| event::poll(POLL_INTERVAL)).await??;
+        if ready && let Event::Key(key) = event::read()? {
+            match key.code {
+                KeyCode::Char('q') => break,
+                // The help overlay is the only popup, Esc closes it first
+                KeyCode::Esc | KeyCode::Char('?') if app.ui_mode == UiMode::Help => {
+                    app.ui_mode = UiMode::Normal;
+                }
+                KeyCode::Esc => break,
+                KeyCode::Char('?') => app.ui_mode = UiMode::Help,
+                KeyCode::Char(' ') => {
+                    // Playing again from the end starts over
+                    if !playing && step == history.steps {
+                        step = 1.min(history.steps);
+                        show_step(&mut app, &history, step);
+                    }
+                    playing = !playing;
+                    last_step = Instant::now();
+                }
+                KeyCode::Left | KeyCode::Right => {
+                    playing = false;
+                    step = if key.code == KeyCode::Left {
+                        step.saturating_sub(1).max(1.min(history.steps))
+                    } else {
+                        (step + 1).min(history.steps)
+                    };
+                    show_step(&mut app, &history, step);
+                }
+                KeyCode::Up => app.move_selection(-1),
+                KeyCode::Down => app.move_selection(1),
+                _ => {}
+            }
+        }
+
+        if playing && last_step.elapsed() >= step_interval {
+            last_step = Instant::now();
+            if step < history.steps {
+                step += 1;
+                show_step(&mut app, &history, step);
+            } else {
+                playing = false; // Stays on the latest update
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,337 @@
+use crate::output::{FleetSnapshot, prometheus_exposition};
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::Semaphore,
+};
+
+// Largest request head read, the endpoints take no body
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+// Time a client gets to send its request head, a silent one is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Connections answered at once, the others wait in the listen backlog
+const MAX_CONNECTIONS: usize = 64;
+// Pause after a failed accept, out of file descriptors it fails again at once
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+const JSON: &str = "application/json";
+const PROMETHEUS_TEXT: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Snapshot the status server answers from, replaced after each update.
+pub type SharedSnapshot = Arc<RwLock<FleetSnapshot>>;
+
+/// Read-only JSON status endpoint started with `--serve`:
+///
+/// - `GET /api/status`: the whole `FleetSnapshot`
+/// - `GET /api/nodes/<name>`: a single node, by the name shown in the Node column
+/// - `GET /metrics`: the same as Prometheus text, see `prometheus_exposition`
+///
+/// Requests are answered from `snapshot`, the UI loop only swaps it after an update.
+pub struct StatusServer {
+    pub addr: SocketAddr,
+    pub snapshot: SharedSnapshot,
+}
+
+impl StatusServer {
+    /// Binds `addr` and starts answering in the background. Failing to bind is an error,
+    /// the server is not silently left out.
+    pub async fn start(addr: &str) -> Result<StatusServer> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind the status server to {}", addr))?;
+        let addr = listener.local_addr()?;
+        let snapshot = SharedSnapshot::default();
+        let shared = snapshot.clone();
+        let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+        tokio::spawn(async move {
+            loop {
+                let Ok(slot) = slots.clone().acquire_owned().await else {
+                    return;
+                };
+                let Ok((stream, _)) = listener.accept().await else {
+                    // Out of file descriptors and the like, give them time to be freed
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                };
+                let snapshot = shared.clone();
+                tokio::spawn(async move {
+                    handle_connection(stream, snapshot).await;
+                    drop(slot);
+                });
+            }
+        });
+        Ok(StatusServer { addr, snapshot })
+    }
+
+    /// Replaces the snapshot served from now on.
+    pub fn publish(&self, snapshot: FleetSnapshot) {
+        if let Ok(mut current) = self.snapshot.write() {
+            *current = snapshot;
+        }
+    }
+}
+
+// Why a request head was not read
+#[derive(Debug, PartialEq)]
+enum HeadError {
+    TimedOut, // Not complete within the timeout
+    TooLarge, // Over MAX_REQUEST_BYTES
+}
+
+// Answers one request and closes the connection
+async fn handle_connection(mut stream: TcpStream, snapshot: SharedSnapshot) {
+    let request = match read_request_head(&mut stream, REQUEST_TIMEOUT).await {
+        Ok(request) => request,
+        Err(HeadError::TimedOut) => return,
+        Err(HeadError::TooLarge) => {
+            let body = error_body("Request head too large");
+            respond(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                JSON,
+                &body,
+            )
+            .await;
+            return;
+        }
+    };
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, path) = (request_line.next(), request_line.next().unwrap_or_default());
+
+    let (status, content_type, body) = match method {
+        Some("GET") => route(path, &snapshot),
+        _ => (
+            "405 Method Not Allowed",
+            JSON,
+            error_body("Only GET is supported"),
+        ),
+    };
+    respond(&mut stream, status, content_type, &body).await;
+}
+
+// Reads up to the blank line ending the request head, or the end of the stream
+async fn read_request_head<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    timeout: Duration,
+) -> Result<String, HeadError> {
+    let read = async {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match reader.read(&mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+            if request.len() > MAX_REQUEST_BYTES {
+                return Err(HeadError::TooLarge);
+            }
+        }
+        Ok(String::from_utf8_lossy(&request).into_owned())
+    };
+    tokio::time::timeout(timeout, read)
+        .await
+        .unwrap_or(Err(HeadError::TimedOut))
+}
+
+/// Body of a request read from `stream`, as long as its Content-Length says, for the
+/// tests of the modules posting to a local server.
+#[cfg(test)]
+pub async fn read_test_request_body(stream: &mut TcpStream) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    let head_end = loop {
+        if let Some(i) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break i + 4;
+        }
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(n > 0, "request head cut short");
+        request.extend_from_slice(&buf[..n]);
+    };
+    let head = String::from_utf8_lossy(&request[..head_end]).to_lowercase();
+    let length: usize = head
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .map(|value| value.trim().parse().unwrap())
+        .unwrap_or(0);
+    while request.len() < head_end + length {
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(n > 0, "request body cut short");
+        request.extend_from_slice(&buf[..n]);
+    }
+    request[head_end..head_end + length].to_vec()
+}
+
+// Writes the response and closes the connection. No CORS header, browser pages of
+// other origins can't read the fleet status
+async fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// Status line, content type and body for a GET of `path`
+fn route(path: &str, snapshot: &SharedSnapshot) -> (&'static str, &'static str, String) {
+    let path = path.split('?').next().unwrap_or_default();
+    let Ok(snapshot) = snapshot.read() else {
+        return (
+            "500 Internal Server Error",
+            JSON,
+            error_body("Snapshot unavailable"),
+        );
+    };
+    if path == "/api/status" {
+        return ("200 OK", JSON, to_json(&*snapshot));
+    }
+    if path == "/metrics" {
+        return ("200 OK", PROMETHEUS_TEXT, prometheus_exposition(&snapshot));
+    }
+    if let Some(name) = path.strip_prefix("/api/nodes/") {
+        let name = percent_decode(name);
+        return match snapshot
+            .nodes
+            .iter()
+            .find(|node| node.name.as_deref() == Some(name.as_str()))
+        {
+            Some(node) => ("200 OK", JSON, to_json(node)),
+            None => (
+                "404 Not Found",
+                JSON,
+                error_body(&format!("No node named '{}'", name)),
+            ),
+        };
+    }
+    (
+        "404 Not Found",
+        JSON,
+        error_body("Unknown path, use /api/status, /api/nodes/<name> or /metrics"),
+    )
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|e| error_body(&e.to_string()))
+}
+
+fn error_body(message: &str) -> String {
+    json!({ "error": message }).to_string()
+}
+
+// Decodes `%20`-style escapes of a path segment, invalid ones are kept as is
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| segment.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_TIMEOUT: Duration = Duration::from_millis(100);
+
+    #[tokio::test]
+    async fn reads_the_head_up_to_the_blank_line() {
+        let mut client = &b"GET /api/status HTTP/1.1\r\nHost: x\r\n\r\nignored"[..];
+        let head = read_request_head(&mut client, TEST_TIMEOUT).await.unwrap();
+        assert!(head.starts_with("GET /api/status HTTP/1.1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn drops_a_client_that_stops_sending() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET /api/status HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        // The client keeps the connection open without ending the head
+        let head = read_request_head(&mut server, TEST_TIMEOUT).await;
+        assert_eq!(head, Err(HeadError::TimedOut));
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_head_over_the_size_cap() {
+        let mut request = b"GET / HTTP/1.1\r\n".to_vec();
+        while request.len() <= MAX_REQUEST_BYTES {
+            request.extend_from_slice(b"X-Padding: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n");
+        }
+        let head = read_request_head(&mut &request[..], TEST_TIMEOUT).await;
+        assert_eq!(head, Err(HeadError::TooLarge));
+    }
+
+    #[tokio::test]
+    async fn answers_without_a_cors_header() {
+        let server = StatusServer::start("127.0.0.1:0").await.unwrap();
+        let mut stream = TcpStream::connect(server.addr).await.unwrap();
+        stream
+            .write_all(b"GET /api/status HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!response.contains("Access-Control-Allow-Origin"));
+    }
+
+    #[tokio::test]
+    async fn connections_over_the_cap_wait_for_a_free_slot() {
+        let server = StatusServer::start("127.0.0.1:0").await.unwrap();
+        // Clients that never send their request hold every slot
+        let mut silent = Vec::new();
+        for _ in 0..MAX_CONNECTIONS {
+            silent.push(TcpStream::connect(server.addr).await.unwrap());
+        }
+        let mut stream = TcpStream::connect(server.addr).await.unwrap();
+        stream
+            .write_all(b"GET /api/status HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(200),
+            stream.read_to_string(&mut response),
+        )
+        .await;
+        assert!(waiting.is_err(), "answered over the cap: {}", response);
+
+        // One of them leaves, the waiting client is answered
+        silent.pop();
+        tokio::time::timeout(REQUEST_TIMEOUT, stream.read_to_string(&mut response))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// Health of a single node, derived from its metrics URL and last fetch result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatus {
+    Running,      // Last fetch succeeded
+    Initializing, // URL known, no fetch result yet
+    Error,        // Last fetch failed
+    Stopped,      // No metrics URL discovered
+}
+
+impl NodeStatus {
+    /// Status of a node from its last fetch: None when it has not been fetched yet,
+    /// `url_known` false when no metrics URL was discovered for it.
+    ///
+    /// ```
+    /// use antop::state::NodeStatus;
+    ///
+    /// let failed: Result<(), String> = Err("Network error".to_string());
+    /// assert_eq!(NodeStatus::from_fetch(true, Some(&failed)), NodeStatus::Error);
+    /// assert_eq!(NodeStatus::from_fetch::<(), String>(true, None), NodeStatus::Initializing);
+    /// ```
+    pub fn from_fetch<T, E>(url_known: bool, last_fetch: Option<&Result<T, E>>) -> NodeStatus {
+        match (url_known, last_fetch) {
+            (false, _) => NodeStatus::Stopped,
+            (true, None) => NodeStatus::Initializing,
+            (true, Some(Ok(_))) => NodeStatus::Running,
+            (true, Some(Err(_))) => NodeStatus::Error,
+        }
+    }
+
+    /// Label shown in the Status column.
+    pub fn label(self) -> &'static str {
+        match self {
+            NodeStatus::Running => "Running",
+            NodeStatus::Initializing => "Starting",
+            NodeStatus::Error => "Error",
+            NodeStatus::Stopped => "Stopped",
+        }
+    }
+}
+
+/// Number of nodes in each `NodeStatus`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatusCounts {
+    pub running: usize,
+    pub initializing: usize,
+    pub error: usize,
+    pub stopped: usize,
+}
+
+impl StatusCounts {
+    /// Counts one more node in `status`.
+    pub fn add(&mut self, status: NodeStatus) {
+        match status {
+            NodeStatus::Running => self.running += 1,
+            NodeStatus::Initializing => self.initializing += 1,
+            NodeStatus::Error => self.error += 1,
+            NodeStatus::Stopped => self.stopped += 1,
+        }
+    }
+}
+
+/// History kept across restarts in the bincode `--state-file`, and replayed by
+/// `antop history`. The histories are keyed by metrics URL, oldest values first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub speed_in_history: HashMap<String, VecDeque<Option<u64>>>,
+    pub speed_out_history: HashMap<String, VecDeque<Option<u64>>>,
+    pub errors_history: HashMap<String, VecDeque<u64>>,
+    pub cpu_history: HashMap<String, VecDeque<f64>>,
+    pub memory_history: HashMap<String, VecDeque<u64>>,
+    pub peers_history: HashMap<String, VecDeque<u64>>,
+    pub total_speed_in_history: VecDeque<u64>,
+    pub total_speed_out_history: VecDeque<u64>,
+    // Up (true) / down (false) changes of the availability timeline, keyed by node
+    // directory path, at Unix milliseconds since the monotonic clock doesn't survive
+    pub availability_changes: HashMap<String, VecDeque<(u64, bool)>>,
+}
@@ -0,0 +1,262 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io, path::Path, process::Stdio, time::Duration};
+use tokio::process::Command;
+
+// Default unit of a node, `{name}` is the name of its directory
+pub const DEFAULT_UNIT_TEMPLATE: &str = "antnode@{name}.service";
+// How often the units of the nodes that don't answer are queried
+pub const UNIT_STATE_INTERVAL: Duration = Duration::from_secs(10);
+// A `systemctl` that takes longer is given up on, a start can wait on a slow unit
+const SYSTEMCTL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The `[systemd]` section of the config file, or `--systemd` with the defaults.
+///
+/// ```toml
+/// [systemd]
+/// unit_template = "antnode@{name}.service" # {name} = node directory name (default)
+/// user = false                             # units of `systemctl --user`
+/// [systemd.units]                          # units not following the template
+/// "node-07" = "antnode-legacy.service"
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SystemdConfig {
+    #[serde(default = "default_unit_template")]
+    pub unit_template: String,
+    #[serde(default)]
+    pub units: HashMap<String, String>, // Node directory name -> unit
+    #[serde(default)]
+    pub user: bool,
+}
+
+fn default_unit_template() -> String {
+    DEFAULT_UNIT_TEMPLATE.to_string()
+}
+
+impl Default for SystemdConfig {
+    fn default() -> Self {
+        SystemdConfig {
+            unit_template: default_unit_template(),
+            units: HashMap::new(),
+            user: false,
+        }
+    }
+}
+
+/// What the actions of the node menu ask systemd to do with the unit of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitCommand {
+    Start,
+    Stop,
+    Restart,
+}
+
+impl UnitCommand {
+    /// The `systemctl` verb.
+    pub fn verb(self) -> &'static str {
+        match self {
+            UnitCommand::Start => "start",
+            UnitCommand::Stop => "stop",
+            UnitCommand::Restart => "restart",
+        }
+    }
+
+    /// Capitalized verb, for the confirmation prompt.
+    pub fn label(self) -> &'static str {
+        match self {
+            UnitCommand::Start => "Start",
+            UnitCommand::Stop => "Stop",
+            UnitCommand::Restart => "Restart",
+        }
+    }
+
+    /// What is being done, for the status message.
+    pub fn doing(self) -> &'static str {
+        match self {
+            UnitCommand::Start => "Starting",
+            UnitCommand::Stop => "Stopping",
+            UnitCommand::Restart => "Restarting",
+        }
+    }
+
+    /// What was done, for the status message.
+    pub fn done(self) -> &'static str {
+        match self {
+            UnitCommand::Start => "Started",
+            UnitCommand::Stop => "Stopped",
+            UnitCommand::Restart => "Restarted",
+        }
+    }
+}
+
+/// Short form of a unit's ActiveState for the Status column, where it follows "unit: ".
+pub fn short_unit_state(state: &str) -> &str {
+    match state {
+        "inactive" => "dead",
+        "activating" => "start",
+        "deactivating" => "stop",
+        "reloading" => "reload",
+        "not-found" => "none",
+        state => state,
+    }
+}
+
+impl SystemdConfig {
+    /// Unit of the node in `dir_path`: its entry in `units`, otherwise the template.
+    pub fn unit_name(&self, dir_path: &str) -> String {
+        let name = Path::new(dir_path)
+            .file_name()
+            .map_or(dir_path.to_string(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+        match self.units.get(&name) {
+            Some(unit) => unit.clone(),
+            None => self.unit_template.replace("{name}", &name),
+        }
+    }
+
+    // `systemctl`, for the user's units with `user`. It never waits for a password,
+    // polkit answers for it
+    fn systemctl(&self) -> Command {
+        let mut command = Command::new("systemctl");
+        if self.user {
+            command.arg("--user");
+        }
+        command
+            .arg("--no-ask-password")
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        command
+    }
+
+    // `systemctl show` of the states of `units`. The `--` keeps a unit named like an
+    // option, from a directory name or the config file, from being taken for one
+    fn show_command(&self, units: &[String]) -> Command {
+        let mut command = self.systemctl();
+        command
+            .args(["show", "--property=LoadState,ActiveState", "--"])
+            .args(units);
+        command
+    }
+
+    // `systemctl <command> -- <unit>`
+    fn unit_command(&self, command: UnitCommand, unit: &str) -> Command {
+        let mut systemctl = self.systemctl();
+        systemctl.args([command.verb(), "--", unit]);
+        systemctl
+    }
+
+    /// ActiveState of each of `units`, "not-found" for the ones systemd doesn't know.
+    /// Fails when there is no systemd to ask.
+    pub async fn unit_states(&self, units: &[String]) -> Result<HashMap<String, String>> {
+        if units.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let output = run(self.show_command(units).output()).await?;
+        // One block per unit in the order asked, separated by empty lines
+        let stdout = String::from_utf8_lossy(&output);
+        let states = stdout.split("\n\n").map(|block| {
+            let property = |name: &str| {
+                block
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+                    .unwrap_or_default()
+            };
+            if property("LoadState") == "not-found" {
+                "not-found".to_string()
+            } else {
+                property("ActiveState").to_string()
+            }
+        });
+        Ok(units.iter().cloned().zip(states).collect())
+    }
+
+    /// Runs `systemctl <command> <unit>`. A denial by polkit is an error like any other.
+    pub async fn run_command(&self, command: UnitCommand, unit: &str) -> Result<()> {
+        match run(self.unit_command(command, unit).output()).await {
+            Ok(_) => Ok(()),
+            // systemctl names the command and the unit in its own errors
+            Err(e) if e.to_string().starts_with("Failed to ") => Err(e),
+            Err(e) => Err(e.context(format!("Failed to {} {}", command.verb(), unit))),
+        }
+    }
+}
+
+// Stdout of a finished `systemctl`, its stderr as the error when it failed
+async fn run(output: impl Future<Output = io::Result<std::process::Output>>) -> Result<Vec<u8>> {
+    let output = match tokio::time::timeout(SYSTEMCTL_TIMEOUT, output).await {
+        Err(_) => bail!("systemctl did not answer within {:?}", SYSTEMCTL_TIMEOUT),
+        Ok(Err(e)) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("systemctl not found, is this a systemd system?")
+        }
+        Ok(output) => output.context("Failed to run systemctl")?,
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "{}",
+            stderr
+                .lines()
+                .next()
+                .filter(|line| !line.is_empty())
+                .unwrap_or("systemctl failed")
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &Command) -> Vec<String> {
+        command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn units_come_after_the_end_of_options() {
+        let config = SystemdConfig::default();
+        let unit = config.unit_name("/var/antctl/services/--force");
+        assert_eq!(unit, "antnode@--force.service");
+
+        let units = ["--help".to_string(), unit.clone()];
+        assert_eq!(
+            args(&config.show_command(&units)),
+            [
+                "--no-ask-password",
+                "show",
+                "--property=LoadState,ActiveState",
+                "--",
+                "--help",
+                "antnode@--force.service"
+            ]
+        );
+        assert_eq!(
+            args(&config.unit_command(UnitCommand::Stop, "--all")),
+            ["--no-ask-password", "stop", "--", "--all"]
+        );
+    }
+
+    #[test]
+    fn user_units_keep_the_user_flag_first() {
+        let config = SystemdConfig {
+            user: true,
+            ..SystemdConfig::default()
+        };
+        assert_eq!(
+            args(&config.unit_command(UnitCommand::Restart, "antnode@node-1.service")),
+            [
+                "--user",
+                "--no-ask-password",
+                "restart",
+                "--",
+                "antnode@node-1.service"
+            ]
+        );
+    }
+}
@@ -1,6 +1,75 @@
-use crate::metrics::NodeMetrics;
-use humansize::{DECIMAL, format_size};
-use std::path::Path;
+use crate::app::{ErrorBaseline, Notation, SizeUnits};
+use antop::metrics::NodeMetrics;
+use humansize::{BINARY, DECIMAL, FormatSizeOptions, format_size};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// Widest Records value that fits its column (without the trailing space)
+const RECORDS_CELL_WIDTH: usize = 10;
+// Marks where the truncation helpers cut a text, it takes one column
+const ELLIPSIS: char = '…';
+
+/// Shortens `text` to at most `max_width` terminal columns, ending with "…" when cut.
+/// Widths are display widths, wide characters (CJK, emoji) take two columns.
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width > max_width - 1 {
+            break; // Keep a column for the ellipsis
+        }
+        truncated.push(c);
+        width += char_width;
+    }
+    truncated.push(ELLIPSIS);
+    truncated
+}
+
+/// Like `truncate_to_width`, but cuts the start of `text` and keeps its end, for
+/// paths whose last components tell them apart: "…/node/antnode-1".
+pub fn truncate_start_to_width(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let mut kept = Vec::new();
+    let mut width = 0;
+    for c in text.chars().rev() {
+        let char_width = c.width().unwrap_or(0);
+        if width + char_width > max_width - 1 {
+            break; // Keep a column for the ellipsis
+        }
+        kept.push(c);
+        width += char_width;
+    }
+    std::iter::once(ELLIPSIS)
+        .chain(kept.into_iter().rev())
+        .collect()
+}
+
+/// Right-aligns `text` in exactly `width` terminal columns. Longer text is cut at
+/// the right, like a `Paragraph` does.
+pub fn right_align_to_width(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            break;
+        }
+        fitted.push(c);
+        used += char_width;
+    }
+    format!("{}{}", " ".repeat(width - used), fitted)
+}
 
 // Helper to format Option<T> for display
 pub fn format_option<T: std::fmt::Display>(opt: Option<T>) -> String {
@@ -10,7 +79,64 @@ pub fn format_option<T: std::fmt::Display>(opt: Option<T>) -> String {
     }
 }
 
-// Helper to format uptime into d HH:MM:SS or HH:MM:SS
+// Helper to format a count with thousands separators: 183429 -> "183,429"
+pub fn format_count_grouped(count: u64) -> String {
+    let digits = count.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+// Helper to format a count with a short-scale suffix: 183429 -> "183K", 1234567 -> "1.2M",
+// 5600000000 -> "5.6B"
+pub fn format_count_compact(count: u64) -> String {
+    const SUFFIXES: [&str; 6] = ["", "K", "M", "B", "T", "Q"];
+    let mut value = count as f64;
+    let mut unit = 0;
+    // Move to the next unit also when rounding would print "1000k"
+    while unit + 1 < SUFFIXES.len() && value.round() >= 1000.0 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        count.to_string()
+    } else if value < 9.95 {
+        format!("{:.1}{}", value, SUFFIXES[unit])
+    } else {
+        format!("{:.0}{}", value, SUFFIXES[unit])
+    }
+}
+
+// Helper to format Option<u64> counts in the given notation
+pub fn format_count(opt: Option<u64>, notation: Notation) -> String {
+    match (opt, notation) {
+        (Some(count), Notation::Grouped) => format_count_grouped(count),
+        (Some(count), Notation::Compact) => format_count_compact(count),
+        (None, _) => "-".to_string(),
+    }
+}
+
+// Helper to format Option<u64> counts in the given notation, falling back to the compact
+// notation when the grouped value would be wider than `max_width`
+pub fn format_count_fit(opt: Option<u64>, notation: Notation, max_width: usize) -> String {
+    let formatted = format_count(opt, notation);
+    match opt {
+        Some(count) if formatted.len() > max_width => format_count_compact(count),
+        _ => formatted,
+    }
+}
+
+// Days from which uptimes roll up into weeks, then months (of 30 days)
+const UPTIME_WEEKS_FROM_DAYS: u64 = 7;
+const UPTIME_MONTHS_FROM_DAYS: u64 = 60;
+
+// Helper to format uptime into HH:MM:SS, d HH:MM:SS, w d HH:MM or mo w d.
+// Longer uptimes drop the smallest units to stay within the Uptime column (11 chars).
 pub fn format_uptime(seconds: Option<u64>) -> String {
     match seconds {
         Some(s) => {
@@ -18,7 +144,12 @@ pub fn format_uptime(seconds: Option<u64>) -> String {
             let hours = (s % (24 * 3600)) / 3600;
             let minutes = (s % 3600) / 60;
             let secs = s % 60;
-            if days > 0 {
+            if days >= UPTIME_MONTHS_FROM_DAYS {
+                let (months, days) = (days / 30, days % 30);
+                format!("{}mo {}w {}d", months, days / 7, days % 7)
+            } else if days >= UPTIME_WEEKS_FROM_DAYS {
+                format!("{}w {}d {:02}:{:02}", days / 7, days % 7, hours, minutes)
+            } else if days > 0 {
                 format!("{}d {:02}:{:02}:{:02}", days, hours, minutes, secs)
             } else {
                 format!("{:02}:{:02}:{:02}", hours, minutes, secs)
@@ -36,61 +167,74 @@ pub fn format_float(opt: Option<f64>, precision: usize) -> String {
     }
 }
 
-// Helper to format Option<u64> bytes into human-readable size (KB, MB, GB)
-pub fn format_option_u64_bytes(opt: Option<u64>) -> String {
+// humansize options for the size units setting
+fn size_format(units: SizeUnits) -> FormatSizeOptions {
+    match units {
+        SizeUnits::Decimal => DECIMAL,
+        SizeUnits::Binary => BINARY,
+    }
+}
+
+// Helper to format Option<u64> bytes into human-readable size (kB, MB, GB or KiB, MiB, GiB)
+pub fn format_option_u64_bytes(opt: Option<u64>, units: SizeUnits) -> String {
     match opt {
-        Some(val) => humansize::format_size(val, humansize::DECIMAL), // Use humansize formatting
+        Some(val) => format_size(val, size_format(units)), // Use humansize formatting
         None => "-".to_string(),
     }
 }
 
-// Helper to format Option<f64> speed in Bps to human-readable KB/s, MB/s etc.
-pub fn format_speed_bps(speed_bps: Option<f64>) -> String {
+// Helper to format Option<f64> speed in Bps to human-readable kB/s, MB/s etc.
+pub fn format_speed_bps(speed_bps: Option<f64>, units: SizeUnits) -> String {
     match speed_bps {
         Some(bps) if bps >= 0.0 => {
             // Use humansize for formatting, append "/s"
-            format!("{}/s", format_size(bps as u64, DECIMAL))
+            format!("{}/s", format_size(bps as u64, size_format(units)))
         }
         _ => "-".to_string(), // Handle None or negative values (e.g., initial state)
     }
 }
 
-// Helper to create a vector of formatted data cell strings for a list item
-pub fn create_list_item_cells(root_path: &str, metrics: &NodeMetrics) -> Vec<String> {
-    let put_err = metrics.put_record_errors.unwrap_or(0);
-    let conn_in_err = metrics.incoming_connection_errors.unwrap_or(0);
-    let conn_out_err = metrics.outgoing_connection_errors.unwrap_or(0);
-    let kad_err = metrics.kad_get_closest_peers_errors.unwrap_or(0);
-    let total_errors = put_err + conn_in_err + conn_out_err + kad_err;
+/// Formats a per-second rate of change with its sign, like `+1.5/s` or `-12/s`.
+/// Rates that round to zero are `0/s`.
+pub fn format_change_rate(rate: f64) -> String {
+    if rate.abs() < 0.05 {
+        "0/s".to_string()
+    } else if rate.abs() < 10.0 {
+        format!("{:+.1}/s", rate)
+    } else {
+        format!("{:+.0}/s", rate)
+    }
+}
 
-    // Extract the last component (directory name)
-    let node_name = Path::new(root_path)
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or(root_path); // Fallback to full path if extraction fails
+// Helper to create a vector of formatted data cell strings for a list item.
+// Errors are counted from `error_baseline` when they were zeroed (`z`).
+pub fn create_list_item_cells(
+    node_name: &str,
+    metrics: &NodeMetrics,
+    notation: Notation,
+    error_baseline: Option<&ErrorBaseline>,
+) -> Vec<String> {
+    let total_errors = error_baseline.map_or_else(
+        || metrics.total_errors(),
+        |baseline| baseline.errors_since(metrics),
+    );
 
     vec![
         node_name.to_string(), // Let widget handle alignment
         format!("{}", format_uptime(metrics.uptime_seconds)), // Uptime
         format!("{}MB", format_float(metrics.memory_used_mb, 1)), // Mem MB
-        format!("{}%", format_float(metrics.cpu_usage_percentage, 2)), // CPU %
-        format!("{}", format_option(metrics.connected_peers)), // Peers (Live)
-        format!("{}", format_option(metrics.peers_in_routing_table)), // Routing Table Size
-        format!("{}", format_option(metrics.records_stored)), // Records
+        format!("{}%", format_float(metrics.cpu_percent(), 2)), // CPU %, smoothed
+        format_count(metrics.connected_peers, notation), // Peers (Live)
+        format_count(metrics.peers_in_routing_table, notation), // Routing Table Size
+        format_count_fit(metrics.records_stored, notation, RECORDS_CELL_WIDTH), // Records
         format!("{}", format_option(metrics.reward_wallet_balance)), // Reward
-        format!("{}", total_errors), // Err
-                               // Status is handled separately in render_custom_node_rows
+        format_count(Some(total_errors), notation), // Err
+                               // Status is handled separately in node_row
     ]
 }
 
 // Helper to create placeholder cells for error/unknown states
-pub fn create_placeholder_cells(root_path: &str) -> Vec<String> {
-    // Extract the last component (directory name)
-    let node_name = Path::new(root_path)
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or(root_path); // Fallback to full path if extraction fails
-
+pub fn create_placeholder_cells(node_name: &str) -> Vec<String> {
     vec![
         node_name.to_string(),  // Let widget handle alignment
         format!("{:>11}", "-"), // Uptime (Right aligned, width 11)
@@ -104,17 +248,145 @@ pub fn create_placeholder_cells(root_path: &str) -> Vec<String> {
     ]
 }
 
-/// Formats a Duration into a human-readable string (ms, s, min, hr).
+/// Formats a Duration into a short human-readable string, in the largest unit that
+/// fits: ns, μs, ms, s, m, h, or days and hours (`3d 4h`). Values are rounded down.
 pub fn format_duration_human(duration: std::time::Duration) -> String {
-    let secs = duration.as_secs_f64();
+    let nanos = duration.as_nanos();
+    let secs = duration.as_secs();
 
-    if secs < 1.0 {
+    if nanos < 1_000 {
+        format!("{}ns", nanos)
+    } else if nanos < 1_000_000 {
+        format!("{}μs", duration.as_micros())
+    } else if secs < 1 {
         format!("{}ms", duration.as_millis())
-    } else if secs < 60.0 {
-        format!("{:.0}s", secs)
-    } else if secs < 3600.0 {
-        format!("{:.0}min", secs / 60.0)
+    } else if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 24 * 3600 {
+        format!("{}h", secs / 3600)
     } else {
-        format!("{:.0}hr", secs / 3600.0)
+        format!("{}d {}h", secs / (24 * 3600), secs % (24 * 3600) / 3600)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_keeps_text_that_fits() {
+        assert_eq!(truncate_to_width("antnode-1", 9), "antnode-1");
+        assert_eq!(truncate_to_width("antnode-1", 20), "antnode-1");
+        assert_eq!(truncate_to_width("", 0), "");
+        assert_eq!(truncate_start_to_width("antnode-1", 9), "antnode-1");
+    }
+
+    #[test]
+    fn truncate_cuts_ascii_with_an_ellipsis() {
+        assert_eq!(truncate_to_width("antnode-12", 6), "antno…");
+        assert_eq!(
+            truncate_start_to_width("/var/node/antnode-12", 11),
+            "…antnode-12"
+        );
+    }
+
+    #[test]
+    fn truncate_to_zero_and_one_column() {
+        for text in ["antnode", "😀", "节点"] {
+            assert_eq!(truncate_to_width(text, 0), "");
+            assert_eq!(truncate_start_to_width(text, 0), "");
+            assert_eq!(truncate_to_width(text, 1), "…");
+            assert_eq!(truncate_start_to_width(text, 1), "…");
+        }
+    }
+
+    #[test]
+    fn truncate_counts_wide_characters_as_two_columns() {
+        // Emoji and CJK take two columns each
+        assert_eq!(truncate_to_width("😀😀😀", 5), "😀😀…");
+        assert_eq!(truncate_to_width("节点节点", 7), "节点节…");
+        assert_eq!(truncate_start_to_width("节点节点", 5), "…节点");
+        assert_eq!(truncate_start_to_width("a😀b😀", 4), "…b😀");
+    }
+
+    #[test]
+    fn truncate_leaves_out_a_wide_character_on_the_last_column() {
+        // "ab" then a two-column character where only the ellipsis column is left
+        assert_eq!(truncate_to_width("ab😀cd", 4), "ab…");
+        assert_eq!(truncate_to_width("ab节cd", 4), "ab…");
+        assert_eq!(truncate_start_to_width("dc😀ba", 4), "…ba");
+        for max_width in 0..12 {
+            for text in ["ab😀cd节点ef", "节a点b😀c", "😀😀😀😀😀😀"] {
+                assert!(truncate_to_width(text, max_width).width() <= max_width);
+                assert!(truncate_start_to_width(text, max_width).width() <= max_width);
+            }
+        }
+    }
+
+    #[test]
+    fn count_grouped_adds_thousands_separators() {
+        assert_eq!(format_count_grouped(0), "0");
+        assert_eq!(format_count_grouped(999), "999");
+        assert_eq!(format_count_grouped(1_000), "1,000");
+        assert_eq!(format_count_grouped(999_950), "999,950");
+        assert_eq!(format_count_grouped(9_950), "9,950");
+        assert_eq!(format_count_grouped(u64::MAX), "18,446,744,073,709,551,615");
+    }
+
+    #[test]
+    fn count_compact_rounds_into_the_next_unit() {
+        assert_eq!(format_count_compact(0), "0");
+        assert_eq!(format_count_compact(999), "999");
+        assert_eq!(format_count_compact(1_000), "1.0K");
+        assert_eq!(format_count_compact(183_429), "183K");
+        // Not "1000K" nor "9.9K"
+        assert_eq!(format_count_compact(999_950), "1.0M");
+        assert_eq!(format_count_compact(9_950), "10K");
+        // Past quadrillions, the largest suffix, the number grows instead
+        assert_eq!(format_count_compact(u64::MAX), "18447Q");
+    }
+
+    #[test]
+    fn uptime_rolls_up_into_weeks_and_months() {
+        const DAY: u64 = 24 * 3600;
+        let uptime = |seconds| format_uptime(Some(seconds));
+        assert_eq!(uptime(0), "00:00:00");
+        assert_eq!(uptime(6 * DAY + DAY - 1), "6d 23:59:59");
+        assert_eq!(uptime(7 * DAY), "1w 0d 00:00");
+        assert_eq!(uptime(59 * DAY), "8w 3d 00:00");
+        assert_eq!(uptime(60 * DAY), "2mo 0w 0d");
+        assert_eq!(format_uptime(None), "-");
+    }
+
+    #[test]
+    fn uptime_fits_the_uptime_column() {
+        const DAY: u64 = 24 * 3600;
+        for seconds in [6 * DAY + DAY - 1, 7 * DAY, 59 * DAY + DAY - 1, 60 * DAY] {
+            assert!(format_uptime(Some(seconds)).len() <= 11, "{}", seconds);
+        }
+        // Every hour of the first ten years
+        for hours in 0..10 * 365 * 24 {
+            let formatted = format_uptime(Some(hours * 3600 + 3599));
+            assert!(formatted.len() <= 11, "{}", formatted);
+        }
+    }
+
+    #[test]
+    fn duration_human_picks_the_largest_unit() {
+        use std::time::Duration;
+        let secs = Duration::from_secs;
+        assert_eq!(format_duration_human(Duration::ZERO), "0ns");
+        assert_eq!(format_duration_human(Duration::from_micros(1500)), "1ms");
+        assert_eq!(format_duration_human(secs(59)), "59s");
+        assert_eq!(format_duration_human(secs(23 * 3600 + 59 * 60 + 59)), "23h");
+        assert_eq!(format_duration_human(secs(24 * 3600)), "1d 0h");
+        assert_eq!(format_duration_human(secs(24 * 3600 + 1)), "1d 0h");
+        assert_eq!(
+            format_duration_human(secs(3 * 24 * 3600 + 4 * 3600)),
+            "3d 4h"
+        );
+        assert_eq!(format_duration_human(secs(u64::MAX)), "213503982334601d 7h");
     }
 }
@@ -1,117 +1,454 @@
 pub mod formatters;
+pub mod popups;
 pub mod widgets;
 
 // --- Imports (Combined and adjusted from src/ui.rs) ---
-use self::widgets::{render_header, render_node_row};
-use crate::ui::formatters::format_duration_human;
-use crate::{app::App, cli::Cli, discovery::find_metrics_nodes, fetch::fetch_metrics};
+use self::widgets::render_node_table;
+use crate::ui::formatters::{format_duration_human, format_option_u64_bytes, truncate_to_width};
+use crate::{
+    alert::send_alert_email,
+    app::{App, NodeAction, Severity, SortKey, TICK_PRESETS, UiMode, node_log_file},
+    cli::Cli,
+    clipboard::{Clipboard, CopyMethod},
+    config::Settings,
+    demo::DemoFleet,
+    hooks,
+    influx::{InfluxEvent, InfluxSink},
+    output::{
+        fleet_snapshot, history_csv, snapshot_table, write_status_file, write_table_snapshot,
+    },
+    process::{PROCESS_STATS_INTERVAL, ProcessSampler},
+    recording::Recorder,
+    systemd::{UNIT_STATE_INTERVAL, UnitCommand},
+    watcher::{glob_parent, watch_parent_directory},
+    webhook,
+};
+use antop::{
+    discovery::{Discovery, DiscoverySource, find_metrics_nodes, matches_interface},
+    fetch::{FetchEvent, fetch_metrics_in_background, fetch_raw_metrics},
+};
 use anyhow::{Context, Result};
 use crossterm::{
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseEvent, MouseEventKind,
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        Event, KeyCode, MouseEvent, MouseEventKind,
     },
     execute,
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    terminal::{
+        self, EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+    },
 };
 use ratatui::{
-    Frame, Terminal,
+    Frame, Terminal, TerminalOptions, Viewport,
     backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::Paragraph,
 };
 use std::{
-    io::{self, Stdout},
+    env,
+    io::{self, Stdout, Write},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
     time::{Duration, Instant},
 };
-use tokio::time::interval;
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    time::interval,
+};
 
 // --- TUI Setup and Restore ---
 
-pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+/// Prepares the terminal for drawing.
+/// `alt_screen`: true to draw fullscreen on the alternate screen with mouse capture,
+/// false to draw inline in the main screen (keeps the native scrollback intact).
+pub fn setup_terminal(alt_screen: bool) -> Result<Terminal<CrosstermBackend<Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    Terminal::new(backend).context("Failed to create terminal")
+    if alt_screen {
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+        let backend = CrosstermBackend::new(stdout);
+        Terminal::new(backend).context("Failed to create terminal")
+    } else {
+        // Inline viewport covering the visible screen, redrawn in place
+        let (_, height) = terminal::size()?;
+        let backend = CrosstermBackend::new(stdout);
+        Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: Viewport::Inline(height),
+            },
+        )
+        .context("Failed to create terminal")
+    }
+}
+
+/// Undoes `setup_terminal`. `alt_screen` must match the value passed to `setup_terminal`.
+pub fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    alt_screen: bool,
+) -> Result<()> {
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), DisableFocusChange)?; // Harmless when never enabled
+    if alt_screen {
+        execute!(
+            terminal.backend_mut(),
+            LeaveAlternateScreen,
+            DisableMouseCapture
+        )?;
+    } else {
+        // Leave the last frame on screen and put the cursor on a fresh line below it
+        let viewport = terminal.get_frame().size();
+        terminal.set_cursor(0, viewport.bottom().saturating_sub(1))?;
+        println!();
+    }
+    terminal.show_cursor()?;
+    Ok(())
 }
 
-pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+/// Hands the terminal back to the shell, e.g. to run an external program.
+/// Undone by `resume_terminal`, `alt_screen` must match the value passed to `setup_terminal`.
+pub fn suspend_terminal<B: Backend>(terminal: &mut Terminal<B>, alt_screen: bool) -> Result<()> {
     disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    execute!(io::stdout(), DisableFocusChange)?;
+    if alt_screen {
+        execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)?;
+    }
     terminal.show_cursor()?;
     Ok(())
 }
 
+/// Takes the terminal back after `suspend_terminal` and forces a full redraw.
+pub fn resume_terminal<B: Backend>(terminal: &mut Terminal<B>, alt_screen: bool) -> Result<()> {
+    enable_raw_mode()?;
+    if alt_screen {
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+    }
+    terminal.hide_cursor()?;
+    terminal.clear()?; // The external program drew over our last frame
+    Ok(())
+}
+
+/// Runs `command` in the foreground with the TUI suspended, and restores the TUI
+/// afterwards whether or not the command could be started.
+pub fn run_external_command<B: Backend>(
+    terminal: &mut Terminal<B>,
+    alt_screen: bool,
+    command: &mut Command,
+) -> Result<ExitStatus> {
+    suspend_terminal(terminal, alt_screen)?;
+    let status = command.status();
+    resume_terminal(terminal, alt_screen)?;
+    status.with_context(|| format!("Failed to run {:?}", command.get_program()))
+}
+
 // --- Main Application Loop ---
 
+// Rows the log tail pane scrolls per PageUp / PageDown
+const LOG_SCROLL_STEP: usize = 5;
+// Rows / columns the raw metrics popup scrolls per PageUp / PageDown and Left / Right
+const RAW_METRICS_PAGE_STEP: i32 = 10;
+const RAW_METRICS_COLUMN_STEP: i32 = 8;
+// Redraws while nothing changes, for the "last: Ns ago" and countdown counters
+const IDLE_REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+// Shortest time between two rewrites of `--status-file`, whatever the update interval
+const STATUS_FILE_INTERVAL: Duration = Duration::from_secs(1);
+
 pub async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
-    _cli: &Cli,
-    effective_log_path: &str,
+    cli: &Cli,
+    settings: &Settings,
+    mut demo: Option<DemoFleet>, // Fake fleet sampled instead of fetching, see `--demo`
 ) -> Result<()> {
+    let effective_log_path = settings.effective_log_path();
+    let alt_screen = !cli.no_altscreen; // Needed to suspend/resume around external commands
     let mut discover_timer = interval(Duration::from_secs(60)); // Check for new node URLs every 60s
-    let mut last_tick = Instant::now(); // Track the last metrics update time
+    let mut save_state_timer = interval(Duration::from_secs(30)); // Persist history for crash recovery
+    // Sweeps run in a background task and report back through this channel
+    let (fetch_tx, mut fetch_rx) = mpsc::unbounded_channel();
+    let mut clipboard = Clipboard::default(); // Opened on first copy
+
+    // New node directories reported by the watcher, only with `--watch-new-nodes`
+    let (new_dir_tx, mut new_dir_rx) = mpsc::channel(16);
+    // Outcome of the alert emails, recipient or error, only with `--alert-email`
+    let (alert_tx, mut alert_rx) = mpsc::channel(4);
+    // State changes of the InfluxDB sink, only with `--influx-url`
+    let (influx_tx, mut influx_rx) = mpsc::channel(4);
+    let influx = InfluxSink::start(settings, influx_tx)?;
+    // Webhook posts that failed for good, only with `[notifications]`
+    let (webhook_failure_tx, mut webhook_failure_rx) = mpsc::channel(4);
+    let webhook = app
+        .webhook
+        .as_ref()
+        .map(|webhook| webhook::start_delivery(webhook.url.clone(), webhook_failure_tx));
+    // Outcome of each hook run, only with `[hooks]`
+    let (hook_tx, mut hook_rx) = mpsc::channel(16);
+    let hooks = app
+        .hooks
+        .as_ref()
+        .map(|hooks| hooks::start_hooks(hooks.max_concurrent(), hook_tx));
+    // Outcome of the `W` snapshots, written off the UI loop
+    let (snapshot_tx, mut snapshot_rx) = mpsc::channel(4);
+    // Last rewrite of `--status-file`, and whether the last one failed (reported once)
+    let mut status_file_written: Option<Instant> = None;
+    let mut status_file_failing = false;
+    // Why the recording stopped, only with `--record`
+    let (record_stopped_tx, mut record_stopped_rx) = mpsc::channel(1);
+    let recorder = cli
+        .record
+        .as_deref()
+        .map(|path| Recorder::start(path, record_stopped_tx))
+        .transpose()?;
+    // Unit states of the nodes that don't answer, and the outcome of the unit actions,
+    // only with `--systemd`. Polling stops for good when systemctl can't answer.
+    let mut unit_state_timer = interval(UNIT_STATE_INTERVAL);
+    let (unit_state_tx, mut unit_state_rx) = mpsc::channel(1);
+    let mut unit_state_query_running = false;
+    let mut unit_states_available = true;
+    let (unit_action_tx, mut unit_action_rx) =
+        mpsc::channel::<(UnitCommand, String, Result<()>)>(4);
+    // CPU and memory of the nodes running without metrics, from their PID files
+    let mut process_stats_timer = interval(PROCESS_STATS_INTERVAL);
+    let (process_stats_tx, mut process_stats_rx) = mpsc::channel(1);
+    let process_sampler = ProcessSampler::start(process_stats_tx);
+    // SIGHUP reloads the config file, see `reload_config`. The settings from the file as
+    // last read tell what changed, `settings` may have been adjusted since for `--demo`
+    let mut reload_signal = reload_signal();
+    let mut loaded_settings = Settings::load(cli).unwrap_or_else(|_| settings.clone());
+    let node_path_pattern = glob::Pattern::new(&settings.path).ok();
+    let mut watcher_task = None; // Kept to report why watching stopped
+    // A snapshot (`--json-input`) or recording (`--replay`) is shown as loaded, nothing is
+    // discovered or fetched
+    let offline = app.offline_source.is_some();
+    if settings.watch_new_nodes && demo.is_none() && !offline {
+        match glob_parent(&settings.path) {
+            Some(parent) => {
+                watcher_task = Some(tokio::task::spawn_blocking(move || {
+                    watch_parent_directory(&parent, new_dir_tx)
+                }));
+            }
+            None => {
+                app.set_status(
+                    Severity::Warning,
+                    format!(
+                        "Cannot watch for new nodes: no single parent directory in {}",
+                        settings.path
+                    ),
+                );
+            }
+        }
+    }
+
+    // Focus changes are only reported when asked for, terminals without support never pause
+    if app.pause_on_focus_loss {
+        execute!(io::stdout(), EnableFocusChange)?;
+    }
 
     // Initial metrics fetch for nodes that had URLs at startup
-    if !app.node_urls.is_empty() {
-        let urls: Vec<String> = app.node_urls.values().cloned().collect();
-        let initial_results = fetch_metrics(&urls).await;
-        app.update_metrics(initial_results);
-        last_tick = Instant::now(); // Reset last_tick after initial fetch
+    if !offline {
+        start_fetch(&mut app, &fetch_tx, demo.as_mut());
     }
 
+    // Only redraw after something happened, the loop wakes up every 50ms for input.
+    // The spinner and the flashing rows animate, they redraw every time. An expired
+    // status message is cleared right away.
+    let mut dirty = true;
+    let mut last_draw = Instant::now();
     loop {
-        terminal.draw(|f| ui(f, &mut app))?;
+        app.sync_log_tail(); // Follow the selection
+        if dirty
+            || app.fetch_in_progress
+            || !app.row_flash_until.is_empty()
+            || app
+                .status_message_expiry
+                .is_some_and(|expiry| Instant::now() > expiry)
+            || last_draw.elapsed() >= IDLE_REDRAW_INTERVAL
+        {
+            terminal.draw(|f| ui(f, &mut app, Instant::now()))?;
+            last_draw = Instant::now();
+        }
+        // Cleared by the branches below that change nothing
+        dirty = true;
 
-        // Calculate time until next tick to potentially sleep or adjust poll timeout
-        let now = Instant::now();
-        let elapsed_since_last_tick = now.duration_since(last_tick);
-        let time_until_next_tick = app.tick_rate.saturating_sub(elapsed_since_last_tick);
+        // Calculate time until next tick to potentially sleep or adjust poll timeout.
+        // While a sweep is in flight the next one waits for it, don't spin on a zero timeout.
+        // Same offline or paused, where no sweep comes until something changes.
+        let time_until_next_tick = if app.fetch_in_progress || offline || app.fetch_paused() {
+            app.tick_rate
+        } else {
+            app.next_fetch_in()
+        };
 
         // Poll for events with a timeout. Use a small fixed timeout for responsiveness,
         // or the time until the next tick, whichever is smaller.
         let poll_timeout = time_until_next_tick.min(Duration::from_millis(50)); // Max 50ms wait for input
 
         tokio::select! {
-            _ = discover_timer.tick() => {
-                let log_path_buf = std::path::PathBuf::from(effective_log_path);
-                match find_metrics_nodes(log_path_buf).await {
-                    Ok(found_nodes_with_urls) => {
-                        // Found nodes are Vec<(dir_path, url)>
-                        let mut updated = false;
-                        for (dir_path, url) in found_nodes_with_urls {
-                             // Check if this directory is known and if the URL is new or changed
-                            if app.nodes.contains(&dir_path) {
-                                let current_url = app.node_urls.get(&dir_path);
-                                if current_url != Some(&url) {
-                                    // New URL or changed URL for a known directory
-                                    app.node_urls.insert(dir_path.clone(), url.clone());
-                                    // Initialize or re-initialize metrics status
-                                    app.node_metrics.insert(url.clone(), Err("Discovered - Fetching...".to_string()));
-                                    updated = true;
+            Some(event) = fetch_rx.recv() => {
+                match event {
+                    FetchEvent::Progress { done, total } => {
+                        app.fetch_done = done;
+                        app.fetch_total = total;
+                    }
+                    FetchEvent::Finished(results) => {
+                        app.update_metrics(results);
+                        app.fetch_in_progress = false;
+                        if std::mem::take(&mut app.bell_pending) {
+                            ring_bell();
+                        }
+                        if let Some(path) = &settings.status_file
+                            && status_file_written.is_none_or(|written| written.elapsed() >= STATUS_FILE_INTERVAL)
+                        {
+                            status_file_written = Some(Instant::now());
+                            match write_status_file(&app, Path::new(path)) {
+                                Ok(()) => status_file_failing = false,
+                                Err(e) if !status_file_failing => {
+                                    status_file_failing = true;
+                                    app.set_status(Severity::Error, format!("{:#}", e));
                                 }
+                                Err(_) => {}
                             }
-                            // We don't add new directories here, only update URLs for existing ones
                         }
-
-                        // Optional: Check for URLs that are no longer found and mark nodes? Maybe later.
-
-                        if updated {
-                            app.status_message = Some("Node URLs updated.".to_string());
+                        if let Some(recorder) = &recorder && !recorder.record(&app) {
+                            app.set_status(Severity::Warning, "Recording is falling behind, dropped the rows of this update");
+                        }
+                        if let Some(influx) = &influx && !influx.write(&app) {
+                            app.set_status(Severity::Warning, "InfluxDB sink is falling behind, dropped the points of this update");
+                        }
+                        if let (Some(notifier), Some(webhook)) = (&mut app.webhook, &webhook) {
+                            let mut dropped = 0;
+                            for payload in notifier.take() {
+                                if webhook.try_send(payload).is_err() {
+                                    dropped += 1;
+                                }
+                            }
+                            if dropped > 0 {
+                                app.set_status(Severity::Warning, format!("Webhook is falling behind, dropped {} notifications", dropped));
+                            }
+                        }
+                        if let (Some(runner), Some(hooks)) = (&mut app.hooks, &hooks) {
+                            let dropped = runner.take().into_iter().filter(|run| hooks.try_send(run.clone()).is_err()).count();
+                            if dropped > 0 {
+                                app.set_status(Severity::Warning, format!("Hooks are falling behind, dropped {} runs", dropped));
+                            }
+                        }
+                        // Sent in the background, a slow SMTP server doesn't hold up the UI
+                        if let Some(emailer) = &mut app.alert_emailer
+                            && let Some((subject, body)) = emailer.take_due(Instant::now())
+                        {
+                            let (smtp, to, alert_tx) = (emailer.smtp.clone(), emailer.to.clone(), alert_tx.clone());
+                            tokio::spawn(async move {
+                                let result = send_alert_email(&smtp, &to, &subject, &body).await;
+                                let _ = alert_tx.send(result.map(|()| to)).await;
+                            });
                         }
                     }
+                }
+            },
+            Some(event) = influx_rx.recv() => {
+                match event {
+                    InfluxEvent::Failing(e) => app.set_status(Severity::Warning, format!("{}, points are dropped until it answers", e)),
+                    InfluxEvent::Recovered => app.set_status(Severity::Info, "InfluxDB sink answers again"),
+                }
+            },
+            Some(e) = record_stopped_rx.recv() => {
+                app.set_status(Severity::Error, e);
+            },
+            _ = unit_state_timer.tick(), if app.systemd.is_some() && unit_states_available && !unit_state_query_running => {
+                let units = app.units_to_query();
+                if let Some(systemd) = app.systemd.clone() {
+                    unit_state_query_running = true;
+                    let unit_state_tx = unit_state_tx.clone();
+                    tokio::spawn(async move {
+                        let names: Vec<String> = units.iter().map(|(_, unit)| unit.clone()).collect();
+                        let result = systemd.unit_states(&names).await.map(|states| {
+                            units
+                                .into_iter()
+                                .filter_map(|(dir_path, unit)| Some((dir_path, states.get(&unit)?.clone())))
+                                .collect()
+                        });
+                        let _ = unit_state_tx.send(result).await;
+                    });
+                }
+                dirty = false;
+            },
+            Some(result) = unit_state_rx.recv() => {
+                unit_state_query_running = false;
+                match result {
+                    Ok(states) => app.unit_states = states,
                     Err(e) => {
-                        app.status_message = Some(format!("Error re-discovering node URLs: {}", e));
+                        unit_states_available = false;
+                        app.set_status(Severity::Warning, format!("No systemd unit states: {:#}", e));
                     }
                 }
             },
+            Some((command, unit, result)) = unit_action_rx.recv() => {
+                match result {
+                    Ok(()) => app.set_status(Severity::Info, format!("{} {}", command.done(), unit)),
+                    Err(e) => app.set_status(Severity::Error, format!("{:#}", e)),
+                }
+                // Show the new state without waiting for the next query
+                unit_state_timer.reset_immediately();
+            },
+            _ = process_stats_timer.tick(), if demo.is_none() && !offline => {
+                let unanswered = app.unanswered_nodes();
+                if !unanswered.is_empty() {
+                    process_sampler.sample(unanswered);
+                }
+                dirty = false;
+            },
+            Some(stats) = process_stats_rx.recv() => {
+                app.node_processes = stats;
+            },
+            Some(()) = async { reload_requested(reload_signal.as_mut()?).await }, if reload_signal.is_some() => {
+                reload_config(&mut app, cli, &mut loaded_settings);
+            },
+            Some(e) = webhook_failure_rx.recv() => {
+                app.set_status(Severity::Error, e);
+            },
+            Some(outcome) = hook_rx.recv() => {
+                app.record_hook(outcome);
+            },
+            Some(result) = snapshot_rx.recv() => {
+                match result {
+                    Ok(path) => app.set_status(Severity::Info, format!("Wrote snapshot to {} (and .json)", path)),
+                    Err(e) => app.set_status(Severity::Error, format!("{:#}", e)),
+                }
+            },
+            Some(result) = alert_rx.recv() => {
+                match result {
+                    Ok(to) => app.set_status(Severity::Info, format!("Alert email sent to {}", to)),
+                    Err(e) => app.set_status(Severity::Error, format!("{:#}", e)),
+                }
+            },
+            _ = save_state_timer.tick() => {
+                if let Err(e) = app.save_state() {
+                    app.set_status(Severity::Error, format!("Error saving state: {}", e));
+                }
+            },
+            _ = discover_timer.tick(), if demo.is_none() && !offline => {
+                discover_node_urls(&mut app, &effective_log_path, settings).await;
+            },
+            Some(result) = async { Some(watcher_task.as_mut()?.await) }, if watcher_task.is_some() => {
+                watcher_task = None;
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => app.set_status(Severity::Error, format!("Stopped watching for new nodes: {:#}", e)),
+                    Err(e) => app.set_status(Severity::Error, format!("Directory watcher failed: {}", e)),
+                }
+            },
+            Some(dir_path) = new_dir_rx.recv() => {
+                // Only directories the node path glob would have matched
+                if node_path_pattern.as_ref().is_some_and(|pattern| pattern.matches(&dir_path))
+                    && app.add_node(dir_path.clone())
+                {
+                    app.set_status(Severity::Info, format!("New node directory: {}", dir_path));
+                    // Scan the logs right away instead of waiting for the discovery timer
+                    discover_node_urls(&mut app, &effective_log_path, settings).await;
+                }
+            },
             // Poll for keyboard/mouse events
             result = tokio::task::spawn_blocking(move || event::poll(poll_timeout)) => { // Use calculated poll_timeout
                 match result {
@@ -120,54 +457,243 @@ pub async fn run_app<B: Backend>(
                         if let Ok(event) = event::read() {
                             match event {
                                 Event::Key(key) => {
-                                    match key.code {
-                                        KeyCode::Char('q') => return Ok(()), // Exit app
-                                        KeyCode::Up => {
-                                            app.scroll_offset = app.scroll_offset.saturating_sub(1);
+                                    // Info messages stay until the next key press at most
+                                    app.dismiss_status_message();
+                                    // The goto prompt takes text, popups capture input until they are closed
+                                    if app.ui_mode == UiMode::Goto {
+                                        handle_goto_key(&mut app, key.code);
+                                    } else if app.ui_mode == UiMode::Confirm {
+                                        // Anything but a yes cancels
+                                        app.ui_mode = UiMode::Normal;
+                                        let pending = app.pending_unit_action.take();
+                                        match (key.code, pending, app.systemd.clone()) {
+                                            (KeyCode::Char('y') | KeyCode::Enter, Some((command, dir_path)), Some(systemd)) => {
+                                                let unit = systemd.unit_name(&dir_path);
+                                                app.set_status(Severity::Info, format!("{} {}...", command.doing(), unit));
+                                                let unit_action_tx = unit_action_tx.clone();
+                                                tokio::spawn(async move {
+                                                    let result = systemd.run_command(command, &unit).await;
+                                                    let _ = unit_action_tx.send((command, unit, result)).await;
+                                                });
+                                            }
+                                            (_, Some((command, _)), _) => {
+                                                app.set_status(Severity::Info, format!("Did not {} the unit", command.verb()));
+                                            }
+                                            _ => {}
                                         }
-                                        KeyCode::Down => {
-                                            let num_nodes = app.nodes.len();
-                                            if num_nodes > 0 {
-                                                let max_offset = num_nodes.saturating_sub(1);
-                                                 app.scroll_offset = (app.scroll_offset + 1).min(max_offset);
+                                    } else if app.ui_mode == UiMode::Actions {
+                                        match key.code {
+                                            KeyCode::Char('q') => break, // Exit app
+                                            KeyCode::Esc | KeyCode::Char(' ') => app.ui_mode = UiMode::Normal,
+                                            KeyCode::Up => {
+                                                app.action_menu_selected = app.action_menu_selected.saturating_sub(1);
+                                            }
+                                            KeyCode::Down => {
+                                                let last = app.available_node_actions().len().saturating_sub(1);
+                                                app.action_menu_selected = (app.action_menu_selected + 1).min(last);
+                                            }
+                                            KeyCode::Enter => {
+                                                app.ui_mode = UiMode::Normal;
+                                                if let Some(&action) = app.available_node_actions().get(app.action_menu_selected) {
+                                                    run_node_action(action, terminal, alt_screen, &mut app, &mut clipboard).await;
+                                                }
+                                            }
+                                            code => {
+                                                // An action's own key works in the menu too
+                                                if let Some(action) = node_action_for_key(code) {
+                                                    app.ui_mode = UiMode::Normal;
+                                                    run_node_action(action, terminal, alt_screen, &mut app, &mut clipboard).await;
+                                                }
                                             }
                                         }
-                                        KeyCode::Char('+') | KeyCode::Char('=') => { // Also handle '=' which is often shift+'+'
-                                            app.adjust_tick_rate(true); // Increase interval (slower)
-                                            // No need to reset timer, logic below handles it
+                                    } else if app.ui_mode == UiMode::RawMetrics {
+                                        match key.code {
+                                            KeyCode::Char('q') => break, // Exit app
+                                            // Back to the detail popup it was opened from
+                                            KeyCode::Esc | KeyCode::Char('r') => app.ui_mode = UiMode::Detail,
+                                            KeyCode::Enter | KeyCode::Char('x') => app.ui_mode = UiMode::Normal,
+                                            KeyCode::Up => app.scroll_raw_metrics(-1, 0),
+                                            KeyCode::Down => app.scroll_raw_metrics(1, 0),
+                                            KeyCode::PageUp => app.scroll_raw_metrics(-RAW_METRICS_PAGE_STEP, 0),
+                                            KeyCode::PageDown => app.scroll_raw_metrics(RAW_METRICS_PAGE_STEP, 0),
+                                            KeyCode::Left => app.scroll_raw_metrics(0, -RAW_METRICS_COLUMN_STEP),
+                                            KeyCode::Right => app.scroll_raw_metrics(0, RAW_METRICS_COLUMN_STEP),
+                                            KeyCode::Home => app.raw_metrics_scroll = (0, 0),
+                                            KeyCode::End => app.scroll_raw_metrics(i32::MAX, 0),
+                                            _ => {}
                                         }
-                                         KeyCode::Char('-') => {
-                                            app.adjust_tick_rate(false); // Decrease interval (faster)
-                                            // No need to reset timer, logic below handles it
+                                    } else if app.ui_mode != UiMode::Normal {
+                                        match key.code {
+                                            KeyCode::Char('q') => break, // Exit app
+                                            KeyCode::Char('r') if app.ui_mode == UiMode::Detail => {
+                                                app.raw_metrics_scroll = (0, 0);
+                                                app.ui_mode = UiMode::RawMetrics;
+                                            }
+                                            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('?') | KeyCode::Char('x') | KeyCode::Char('M') => {
+                                                app.ui_mode = UiMode::Normal;
+                                            }
+                                            _ => {} // Ignore other keys while a popup is open
+                                        }
+                                    } else {
+                                        match key.code {
+                                            KeyCode::Char('q') => break, // Exit app
+                                            KeyCode::Up => {
+                                                app.move_selection(-1);
+                                            }
+                                            KeyCode::Down => {
+                                                app.move_selection(1);
+                                            }
+                                            // Back and forth in the recording of `--replay`
+                                            KeyCode::Left | KeyCode::Right if app.replay.is_some() => {
+                                                let delta = if key.code == KeyCode::Left { -1 } else { 1 };
+                                                if let Err(e) = app.step_replay(delta) {
+                                                    app.set_status(Severity::Error, format!("Failed to read the recording: {:#}", e));
+                                                }
+                                            }
+                                            KeyCode::Char(' ') if app.selected_node().is_some() => {
+                                                app.action_menu_selected = 0;
+                                                app.ui_mode = UiMode::Actions; // Open the action menu
+                                            }
+                                            KeyCode::Char('+') | KeyCode::Char('=') => { // Also handle '=' which is often shift+'+'
+                                                app.adjust_tick_rate(true); // Increase interval (slower)
+                                                // No need to reset timer, logic below handles it
+                                            }
+                                             KeyCode::Char('-') => {
+                                                app.adjust_tick_rate(false); // Decrease interval (faster)
+                                                // No need to reset timer, logic below handles it
+                                            }
+                                            KeyCode::Char(digit @ '1'..='9') => {
+                                                if let Some((_, tick_rate)) = TICK_PRESETS.iter().find(|(key, _)| *key == digit) {
+                                                    app.set_tick_rate(*tick_rate);
+                                                    app.set_status(Severity::Info, format!("Update interval: {}", format_duration_human(app.tick_rate)));
+                                                }
+                                            }
+                                            KeyCode::Char('*') => {
+                                                app.toggle_mark(); // Mark / unmark, then next row
+                                            }
+                                            KeyCode::Char('p') => {
+                                                app.toggle_pin(); // Pin to / unpin from the top
+                                            }
+                                            KeyCode::Char('A') => {
+                                                app.mark_all_visible();
+                                            }
+                                            KeyCode::Char('N') => {
+                                                app.unmark_all_visible();
+                                            }
+                                            KeyCode::Esc if !app.marked_nodes.is_empty() => {
+                                                app.marked_nodes.clear();
+                                            }
+                                            KeyCode::Char('M') => {
+                                                app.ui_mode = UiMode::Messages; // Status message history
+                                            }
+                                            KeyCode::Char(':') => {
+                                                app.goto_input.clear();
+                                                app.ui_mode = UiMode::Goto; // Open the goto prompt
+                                            }
+                                            KeyCode::Char('[') => {
+                                                app.adjust_fetch_concurrency(false); // Fewer nodes fetched at once
+                                            }
+                                            KeyCode::Char(']') => {
+                                                app.adjust_fetch_concurrency(true); // More nodes fetched at once
+                                            }
+                                            KeyCode::Char('v') => {
+                                                app.view_mode = app.view_mode.toggle(); // Compact <-> Detailed
+                                            }
+                                            KeyCode::Char('f') => {
+                                                app.show_fleet_panel = !app.show_fleet_panel; // Peers histogram and growth charts
+                                            }
+                                            KeyCode::Char('a') => {
+                                                app.show_timeline = !app.show_timeline; // Availability column
+                                            }
+                                            KeyCode::Char('b') => {
+                                                app.show_traffic_bars = !app.show_traffic_bars; // Rx + Tx speed bars
+                                            }
+                                            KeyCode::Char('t') => {
+                                                app.totals_mode = app.totals_mode.toggle(); // Lifetime <-> Session
+                                            }
+                                            KeyCode::Char('?') => {
+                                                app.ui_mode = UiMode::Help; // Open the help overlay
+                                            }
+                                            KeyCode::Char('w') => {
+                                                write_warning_report(&mut app).await;
+                                            }
+                                            KeyCode::Char('W') => {
+                                                write_snapshot(&app, settings.snapshot_dir.as_deref(), snapshot_tx.clone());
+                                            }
+                                            KeyCode::Char('e') => {
+                                                write_history_csv(&mut app, settings.snapshot_dir.as_deref()).await;
+                                            }
+                                            KeyCode::Char('L') => {
+                                                app.toggle_log_tail(); // Inline tail of antnode.log
+                                            }
+                                            KeyCode::PageUp => {
+                                                if let Some(tail) = &mut app.log_tail {
+                                                    tail.scroll += LOG_SCROLL_STEP;
+                                                }
+                                            }
+                                            KeyCode::PageDown => {
+                                                if let Some(tail) = &mut app.log_tail {
+                                                    tail.scroll = tail.scroll.saturating_sub(LOG_SCROLL_STEP);
+                                                }
+                                            }
+                                            KeyCode::Char('n') => {
+                                                app.cycle_list_view_filter(); // All -> Running -> ... -> Shunned
+                                            }
+                                            KeyCode::Char('s') => {
+                                                app.cycle_sort_key(); // Name -> Status -> CPU -> ... -> Last update
+                                            }
+                                            KeyCode::Char('P') => {
+                                                app.show_full_paths = !app.show_full_paths; // Name <-> directory path
+                                            }
+                                            KeyCode::Char('#') => {
+                                                app.notation = app.notation.toggle(); // Grouped <-> Compact
+                                            }
+                                            KeyCode::Char('u') => {
+                                                app.size_units = app.size_units.toggle(); // GB <-> GiB
+                                            }
+                                            KeyCode::Char('m') => {
+                                                app.chart_marker = app.chart_marker.next(); // Braille -> Dot -> Block
+                                            }
+                                            KeyCode::Char('c') => {
+                                                app.swap_rx_tx_colors = !app.swap_rx_tx_colors;
+                                            }
+                                            KeyCode::Char('z') => {
+                                                app.zero_errors(); // Display only, metrics keep their counts
+                                            }
+                                            code => {
+                                                // Actions on the selected node, also in the action menu
+                                                if let Some(action) = node_action_for_key(code) {
+                                                    run_node_action(action, terminal, alt_screen, &mut app, &mut clipboard).await;
+                                                }
+                                            }
                                         }
-                                        _ => {} // Ignore other keys
                                     }
                                 }
                                 Event::Mouse(MouseEvent { kind, .. }) => {
                                     match kind {
                                         MouseEventKind::ScrollUp => {
-                                            app.scroll_offset = app.scroll_offset.saturating_sub(1);
+                                            app.move_selection(-1);
                                         }
                                         MouseEventKind::ScrollDown => {
-                                            let num_nodes = app.nodes.len();
-                                            if num_nodes > 0 {
-                                                let max_offset = num_nodes.saturating_sub(1);
-                                                 app.scroll_offset = (app.scroll_offset + 1).min(max_offset);
-                                            }
+                                            app.move_selection(1);
                                         }
                                         _ => {} // Ignore other mouse events like move, click
                                     }
                                 }
+                                // Only reported with `--pause-on-focus-loss`, the next sweep
+                                // starts right away on focus if it came due meanwhile
+                                Event::FocusLost => app.unfocused = true,
+                                Event::FocusGained => app.unfocused = false,
                                 _ => {} // Ignore other event types
                             }
                         }
                     }
-                    Ok(Ok(false)) => {} // Timeout elapsed without event
+                    Ok(Ok(false)) => dirty = false, // Timeout elapsed without event
                     Ok(Err(e)) => {
-                        app.status_message = Some(format!("Input polling error: {}", e));
+                        app.set_status(Severity::Error, format!("Input polling error: {}", e));
                     }
                     Err(e) => {
-                         app.status_message = Some(format!("Input task spawn error: {}", e));
+                         app.set_status(Severity::Error, format!("Input task spawn error: {}", e));
                     }
                 }
             },
@@ -175,163 +701,914 @@ pub async fn run_app<B: Backend>(
             _ = tokio::time::sleep(poll_timeout), if !poll_timeout.is_zero() => {
                 // This branch ensures the loop doesn't spin wildly if poll_timeout is very small
                 // but it's not yet time for the next tick.
+                dirty = false;
             }
         }
 
-        // Check if it's time for the next tick AFTER handling events/sleep
-        if Instant::now().duration_since(last_tick) >= app.tick_rate {
-            // Fetch metrics only for nodes with known URLs
-            if !app.node_urls.is_empty() {
-                let urls: Vec<String> = app.node_urls.values().cloned().collect();
-                let results = fetch_metrics(&urls).await;
-                app.update_metrics(results);
+        // Check if it's time for the next tick AFTER handling events/sleep.
+        // A slow sweep delays the next one rather than piling up concurrent sweeps.
+        if !offline
+            && !app.fetch_in_progress
+            && !app.fetch_paused()
+            && app.next_fetch_in().is_zero()
+        {
+            start_fetch(&mut app, &fetch_tx, demo.as_mut());
+            if let Some(tail) = &mut app.log_tail {
+                tail.poll();
+            }
+            dirty = true;
+        }
+    }
+
+    // Keep the history for the next run
+    app.save_state().context("Failed to save state")
+}
+
+/// Writes `App::generate_warning_report` to a timestamped file in the current directory.
+async fn write_warning_report(app: &mut App) {
+    let path = format!(
+        "antop-warnings-{}.txt",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    );
+    match tokio::fs::write(&path, app.generate_warning_report()).await {
+        Ok(()) => app.set_status(Severity::Info, format!("Wrote warning report to {}", path)),
+        Err(e) => app.set_status(
+            Severity::Error,
+            format!("Failed to write warning report {}: {}", path, e),
+        ),
+    }
+}
+
+/// Writes the histories of the selected node, `history_csv`, to a timestamped CSV file
+/// in `dir` (the current directory by default).
+async fn write_history_csv(app: &mut App, dir: Option<&str>) {
+    let Some(dir_path) = app.selected_node().cloned() else {
+        return;
+    };
+    let name = app.node_name(&dir_path);
+    let Some(csv) = app
+        .node_urls
+        .get(&dir_path)
+        .and_then(|url| history_csv(app, url))
+    else {
+        app.set_status(Severity::Warning, format!("No history for {} yet", name));
+        return;
+    };
+    // The name may come from `--name-regex`, keep it to characters safe in a file name
+    let file_name: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' => c,
+            _ => '_',
+        })
+        .collect();
+    let path = Path::new(dir.unwrap_or(".")).join(format!(
+        "antop-history-{}-{}.csv",
+        file_name,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    match tokio::fs::write(&path, csv).await {
+        Ok(()) => app.set_status(
+            Severity::Info,
+            format!("Wrote history of {} to {}", name, path.display()),
+        ),
+        Err(e) => app.set_status(
+            Severity::Error,
+            format!("Failed to write history {}: {}", path.display(), e),
+        ),
+    }
+}
+
+/// Writes the node table, all nodes in the current sort order, to a timestamped
+/// `.txt` and `.json` pair in `dir` (the current directory by default). The files are
+/// written on a blocking task, `done` gets the path of the text file or the error.
+fn write_snapshot(app: &App, dir: Option<&str>, done: mpsc::Sender<Result<String>>) {
+    let snapshot = fleet_snapshot(app);
+    let table = snapshot_table(app, &snapshot);
+    let stem = Path::new(dir.unwrap_or(".")).join(format!(
+        "antop-snapshot-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    tokio::spawn(async move {
+        let result =
+            tokio::task::spawn_blocking(move || write_table_snapshot(&stem, &table, &snapshot))
+                .await
+                .context("Snapshot task failed")
+                .and_then(|result| result);
+        let _ = done.send(result).await;
+    });
+}
+
+/// Writes the BEL character, most terminals then flag their tab or window.
+fn ring_bell() {
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+}
+
+/// Edits the goto prompt. Enter jumps when the input matches a single node and
+/// keeps the prompt open otherwise, Esc cancels.
+fn handle_goto_key(app: &mut App, key: KeyCode) {
+    match key {
+        KeyCode::Esc => app.ui_mode = UiMode::Normal,
+        KeyCode::Enter => {
+            app.ui_mode = if app.goto_selected_input() {
+                UiMode::Normal
+            } else {
+                UiMode::Goto // Ambiguous or no match, keep typing
+            };
+        }
+        KeyCode::Backspace => {
+            app.goto_input.pop();
+        }
+        KeyCode::Char(c) => app.goto_input.push(c),
+        _ => {}
+    }
+}
+
+/// Scans the log files for metrics URLs and updates the known nodes whose URL is new
+/// or changed. New directories are not added here, see `--watch-new-nodes`. URLs
+/// outside `settings.network_interface` are ignored.
+async fn discover_node_urls(app: &mut App, effective_log_path: &str, settings: &Settings) {
+    let log_path_buf = PathBuf::from(effective_log_path);
+    let network_interface = settings.network_interface.as_deref();
+    match find_metrics_nodes(
+        log_path_buf,
+        settings.discovery_log_glob_override.as_deref(),
+        &app.nodes,
+        settings.log_match_strategy,
+        settings.log_match_nth,
+        settings.max_log_file_size,
+    )
+    .await
+    {
+        Ok(Discovery {
+            nodes: found_nodes_with_urls,
+            oversized_logs: oversized,
+        }) => {
+            // Warned about once, until the file is small enough again (rotated)
+            app.oversized_logs
+                .retain(|path| oversized.iter().any(|log| &log.path == path));
+            for log in oversized {
+                if !app.oversized_logs.contains(&log.path) {
+                    app.set_status(
+                        Severity::Warning,
+                        format!(
+                            "Skipped log file {} ({}), larger than --max-log-file-size",
+                            log.path.display(),
+                            format_option_u64_bytes(Some(log.size), app.size_units)
+                        ),
+                    );
+                    app.oversized_logs.push(log.path);
+                }
+            }
+            // Found nodes are Vec<DiscoveredNode>
+            let mut updated = false;
+            for node in found_nodes_with_urls {
+                if network_interface.is_some_and(|filter| !matches_interface(&node.url, filter)) {
+                    continue;
+                }
+                // Overridden URLs stay, whatever the logs say. They went through the
+                // interface filter at startup, nodes bound elsewhere were left out then
+                if matches!(
+                    app.node_discovery_sources.get(&node.dir_path),
+                    Some(DiscoverySource::Override(_))
+                ) {
+                    continue;
+                }
+                // Check if this directory is known and if the URL is new or changed
+                if app.nodes.contains(&node.dir_path) {
+                    let current_url = app.node_urls.get(&node.dir_path);
+                    if current_url != Some(&node.url) {
+                        // New URL or changed URL for a known directory
+                        app.node_urls
+                            .insert(node.dir_path.clone(), node.url.clone());
+                        // Initialize or re-initialize metrics status
+                        app.node_metrics.insert(
+                            node.url.clone(),
+                            Err("Discovered - Fetching...".to_string()),
+                        );
+                        updated = true;
+                    }
+                    app.node_discovery_sources
+                        .insert(node.dir_path, node.source);
+                }
+            }
+
+            // Optional: Check for URLs that are no longer found and mark nodes? Maybe later.
+
+            if updated {
+                app.refresh_node_order();
+                app.set_status(Severity::Info, "Node URLs updated.");
+            }
+        }
+        Err(e) => {
+            app.set_status(
+                Severity::Error,
+                format!("Error re-discovering node URLs: {}", e),
+            );
+        }
+    }
+}
+
+/// Copies `value` (the `what` of the selected node) and reports the outcome in the
+/// status bar. Without a usable clipboard the value is shown there instead.
+fn copy_to_clipboard(app: &mut App, clipboard: &mut Clipboard, what: &str, value: Option<String>) {
+    let Some(value) = value else {
+        app.set_status(
+            Severity::Warning,
+            format!("No {} for the selected node", what),
+        );
+        return;
+    };
+    match clipboard.copy(&value) {
+        Ok(CopyMethod::System) => app.set_status(
+            Severity::Info,
+            format!("Copied {} to the clipboard: {}", what, value),
+        ),
+        Ok(CopyMethod::Osc52) => app.set_status(
+            Severity::Info,
+            format!("Sent {} to the terminal clipboard: {}", what, value),
+        ),
+        Err(e) => app.set_status(
+            Severity::Warning,
+            format!("{}: {} (clipboard unavailable: {})", what, value, e),
+        ),
+    }
+}
+
+// SIGHUPs, sent to reload the config file. None where they can't be received
+#[cfg(unix)]
+type ReloadSignal = tokio::signal::unix::Signal;
+#[cfg(not(unix))]
+type ReloadSignal = ();
+
+#[cfg(unix)]
+fn reload_signal() -> Option<ReloadSignal> {
+    use tokio::signal::unix::{SignalKind, signal};
+    signal(SignalKind::hangup()).ok()
+}
+
+#[cfg(not(unix))]
+fn reload_signal() -> Option<ReloadSignal> {
+    None
+}
+
+#[cfg(unix)]
+async fn reload_requested(signal: &mut ReloadSignal) -> Option<()> {
+    signal.recv().await
+}
+
+#[cfg(not(unix))]
+async fn reload_requested(_signal: &mut ReloadSignal) -> Option<()> {
+    None
+}
+
+/// Reads the config file again, merged with the same flags, and applies the settings
+/// that changed since `loaded` was read to the live dashboard. Those that only take
+/// effect at startup are named in the status bar. A broken config changes nothing.
+fn reload_config(app: &mut App, cli: &Cli, loaded: &mut Settings) {
+    let changed = Settings::load(cli).and_then(|settings| {
+        let changed = loaded.changed_keys(&settings)?;
+        Ok((settings, changed))
+    });
+    let (settings, changed) = match changed {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            app.set_status(
+                Severity::Error,
+                format!("Config not reloaded, keeping the current settings: {:#}", e),
+            );
+            return;
+        }
+    };
+    let not_applied = app.reload_settings(&settings, &changed);
+    let applied: Vec<&str> = changed
+        .iter()
+        .filter(|key| !not_applied.contains(key))
+        .map(String::as_str)
+        .collect();
+    *loaded = settings;
+    match (applied.is_empty(), not_applied.is_empty()) {
+        (true, true) => app.set_status(Severity::Info, "Config reloaded, nothing changed"),
+        (false, true) => app.set_status(
+            Severity::Info,
+            format!("Config reloaded: applied {}", applied.join(", ")),
+        ),
+        (true, false) => app.set_status(
+            Severity::Warning,
+            format!(
+                "Config reloaded, restart to apply {}",
+                not_applied.join(", ")
+            ),
+        ),
+        (false, false) => app.set_status(
+            Severity::Warning,
+            format!(
+                "Config reloaded: applied {}; restart to apply {}",
+                applied.join(", "),
+                not_applied.join(", ")
+            ),
+        ),
+    }
+}
+
+/// Key of each node action, listed in the help overlay and the action menu.
+fn node_action_key(action: NodeAction) -> KeyCode {
+    match action {
+        NodeAction::ShowDetails => KeyCode::Enter,
+        NodeAction::ShowErrors => KeyCode::Char('x'),
+        NodeAction::OpenLog => KeyCode::Char('l'),
+        NodeAction::OpenMetrics => KeyCode::Char('o'),
+        NodeAction::CopyUrl => KeyCode::Char('y'),
+        NodeAction::CopyPath => KeyCode::Char('Y'),
+        NodeAction::StartUnit => KeyCode::Char('S'),
+        NodeAction::StopUnit => KeyCode::Char('T'),
+        NodeAction::RestartUnit => KeyCode::Char('R'),
+    }
+}
+
+/// Name of the key of `action`, as shown to the user.
+pub fn node_action_key_label(action: NodeAction) -> String {
+    match node_action_key(action) {
+        KeyCode::Char(c) => c.to_string(),
+        code => format!("{:?}", code),
+    }
+}
+
+// The node action bound to `code`, if any
+fn node_action_for_key(code: KeyCode) -> Option<NodeAction> {
+    NodeAction::ALL
+        .into_iter()
+        .find(|&action| node_action_key(action) == code)
+}
+
+/// Runs `action` on the selected node, from its key or the action menu. Problems,
+/// like a missing log file, are reported in the status bar.
+async fn run_node_action<B: Backend>(
+    action: NodeAction,
+    terminal: &mut Terminal<B>,
+    alt_screen: bool,
+    app: &mut App,
+    clipboard: &mut Clipboard,
+) {
+    let Some(dir_path) = app.selected_node().cloned() else {
+        return;
+    };
+    match action {
+        NodeAction::ShowDetails => app.ui_mode = UiMode::Detail,
+        NodeAction::ShowErrors => app.ui_mode = UiMode::Errors,
+        NodeAction::OpenLog => {
+            let log_file = node_log_file(&dir_path);
+            if !log_file.is_file() {
+                app.set_status(
+                    Severity::Warning,
+                    format!("Log file not found: {}", log_file.display()),
+                );
+                return;
+            }
+            // Starting at the end, where the latest lines are
+            open_in_pager(terminal, alt_screen, app, &log_file, "less +G");
+        }
+        NodeAction::OpenMetrics => open_raw_metrics(terminal, alt_screen, app, &dir_path).await,
+        NodeAction::CopyUrl => {
+            let url = app.node_urls.get(&dir_path).cloned();
+            copy_to_clipboard(app, clipboard, "metrics URL", url);
+        }
+        NodeAction::CopyPath => copy_to_clipboard(app, clipboard, "directory path", Some(dir_path)),
+        NodeAction::StartUnit | NodeAction::StopUnit | NodeAction::RestartUnit => {
+            if app.systemd.is_none() {
+                app.set_status(
+                    Severity::Warning,
+                    "No systemd units, start antop with --systemd",
+                );
+                return;
             }
-            last_tick = Instant::now(); // Update last tick time
+            // Run once confirmed, see `UiMode::Confirm`
+            app.pending_unit_action = action.unit_command().map(|command| (command, dir_path));
+            app.ui_mode = UiMode::Confirm;
         }
     }
 }
 
+/// Fetches the metrics of the node in `dir_path` as served and opens them in `$PAGER`,
+/// through a file in the temporary directory.
+async fn open_raw_metrics<B: Backend>(
+    terminal: &mut Terminal<B>,
+    alt_screen: bool,
+    app: &mut App,
+    dir_path: &str,
+) {
+    if !app
+        .available_node_actions()
+        .contains(&NodeAction::OpenMetrics)
+    {
+        app.set_status(
+            Severity::Warning,
+            "No metrics server to fetch for the selected node",
+        );
+        return;
+    }
+    let Some(url) = app.node_urls.get(dir_path).cloned() else {
+        return;
+    };
+    let metrics = match fetch_raw_metrics(url.clone(), app.max_redirects).await {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            app.set_status(
+                Severity::Error,
+                format!("Could not fetch {}/metrics: {}", url, e),
+            );
+            return;
+        }
+    };
+    let node_name = Path::new(dir_path)
+        .file_name()
+        .map_or_else(|| "node".into(), |name| name.to_string_lossy());
+    // A new file with a random name, not one another user could have planted a link
+    // at. It is removed once the pager exits
+    let file = tempfile::Builder::new()
+        .prefix(&format!("antop-metrics-{}-", node_name))
+        .suffix(".txt")
+        .tempfile()
+        .and_then(|mut file| file.write_all(metrics.as_bytes()).map(|()| file));
+    let file = match file {
+        Ok(file) => file,
+        Err(e) => {
+            app.set_status(
+                Severity::Error,
+                format!("Could not write the metrics to a temporary file: {}", e),
+            );
+            return;
+        }
+    };
+    open_in_pager(terminal, alt_screen, app, file.path(), "less");
+}
+
+/// Opens `file` in `$PAGER`, or `default_pager` when it is not set, suspending the
+/// TUI until it exits. Problems are reported in the status bar.
+fn open_in_pager<B: Backend>(
+    terminal: &mut Terminal<B>,
+    alt_screen: bool,
+    app: &mut App,
+    file: &Path,
+    default_pager: &str,
+) {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| default_pager.to_string());
+    let mut pager_parts = pager.split_whitespace();
+    let Some(program) = pager_parts.next() else {
+        return;
+    };
+    let mut command = Command::new(program);
+    command.args(pager_parts).arg(file);
+
+    let result = run_external_command(terminal, alt_screen, &mut command);
+    if app.pause_on_focus_loss {
+        let _ = execute!(io::stdout(), EnableFocusChange); // Turned off while suspended
+    }
+    match result {
+        Ok(status) if !status.success() => {
+            app.set_status(
+                Severity::Warning,
+                format!("Pager '{}' exited with {}", pager, status),
+            );
+        }
+        Ok(_) => {}
+        Err(e) => app.set_status(
+            Severity::Error,
+            format!("Could not open {}: {:#}", file.display(), e),
+        ),
+    }
+}
+
+/// Spawns a metrics sweep over the nodes with known URLs. Results come back on the
+/// channel of `events` as `FetchEvent`s. With `--demo` the fake fleet is sampled instead.
+fn start_fetch(app: &mut App, events: &UnboundedSender<FetchEvent>, demo: Option<&mut DemoFleet>) {
+    let urls: Vec<String> = app.node_urls.values().cloned().collect();
+    if urls.is_empty() {
+        // Nothing to fetch, still restart the countdown
+        app.last_fetch_started = Instant::now();
+        return;
+    }
+    app.start_fetch(urls.len());
+    if let Some(demo) = demo {
+        // Generated on the spot, still delivered like a finished sweep
+        let _ = events.send(FetchEvent::Finished(demo.sample()));
+        return;
+    }
+    tokio::spawn(fetch_metrics_in_background(
+        urls,
+        app.max_concurrent_fetches,
+        app.max_redirects,
+        events.clone(),
+    ));
+}
+
 // --- UI Rendering ---
 
-// This function is now internal to the ui module, called by run_app
-fn ui(f: &mut Frame, app: &mut App) {
+/// Draws a whole frame from the app state alone, no terminal or network involved, so
+/// it can also draw into a ratatui `TestBackend`. `now` is the time the status bar
+/// counts from, pass a fixed one for reproducible frames.
+pub(crate) fn ui(f: &mut Frame, app: &mut App, now: Instant) {
+    // The fleet panel and the log tail pane, when open, take their rows from the node table
+    let fleet_panel_height = if app.show_fleet_panel {
+        widgets::FLEET_PANEL_HEIGHT
+    } else {
+        0
+    };
+    let log_pane_height = if app.log_tail.is_some() {
+        app.log_pane_height
+    } else {
+        0
+    };
+    // The selection footer only shows while nodes are marked
+    let marked_footer_height = if app.marked_nodes.is_empty() { 0 } else { 1 };
+    let group_pane_height = widgets::group_pane_height(app);
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints(
             [
                 Constraint::Length(2), // Top Title area (might need adjustment if content wraps)
-                Constraint::Length(2), // Summary Gauges
+                Constraint::Length(3), // Summary Gauges
+                Constraint::Length(fleet_panel_height), // Fleet panel
+                Constraint::Length(group_pane_height), // Group summaries
                 Constraint::Min(0),    // Node Table
+                Constraint::Length(marked_footer_height), // Sums of the marked nodes
+                Constraint::Length(log_pane_height), // Log tail pane
                 Constraint::Length(1), // Bottom Status / Error
             ]
             .as_ref(),
         )
         .split(f.size());
 
-    // --- Calculate Running Node Count ---
-    let mut running_nodes_count = 0;
-    for node_path in &app.nodes {
-        if let Some(url) = app.node_urls.get(node_path) {
-            if let Some(Ok(_)) = app.node_metrics.get(url) {
-                running_nodes_count += 1;
-            }
-        }
-    }
-    let total_nodes_count = app.nodes.len();
+    // --- Node Status Breakdown (once per frame) ---
+    let status_counts = app.status_counts();
 
     // --- Top Bar (Title + Node Count) ---
     let top_area = main_chunks[0];
+    let tags = vec![
+        Span::styled(
+            format!("[View: {}]", app.list_view_filter.label()),
+            Style::default().fg(Color::Rgb(255, 165, 0)),
+        ),
+        Span::styled(
+            if app.sort_key == SortKey::Name {
+                String::new()
+            } else {
+                format!(" [Sort: {}]", app.sort_key.label())
+            },
+            Style::default().fg(Color::Rgb(255, 165, 0)),
+        ),
+        Span::styled(
+            match (&app.history_playback, &app.replay, &app.offline_source) {
+                (Some(playback), _, _) => format!(
+                    " [HISTORY - {} {}/{}]",
+                    playback.file, playback.step, playback.steps
+                ),
+                (None, Some(replay), _) => format!(
+                    " [REPLAY - {} {} ({}/{})]",
+                    replay.file,
+                    replay
+                        .timestamp()
+                        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_default(),
+                    replay.position + 1,
+                    replay.timestamps.len()
+                ),
+                (None, None, Some(file)) => format!(" [OFFLINE - {}]", file),
+                (None, None, None) => String::new(),
+            },
+            Style::default().fg(Color::Red),
+        ),
+        // Reminder that the fleet status is being served, see `--serve`
+        Span::styled(
+            app.status_server
+                .as_ref()
+                .map(|server| format!(" [API :{}]", server.addr.port()))
+                .unwrap_or_default(),
+            Style::default().fg(Color::Cyan),
+        ),
+    ];
+    // On a narrow terminal the title gives way first, cut with an ellipsis, the tags
+    // and the symbols only breakdown keep their room
+    let tags_width: usize = tags.iter().map(Span::width).sum();
+    let compact_breakdown_width = widgets::status_breakdown_line(
+        status_counts,
+        app.summary_stale_count,
+        false,
+        &app.color_config,
+    )
+    .width();
+    let title_room =
+        (top_area.width as usize).saturating_sub(tags_width + compact_breakdown_width + 2);
+    let title_text = truncate_to_width(&app.title, title_room);
+    let mut title_spans = Vec::new();
+    if !title_text.is_empty() {
+        title_spans.push(Span::styled(
+            format!("{} ", title_text),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    title_spans.extend(tags);
+    let title_line = Line::from(title_spans);
+
+    // Running / error / stopped breakdown, with labels when they fit next to the
+    // title, otherwise symbols only (the legend is in the help overlay)
+    let mut breakdown = widgets::status_breakdown_line(
+        status_counts,
+        app.summary_stale_count,
+        true,
+        &app.color_config,
+    );
+    if title_line.width() + breakdown.width() + 1 > top_area.width as usize {
+        breakdown = widgets::status_breakdown_line(
+            status_counts,
+            app.summary_stale_count,
+            false,
+            &app.color_config,
+        );
+    }
     let top_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Min(0),     // Title takes remaining space
-            Constraint::Length(20), // Increased space for "Nodes: N / N"
+            Constraint::Min(0),                           // Title takes remaining space
+            Constraint::Length(breakdown.width() as u16), // Status breakdown
         ])
         .split(top_area);
 
-    let title = Paragraph::new("Autonomi Node Dashboard")
-        .style(Style::default().fg(Color::DarkGray))
-        .alignment(Alignment::Left);
+    let title = Paragraph::new(title_line).alignment(Alignment::Left);
     f.render_widget(title, top_chunks[0]);
 
-    // Create spans for different parts of the node count text
-    let node_count_spans = Line::from(vec![
-        Span::styled("Nodes: ", Style::default().fg(Color::DarkGray)),
-        Span::styled(
-            running_nodes_count.to_string(),
-            Style::default().fg(Color::Rgb(255, 165, 0)),
-        ),
-        Span::styled(" / ", Style::default().fg(Color::DarkGray)),
-        Span::styled(
-            total_nodes_count.to_string(),
-            Style::default().fg(Color::Rgb(255, 165, 0)),
-        ),
-    ]);
-
-    // Create the paragraph with the styled spans
-    let node_count_widget = Paragraph::new(node_count_spans).alignment(Alignment::Right);
+    let node_count_widget = Paragraph::new(breakdown).alignment(Alignment::Right);
 
     f.render_widget(node_count_widget, top_chunks[1]);
 
     // Render summary gauges in the next chunk
     widgets::render_summary_gauges(f, app, main_chunks[1]);
 
+    if app.show_fleet_panel {
+        widgets::render_fleet_panel(f, app, main_chunks[2]);
+    }
+
+    if group_pane_height > 0 {
+        widgets::render_group_pane(f, app, main_chunks[3]);
+    }
+
     // Render node table in the adjusted chunk
-    render_custom_node_rows(f, app, main_chunks[2]);
+    render_custom_node_rows(f, app, main_chunks[4]);
+
+    if !app.marked_nodes.is_empty() {
+        widgets::render_marked_footer(f, app, main_chunks[5]);
+    }
+
+    if let Some(tail) = &mut app.log_tail {
+        widgets::render_log_tail(f, tail, main_chunks[6]);
+    }
 
     // --- Bottom Status Bar ---
-    let bottom_area = main_chunks[3];
-    if let Some(msg) = &app.status_message {
-        // If there's an error/status message, display it across the whole bottom bar
-        let error_paragraph = Paragraph::new(msg.clone()).style(Style::default().fg(Color::Red));
-        f.render_widget(error_paragraph, bottom_area);
+    let bottom_area = main_chunks[7];
+    if app.ui_mode == UiMode::Goto {
+        f.render_widget(Paragraph::new(goto_prompt_line(app)), bottom_area);
     } else {
-        // Otherwise, split the bottom bar for standard status
+        // The right side is always drawn at its full width, messages and hints take
+        // what is left and are cut to fit
+        let right_status_spans = right_status_line(app, now);
         let status_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
-                Constraint::Percentage(50), // Left side ("Press 'q' to quit")
-                Constraint::Percentage(50), // Right side (Update info)
+                Constraint::Min(0), // Left side (message, or "Press 'q' to quit, '?' for help")
+                Constraint::Length(right_status_spans.width() as u16 + 1), // Right side (Update info)
             ])
             .split(bottom_area);
+        let left_width = status_chunks[0].width as usize;
+        app.clear_status_message_after_duration(now);
 
-        // Left status with 'q' highlighted
-        let left_status_spans = Line::from(vec![
-            Span::styled("Press '", Style::default().fg(Color::DarkGray)),
-            Span::styled("q", Style::default().fg(Color::Rgb(255, 165, 0))),
-            Span::styled("' to quit", Style::default().fg(Color::DarkGray)),
-        ]);
+        let left_status_spans = if let Some(msg) = &app.status_message {
+            // The full text stays available in the message history (`M`)
+            Line::styled(
+                truncate_to_width(&msg.text, left_width),
+                Style::default().fg(widgets::severity_color(msg.severity)),
+            )
+        } else if let Some((name, amount)) = next_reward_notification(app) {
+            // Reward gains flash for one update each, queued gains follow on the next ones
+            Line::styled(
+                truncate_to_width(
+                    &format!("💰 {} earned +{} tokens", name, amount),
+                    left_width,
+                ),
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            // Left status with 'q' highlighted
+            Line::from(vec![
+                Span::styled("Press '", Style::default().fg(Color::DarkGray)),
+                Span::styled("q", Style::default().fg(Color::Rgb(255, 165, 0))),
+                Span::styled("' to quit, '", Style::default().fg(Color::DarkGray)),
+                Span::styled("?", Style::default().fg(Color::Rgb(255, 165, 0))),
+                Span::styled("' for help", Style::default().fg(Color::DarkGray)),
+            ])
+        };
         let left_status = Paragraph::new(left_status_spans).alignment(Alignment::Left);
+        let right_status = Paragraph::new(right_status_spans).alignment(Alignment::Right);
+
+        f.render_widget(left_status, status_chunks[0]);
+        f.render_widget(right_status, status_chunks[1]);
+    }
+
+    // Popups are drawn last so they sit on top of everything else
+    match app.ui_mode {
+        UiMode::Normal => {}
+        UiMode::Detail => popups::render_detail_popup(f, app),
+        UiMode::Help => popups::render_help_popup(f, app),
+        UiMode::Errors => popups::render_errors_popup(f, app),
+        UiMode::Goto => {} // Drawn in the bottom bar
+        UiMode::Actions => popups::render_action_menu(f, app),
+        UiMode::Messages => popups::render_messages_popup(f, app),
+        UiMode::RawMetrics => popups::render_raw_metrics_popup(f, app),
+        UiMode::Confirm => popups::render_confirm_popup(f, app),
+    }
+}
 
-        // Right status with values highlighted
-        let tick_rate_str = format_duration_human(app.tick_rate);
-        let elapsed_secs_str = app.last_update.elapsed().as_secs().to_string();
-        let right_status_spans = Line::from(vec![
-            Span::styled("Update: ", Style::default().fg(Color::DarkGray)),
-            Span::styled(tick_rate_str, Style::default().fg(Color::Rgb(255, 165, 0))),
-            Span::styled(" | Last: ", Style::default().fg(Color::DarkGray)),
+/// Right side of the bottom bar: update interval, fetch concurrency and progress.
+fn right_status_line(app: &App, now: Instant) -> Line<'static> {
+    let tick_rate_str = format_duration_human(app.tick_rate);
+    let mut right_status_spans = Vec::new();
+    if let Some(zeroed_at) = app.errors_zeroed_at {
+        let secs = now.saturating_duration_since(zeroed_at).as_secs();
+        right_status_spans.extend([
+            Span::styled("Errors zeroed ", Style::default().fg(Color::DarkGray)),
             Span::styled(
-                elapsed_secs_str,
+                format!("{}:{:02}", secs / 60, secs % 60),
                 Style::default().fg(Color::Rgb(255, 165, 0)),
             ),
-            Span::styled("s ago", Style::default().fg(Color::DarkGray)),
-            Span::styled(" | Speed: ", Style::default().fg(Color::DarkGray)),
-            Span::styled("+/-", Style::default().fg(Color::Rgb(255, 165, 0))),
+            Span::styled(" ago | ", Style::default().fg(Color::DarkGray)),
         ]);
-        let right_status = Paragraph::new(right_status_spans).alignment(Alignment::Right);
+    }
+    right_status_spans.extend([
+        Span::styled("Update: ", Style::default().fg(Color::DarkGray)),
+        Span::styled(tick_rate_str, Style::default().fg(Color::Rgb(255, 165, 0))),
+        Span::styled(" | Parallel: ", Style::default().fg(Color::DarkGray)),
+        Span::styled(
+            app.max_concurrent_fetches
+                .map_or_else(|| "all".to_string(), |limit| limit.to_string()),
+            Style::default().fg(Color::Rgb(255, 165, 0)),
+        ),
+        Span::styled(" | ", Style::default().fg(Color::DarkGray)),
+    ]);
+    right_status_spans.extend(fetch_status_spans(app, now));
+    right_status_spans.extend([
+        Span::styled(" | Speed: ", Style::default().fg(Color::DarkGray)),
+        Span::styled("+/-", Style::default().fg(Color::Rgb(255, 165, 0))),
+    ]);
+    Line::from(right_status_spans)
+}
 
-        f.render_widget(left_status, status_chunks[0]);
-        f.render_widget(right_status, status_chunks[1]);
+/// Goto prompt with the number of nodes the input matches so far.
+fn goto_prompt_line(app: &App) -> Line<'static> {
+    let hint = match app.goto_matches().len() {
+        _ if app.goto_input.trim().is_empty() => "node name prefix or row number".to_string(),
+        0 => "no match".to_string(),
+        1 => "Enter to jump".to_string(),
+        count => format!("{} matches", count),
+    };
+    Line::from(vec![
+        Span::styled(":", Style::default().fg(Color::Rgb(255, 165, 0))),
+        Span::styled(app.goto_input.clone(), Style::default().fg(Color::Gray)),
+        Span::styled("█", Style::default().fg(Color::DarkGray)),
+        Span::styled(
+            format!("  ({})", hint),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ])
+}
+
+/// Reward gain to show in the status bar. The current one stays up until the next
+/// metrics update, then the next queued one takes its place.
+fn next_reward_notification(app: &mut App) -> Option<(String, u64)> {
+    let expired = app
+        .current_reward_notification
+        .as_ref()
+        .is_none_or(|(_, _, shown_for)| *shown_for != app.last_update);
+    if expired {
+        app.current_reward_notification = app
+            .reward_notifications
+            .pop_front()
+            .map(|(name, amount)| (name, amount, app.last_update));
     }
+    app.current_reward_notification
+        .as_ref()
+        .map(|(name, amount, _)| (name.clone(), *amount))
+}
+
+/// Spinner and "212/500" progress while a sweep is in flight, otherwise the time
+/// since the last update and the countdown to the next one, or "Paused" while unfocused.
+/// A snapshot is never updated.
+fn fetch_status_spans(app: &App, now: Instant) -> Vec<Span<'static>> {
+    const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    let highlight = Style::default().fg(Color::Rgb(255, 165, 0));
+    let dim = Style::default().fg(Color::DarkGray);
 
-    // Clear the status message after displaying it once (optional, remove if messages should persist)
-    // app.status_message = None;
+    if let Some(playback) = &app.history_playback {
+        let state = if playback.playing {
+            "Playing, Space to pause"
+        } else {
+            "Paused, Space to play, ←/→ to step"
+        };
+        vec![Span::styled(state, dim)]
+    } else if app.replay.is_some() {
+        vec![Span::styled("Replay, ←/→ to step", dim)]
+    } else if app.offline_source.is_some() {
+        vec![Span::styled("Snapshot, not updated", dim)]
+    } else if app.fetch_in_progress {
+        let frame = (now
+            .saturating_duration_since(app.last_fetch_started)
+            .as_millis()
+            / 100) as usize;
+        vec![
+            Span::styled(
+                SPINNER_FRAMES[frame % SPINNER_FRAMES.len()].to_string(),
+                highlight,
+            ),
+            Span::styled(" fetching… ", dim),
+            Span::styled(format!("{}/{}", app.fetch_done, app.fetch_total), highlight),
+        ]
+    } else if app.fetch_paused() {
+        vec![
+            Span::styled("Paused", highlight),
+            Span::styled(" (unfocused), last: ", dim),
+            Span::styled(
+                now.saturating_duration_since(app.last_update)
+                    .as_secs()
+                    .to_string(),
+                highlight,
+            ),
+            Span::styled("s ago", dim),
+        ]
+    } else {
+        vec![
+            Span::styled("Last: ", dim),
+            Span::styled(
+                now.saturating_duration_since(app.last_update)
+                    .as_secs()
+                    .to_string(),
+                highlight,
+            ),
+            Span::styled("s ago, next in ", dim),
+            Span::styled(
+                app.next_fetch_in_from(now).as_secs_f64().ceil().to_string(),
+                highlight,
+            ),
+            Span::styled("s", dim),
+        ]
+    }
 }
 
 /// Renders the main content area containing the node list (header + rows).
 /// Delegates rendering the header and individual rows to helper functions.
-// This function is also internal to the ui module, called by ui
-fn render_custom_node_rows(f: &mut Frame, app: &mut App, area: Rect) {
+pub(crate) fn render_custom_node_rows(f: &mut Frame, app: &mut App, area: Rect) {
     let inner_area = area.inner(&Margin {
         vertical: 1,
         horizontal: 1,
     });
 
-    let num_nodes = app.nodes.len();
+    // Nodes passing the current list view filter, in display order
+    let num_nodes = app.visible_nodes().len();
     if num_nodes == 0 {
-        let no_nodes_text = Paragraph::new("No nodes discovered yet...")
+        let message = if app.nodes.is_empty() {
+            "No nodes discovered yet...".to_string()
+        } else {
+            format!(
+                "No nodes in the {} view, press 'n' to switch",
+                app.list_view_filter.label()
+            )
+        };
+        let no_nodes_text = Paragraph::new(message)
             .style(Style::default().fg(Color::DarkGray))
             .alignment(Alignment::Center);
         f.render_widget(no_nodes_text, inner_area);
         return;
     }
 
+    // Rows may have moved or gone since the last frame, find the selected node again
+    app.resolve_selection();
+
     // Calculate available height for node rows (excluding header)
     let header_height = 1;
     let available_height = inner_area.height.saturating_sub(header_height);
     let num_visible_rows = available_height as usize;
 
+    // Keep the selected row inside the visible window
+    if app.selected < app.scroll_offset {
+        app.scroll_offset = app.selected;
+    } else if num_visible_rows > 0 && app.selected >= app.scroll_offset + num_visible_rows {
+        app.scroll_offset = app.selected + 1 - num_visible_rows;
+    }
+
     // Adjust scroll offset if it's too large for the current number of nodes/visible rows
     if num_nodes > num_visible_rows {
         app.scroll_offset = app
@@ -342,39 +1619,192 @@ fn render_custom_node_rows(f: &mut Frame, app: &mut App, area: Rect) {
         app.scroll_offset = 0;
     }
 
-    // Define layout constraints: 1 for header, then 1 for each VISIBLE row
-    let mut constraints = vec![Constraint::Length(header_height)];
-    constraints.extend(std::iter::repeat_n(
-        Constraint::Length(1),
-        num_visible_rows.min(num_nodes), // Don't create more constraints than nodes
-    ));
-
-    let vertical_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(constraints)
-        .split(inner_area);
-
-    render_header(f, vertical_chunks[0]);
-
     // Determine the range of nodes to display
     let start_index = app.scroll_offset;
     let end_index = (start_index + num_visible_rows).min(num_nodes);
+    let visible_rows = &app.visible_nodes()[start_index..end_index];
+
+    // The Node column fits the longest name on screen, with its relay flag
+    let node_width = widgets::node_column_width(visible_rows.iter().map(|dir_path| {
+        let relay = widgets::relay_flag(app, dir_path).map(|flag| flag.content.into_owned());
+        widgets::node_markers(app, dir_path)
+            + &widgets::node_column_label(app, dir_path)
+            + &relay.unwrap_or_default()
+    }));
+    let selected_row = app.selected.checked_sub(start_index);
+    render_node_table(f, app, inner_area, visible_rows, selected_row, node_width);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::test_metrics_body;
+    use antop::{
+        discovery::DiscoveredNode,
+        fetch::{FetchError, FetchErrorKind},
+    };
+    use ratatui::{backend::TestBackend, buffer::Buffer};
+    use unicode_width::UnicodeWidthStr;
+
+    const SIZES: [(u16, u16); 3] = [(80, 24), (200, 50), (60, 15)];
 
-    // Iterate only over the visible nodes based on scroll offset
-    for (relative_index, node_index) in (start_index..end_index).enumerate() {
-        let chunk_index = relative_index + 1; // +1 to skip header chunk
-        if chunk_index >= vertical_chunks.len() {
-            // Should not happen with correct constraint calculation, but safeguard
-            break;
+    // The same RAM on every machine, for the memory gauge
+    const TEST_SYSTEM_MEMORY: u64 = 16_000_000_000;
+
+    fn fixture(mut app: App) -> App {
+        app.system_memory_bytes = Some(TEST_SYSTEM_MEMORY);
+        app
+    }
+
+    // No node found
+    fn empty_fleet() -> App {
+        fixture(App::for_tests(&[]))
+    }
+
+    // Running, failing and stopped nodes, one of each error kind that has its own color
+    fn failing_fleet() -> App {
+        let dirs = [
+            "antnode-1",
+            "antnode-2",
+            "antnode-3",
+            "antnode-4",
+            "antnode-5",
+        ]
+        .map(|name| format!("/var/antctl/services/{}", name));
+        let discovered = dirs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 3) // antnode-4 has no metrics URL, it is stopped
+            .map(|(i, dir_path)| DiscoveredNode {
+                dir_path: dir_path.clone(),
+                url: format!("http://127.0.0.1:{}/metrics", 13001 + i),
+                source: DiscoverySource::Demo,
+            })
+            .collect();
+        let mut app = fixture(App::new(dirs.to_vec(), discovered, &Settings::for_tests()));
+        app.answer_all(|i| match i {
+            0 => Ok(test_metrics_body(120_345, 18)),
+            1 => Err(FetchError::new(
+                FetchErrorKind::Connection,
+                "Connection refused",
+            )),
+            2 => Err(FetchError::new(FetchErrorKind::Server(500), "HTTP 500")),
+            _ => Err(FetchError::new(FetchErrorKind::Timeout, "Timed out")),
+        });
+        app
+    }
+
+    // More nodes than rows, scrolled down to a selection near the end
+    fn large_fleet(nodes: usize) -> App {
+        let dirs: Vec<String> = (1..=nodes)
+            .map(|i| format!("/srv/node/antnode-{}", i))
+            .collect();
+        let dirs: Vec<&str> = dirs.iter().map(String::as_str).collect();
+        let mut app = fixture(App::for_tests(&dirs));
+        app.answer_all(|i| Ok(test_metrics_body(1_000 * i as u64 + 17, i as u64 % 9)));
+        app.move_selection(nodes as isize * 3 / 4);
+        app
+    }
+
+    // Text of a buffer, one line per row. The cells covered by a wide character are
+    // left out, they hold no text of their own
+    fn buffer_text(buffer: &Buffer) -> String {
+        let area = buffer.area;
+        let mut lines = Vec::with_capacity(area.height as usize);
+        for y in area.top()..area.bottom() {
+            let mut line = String::new();
+            let mut skip = 0;
+            for x in area.left()..area.right() {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                let symbol = buffer.get(x, y).symbol();
+                skip = symbol.width().saturating_sub(1);
+                line.push_str(symbol);
+            }
+            lines.push(line.trim_end().to_string());
         }
-        let row_area = vertical_chunks[chunk_index];
+        lines.join("\n")
+    }
+
+    // A frame drawn at the time of the last update, so the status bar reads the same
+    fn render(app: &mut App, width: u16, height: u16) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let now = app.last_update;
+        terminal.draw(|f| ui(f, app, now)).unwrap();
+        buffer_text(terminal.backend().buffer())
+    }
+
+    fn assert_frames(fixture_name: &str, fleet: fn() -> App) {
+        for (width, height) in SIZES {
+            let mut app = fleet();
+            insta::assert_snapshot!(
+                format!("{}_{}x{}", fixture_name, width, height),
+                render(&mut app, width, height)
+            );
+        }
+    }
 
-        // Get the directory path for the current node index
-        let dir_path = &app.nodes[node_index];
-        // Find the corresponding URL, if it exists
-        let url_option = app.node_urls.get(dir_path);
+    #[test]
+    fn frame_without_nodes() {
+        assert_frames("empty", empty_fleet);
+    }
+
+    #[test]
+    fn frame_with_failing_nodes() {
+        assert_frames("errors", failing_fleet);
+    }
+
+    #[test]
+    fn frame_scrolled_to_the_selection() {
+        assert_frames("scrolled", || large_fleet(60));
+    }
+
+    #[test]
+    fn tiny_terminals_do_not_panic() {
+        let fleets: [fn() -> App; 3] = [empty_fleet, failing_fleet, || large_fleet(60)];
+        for fleet in fleets {
+            let mut app = fleet();
+            for width in 0..=10 {
+                for height in 0..=3 {
+                    render(&mut app, width, height);
+                }
+            }
+        }
+    }
+
+    // cargo test --release node_table_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn node_table_benchmark() {
+        const NODES: usize = 1_000;
+        const ROUNDS: u32 = 100;
+        let mut app = large_fleet(NODES);
+
+        // A whole frame, the table only builds the rows on screen
+        let mut terminal = Terminal::new(TestBackend::new(200, 50)).unwrap();
+        let now = app.last_update;
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            terminal.draw(|f| ui(f, &mut app, now)).unwrap();
+        }
+        let frame = start.elapsed() / ROUNDS;
+
+        // The table alone with a row for every node
+        let dir_paths = app.sorted_nodes().to_vec();
+        let mut terminal = Terminal::new(TestBackend::new(200, NODES as u16 + 1)).unwrap();
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            terminal
+                .draw(|f| render_node_table(f, &app, f.size(), &dir_paths, Some(0), 20))
+                .unwrap();
+        }
+        let table = start.elapsed() / ROUNDS;
 
-        // Pass the directory path and the Option<&String> URL to render_node_row
-        render_node_row(f, app, row_area, dir_path, url_option);
+        println!(
+            "{} nodes: {:?} per 200x50 frame, {:?} per table of every row",
+            NODES, frame, table
+        );
     }
 }
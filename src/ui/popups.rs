@@ -0,0 +1,780 @@
+use super::formatters::{
+    format_change_rate, format_count_grouped, format_duration_human, format_float, format_option,
+    format_option_u64_bytes, format_speed_bps, format_uptime, truncate_to_width,
+};
+use super::node_action_key_label;
+use super::widgets::{
+    SELECTED_ROW_STYLE, STALE_SYMBOL, create_summary_chart, fetch_error_color, severity_color,
+    status_symbol,
+};
+use crate::app::{App, CounterSample, NodeAction, TICK_PRESETS, format_node_id};
+use antop::{
+    fetch::FetchErrorKind,
+    metrics::{NAT_SUSPECT_INBOUND_RATIO, NodeMetrics},
+    state::NodeStatus,
+};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+
+const LABEL_STYLE: Style = Style::new().fg(Color::DarkGray);
+const VALUE_STYLE: Style = Style::new().fg(Color::Gray);
+// Width of the label column of the detail popup fields
+const FIELD_LABEL_WIDTH: usize = 14;
+const BORDER_STYLE: Style = Style::new().fg(Color::Yellow);
+const KEY_STYLE: Style = Style::new().fg(Color::Rgb(255, 165, 0));
+
+// Keys listed in the help overlay, keep in sync with the key handling in `run_app`
+// (the actions on the selected node are listed from `NodeAction`)
+const KEYBINDINGS: [(&str, &str); 33] = [
+    ("q", "Quit"),
+    ("Up / Down", "Select a node"),
+    ("Space", "Action menu for the selected node"),
+    (":", "Jump to a node by name prefix or row number"),
+    ("*", "Mark / unmark the selected node, sums in a footer"),
+    ("A / N", "Mark / unmark all the nodes in the view"),
+    ("p", "Pin / unpin the selected node to the top"),
+    ("w", "Write a report of the alerting nodes to a file"),
+    ("W", "Write the node table to a text and a JSON file"),
+    (
+        "e",
+        "Write the histories of the selected node to a CSV file",
+    ),
+    ("L", "Toggle the log tail pane"),
+    ("PgUp / PgDn", "Scroll the log tail pane"),
+    ("+ / -", "Slower / faster update interval"),
+    ("1 - 9", "Update interval preset, see below"),
+    ("[ / ]", "Fewer / more nodes fetched at once"),
+    ("v", "Toggle compact / detailed view"),
+    ("t", "Toggle lifetime / session Rx/Tx totals"),
+    (
+        "f",
+        "Toggle the fleet panel (peers histogram, records/rewards growth)",
+    ),
+    ("a", "Toggle the availability timeline (span in its header)"),
+    (
+        "b",
+        "Toggle the traffic bars, Rx + Tx speed against the busiest node",
+    ),
+    (
+        "n",
+        "Cycle the list view: all, running, stopped, error, shunned",
+    ),
+    (
+        "s",
+        "Cycle the sort: name, status, CPU, memory, records, uptime, last update",
+    ),
+    ("P", "Toggle node names / full directory paths"),
+    ("#", "Toggle grouped / compact counts"),
+    ("u", "Toggle decimal (GB) / binary (GiB) sizes"),
+    ("m", "Cycle the sparkline marker: braille, dot, block"),
+    ("c", "Swap the Rx / Tx colors"),
+    ("z", "Zero the displayed error counts"),
+    ("M", "Show the status message history"),
+    ("r", "In the detail popup: raw Prometheus text of the node"),
+    (
+        "Left / Right",
+        "With --replay or history: previous / next update",
+    ),
+    ("?", "Show this help"),
+    ("Esc", "Close the popup, or clear the marks"),
+];
+
+// Status legend for the top bar breakdown
+const STATUS_LEGEND: [(NodeStatus, &str); 4] = [
+    (NodeStatus::Running, "Running, metrics fetched"),
+    (
+        NodeStatus::Initializing,
+        "Metrics URL found, waiting for the first fetch",
+    ),
+    (
+        NodeStatus::Error,
+        "Metrics fetch failed or not Prometheus text",
+    ),
+    (NodeStatus::Stopped, "No metrics URL found in the logs"),
+];
+// Status column of the failed fetches, by category, with the label shown for it
+const FETCH_ERROR_LEGEND: [(FetchErrorKind, &str, &str); 6] = [
+    (
+        FetchErrorKind::Connection,
+        "Conn error",
+        "Connection failed, the node is likely down",
+    ),
+    (FetchErrorKind::Timeout, "Timeout", "No answer in time"),
+    (
+        FetchErrorKind::Client(404),
+        "HTTP 4xx",
+        "Client error, usually a wrong metrics URL",
+    ),
+    (
+        FetchErrorKind::Server(500),
+        "HTTP 5xx",
+        "Up, but its metrics handler failed",
+    ),
+    (
+        FetchErrorKind::Redirect,
+        "Redirect",
+        "Redirect loop, too many redirects or not followed",
+    ),
+    (
+        FetchErrorKind::Body,
+        "Bad body",
+        "Answer not Prometheus text",
+    ),
+];
+
+// Reads one error counter out of a counter sample, for its rate
+type SampleCounter = fn(&CounterSample) -> Option<u64>;
+
+// --- Helper Functions ---
+
+/// Returns a rect centered in `area` taking the given percentages of its size.
+pub fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+// Helper to build a "label: value" line with aligned values
+fn field_line(label: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{:<FIELD_LABEL_WIDTH$}", label), LABEL_STYLE),
+        Span::styled(value, VALUE_STYLE),
+    ])
+}
+
+// Helper to format an exact count with thousands separators, whatever the table notation
+fn format_exact(opt: Option<u64>) -> String {
+    opt.map_or_else(|| "-".to_string(), format_count_grouped)
+}
+
+/// Live peers gained or lost per second, now and on average over the kept history.
+fn peer_rate_text(app: &App, url: &str, metrics: &NodeMetrics) -> String {
+    let Some(rate) = metrics.peer_count_rate else {
+        return "-".to_string();
+    };
+    match app.peers_rate_history.get(url) {
+        Some(history) if history.len() > 1 => format!(
+            "{} now, {} on average over the last {} updates",
+            format_change_rate(rate),
+            format_change_rate(history.iter().sum::<f64>() / history.len() as f64),
+            history.len()
+        ),
+        _ => format_change_rate(rate),
+    }
+}
+
+// Helper to build the inbound ratio line, flagging nodes that are likely behind a NAT
+fn inbound_ratio_line(metrics: &NodeMetrics) -> Line<'static> {
+    let Some(ratio) = metrics.inbound_ratio() else {
+        return field_line("Inbound ratio", "-".to_string());
+    };
+    let mut line = field_line("Inbound ratio", format!("{:.1}%", ratio));
+    if ratio < NAT_SUSPECT_INBOUND_RATIO {
+        line.spans
+            .push(Span::styled("  NAT?", Style::default().fg(Color::Yellow)));
+    }
+    line
+}
+
+// Helper to build the relay line: circuits asked and accepted, with the acceptance rate
+fn relay_line(metrics: &NodeMetrics) -> Line<'static> {
+    let Some(rate) = metrics.relay_acceptance_rate() else {
+        return field_line("Relay", "-".to_string());
+    };
+    field_line(
+        "Relay",
+        format!(
+            "{} of {} circuits accepted ({:.1}%)",
+            format_exact(metrics.relay_hop_circuits_accepted),
+            format_exact(metrics.relay_hop_requests),
+            rate
+        ),
+    )
+}
+
+// Helper to build the memory line, flagging a steady climb as a possible leak
+fn memory_line(metrics: &NodeMetrics) -> Line<'static> {
+    let mut line = field_line(
+        "Memory",
+        format!("{} MB", format_float(metrics.memory_used_mb, 1)),
+    );
+    if let Some(mb_per_min) = metrics.memory_leak_mb_per_min {
+        line.spans.push(Span::styled(
+            format!("  ↑Mem +{:.1} MB/min, possible leak", mb_per_min),
+            Style::default().fg(Color::Red),
+        ));
+    }
+    line
+}
+
+// --- Popups ---
+
+/// Renders the detail popup for the selected node over the whole frame.
+pub fn render_detail_popup(f: &mut Frame, app: &App) {
+    let Some(dir_path) = app.selected_node() else {
+        return;
+    };
+    let url = app.node_urls.get(dir_path);
+    let metrics_result = url.and_then(|url| app.node_metrics.get(url));
+
+    let dir_name = app.node_name(dir_path);
+    let title = match app.node_short_ids.get(dir_path) {
+        Some(id) => format!(" {} ({}) ", dir_name, format_node_id(*id)),
+        None => format!(" {} ", dir_name),
+    };
+
+    let status = match (url, metrics_result) {
+        (None, _) => "Stopped".to_string(),
+        (Some(_), None) => "Initializing".to_string(),
+        (Some(_), Some(Ok(_))) => "Running".to_string(),
+        (Some(_), Some(Err(e))) => e.clone(),
+    };
+
+    // Paths longer than the popup are cut with an ellipsis
+    let area = centered_rect(70, 60, f.size());
+    let value_width = (area.width.saturating_sub(2) as usize).saturating_sub(FIELD_LABEL_WIDTH);
+
+    let mut lines = vec![
+        field_line("Path", truncate_to_width(dir_path, value_width)),
+        field_line(
+            "Metrics URL",
+            url.cloned().unwrap_or_else(|| "-".to_string()),
+        ),
+        field_line(
+            "Found via",
+            app.node_discovery_sources.get(dir_path).map_or_else(
+                || "-".to_string(),
+                |source| truncate_to_width(&source.to_string(), value_width),
+            ),
+        ),
+        field_line("Status", status),
+    ];
+    if let Some(process) = app.node_process(dir_path) {
+        lines.push(field_line(
+            "Process",
+            format!(
+                "PID {} running without metrics, {:.2}% CPU, {:.1} MB resident",
+                process.pid,
+                process.cpu_percent,
+                process.memory_mb()
+            ),
+        ));
+    }
+    if let Some(systemd) = &app.systemd {
+        let unit = systemd.unit_name(dir_path);
+        lines.push(field_line(
+            "Unit",
+            match app.unit_state(dir_path) {
+                Some(state) => format!("{} ({})", unit, state),
+                None => unit,
+            },
+        ));
+    }
+    if let Some(anomalies) = app.anomalies.get(dir_path) {
+        let labels: Vec<&str> = anomalies.iter().map(|anomaly| anomaly.label()).collect();
+        let mut line = field_line("Outlier", String::new());
+        line.spans.push(Span::styled(
+            format!("⚑ {} for the fleet", labels.join(", ")),
+            Style::default().fg(Color::Yellow),
+        ));
+        lines.push(line);
+    }
+    lines.push(Line::default());
+
+    if let (Some(url), Some(Ok(metrics))) = (url, metrics_result) {
+        let (total_in, total_out) = app.displayed_bandwidth(url, metrics);
+        lines.extend([
+            field_line("Uptime", format_uptime(metrics.uptime_seconds)),
+            memory_line(metrics),
+            field_line(
+                "CPU",
+                format!(
+                    "{}% (mean of the last {} updates)",
+                    format_float(metrics.cpu_percent(), 2),
+                    app.cpu_avg_window
+                ),
+            ),
+            field_line(
+                "CPU raw",
+                format!("{}%", format_float(metrics.cpu_usage_percentage, 2)),
+            ),
+            field_line(
+                "Peers",
+                format!(
+                    "{} live / {} in routing table",
+                    format_exact(metrics.connected_peers),
+                    format_exact(metrics.peers_in_routing_table)
+                ),
+            ),
+            field_line("Peer rate", peer_rate_text(app, url, metrics)),
+            inbound_ratio_line(metrics),
+            relay_line(metrics),
+            field_line("Network size", format_exact(metrics.estimated_network_size)),
+            field_line("Records", format_exact(metrics.records_stored)),
+            field_line("Rewards", format_option(metrics.reward_wallet_balance)),
+            field_line(
+                "Rx",
+                format!(
+                    "{} ({})",
+                    format_option_u64_bytes(total_in, app.size_units),
+                    format_speed_bps(metrics.speed_in_bps, app.size_units)
+                ),
+            ),
+            field_line(
+                "Tx",
+                format!(
+                    "{} ({})",
+                    format_option_u64_bytes(total_out, app.size_units),
+                    format_speed_bps(metrics.speed_out_bps, app.size_units)
+                ),
+            ),
+            field_line(
+                "Errors",
+                format!(
+                    "{} (put {}, conn in {}, conn out {}, kad {})",
+                    metrics.total_errors(),
+                    format_option(metrics.put_record_errors),
+                    format_option(metrics.incoming_connection_errors),
+                    format_option(metrics.outgoing_connection_errors),
+                    format_option(metrics.kad_get_closest_peers_errors)
+                ),
+            ),
+        ]);
+    }
+
+    // Fetch errors stay listed after the node recovers, to catch intermittent ones
+    if let Some(errors) = url.and_then(|url| app.recent_errors.get(url))
+        && !errors.is_empty()
+    {
+        lines.push(Line::default());
+        lines.push(Line::styled("Recent errors", LABEL_STYLE));
+        for error in errors.iter().rev() {
+            let repeats = if error.count > 1 {
+                format!(" (x{})", error.count)
+            } else {
+                String::new()
+            };
+            // Cut the message, not the repeat count
+            let message = format!(
+                "{}{}",
+                truncate_to_width(&error.message, value_width.saturating_sub(repeats.len())),
+                repeats
+            );
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{:<FIELD_LABEL_WIDTH$}", error.last_seen.format("%H:%M:%S")),
+                    LABEL_STYLE,
+                ),
+                Span::styled(message, Style::default().fg(Color::Red)),
+            ]));
+        }
+    }
+
+    // Hook runs of the node, with their exit code and the end of their stderr
+    if let Some(runs) = app.hook_log.get(dir_path)
+        && !runs.is_empty()
+    {
+        lines.push(Line::default());
+        lines.push(Line::styled("Recent hooks", LABEL_STYLE));
+        for run in runs.iter().rev() {
+            let color = if run.succeeded() {
+                Color::Green
+            } else {
+                Color::Red
+            };
+            let text = format!("on_{} {}", run.event.name(), run.result);
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("{:<FIELD_LABEL_WIDTH$}", run.at.format("%H:%M:%S")),
+                    LABEL_STYLE,
+                ),
+                Span::styled(
+                    truncate_to_width(&text, value_width),
+                    Style::default().fg(color),
+                ),
+            ]));
+        }
+    }
+
+    let block = Block::default()
+        .title(title)
+        .title_bottom(Line::styled(
+            " r for raw metrics, Esc to close ",
+            LABEL_STYLE,
+        ))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    f.render_widget(Clear, area);
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
+
+    // Fields on top, CPU and peers trends side by side below
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(6)])
+        .split(inner_area);
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+
+    if let Some(Ok(metrics)) = metrics_result {
+        let chart_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[1]);
+        let trends = [
+            ("CPU", metrics.chart_data_cpu.as_deref(), Color::Green),
+            ("Peers", metrics.chart_data_peers.as_deref(), Color::Cyan),
+        ];
+        for ((name, data, color), chunk) in trends.into_iter().zip(chart_chunks.iter()) {
+            let trend_block = Block::default()
+                .title(Span::styled(format!(" {} trend ", name), LABEL_STYLE))
+                .borders(Borders::TOP)
+                .border_style(LABEL_STYLE);
+            let trend_area = trend_block.inner(*chunk);
+            f.render_widget(trend_block, *chunk);
+            if let Some(chart) =
+                data.and_then(|data| create_summary_chart(data, color, name, app.chart_marker))
+            {
+                f.render_widget(chart, trend_area);
+            }
+        }
+    }
+}
+
+/// Renders the error breakdown popup for the selected node: lifetime count and recent
+/// rate of each error category.
+pub fn render_errors_popup(f: &mut Frame, app: &App) {
+    let Some(dir_path) = app.selected_node() else {
+        return;
+    };
+    let url = app.node_urls.get(dir_path);
+    let metrics = url.and_then(|url| match app.node_metrics.get(url) {
+        Some(Ok(metrics)) => Some(metrics),
+        _ => None,
+    });
+
+    let mut lines = vec![Line::styled(
+        format!("{:<24}{:>14}{:>12}", "Category", "Total", "Rate/min"),
+        LABEL_STYLE,
+    )];
+    match (url, metrics) {
+        (Some(url), Some(metrics)) => {
+            let categories: [(&str, Option<u64>, SampleCounter); 4] = [
+                ("Put record", metrics.put_record_errors, |s| {
+                    s.put_record_errors
+                }),
+                (
+                    "Incoming connection",
+                    metrics.incoming_connection_errors,
+                    |s| s.incoming_connection_errors,
+                ),
+                (
+                    "Outgoing connection",
+                    metrics.outgoing_connection_errors,
+                    |s| s.outgoing_connection_errors,
+                ),
+                (
+                    "Kad get closest peers",
+                    metrics.kad_get_closest_peers_errors,
+                    |s| s.kad_get_closest_peers_errors,
+                ),
+            ];
+            for (name, total, counter) in categories {
+                let rate = app
+                    .counter_rate(url, counter)
+                    .map(|per_second| per_second * 60.0);
+                let style = if total.unwrap_or(0) > 0 {
+                    Style::default().fg(Color::Red)
+                } else {
+                    VALUE_STYLE
+                };
+                lines.push(Line::styled(
+                    format!(
+                        "{:<24}{:>14}{:>12}",
+                        name,
+                        format_exact(total),
+                        format_float(rate, 1)
+                    ),
+                    style,
+                ));
+            }
+            lines.push(Line::styled(
+                format!(
+                    "{:<24}{:>14}",
+                    "Total",
+                    format_count_grouped(metrics.total_errors())
+                ),
+                BORDER_STYLE,
+            ));
+        }
+        _ => lines.push(Line::styled("No metrics for this node", VALUE_STYLE)),
+    }
+
+    let block = Block::default()
+        .title(format!(" Errors: {} ", app.node_display_name(dir_path)))
+        .title_bottom(Line::styled(" Esc to close ", LABEL_STYLE))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    // Sized to the table of categories, centered over the node table
+    let frame_area = f.size();
+    let width = 54.min(frame_area.width);
+    let height = (lines.len() as u16 + 2).min(frame_area.height);
+    let area = Rect::new(
+        frame_area.x + (frame_area.width - width) / 2,
+        frame_area.y + (frame_area.height - height) / 2,
+        width,
+        height,
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Renders the help overlay with the keybindings and the status legend.
+pub fn render_help_popup(f: &mut Frame, app: &App) {
+    let mut lines = vec![Line::styled("Keys", BORDER_STYLE)];
+    for (key, action) in KEYBINDINGS {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:<12}", key), KEY_STYLE),
+            Span::styled(action, VALUE_STYLE),
+        ]));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::styled("Selected node", BORDER_STYLE));
+    for action in NodeAction::ALL {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {:<12}", node_action_key_label(action)),
+                KEY_STYLE,
+            ),
+            Span::styled(action.label(), VALUE_STYLE),
+        ]));
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::styled("Update interval presets", BORDER_STYLE));
+    let mut presets = vec![Span::raw("  ")];
+    for (key, tick_rate) in TICK_PRESETS {
+        presets.push(Span::styled(key.to_string(), KEY_STYLE));
+        presets.push(Span::styled(
+            format!(" {}  ", format_duration_human(tick_rate)),
+            VALUE_STYLE,
+        ));
+    }
+    lines.push(Line::from(presets));
+
+    lines.push(Line::default());
+    lines.push(Line::styled("Status", BORDER_STYLE));
+    for (status, description) in STATUS_LEGEND {
+        let (symbol, color) = status_symbol(status, &app.color_config);
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:<12}", symbol), Style::default().fg(color)),
+            Span::styled(description, VALUE_STYLE),
+        ]));
+    }
+    let (stale_symbol, stale_color) = STALE_SYMBOL;
+    lines.push(Line::from(vec![
+        Span::styled(
+            format!("  {:<12}", stale_symbol),
+            Style::default().fg(stale_color),
+        ),
+        Span::styled(
+            format!(
+                "Running, same metrics for {} updates in a row",
+                app.stale_threshold
+            ),
+            VALUE_STYLE,
+        ),
+    ]));
+    for (kind, label, description) in FETCH_ERROR_LEGEND {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("  {:<12}", label),
+                Style::default().fg(fetch_error_color(kind, &app.color_config)),
+            ),
+            Span::styled(description, VALUE_STYLE),
+        ]));
+    }
+
+    let block = Block::default()
+        .title(" Help ")
+        .title_bottom(Line::styled(" Esc to close ", LABEL_STYLE))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    let area = centered_rect(60, 70, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Renders the action menu of the selected node: its available actions with their
+/// key, the highlighted one runs on `Enter`.
+pub fn render_action_menu(f: &mut Frame, app: &App) {
+    let Some(dir_path) = app.selected_node() else {
+        return;
+    };
+    let lines: Vec<Line> = app
+        .available_node_actions()
+        .into_iter()
+        .enumerate()
+        .map(|(i, action)| {
+            let line = Line::from(vec![
+                Span::styled(format!(" {:<7}", node_action_key_label(action)), KEY_STYLE),
+                Span::styled(action.label(), VALUE_STYLE),
+            ]);
+            if i == app.action_menu_selected {
+                line.style(SELECTED_ROW_STYLE)
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(format!(" {} ", app.node_display_name(dir_path)))
+        .title_bottom(Line::styled(" Enter to run, Esc to close ", LABEL_STYLE))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    // Sized to the list, centered over the node table
+    let frame_area = f.size();
+    let width = 42.min(frame_area.width);
+    let height = (lines.len() as u16 + 2).min(frame_area.height);
+    let area = Rect::new(
+        frame_area.x + (frame_area.width - width) / 2,
+        frame_area.y + (frame_area.height - height) / 2,
+        width,
+        height,
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Renders the yes / no prompt before a systemd action runs on a node.
+pub fn render_confirm_popup(f: &mut Frame, app: &App) {
+    let (Some((command, dir_path)), Some(systemd)) = (&app.pending_unit_action, &app.systemd)
+    else {
+        return;
+    };
+    let question = format!(" {} {}? ", command.label(), systemd.unit_name(dir_path));
+    let block = Block::default()
+        .title(format!(" {} ", app.node_display_name(dir_path)))
+        .title_bottom(Line::styled(
+            " y or Enter: yes, any other key: no ",
+            LABEL_STYLE,
+        ))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    let frame_area = f.size();
+    let width = (question.chars().count() as u16 + 2)
+        .max(40)
+        .min(frame_area.width);
+    let height = 3.min(frame_area.height);
+    let area = Rect::new(
+        frame_area.x + (frame_area.width - width) / 2,
+        frame_area.y + (frame_area.height - height) / 2,
+        width,
+        height,
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(Line::styled(question, VALUE_STYLE)).block(block),
+        area,
+    );
+}
+
+/// Renders the status message history, newest first, with the full text of messages
+/// the bottom bar had to cut.
+pub fn render_messages_popup(f: &mut Frame, app: &App) {
+    let lines: Vec<Line> = if app.status_history.is_empty() {
+        vec![Line::styled("No messages yet", VALUE_STYLE)]
+    } else {
+        app.status_history
+            .iter()
+            .rev()
+            .map(|message| {
+                Line::from(vec![
+                    Span::styled(message.at.format("%H:%M:%S ").to_string(), LABEL_STYLE),
+                    Span::styled(
+                        message.text.clone(),
+                        Style::default().fg(severity_color(message.severity)),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    let block = Block::default()
+        .title(" Messages ")
+        .title_bottom(Line::styled(" Esc to close ", LABEL_STYLE))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    let area = centered_rect(80, 60, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+/// Raw Prometheus text of the selected node's last successful fetch, unwrapped and
+/// scrolled with the arrows and PageUp / PageDown.
+pub fn render_raw_metrics_popup(f: &mut Frame, app: &App) {
+    let Some(dir_path) = app.selected_node() else {
+        return;
+    };
+    let url = app.node_urls.get(dir_path);
+    let raw_metrics = url.and_then(|url| app.raw_metrics_for_node(url));
+
+    let mut title = format!(" {} metrics ", app.node_name(dir_path));
+    if raw_metrics.is_some()
+        && matches!(url.and_then(|url| app.node_metrics.get(url)), Some(Err(_)))
+    {
+        title.push_str("(last successful fetch) ");
+    }
+    let text = match raw_metrics {
+        Some(raw_metrics) => Text::styled(raw_metrics, VALUE_STYLE),
+        None => Text::styled("No metrics fetched yet", LABEL_STYLE),
+    };
+
+    let block = Block::default()
+        .title(title)
+        .title_bottom(Line::styled(
+            " Arrows / PgUp / PgDn to scroll, Esc to go back ",
+            LABEL_STYLE,
+        ))
+        .borders(Borders::ALL)
+        .border_style(BORDER_STYLE);
+
+    let area = centered_rect(90, 80, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .scroll(app.raw_metrics_scroll),
+        area,
+    );
+}
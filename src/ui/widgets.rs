@@ -1,46 +1,295 @@
 use super::formatters::{
-    create_list_item_cells, create_placeholder_cells, format_option_u64_bytes, format_speed_bps,
+    create_list_item_cells, create_placeholder_cells, format_change_rate, format_count,
+    format_count_grouped, format_duration_human, format_option_u64_bytes, format_speed_bps,
+    right_align_to_width, truncate_start_to_width, truncate_to_width,
+};
+use crate::{
+    app::{
+        App, Availability, ChartMarker, ColorConfig, GROWTH_HISTORY_LENGTH,
+        PEERS_HISTOGRAM_BUCKETS, Severity, StorageGauge, TIMELINE_BUCKETS, TotalsMode, ViewMode,
+    },
+    log_tail::LogTail,
+    systemd::short_unit_state,
+};
+use antop::{
+    fetch::FetchErrorKind,
+    metrics::NodeMetrics,
+    state::{NodeStatus, StatusCounts},
 };
-use crate::app::App;
 use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     symbols,
     text::{Line, Span},
-    widgets::{Axis, Chart, Dataset, Gauge, GraphType, Paragraph},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Dataset, Gauge, GraphType, Paragraph, Row, Table,
+        TableState,
+    },
 };
+use std::rc::Rc;
+use unicode_width::UnicodeWidthStr;
 
 // --- Constants ---
 
-const HEADER_TITLES: [&str; 9] = [
+pub const HEADER_TITLES: [&str; 9] = [
     "Node", "Uptime", "Mem", "CPU", "Peers",   // Live Peers
     "Routing", // Routing Table Size
     "Recs", "Rwds", "Err",
 ];
 const HEADER_STYLE: Style = Style::new().fg(Color::Yellow);
 const DATA_CELL_STYLE: Style = Style::new().fg(Color::Gray);
+pub const SELECTED_ROW_STYLE: Style = Style::new().bg(Color::Rgb(45, 45, 45));
 
-// New constraints with fixed width for data columns and expanding charts
-pub const COLUMN_CONSTRAINTS: [Constraint; 14] = [
-    Constraint::Length(20), // 0: Node
-    Constraint::Length(12), // 1: Uptime
-    Constraint::Length(9),  // 2: Mem MB
-    Constraint::Length(8),  // 3: CPU %
-    Constraint::Length(6),  // 4: Peers (Live)
-    Constraint::Length(8),  // 5: Routing
-    Constraint::Length(7),  // 6: Records
-    Constraint::Length(7),  // 7: Reward
-    Constraint::Length(6),  // 8: Err
-    Constraint::Length(1),  // 9: Spacer 1
-    Constraint::Min(1),     // 10: Rx Chart Area (EXPANDS)
-    Constraint::Length(1),  // 11: Spacer 2
-    Constraint::Min(1),     // 12: Tx Chart Area (EXPANDS)
-    Constraint::Length(10), // 13: Status
-];
+/// Columns of the node table, in display order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnId {
+    Node,
+    Uptime,
+    Mem,
+    Cpu,
+    Peers, // Live, with the rate of change
+    Routing,
+    Recs,
+    Rwds,
+    Err,
+    Rx, // Total, sparkline and speed
+    Tx,
+    Traffic, // Rx + Tx speed bar, before Status
+    Status,
+    Timeline,
+}
+
+impl ColumnId {
+    /// Columns always shown, the traffic bars and availability timeline are added when
+    /// toggled on.
+    pub const DEFAULT: [ColumnId; 12] = [
+        ColumnId::Node,
+        ColumnId::Uptime,
+        ColumnId::Mem,
+        ColumnId::Cpu,
+        ColumnId::Peers,
+        ColumnId::Routing,
+        ColumnId::Recs,
+        ColumnId::Rwds,
+        ColumnId::Err,
+        ColumnId::Rx,
+        ColumnId::Tx,
+        ColumnId::Status,
+    ];
+
+    // Fixed width of the column, Rx and Tx take what is left
+    fn width(self, view_mode: ViewMode) -> u16 {
+        match self {
+            ColumnId::Node => 20,   // Resized to the names, see `node_column_width`
+            ColumnId::Uptime => 12, // Fits 8w 4d 23:59
+            ColumnId::Mem => 10,    // With the leak arrow
+            ColumnId::Cpu => 8,
+            ColumnId::Peers => 13,
+            ColumnId::Routing => 8,
+            ColumnId::Recs => 11, // Fits 99,999,999
+            ColumnId::Rwds => 7,
+            ColumnId::Err => match view_mode {
+                ViewMode::Compact => 8,   // Fits 9,999,999
+                ViewMode::Detailed => 18, // Sparkline + count
+            },
+            ColumnId::Rx | ColumnId::Tx => 0,
+            ColumnId::Traffic => TRAFFIC_COLUMN_WIDTH,
+            ColumnId::Status => 13, // Fits " unit: failed"
+            ColumnId::Timeline => TIMELINE_COLUMN_WIDTH,
+        }
+    }
+}
+
+// Terminals narrower than this have no room for the Rx/Tx columns
+const NARROW_TERMINAL_WIDTH: u16 = 80;
+
+/// Column constraints of the node table for `active_columns`, with a spacer before
+/// Rx and Tx. Both split the width left by the other columns, so their sparklines grow
+/// with the terminal. Below 80 columns they are dropped (zero wide), leaving room for
+/// the values. The detailed view widens Err for its sparkline.
+pub fn build_column_constraints(
+    terminal_width: u16,
+    active_columns: &[ColumnId],
+    view_mode: ViewMode,
+) -> Vec<Constraint> {
+    let show_charts = terminal_width >= NARROW_TERMINAL_WIDTH;
+    let mut constraints = Vec::with_capacity(active_columns.len() + 2);
+    for &column in active_columns {
+        match column {
+            ColumnId::Rx | ColumnId::Tx if show_charts => {
+                constraints.push(Constraint::Length(1));
+                constraints.push(Constraint::Fill(1));
+            }
+            ColumnId::Rx | ColumnId::Tx => {
+                constraints.push(Constraint::Length(0));
+                constraints.push(Constraint::Length(0));
+            }
+            _ => constraints.push(Constraint::Length(column.width(view_mode))),
+        }
+    }
+    constraints
+}
+
+// Width of the peers rate of change after the Peers value, like `+1.5/s`
+const PEER_RATE_WIDTH: usize = 6;
+
+// Node column width bounds: room for the title, and a cap for long directory names
+const NODE_COLUMN_MIN_WIDTH: u16 = 8;
+const NODE_COLUMN_MAX_WIDTH: u16 = 32;
+// Node column cells after the name: a space, then room for the outlier flag
+const NODE_COLUMN_PADDING: u16 = 3;
+
+/// Text of the Node column for a node, before truncation: its directory path with
+/// `P`, otherwise its display name.
+pub fn node_column_label(app: &App, dir_path: &str) -> String {
+    if app.show_full_paths {
+        dir_path.to_string()
+    } else {
+        app.node_display_name(dir_path)
+    }
+}
+
+/// Width of the Node column fitting the longest of `names` (the visible rows),
+/// capped so long directory names don't squeeze the charts. Longer names are truncated.
+pub fn node_column_width(names: impl IntoIterator<Item = String>) -> u16 {
+    let longest = names
+        .into_iter()
+        .map(|name| name.width())
+        .max()
+        .unwrap_or(0);
+    (longest as u16)
+        .saturating_add(NODE_COLUMN_PADDING)
+        .clamp(NODE_COLUMN_MIN_WIDTH, NODE_COLUMN_MAX_WIDTH)
+}
 
 // --- Helper Functions ---
 
+/// Symbol and color used for a node status in the top bar breakdown and the help legend.
+pub fn status_symbol(status: NodeStatus, colors: &ColorConfig) -> (&'static str, Color) {
+    let symbol = match status {
+        NodeStatus::Running => "●",
+        NodeStatus::Initializing => "◌",
+        NodeStatus::Error => "✖",
+        NodeStatus::Stopped => "○",
+    };
+    (symbol, colors.status_color(status))
+}
+
+/// Status color of a node whose fetch failed. Only unreachable nodes take the error
+/// color, the nodes answering with an HTTP error are up and get their own.
+pub fn fetch_error_color(kind: FetchErrorKind, colors: &ColorConfig) -> Color {
+    match kind {
+        FetchErrorKind::Connection => colors.error,
+        FetchErrorKind::Timeout => Color::LightRed,
+        FetchErrorKind::Client(_) => Color::Magenta,
+        FetchErrorKind::Server(_) => Color::Rgb(255, 135, 0), // Orange
+        FetchErrorKind::Redirect => Color::LightBlue,
+        FetchErrorKind::Body => Color::LightMagenta,
+    }
+}
+
+/// Color of status bar messages, in the bottom bar and the message history.
+pub fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Info => Color::Cyan,
+        Severity::Warning => Color::Yellow,
+        Severity::Error => Color::Red,
+    }
+}
+
+/// Prefix of marked nodes in the Node column and the selection footer.
+pub const MARKED_SYMBOL: &str = "✓";
+/// Prefix of pinned nodes in the Node column.
+pub const PINNED_SYMBOL: &str = "▲";
+
+/// What the Node column shows before the name of a node: the pinned and marked
+/// symbols, each followed by a space, or nothing.
+pub fn node_markers(app: &App, dir_path: &str) -> String {
+    let mut markers = String::new();
+    if app.pinned_nodes.contains(dir_path) {
+        markers.push_str(PINNED_SYMBOL);
+        markers.push(' ');
+    }
+    if app.marked_nodes.contains(dir_path) {
+        markers.push_str(MARKED_SYMBOL);
+        markers.push(' ');
+    }
+    markers
+}
+
+/// Flag at the end of the Node column of nodes relaying circuits for other peers.
+pub const RELAY_SYMBOL: &str = "R";
+
+/// Relay flag of a node, colored by the share of relay requests it accepts: green
+/// from 80%, yellow from 50%, red below. None when the node relays nothing.
+pub fn relay_flag(app: &App, dir_path: &str) -> Option<Span<'static>> {
+    let metrics = app
+        .node_urls
+        .get(dir_path)
+        .and_then(|url| app.node_metrics.get(url))?
+        .as_ref()
+        .ok()?;
+    let rate = metrics.relay_acceptance_rate()?;
+    let color = if rate >= 80.0 {
+        Color::Green
+    } else if rate >= 50.0 {
+        Color::Yellow
+    } else {
+        Color::Red
+    };
+    Some(Span::styled(
+        format!("{} ", RELAY_SYMBOL),
+        Style::default().fg(color),
+    ))
+}
+
+/// Symbol and color of running nodes whose metrics stopped changing, see `App::is_stale`.
+pub const STALE_SYMBOL: (&str, Color) = ("◔", Color::Yellow);
+
+/// Builds the colored status breakdown, "● Running 61 | ✖ Error 4 | ○ Stopped 15"
+/// or without the labels "● 61 ✖ 4 ○ 15". Initializing nodes are only shown while
+/// there are some, they are a transient state. So are `stale` nodes, which are also
+/// counted as running.
+pub fn status_breakdown_line(
+    counts: StatusCounts,
+    stale: usize,
+    labeled: bool,
+    colors: &ColorConfig,
+) -> Line<'static> {
+    let entry = |status: NodeStatus, count| {
+        let (symbol, color) = status_symbol(status, colors);
+        (symbol, status.label(), color, count)
+    };
+    let mut entries = vec![entry(NodeStatus::Running, counts.running)];
+    if stale > 0 {
+        let (symbol, color) = STALE_SYMBOL;
+        entries.push((symbol, "Stale", color, stale));
+    }
+    if counts.initializing > 0 {
+        entries.push(entry(NodeStatus::Initializing, counts.initializing));
+    }
+    entries.push(entry(NodeStatus::Error, counts.error));
+    entries.push(entry(NodeStatus::Stopped, counts.stopped));
+
+    let mut spans = Vec::new();
+    for (i, (symbol, label, color, count)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(
+                if labeled { " | " } else { " " },
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        let text = if labeled {
+            format!("{} {} {}", symbol, label, count)
+        } else {
+            format!("{} {}", symbol, count)
+        };
+        spans.push(Span::styled(text, Style::default().fg(color)));
+    }
+    Line::from(spans)
+}
+
 /// Returns a color based on the CPU usage percentage.
 pub fn get_cpu_color(percentage: f64) -> Color {
     if percentage >= 75.0 {
@@ -64,13 +313,13 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
     let outer_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(20), // 0: Gauges (CPU/Storage)
+            Constraint::Percentage(20), // 0: Gauges (CPU/Memory/Storage)
             Constraint::Length(2),      // 1: Spacer
             Constraint::Length(10),     // 2: Peers (Fixed width)
             Constraint::Length(2),      // 3: Spacer
             Constraint::Min(0),         // 4: Bandwidth (Expands to fill, align w/ Rx/Tx)
             Constraint::Length(2),      // 5: Spacer
-            Constraint::Length(10),     // 6: Recs/Rwds (Fixed width, align w/ Status)
+            Constraint::Length(13),     // 6: Recs/Rwds (Fixed width, align w/ Status)
         ])
         .split(area);
 
@@ -82,7 +331,11 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
     // --- 1. Gauges Rendering (Rendered into gauges_area) ---
     let gauge_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
         .split(gauges_area);
 
     // --- CPU Gauge ---
@@ -95,64 +348,79 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
     .bold();
     let cpu_gauge = Gauge::default()
         .gauge_style(Color::Black)
-        .ratio(cpu_percentage / 100.0)
+        .ratio((cpu_percentage / 100.0).clamp(0.0, 1.0)) // Summed over nodes, can exceed 100%
         .label(cpu_label);
     f.render_widget(cpu_gauge, gauge_chunks[0]);
 
-    // --- Storage Gauge ---
-    let allocated_bytes = app.total_allocated_storage;
-    let allocated_formatted = format_option_u64_bytes(Some(allocated_bytes));
-    let (storage_ratio, storage_label) = match app.total_used_storage_bytes {
-        Some(used_bytes) if allocated_bytes > 0 => {
-            let ratio = (used_bytes as f64 / allocated_bytes as f64).clamp(0.0, 1.0);
-            let used_formatted = format_option_u64_bytes(Some(used_bytes));
-            let label = Span::styled(
-                format!(
-                    "{} / {} ({:.2}%)",
-                    used_formatted,
-                    allocated_formatted,
-                    ratio * 100.0
-                ),
-                Style::default().fg(Color::Green),
+    // --- Memory Gauge ---
+    // Nodes report decimal MB
+    let memory_used_bytes = (app.total_memory_used_mb * 1_000_000.0) as u64;
+    let memory_used_formatted = format_option_u64_bytes(Some(memory_used_bytes), app.size_units);
+    let (memory_ratio, memory_text) = match app.system_memory_bytes {
+        Some(total_bytes) if total_bytes > 0 => {
+            let ratio = memory_used_bytes as f64 / total_bytes as f64;
+            let text = format!(
+                "Mem {} / {} ({:.0}%)",
+                memory_used_formatted,
+                format_option_u64_bytes(Some(total_bytes), app.size_units),
+                ratio * 100.0
             );
-            (ratio, label)
+            (ratio, text)
         }
-        Some(_) => (
-            0.0,
-            Span::styled(
-                format!("0 / {}", allocated_formatted),
-                Style::default().fg(Color::Green),
-            ),
-        ),
-        None => (
-            0.0,
-            Span::styled("Error".to_string(), Style::default().fg(Color::Red)),
-        ),
+        _ => (0.0, format!("Mem {}", memory_used_formatted)),
     };
-    let storage_gauge = Gauge::default()
+    let memory_label = Span::styled(
+        memory_text,
+        Style::default().fg(get_cpu_color(memory_ratio * 100.0)),
+    )
+    .bold();
+    let memory_gauge = Gauge::default()
         .gauge_style(Color::Black)
-        .ratio(storage_ratio)
-        .label(storage_label);
-    f.render_widget(storage_gauge, gauge_chunks[1]);
+        .ratio(memory_ratio.clamp(0.0, 1.0))
+        .label(memory_label);
+    f.render_widget(memory_gauge, gauge_chunks[1]);
+
+    // --- Storage Gauge ---
+    if app.storage_gauge == StorageGauge::Filesystem {
+        render_filesystem_gauge(f, app, gauge_chunks[2]);
+    } else {
+        render_allocation_gauge(f, app, gauge_chunks[2]);
+    }
 
     // --- 2. Peers Column Rendering (Rendered into peers_area) ---
-    let peers_text = Line::from(vec![
+    let mut peers_text = vec![Line::from(vec![
         Span::styled("Peers: ", Style::default().fg(Color::DarkGray)),
         Span::styled(
             format!("{}", app.summary_total_live_peers),
             Style::default().fg(Color::Rgb(255, 165, 0)),
         ),
-    ]);
+    ])];
+    // Nodes relaying circuits for other peers, only once there are some
+    if app.summary_relay_node_count > 0 {
+        peers_text.push(Line::from(vec![
+            Span::styled("Relays: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format!("{}", app.summary_relay_node_count),
+                Style::default().fg(Color::Cyan),
+            ),
+        ]));
+    }
     f.render_widget(
         Paragraph::new(peers_text).alignment(Alignment::Left),
         peers_area,
     );
 
     // --- 3. Bandwidth Area Rendering (Rendered into bandwidth_area) ---
-    let formatted_data_in = format_option_u64_bytes(Some(app.summary_total_data_in_bytes));
-    let formatted_data_out = format_option_u64_bytes(Some(app.summary_total_data_out_bytes));
-    let total_in_speed_str = format_speed_bps(Some(app.summary_total_in_speed));
-    let total_out_speed_str = format_speed_bps(Some(app.summary_total_out_speed));
+    let (data_in_bytes, data_out_bytes) = app.displayed_summary_bandwidth();
+    let formatted_data_in = format_option_u64_bytes(Some(data_in_bytes), app.size_units);
+    let formatted_data_out = format_option_u64_bytes(Some(data_out_bytes), app.size_units);
+    // Label the active totals mode, the label column grows to fit it
+    let (in_label_text, out_label_text, label_width) = match app.totals_mode {
+        TotalsMode::Lifetime => ("In:", "Out:", 5),
+        TotalsMode::Session => ("In (session):", "Out (session):", 15),
+    };
+    let total_in_speed_str = format_speed_bps(Some(app.summary_total_in_speed), app.size_units);
+    let total_out_speed_str = format_speed_bps(Some(app.summary_total_out_speed), app.size_units);
 
     // Get chart data
     let total_in_chart_data: Vec<(f64, f64)> = app
@@ -168,31 +436,42 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
         .map(|(i, &val)| (i as f64, val as f64))
         .collect();
 
-    let in_chart = create_summary_chart(&total_in_chart_data, Color::Cyan, "Total Rx");
-    let out_chart = create_summary_chart(&total_out_chart_data, Color::Magenta, "Total Tx");
+    let (rx_color, tx_color) = rx_tx_colors(app);
+    let in_chart =
+        create_summary_chart(&total_in_chart_data, rx_color, "Total Rx", app.chart_marker);
+    let out_chart = create_summary_chart(
+        &total_out_chart_data,
+        tx_color,
+        "Total Tx",
+        app.chart_marker,
+    );
 
     let bandwidth_layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .constraints([
+            Constraint::Length(1), // In
+            Constraint::Length(1), // Out
+            Constraint::Length(1), // Time covered by the charts
+        ])
         .split(bandwidth_area); // Use the correct area variable
 
     // --- In Row ---
     let in_row_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Length(5),  // Label "In:"
-            Constraint::Length(10), // Data (Bytes)
-            Constraint::Length(1),  // Spacer
-            Constraint::Min(1),     // Chart
-            Constraint::Length(1),  // Spacer
-            Constraint::Length(10), // Speed
+            Constraint::Length(label_width), // Label "In:"
+            Constraint::Length(10),          // Data (Bytes)
+            Constraint::Length(1),           // Spacer
+            Constraint::Min(1),              // Chart
+            Constraint::Length(1),           // Spacer
+            Constraint::Length(10),          // Speed
         ])
         .split(bandwidth_layout[0]);
 
-    let in_label = Paragraph::new("In:").alignment(Alignment::Left);
+    let in_label = Paragraph::new(in_label_text).alignment(Alignment::Left);
     f.render_widget(in_label, in_row_layout[0]);
     let in_data_para = Paragraph::new(formatted_data_in)
-        .style(Style::default().fg(Color::Cyan))
+        .style(Style::default().fg(rx_color))
         .alignment(Alignment::Right);
     f.render_widget(in_data_para, in_row_layout[1]);
     if let Some(chart) = in_chart {
@@ -206,7 +485,7 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
         );
     }
     let in_speed_para = Paragraph::new(total_in_speed_str)
-        .style(Style::default().fg(Color::Cyan))
+        .style(Style::default().fg(rx_color))
         .alignment(Alignment::Right);
     f.render_widget(in_speed_para, in_row_layout[5]);
 
@@ -214,19 +493,19 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
     let out_row_layout = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Length(5),  // Label "Out:"
-            Constraint::Length(10), // Data (Bytes)
-            Constraint::Length(1),  // Spacer
-            Constraint::Min(1),     // Chart
-            Constraint::Length(1),  // Spacer
-            Constraint::Length(10), // Speed
+            Constraint::Length(label_width), // Label "Out:"
+            Constraint::Length(10),          // Data (Bytes)
+            Constraint::Length(1),           // Spacer
+            Constraint::Min(1),              // Chart
+            Constraint::Length(1),           // Spacer
+            Constraint::Length(10),          // Speed
         ])
         .split(bandwidth_layout[1]);
 
-    let out_label = Paragraph::new("Out:").alignment(Alignment::Left);
+    let out_label = Paragraph::new(out_label_text).alignment(Alignment::Left);
     f.render_widget(out_label, out_row_layout[0]);
     let out_data_para = Paragraph::new(formatted_data_out)
-        .style(Style::default().fg(Color::Magenta))
+        .style(Style::default().fg(tx_color))
         .alignment(Alignment::Right);
     f.render_widget(out_data_para, out_row_layout[1]);
     if let Some(chart) = out_chart {
@@ -240,10 +519,50 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
         );
     }
     let out_speed_para = Paragraph::new(total_out_speed_str)
-        .style(Style::default().fg(Color::Magenta))
+        .style(Style::default().fg(tx_color))
         .alignment(Alignment::Right);
     f.render_widget(out_speed_para, out_row_layout[5]);
 
+    // --- Session Totals, under the lifetime ones (the rows above show them otherwise) ---
+    let mut session_width = 0;
+    if app.totals_mode == TotalsMode::Lifetime {
+        let (session_in, session_out) = app.rolling_bandwidth_total();
+        let session_text = Line::from(vec![
+            Span::styled("Session Rx: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format_option_u64_bytes(Some(session_in), app.size_units),
+                Style::default().fg(rx_color),
+            ),
+            Span::styled("  Tx: ", Style::default().fg(Color::DarkGray)),
+            Span::styled(
+                format_option_u64_bytes(Some(session_out), app.size_units),
+                Style::default().fg(tx_color),
+            ),
+        ]);
+        session_width = session_text.width() as u16;
+        f.render_widget(Paragraph::new(session_text), bandwidth_layout[2]);
+    }
+
+    // --- Chart Window, under the right end of the charts (now), if the session totals leave room ---
+    let window_text = format!("last {}", format_duration_human(app.bandwidth_chart_span()));
+    let chart_end = out_row_layout[3].x + out_row_layout[3].width;
+    let window_x = out_row_layout[3]
+        .x
+        .max(bandwidth_layout[2].x + session_width + 1);
+    if window_x + window_text.len() as u16 <= chart_end {
+        f.render_widget(
+            Paragraph::new(window_text)
+                .style(Style::default().fg(Color::DarkGray))
+                .alignment(Alignment::Right),
+            Rect::new(
+                window_x,
+                bandwidth_layout[2].y,
+                chart_end - window_x,
+                bandwidth_layout[2].height,
+            ),
+        );
+    }
+
     // --- 4. Recs/Rwds Column Rendering (Rendered into recs_rwds_area) ---
     let recs_rwds_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -275,29 +594,117 @@ pub fn render_summary_gauges(f: &mut Frame, app: &App, area: Rect) {
     );
 }
 
+// Storage gauge of `StorageGauge::Allocation`: record stores against the space allocated per node
+fn render_allocation_gauge(f: &mut Frame, app: &App, area: Rect) {
+    let allocated_bytes = app.total_allocated_storage;
+    let allocated_formatted = format_option_u64_bytes(Some(allocated_bytes), app.size_units);
+    let (storage_ratio, storage_label) = match app.total_used_storage_bytes {
+        Some(used_bytes) if allocated_bytes > 0 => {
+            let ratio = (used_bytes as f64 / allocated_bytes as f64).clamp(0.0, 1.0);
+            let used_formatted = format_option_u64_bytes(Some(used_bytes), app.size_units);
+            let label = Span::styled(
+                format!(
+                    "{} / {} ({:.2}%)",
+                    used_formatted,
+                    allocated_formatted,
+                    ratio * 100.0
+                ),
+                Style::default().fg(app.color_config.running),
+            );
+            (ratio, label)
+        }
+        Some(_) => (
+            0.0,
+            Span::styled(
+                format!("0 / {}", allocated_formatted),
+                Style::default().fg(app.color_config.running),
+            ),
+        ),
+        None => (
+            0.0,
+            Span::styled(
+                "Error".to_string(),
+                Style::default().fg(app.color_config.error),
+            ),
+        ),
+    };
+    let storage_gauge = Gauge::default()
+        .gauge_style(Color::Black)
+        .ratio(storage_ratio)
+        .label(storage_label);
+    f.render_widget(storage_gauge, area);
+}
+
+// Storage gauge of `StorageGauge::Filesystem`: the fullest filesystem holding record stores,
+// colored like the CPU gauge as it fills up
+fn render_filesystem_gauge(f: &mut Frame, app: &App, area: Rect) {
+    let (ratio, label) = match app.filesystem_usage {
+        Some(usage) => {
+            let ratio = (usage.used_bytes as f64 / usage.total_bytes as f64).clamp(0.0, 1.0);
+            let fullest = if usage.filesystems > 1 {
+                format!(", fullest of {}", usage.filesystems)
+            } else {
+                String::new()
+            };
+            let label = Span::styled(
+                format!(
+                    "Disk {} / {} ({:.0}%{})",
+                    format_option_u64_bytes(Some(usage.used_bytes), app.size_units),
+                    format_option_u64_bytes(Some(usage.total_bytes), app.size_units),
+                    ratio * 100.0,
+                    fullest
+                ),
+                Style::default().fg(get_cpu_color(ratio * 100.0)),
+            );
+            (ratio, label)
+        }
+        None => (
+            0.0,
+            Span::styled(
+                "Disk unknown".to_string(),
+                Style::default().fg(app.color_config.error),
+            ),
+        ),
+    };
+    let storage_gauge = Gauge::default()
+        .gauge_style(Color::Black)
+        .ratio(ratio)
+        .label(label.bold());
+    f.render_widget(storage_gauge, area);
+}
+
 // Helper function to create summary charts consistently
-fn create_summary_chart<'a>(
+pub fn create_summary_chart<'a>(
     data: &'a [(f64, f64)],
     color: Color,
     name: &'a str,
+    marker: ChartMarker,
 ) -> Option<Chart<'a>> {
     if data.len() < 2 {
         // Not enough data to draw a line
         return None;
     }
 
-    let max_len = data.len();
+    // X values are history indices, some may be missing (see `GapFill::Gap`)
+    let max_x = data
+        .iter()
+        .map(|&(x, _)| x)
+        .fold(0.0f64, |max, x| max.max(x));
     let max_y = data
         .iter()
         .map(|&(_, y)| y)
         .fold(0.0f64, |max, y| max.max(y));
 
-    let x_bounds = [0.0, (max_len.saturating_sub(1)).max(1) as f64];
+    let x_bounds = [0.0, max_x.max(1.0)];
     let y_bounds = [0.0, max_y.max(1.0)];
 
     let dataset = Dataset::default()
         .name(name)
-        .marker(symbols::Marker::Braille)
+        .marker(match marker {
+            ChartMarker::Braille => symbols::Marker::Braille,
+            ChartMarker::Dot => symbols::Marker::Dot,
+            ChartMarker::Block => symbols::Marker::Block,
+        })
         .graph_type(GraphType::Line)
         .style(Style::default().fg(color))
         .data(data);
@@ -320,102 +727,259 @@ fn create_summary_chart<'a>(
     Some(chart)
 }
 
-/// Renders the header row with column titles.
-pub fn render_header(f: &mut Frame, area: Rect) {
-    let header_column_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(COLUMN_CONSTRAINTS) // Use the NEW constraints (14 total)
-        .split(area);
-
-    // Render original titles with spacing added manually
-    for (i, title) in HEADER_TITLES.iter().enumerate() {
-        let chunk_index = i;
-        let is_last_data_col = i == HEADER_TITLES.len() - 1; // Check if it's the last *data* title ("Err")
-
-        if chunk_index < header_column_chunks.len() {
-            let alignment = if i == 0 {
-                Alignment::Left // Node title left-aligned
-            } else {
-                Alignment::Right // Other titles right-aligned
-            };
-            // Add a space for separation after each title, unless it's the last data col
-            let title_text = if !is_last_data_col {
-                format!("{} ", title)
-            } else {
-                title.to_string()
-            };
-            let title_paragraph = Paragraph::new(title_text)
-                .style(HEADER_STYLE)
-                .alignment(alignment);
-            f.render_widget(title_paragraph, header_column_chunks[chunk_index]);
-        }
+/// Colors of the Rx and Tx values and sparklines, cyan and magenta unless swapped with `c`.
+fn rx_tx_colors(app: &App) -> (Color, Color) {
+    if app.swap_rx_tx_colors {
+        (Color::Magenta, Color::Cyan)
+    } else {
+        (Color::Cyan, Color::Magenta)
     }
+}
 
-    // Render Rx, Tx, Status titles (Indices 10, 12, 13)
-    let rx_index = 10;
-    let tx_index = 12;
-    let status_index = 13;
+/// Width of the availability timeline column: a space then one cell per bucket.
+const TIMELINE_COLUMN_WIDTH: u16 = TIMELINE_BUCKETS as u16 + 1;
+// Cells of a full traffic bar, the busiest node
+const TRAFFIC_BAR_WIDTH: usize = 10;
+/// Width of the traffic column: a space then the bar.
+const TRAFFIC_COLUMN_WIDTH: u16 = TRAFFIC_BAR_WIDTH as u16 + 1;
+// Bar cells filled by one to eight eighths
+const BAR_EIGHTHS: [&str; 8] = ["▏", "▎", "▍", "▌", "▋", "▊", "▉", "█"];
 
-    if rx_index < header_column_chunks.len() {
-        let rx_title_paragraph = Paragraph::new("Rx ")
-            .style(HEADER_STYLE)
-            .alignment(Alignment::Center);
-        f.render_widget(rx_title_paragraph, header_column_chunks[rx_index]);
-    }
+// Positions of the node table columns with more than a plain value, among the
+// constraints of `build_column_constraints` (Rx and Tx come after their spacer)
+const PEERS_COLUMN: usize = 4;
+const ERR_COLUMN: usize = 8;
+const RX_COLUMN: usize = 10;
+const TX_COLUMN: usize = 12;
 
-    if tx_index < header_column_chunks.len() {
-        let tx_title_paragraph = Paragraph::new("Tx ")
-            .style(HEADER_STYLE)
-            .alignment(Alignment::Center);
-        f.render_widget(tx_title_paragraph, header_column_chunks[tx_index]);
+/// Column widths of the node table for a terminal `terminal_width` wide, with a Node
+/// column `node_width` wide, the traffic bars before Status and the availability
+/// timeline last when shown.
+fn table_widths(app: &App, terminal_width: u16, node_width: u16) -> Vec<Constraint> {
+    let mut columns = ColumnId::DEFAULT.to_vec();
+    if app.show_traffic_bars {
+        columns.insert(columns.len() - 1, ColumnId::Traffic);
+    }
+    if app.show_timeline {
+        columns.push(ColumnId::Timeline);
     }
+    let mut widths = build_column_constraints(terminal_width, &columns, app.view_mode);
+    widths[0] = Constraint::Length(node_width);
+    widths
+}
+
+/// Splits an Rx or Tx column into its total, sparkline and speed parts. At any width
+/// there are exactly five, some may be empty on a tiny terminal.
+fn bandwidth_parts(column: Rect) -> [Rect; 5] {
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Length(10), // Total Bytes, fits "123.45 GiB"
+            Constraint::Length(1),  // Spacer
+            Constraint::Min(1),     // Chart
+            Constraint::Length(1),  // Spacer
+            Constraint::Length(12), // Speed, fits "123.45 KiB/s"
+        ])
+        .areas(column)
+}
+
+/// Node table columns, laid out once per frame on the header row: every node row
+/// has the same, only their `y` differs.
+struct TableLayout {
+    columns: Rc<[Rect]>,
+    rx_parts: [Rect; 5],     // Rx total, spacer, chart, spacer, speed
+    tx_parts: [Rect; 5],     // Tx total, spacer, chart, spacer, speed
+    err_chart: Option<Rect>, // Error trend sparkline left of the count, detailed view only
+}
 
-    if status_index < header_column_chunks.len() {
-        let status_title_paragraph = Paragraph::new("Status")
-            .style(HEADER_STYLE)
-            .alignment(Alignment::Right);
-        f.render_widget(status_title_paragraph, header_column_chunks[status_index]);
+impl TableLayout {
+    fn new(app: &App, header_area: Rect, widths: &[Constraint]) -> TableLayout {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(widths.to_vec())
+            .split(header_area);
+        let err_chart = (app.view_mode == ViewMode::Detailed).then(|| {
+            let [chart, _spacer, _count] = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Min(1),    // Chart
+                    Constraint::Length(1), // Spacer
+                    Constraint::Length(7), // Count
+                ])
+                .areas(columns[ERR_COLUMN]);
+            chart
+        });
+        TableLayout {
+            rx_parts: bandwidth_parts(columns[RX_COLUMN]),
+            tx_parts: bandwidth_parts(columns[TX_COLUMN]),
+            err_chart,
+            columns,
+        }
     }
 }
 
-/// Renders a single node's data row, including text cells and bandwidth charts.
-pub fn render_node_row(
+/// Renders the node table: the header, then one row per node of `dir_paths` (the
+/// rows scrolled into view), with the `selected` one of them highlighted. The text
+/// goes through a `Table`, the sparklines are drawn over their cells afterwards.
+pub fn render_node_table(
     f: &mut Frame,
     app: &App,
     area: Rect,
-    dir_path: &str,
-    url_option: Option<&String>,
+    dir_paths: &[String],
+    selected: Option<usize>,
+    node_width: u16,
 ) {
-    let column_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(COLUMN_CONSTRAINTS) // Use the NEW constraints (14 total)
-        .split(area);
+    let widths = table_widths(app, f.size().width, node_width);
+    let layout = TableLayout::new(app, Rect { height: 1, ..area }, &widths);
+
+    let rows: Vec<Row> = dir_paths
+        .iter()
+        .map(|dir_path| node_row(app, &layout, dir_path))
+        .collect();
+    // Cells only set their foreground, the selected row keeps its background under them
+    let table = Table::new(rows, widths)
+        .header(header_row(app, &layout))
+        .column_spacing(0)
+        .highlight_style(SELECTED_ROW_STYLE);
+    f.render_stateful_widget(
+        table,
+        area,
+        &mut TableState::default().with_selected(selected),
+    );
+
+    for (i, dir_path) in dir_paths.iter().enumerate() {
+        let y = area.y + 1 + i as u16; // Below the header
+        if y >= area.bottom() {
+            break;
+        }
+        render_row_charts(f, app, &layout, y, dir_path);
+
+        // A node that just went down flashes inverted
+        if app.is_row_flashing(dir_path) {
+            f.buffer_mut().set_style(
+                Rect {
+                    y,
+                    height: 1,
+                    ..area
+                },
+                Style::default().add_modifier(Modifier::REVERSED),
+            );
+        }
+    }
+}
+
+/// Header row with the column titles.
+fn header_row(app: &App, layout: &TableLayout) -> Row<'static> {
+    // Centered like a Paragraph would, which rounds the other way than a Line
+    let centered = |title: &str, column: usize| {
+        let offset = (layout.columns[column].width / 2).saturating_sub(title.width() as u16 / 2);
+        Cell::from(format!("{:offset$}{}", "", title, offset = offset as usize))
+    };
+    let mut cells: Vec<Cell> = HEADER_TITLES
+        .iter()
+        .enumerate()
+        .map(|(i, title)| {
+            // Add a space for separation after each title, unless it's the last data col.
+            // Peers sits over the values, left of their rates.
+            if i == 0 {
+                Line::from(format!("{} ", title))
+            } else if i == PEERS_COLUMN {
+                Line::from(format!("{} {:PEER_RATE_WIDTH$} ", title, ""))
+                    .alignment(Alignment::Right)
+            } else if i != ERR_COLUMN {
+                Line::from(format!("{} ", title)).alignment(Alignment::Right)
+            } else {
+                Line::from(*title).alignment(Alignment::Right)
+            }
+            .into()
+        })
+        .collect();
+    cells.extend([
+        Cell::default(), // Spacer 1
+        centered("Rx ", RX_COLUMN),
+        Cell::default(), // Spacer 2
+        centered("Tx ", TX_COLUMN),
+    ]);
+    if app.show_traffic_bars {
+        cells.push(Line::from(" Traffic").into()); // Over the start of the bars
+    }
+    cells.push(Line::from("Status").alignment(Alignment::Right).into());
+    // The timeline title tells the time it covers, e.g. "Up 1m"
+    if app.show_timeline {
+        let title = format!("Up {}", format_duration_human(app.timeline_span()));
+        cells.push(Line::from(title).alignment(Alignment::Right).into());
+    }
+    Row::new(cells).style(HEADER_STYLE)
+}
+
+/// A node's row of text cells. The Rx/Tx and detailed Err cells leave room for the
+/// sparklines `render_row_charts` draws over them.
+fn node_row(app: &App, layout: &TableLayout, dir_path: &str) -> Row<'static> {
+    let url_option = app.node_urls.get(dir_path);
+    let node_column_width = layout.columns[0].width;
+    // Pinned and marked nodes start with their symbols, taken from the room for the name,
+    // as does the relay flag at the end
+    let marker = node_markers(app, dir_path);
+    let relay = relay_flag(app, dir_path);
+    let relay_width = relay.as_ref().map_or(0, |flag| flag.width());
+    let name_width = (node_column_width.saturating_sub(NODE_COLUMN_PADDING) as usize)
+        .saturating_sub(marker.width() + relay_width);
+    let node_name = marker.clone()
+        + &if app.show_full_paths {
+            truncate_start_to_width(dir_path, name_width) // The end of a path tells nodes apart
+        } else {
+            truncate_to_width(&app.node_display_name(dir_path), name_width)
+        };
 
     // Determine metrics, status text, and style based on URL presence and metrics map
-    let (cells, status_text, status_style, metrics_option) = match url_option {
+    let (cells, status_text, status_style, metrics) = match url_option {
         Some(url) => {
             // URL exists, try to get metrics
             match app.node_metrics.get(url) {
+                Some(Ok(metrics)) if app.is_stale(url) => (
+                    create_list_item_cells(
+                        &node_name,
+                        metrics,
+                        app.notation,
+                        app.error_baseline.get(url),
+                    ),
+                    "Stale".to_string(),
+                    Style::default().fg(STALE_SYMBOL.1),
+                    Some(metrics),
+                ),
                 Some(Ok(metrics)) => (
-                    create_list_item_cells(dir_path, metrics),
+                    create_list_item_cells(
+                        &node_name,
+                        metrics,
+                        app.notation,
+                        app.error_baseline.get(url),
+                    ),
                     "Running".to_string(),
-                    Style::default().fg(Color::Green),
-                    Some(Ok(metrics)), // Pass the successful metrics result
-                ),
-                Some(Err(e)) => (
-                    create_placeholder_cells(dir_path),
-                    // Display the first part of the error message as status
-                    e.split_whitespace().next().unwrap_or("Error").to_string(),
-                    Style::default().fg(Color::Red),
-                    Some(Err(e)), // Pass the error result
+                    Style::default().fg(app.color_config.running),
+                    Some(metrics),
                 ),
+                Some(Err(e)) => match app.fetch_error_kinds.get(url) {
+                    Some(&kind) => (
+                        create_placeholder_cells(&node_name),
+                        kind.label(),
+                        Style::default().fg(fetch_error_color(kind, &app.color_config)),
+                        None,
+                    ),
+                    None => (
+                        create_placeholder_cells(&node_name),
+                        // Display the first part of the error message as status
+                        e.split_whitespace().next().unwrap_or("Error").to_string(),
+                        Style::default().fg(app.color_config.error),
+                        None,
+                    ),
+                },
                 None => {
                     // URL exists but no entry in metrics map yet (should be rare after init)
                     (
-                        create_placeholder_cells(dir_path),
+                        create_placeholder_cells(&node_name),
                         "Initializing".to_string(),
                         Style::default().fg(Color::Yellow),
-                        None, // No metrics result available
+                        None,
                     )
                 }
             }
@@ -423,169 +987,536 @@ pub fn render_node_row(
         None => {
             // No URL found for this directory path
             (
-                create_placeholder_cells(dir_path),
+                create_placeholder_cells(&node_name),
                 "Stopped".to_string(),
-                Style::default().fg(Color::DarkGray),
-                None, // No metrics result available
+                Style::default().fg(app.color_config.stopped),
+                None,
             )
         }
     };
+    // Nodes that don't answer show the CPU and memory of their process when it runs,
+    // otherwise the state of their unit, see `--systemd`
+    let process = app.node_process(dir_path);
+    let mut cells = cells;
+    if let Some(process) = process {
+        cells[2] = format!("{:.1}MB", process.memory_mb());
+        cells[3] = format!("{:.2}%", process.cpu_percent);
+    }
+    let (status_text, status_style) = match app.unit_state(dir_path) {
+        _ if process.is_some() => ("No metrics".to_string(), Style::default().fg(Color::Yellow)),
+        Some(state) => (
+            format!("unit: {}", short_unit_state(state)),
+            Style::default().fg(match state {
+                "failed" => app.color_config.error,
+                "inactive" | "not-found" => app.color_config.stopped,
+                _ => Color::Yellow, // Active, starting or stopping but not answering
+            }),
+        ),
+        None => (status_text, status_style),
+    };
 
-    // --- Render Rx/Tx Columns (Indices 10, 12) --- Get data first ---
-    let (
-        cpu_usage_percentage_opt,
-        chart_data_in,
-        chart_data_out,
-        speed_in_bps,
-        speed_out_bps,
-        total_in_bytes,
-        total_out_bytes,
-    ) = metrics_option // Use the metrics_option determined above
-        .and_then(|res| res.ok()) // Get NodeMetrics only if the result was Ok
-        .map_or((None, None, None, None, None, None, None), |m| {
-            (
-                Some(m.cpu_usage_percentage),
-                m.chart_data_in.as_deref(),
-                m.chart_data_out.as_deref(),
-                m.speed_in_bps,
-                m.speed_out_bps,
-                m.bandwidth_inbound_bytes,
-                m.bandwidth_outbound_bytes,
+    let memory_leak = metrics.is_some_and(|m| m.memory_leak_mb_per_min.is_some());
+    let cpu_style = match metrics.and_then(|m| m.cpu_percent()) {
+        Some(percent) => Style::default().fg(get_cpu_color(percent)),
+        None => DATA_CELL_STYLE,
+    };
+
+    // --- Data Cells (Indices 0..=8) ---
+    let mut row_cells: Vec<Cell> = cells
+        .into_iter()
+        .enumerate()
+        .map(|(i, cell_content)| match i {
+            // Relays and fleet outliers get a flag at the end of the Node column
+            0 if relay.is_some() || app.anomalies.contains_key(dir_path) => {
+                let mut flags: Vec<Span> = relay.clone().into_iter().collect();
+                if app.anomalies.contains_key(dir_path) {
+                    flags.push(Span::styled("⚑ ", Style::default().fg(Color::Yellow)));
+                }
+                let flags_width: usize = flags.iter().map(Span::width).sum();
+                let padding =
+                    (node_column_width as usize).saturating_sub(cell_content.width() + flags_width);
+                let mut spans = vec![Span::raw(cell_content), Span::raw(" ".repeat(padding))];
+                spans.extend(flags);
+                Cell::from(Line::from(spans)).style(DATA_CELL_STYLE)
+            }
+            0 => Cell::from(format!("{} ", cell_content)).style(DATA_CELL_STYLE),
+            2 if memory_leak => Cell::from(
+                Line::from(format!("↑{} ", cell_content)).alignment(Alignment::Right), // Possible memory leak
             )
-        });
+            .style(Style::default().fg(Color::Red)),
+            3 => Cell::from(Line::from(format!("{} ", cell_content)).alignment(Alignment::Right))
+                .style(cpu_style),
+            // Peers: the value, then its rate of change colored by direction
+            PEERS_COLUMN => {
+                let rate_text = metrics
+                    .and_then(|m| m.peer_count_rate)
+                    .map(format_change_rate)
+                    .unwrap_or_default();
+                let rate_color = match rate_text.chars().next() {
+                    Some('+') => Color::Green,
+                    Some('-') => Color::Red,
+                    _ => Color::DarkGray, // Stable
+                };
+                Cell::from(
+                    Line::from(vec![
+                        Span::raw(format!("{} ", cell_content)),
+                        Span::styled(
+                            format!("{:>PEER_RATE_WIDTH$} ", rate_text),
+                            Style::default().fg(rate_color),
+                        ),
+                    ])
+                    .alignment(Alignment::Right),
+                )
+                .style(DATA_CELL_STYLE)
+            }
+            // No space after the Err column, its sparkline goes on the left in the detailed view
+            ERR_COLUMN => Cell::from(Line::from(cell_content).alignment(Alignment::Right))
+                .style(DATA_CELL_STYLE),
+            _ => Cell::from(Line::from(format!("{} ", cell_content)).alignment(Alignment::Right))
+                .style(DATA_CELL_STYLE),
+        })
+        .collect();
 
-    let formatted_total_in = format_option_u64_bytes(total_in_bytes);
-    let formatted_total_out = format_option_u64_bytes(total_out_bytes);
-    let formatted_speed_in = format_speed_bps(speed_in_bps);
-    let formatted_speed_out = format_speed_bps(speed_out_bps);
-
-    // --- Render Data Cells (Indices 0..=8) ---
-    for (i, cell_content) in cells.iter().enumerate() {
-        let chunk_index = i;
-        if chunk_index < column_layout.len() {
-            let alignment = if i == 0 {
-                Alignment::Left
-            } else {
-                Alignment::Right
-            };
+    // --- Rx/Tx Columns (Indices 10, 12): totals and speeds around the sparklines ---
+    // Lifetime counters or bytes since antop started, depending on the totals mode
+    let (total_in, total_out) = match (url_option, metrics) {
+        (Some(url), Some(m)) => app.displayed_bandwidth(url, m),
+        _ => (None, None),
+    };
+    let (rx_color, tx_color) = rx_tx_colors(app);
+    row_cells.extend([
+        Cell::default(), // Spacer 1
+        bandwidth_cell(
+            format_option_u64_bytes(total_in, app.size_units),
+            format_speed_bps(metrics.and_then(|m| m.speed_in_bps), app.size_units),
+            &layout.rx_parts,
+            rx_color,
+        ),
+        Cell::default(), // Spacer 2
+        bandwidth_cell(
+            format_option_u64_bytes(total_out, app.size_units),
+            format_speed_bps(metrics.and_then(|m| m.speed_out_bps), app.size_units),
+            &layout.tx_parts,
+            tx_color,
+        ),
+    ]);
 
-            // Determine style: special for CPU (index 3), default otherwise
-            let style = if i == 3 {
-                // Index 3 is CPU
-                match cpu_usage_percentage_opt {
-                    Some(Some(percent)) => Style::default().fg(get_cpu_color(percent)), // Inner Option is Some(f64)
-                    Some(None) => DATA_CELL_STYLE, // Inner Option is None (metric exists but CPU is None)
-                    None => DATA_CELL_STYLE,       // Outer Option is None (no metrics result)
+    // --- Traffic Bar (left of Status) ---
+    if app.show_traffic_bars {
+        row_cells.push(traffic_bar(app, metrics).alignment(Alignment::Right).into());
+    }
+
+    // --- Status Column ---
+    row_cells
+        .push(Cell::from(Line::from(status_text).alignment(Alignment::Right)).style(status_style));
+
+    // --- Availability Timeline (right of Status) ---
+    if app.show_timeline {
+        let cells: Vec<Span> = app
+            .availability_timeline(dir_path)
+            .into_iter()
+            .map(|availability| match availability {
+                Availability::Up => {
+                    Span::styled("█", Style::default().fg(app.color_config.running))
                 }
-            } else {
-                // Other columns use default data style
-                DATA_CELL_STYLE
-            };
+                Availability::Down => {
+                    Span::styled("█", Style::default().fg(app.color_config.error))
+                }
+                Availability::Partial => Span::styled("█", Style::default().fg(Color::Yellow)),
+                Availability::Unknown => Span::styled("·", Style::default().fg(Color::DarkGray)),
+            })
+            .collect();
+        row_cells.push(Line::from(cells).alignment(Alignment::Right).into());
+    }
 
-            // Add space suffix EXCEPT for the Err column (index 8)
-            let cell_text = if i != 8 {
-                format!("{} ", cell_content)
-            } else {
-                cell_content.clone()
-            };
+    Row::new(row_cells)
+}
 
-            let cell_paragraph = Paragraph::new(cell_text).style(style).alignment(alignment);
-            f.render_widget(cell_paragraph, column_layout[chunk_index]);
+/// Bar of the Rx + Tx speed of a node, the busiest node filling all of it, in eighths
+/// of a cell. Each cell takes the color of the direction most of it stands for.
+fn traffic_bar(app: &App, metrics: Option<&NodeMetrics>) -> Line<'static> {
+    let max = app.summary_max_node_speed;
+    let Some(metrics) = metrics.filter(|_| max > 0.0) else {
+        return Line::default();
+    };
+    let eighths = |speed: f64| (speed / max * (TRAFFIC_BAR_WIDTH * 8) as f64).round() as usize;
+    let total = eighths(metrics.total_speed_bps()).min(TRAFFIC_BAR_WIDTH * 8);
+    let rx = eighths(metrics.speed_in_bps.unwrap_or(0.0));
+    let (rx_color, tx_color) = rx_tx_colors(app);
+    let mut spans: Vec<Span> = (0..TRAFFIC_BAR_WIDTH * 8)
+        .step_by(8)
+        .take_while(|&start| start < total)
+        .map(|start| {
+            let filled = (total - start).min(8);
+            let color = if start + 4 < rx { rx_color } else { tx_color };
+            Span::styled(BAR_EIGHTHS[filled - 1], Style::default().fg(color))
+        })
+        .collect();
+    // Left aligned in the column, like a gauge
+    let padding = TRAFFIC_BAR_WIDTH - spans.len();
+    spans.push(Span::raw(" ".repeat(padding)));
+    Line::from(spans)
+}
+
+/// Rx or Tx cell: the total on the left and the speed on the right of `parts`,
+/// with blanks where the sparkline goes.
+fn bandwidth_cell(total: String, speed: String, parts: &[Rect; 5], color: Color) -> Cell<'static> {
+    let [total_area, spacer_left, chart, spacer_right, speed_area] = parts;
+    let text = [
+        right_align_to_width(&total, total_area.width as usize),
+        " ".repeat((spacer_left.width + chart.width + spacer_right.width) as usize),
+        right_align_to_width(&speed, speed_area.width as usize),
+    ]
+    .concat();
+    Cell::from(text).style(Style::default().fg(color))
+}
+
+/// Draws the sparklines of a node row at line `y`: Rx, Tx and, in the detailed
+/// view, the error trend. A placeholder stands in for Rx/Tx charts with too few points.
+fn render_row_charts(f: &mut Frame, app: &App, layout: &TableLayout, y: u16, dir_path: &str) {
+    let metrics = app
+        .node_urls
+        .get(dir_path)
+        .and_then(|url| app.node_metrics.get(url))
+        .and_then(|result| result.as_ref().ok());
+    let (rx_color, tx_color) = rx_tx_colors(app);
+
+    let charts = [
+        (
+            metrics.and_then(|m| m.chart_data_in.as_deref()),
+            rx_color,
+            "Rx",
+            Some(layout.rx_parts[2]),
+            true,
+        ),
+        (
+            metrics.and_then(|m| m.chart_data_out.as_deref()),
+            tx_color,
+            "Tx",
+            Some(layout.tx_parts[2]),
+            true,
+        ),
+        (
+            metrics.and_then(|m| m.chart_data_errors.as_deref()),
+            Color::Red,
+            "Err",
+            layout.err_chart,
+            false, // The count is enough
+        ),
+    ];
+    for (data, color, name, area, placeholder) in charts {
+        let Some(area) = area.map(|area| Rect { y, ..area }) else {
+            continue;
+        };
+        match data.and_then(|data| create_summary_chart(data, color, name, app.chart_marker)) {
+            Some(chart) => f.render_widget(chart, area),
+            None if placeholder => f.render_widget(
+                Paragraph::new("-")
+                    .style(DATA_CELL_STYLE)
+                    .alignment(Alignment::Center),
+                area,
+            ),
+            None => {}
         }
     }
+}
 
-    // --- Rx Column Rendering (Index 10) ---
-    let rx_col_index = 10;
-    if rx_col_index < column_layout.len() {
-        // Restore original internal layout for Rx
-        let rx_col_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Length(8), // Total Bytes
-                Constraint::Length(1), // Spacer
-                Constraint::Min(1),    // Chart
-                Constraint::Length(1), // Spacer
-                Constraint::Length(8), // Speed
-            ])
-            .split(column_layout[rx_col_index]);
+/// Rows taken by the fleet panel, borders included.
+pub const FLEET_PANEL_HEIGHT: u16 = PEERS_HISTOGRAM_BUCKETS.len() as u16 + 2;
+
+/// Renders the fleet panel: the live peers histogram on the left, the records and
+/// rewards growth charts on the right.
+pub fn render_fleet_panel(f: &mut Frame, app: &App, area: Rect) {
+    let halves = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    render_peers_histogram(f, app, halves[0]);
+    render_growth_charts(f, app, halves[1]);
+}
+
+// How many nodes fall in each live peers bucket, as horizontal bars scaled to the
+// largest bucket
+fn render_peers_histogram(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .title(Span::styled(
+            " Live peers per node ",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
 
-        // Render widgets into correct chunks (0, 1, 2)
-        let total_in_para = Paragraph::new(formatted_total_in)
-            .style(Style::default().fg(Color::Cyan))
-            .alignment(Alignment::Right);
-        f.render_widget(total_in_para, rx_col_layout[0]); // Bytes in chunk 0
+    // "10-50 ████████ 12": label, bar, count
+    const LABEL_WIDTH: usize = 7;
+    const COUNT_WIDTH: usize = 6;
+    let bar_width = (inner_area.width as usize).saturating_sub(LABEL_WIDTH + COUNT_WIDTH);
+    let max_count = app
+        .peers_histogram
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(0)
+        .max(1);
 
-        if let Some(data) = chart_data_in {
-            if let Some(chart) = create_summary_chart(data, Color::Cyan, "Rx") {
-                f.render_widget(chart, rx_col_layout[2]); // Chart in chunk 2 (was 1)
+    let lines: Vec<Line> = PEERS_HISTOGRAM_BUCKETS
+        .iter()
+        .zip(app.peers_histogram)
+        .map(|((label, _), count)| {
+            let bar_len = count * bar_width / max_count;
+            // Poorly connected nodes stand out in red
+            let color = if *label == PEERS_HISTOGRAM_BUCKETS[0].0 && count > 0 {
+                Color::Red
             } else {
-                let placeholder = Paragraph::new("-")
-                    .style(DATA_CELL_STYLE)
-                    .alignment(Alignment::Center);
-                f.render_widget(placeholder, rx_col_layout[2]); // Placeholder in chunk 2 (was 1)
-            }
-        } else {
-            let placeholder = Paragraph::new("-")
-                .style(DATA_CELL_STYLE)
-                .alignment(Alignment::Center);
-            f.render_widget(placeholder, rx_col_layout[2]); // Placeholder in chunk 2 (was 1)
-        }
+                Color::Green
+            };
+            Line::from(vec![
+                Span::styled(format!("{:<LABEL_WIDTH$}", label), HEADER_STYLE),
+                Span::styled("█".repeat(bar_len), Style::default().fg(color)),
+                Span::styled(format!(" {}", count), DATA_CELL_STYLE),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner_area);
+}
 
-        let speed_in_para = Paragraph::new(formatted_speed_in)
-            .style(Style::default().fg(Color::Cyan))
-            .alignment(Alignment::Right);
-        f.render_widget(speed_in_para, rx_col_layout[4]); // Speed in chunk 4 (was 2)
-    }
+// Fleet records and rewards totals over the last updates, each charted from its lowest
+// value so the growth shows, with the gain since the first point on the right
+fn render_growth_charts(f: &mut Frame, app: &App, area: Rect) {
+    let span = format_duration_human(app.tick_rate * GROWTH_HISTORY_LENGTH as u32);
+    let block = Block::default()
+        .title(Span::styled(
+            format!(" Fleet growth, last {} ", span),
+            Style::default().fg(Color::DarkGray),
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
 
-    // --- Tx Column Rendering (Index 12) ---
-    let tx_col_index = 12;
-    if tx_col_index < column_layout.len() {
-        // Restore original internal layout for Tx
-        let tx_col_layout = Layout::default()
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .split(inner_area);
+    let charts = [
+        ("Recs", &app.records_history, Color::Green),
+        ("Rwds", &app.rewards_history, Color::Yellow),
+    ];
+    for ((name, history, color), row) in charts.into_iter().zip(rows.iter()) {
+        let row_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
-                Constraint::Length(8), // Total Bytes
-                Constraint::Length(1), // Spacer
-                Constraint::Min(1),    // Chart
-                Constraint::Length(1), // Spacer
-                Constraint::Length(8), // Speed
+                Constraint::Length(5),  // Label
+                Constraint::Min(1),     // Chart
+                Constraint::Length(1),  // Spacer
+                Constraint::Length(10), // Gain
             ])
-            .split(column_layout[tx_col_index]);
+            .split(*row);
+        let lowest = history.iter().copied().min().unwrap_or(0);
+        let gain = match (history.front(), history.back()) {
+            (Some(first), Some(last)) => last.saturating_sub(*first),
+            _ => 0,
+        };
+        let chart_data: Vec<(f64, f64)> = history
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| (i as f64, (value - lowest) as f64))
+            .collect();
 
-        // Render widgets into correct chunks (0, 1, 2)
-        let total_out_para = Paragraph::new(formatted_total_out)
-            .style(Style::default().fg(Color::Magenta))
-            .alignment(Alignment::Right);
-        f.render_widget(total_out_para, tx_col_layout[0]); // Bytes in chunk 0
-
-        if let Some(data) = chart_data_out {
-            if let Some(chart) = create_summary_chart(data, Color::Magenta, "Tx") {
-                f.render_widget(chart, tx_col_layout[2]); // Chart in chunk 2 (was 1)
-            } else {
-                let placeholder = Paragraph::new("-")
-                    .style(DATA_CELL_STYLE)
-                    .alignment(Alignment::Center);
-                f.render_widget(placeholder, tx_col_layout[2]); // Placeholder in chunk 2 (was 1)
-            }
-        } else {
-            let placeholder = Paragraph::new("-")
-                .style(DATA_CELL_STYLE)
-                .alignment(Alignment::Center);
-            f.render_widget(placeholder, tx_col_layout[2]); // Placeholder in chunk 2 (was 1)
+        f.render_widget(Paragraph::new(name).style(HEADER_STYLE), row_layout[0]);
+        match create_summary_chart(&chart_data, color, name, app.chart_marker) {
+            Some(chart) => f.render_widget(chart, row_layout[1]),
+            None => f.render_widget(Paragraph::new("-").style(DATA_CELL_STYLE), row_layout[1]),
         }
+        f.render_widget(
+            Paragraph::new(format!("+{}", format_count_grouped(gain)))
+                .style(Style::default().fg(color))
+                .alignment(Alignment::Right),
+            row_layout[3],
+        );
+    }
+}
 
-        let speed_out_para = Paragraph::new(formatted_speed_out)
-            .style(Style::default().fg(Color::Magenta))
-            .alignment(Alignment::Right);
-        f.render_widget(speed_out_para, tx_col_layout[4]); // Speed in chunk 4 (was 2)
+/// Renders the footer summing the marked nodes: their count, then CPU, memory,
+/// bandwidth and records of the ones answering.
+pub fn render_marked_footer(f: &mut Frame, app: &App, area: Rect) {
+    let totals = app.marked_totals();
+    let label = Style::default().fg(Color::DarkGray);
+    let value = Style::default().fg(Color::Rgb(255, 165, 0));
+    let (rx_color, tx_color) = rx_tx_colors(app);
+    let answering = if totals.answering < totals.marked {
+        format!(" ({} answering)", totals.answering)
+    } else {
+        String::new()
+    };
+    let memory_bytes = (totals.memory_mb * 1_000_000.0) as u64; // Nodes report decimal MB
+    let line = Line::from(vec![
+        Span::styled(
+            format!(" {} ", MARKED_SYMBOL),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::styled(format!("{} marked", totals.marked), value),
+        Span::styled(format!("{} | CPU ", answering), label),
+        Span::styled(format!("{:.1}%", totals.cpu), value),
+        Span::styled(" | Mem ", label),
+        Span::styled(
+            format_option_u64_bytes(Some(memory_bytes), app.size_units),
+            value,
+        ),
+        Span::styled(" | Rx ", label),
+        Span::styled(
+            format!(
+                "{} {}",
+                format_option_u64_bytes(Some(totals.data_in_bytes), app.size_units),
+                format_speed_bps(Some(totals.speed_in_bps), app.size_units)
+            ),
+            Style::default().fg(rx_color),
+        ),
+        Span::styled(" | Tx ", label),
+        Span::styled(
+            format!(
+                "{} {}",
+                format_option_u64_bytes(Some(totals.data_out_bytes), app.size_units),
+                format_speed_bps(Some(totals.speed_out_bps), app.size_units)
+            ),
+            Style::default().fg(tx_color),
+        ),
+        Span::styled(" | Recs ", label),
+        Span::styled(format_count_grouped(totals.records), value),
+    ]);
+    f.render_widget(Paragraph::new(line), area);
+}
+
+/// Rows of the group pane of `--group-by-prefix`, more groups are counted on the last one.
+pub const GROUP_PANE_MAX_ROWS: usize = 5;
+
+/// Rows taken by the group pane, 0 when it is off.
+pub fn group_pane_height(app: &App) -> u16 {
+    if !app.group_by_prefix {
+        return 0;
     }
+    app.group_members.len().min(GROUP_PANE_MAX_ROWS) as u16
+}
 
-    // --- Status Column Rendering (Index 13) ---
-    let status_index = 13;
-    if status_index < column_layout.len() {
-        let status_paragraph = Paragraph::new(status_text)
-            .style(status_style)
-            .alignment(Alignment::Right);
-        f.render_widget(status_paragraph, column_layout[status_index]);
+/// Renders a summary line per node group, from the group cache. A group not in the
+/// cache yet, before the first update, is computed on the spot.
+pub fn render_group_pane(f: &mut Frame, app: &App, area: Rect) {
+    let label = Style::default().fg(Color::DarkGray);
+    let value = Style::default().fg(Color::Rgb(255, 165, 0));
+    let (rx_color, tx_color) = rx_tx_colors(app);
+    let groups = &app.group_members;
+    let name_width = groups.keys().map(|group| group.width()).max().unwrap_or(0);
+    let shown = if groups.len() > GROUP_PANE_MAX_ROWS {
+        GROUP_PANE_MAX_ROWS - 1
+    } else {
+        groups.len()
+    };
+
+    let mut lines: Vec<Line> = groups
+        .iter()
+        .take(shown)
+        .map(|(group, members)| {
+            let summary = app
+                .node_group_summary(group)
+                .copied()
+                .unwrap_or_else(|| app.compute_group_summary(members));
+            let (running_symbol, running_color) =
+                status_symbol(NodeStatus::Running, &app.color_config);
+            let (error_symbol, error_color) = status_symbol(NodeStatus::Error, &app.color_config);
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:<width$} ", group, width = name_width),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(format!("{} nodes ", summary.nodes), label),
+                Span::styled(
+                    format!("{} {}", running_symbol, summary.running),
+                    Style::default().fg(running_color),
+                ),
+                Span::styled(
+                    format!(" {} {}", error_symbol, summary.errors),
+                    Style::default().fg(error_color),
+                ),
+                Span::styled(" | Rx ", label),
+                Span::styled(
+                    format_speed_bps(Some(summary.speed_in_bps), app.size_units),
+                    Style::default().fg(rx_color),
+                ),
+                Span::styled(" | Tx ", label),
+                Span::styled(
+                    format_speed_bps(Some(summary.speed_out_bps), app.size_units),
+                    Style::default().fg(tx_color),
+                ),
+                Span::styled(" | Recs ", label),
+                Span::styled(format_count(Some(summary.records), app.notation), value),
+                Span::styled(" | Rwds ", label),
+                Span::styled(format_count(Some(summary.rewards), app.notation), value),
+            ])
+        })
+        .collect();
+    if shown < groups.len() {
+        lines.push(Line::styled(
+            format!(" +{} more groups", groups.len() - shown),
+            label,
+        ));
     }
+    f.render_widget(Paragraph::new(lines), area);
+}
+
+/// Renders the log tail pane. Lines are wrapped to the pane width, the view sticks to
+/// the end unless scrolled up. Read errors are shown inside the pane.
+pub fn render_log_tail(f: &mut Frame, tail: &mut LogTail, area: Rect) {
+    let block = Block::default()
+        .title(Span::styled(
+            format!(
+                " {} ",
+                // Borders and the spaces around the title
+                truncate_to_width(
+                    &tail.path.to_string_lossy(),
+                    area.width.saturating_sub(4) as usize
+                )
+            ),
+            Style::default().fg(Color::DarkGray),
+        ))
+        .title_bottom(Line::styled(
+            " PgUp/PgDn to scroll, L to close ",
+            Style::default().fg(Color::DarkGray),
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
+    if inner_area.width == 0 || inner_area.height == 0 {
+        return;
+    }
+
+    if let Some(error) = &tail.error {
+        let error_paragraph = Paragraph::new(error.clone()).style(Style::default().fg(Color::Red));
+        f.render_widget(error_paragraph, inner_area);
+        return;
+    }
+
+    // Wrap by hand so the number of rows, and so the scroll range, is exact
+    let width = inner_area.width as usize;
+    let rows: Vec<Line> = tail
+        .display_lines()
+        .flat_map(|line| {
+            let chars: Vec<char> = line.chars().collect();
+            if chars.is_empty() {
+                return vec![Line::default()];
+            }
+            chars
+                .chunks(width)
+                .map(|chunk| Line::styled(chunk.iter().collect::<String>(), DATA_CELL_STYLE))
+                .collect()
+        })
+        .collect();
+
+    let height = inner_area.height as usize;
+    let max_scroll = rows.len().saturating_sub(height);
+    tail.scroll = tail.scroll.min(max_scroll);
+    let top = max_scroll - tail.scroll;
+    let top = top.min(u16::MAX as usize) as u16;
+    f.render_widget(Paragraph::new(rows).scroll((top, 0)), inner_area);
 }
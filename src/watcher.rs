@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc as std_mpsc,
+};
+use tokio::sync::mpsc;
+
+/// Returns the directory new node directories appear in: the parent of the node
+/// path glob, e.g. `~/.local/share/autonomi/node` for `~/.local/share/autonomi/node/*`.
+/// None when the parent itself contains wildcards, there is no single directory to watch then.
+pub fn glob_parent(path_glob: &str) -> Option<PathBuf> {
+    let parent = Path::new(path_glob).parent()?;
+    let has_wildcards = parent
+        .to_string_lossy()
+        .contains(['*', '?', '[', ']', '{', '}']);
+    (!has_wildcards && parent.is_dir()).then(|| parent.to_path_buf())
+}
+
+/// Watches `parent` for new subdirectories and sends their paths to `tx`.
+/// Blocks until the receiving end is dropped, run it on a blocking task.
+pub fn watch_parent_directory(parent: &Path, tx: mpsc::Sender<String>) -> Result<()> {
+    let (event_tx, event_rx) = std_mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(event_tx).context("Failed to create the directory watcher")?;
+    watcher
+        .watch(parent, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", parent.display()))?;
+
+    for result in event_rx {
+        let Ok(event) = result else {
+            continue; // Transient watcher errors, keep watching
+        };
+        if !matches!(event.kind, EventKind::Create(_)) {
+            continue;
+        }
+        for path in event.paths.into_iter().filter(|path| path.is_dir()) {
+            if tx
+                .blocking_send(path.to_string_lossy().into_owned())
+                .is_err()
+            {
+                return Ok(()); // The app is shutting down
+            }
+        }
+    }
+    Ok(())
+}
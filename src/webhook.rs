@@ -0,0 +1,424 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+// Default time between two notifications of the same event for the same node
+pub const DEFAULT_WEBHOOK_RATE_LIMIT_SECS: u64 = 300;
+// Tries per notification, waiting RETRY_DELAY, then twice as long, between them
+const DELIVERY_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+// Notifications waiting to be posted, the ones arriving while the queue is full are dropped
+const MAX_QUEUED_NOTIFICATIONS: usize = 64;
+
+/// Node events posted to the webhook, the `events` filter of `[notifications]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    NodeDown,      // A running node stopped answering
+    NodeRecovered, // A down node answers again
+    Outlier,       // Fleet outlier alert, see `App::node_alerts`
+    Nat,           // Few inbound connections, possibly behind a NAT
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::NodeDown,
+        WebhookEvent::NodeRecovered,
+        WebhookEvent::Outlier,
+        WebhookEvent::Nat,
+    ];
+
+    /// Name of the event in the config file and the payloads.
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::NodeDown => "node_down",
+            WebhookEvent::NodeRecovered => "node_recovered",
+            WebhookEvent::Outlier => "outlier",
+            WebhookEvent::Nat => "nat",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            WebhookEvent::NodeDown => "is down",
+            WebhookEvent::NodeRecovered => "is back up",
+            WebhookEvent::Outlier => "is a fleet outlier",
+            WebhookEvent::Nat => "may be behind a NAT",
+        }
+    }
+}
+
+/// The `[notifications]` section of the config file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationsConfig {
+    #[serde(serialize_with = "crate::config::redact_required")]
+    pub webhook_url: String,
+    #[serde(default = "all_events")]
+    pub events: Vec<WebhookEvent>, // Events posted, all by default
+    #[serde(default = "default_rate_limit_secs")]
+    pub rate_limit_secs: u64, // Per node and event
+}
+
+fn all_events() -> Vec<WebhookEvent> {
+    WebhookEvent::ALL.to_vec()
+}
+
+fn default_rate_limit_secs() -> u64 {
+    DEFAULT_WEBHOOK_RATE_LIMIT_SECS
+}
+
+impl NotificationsConfig {
+    /// The webhook URL, which must be http(s).
+    pub fn url(&self) -> Result<Url> {
+        let url = Url::parse(&self.webhook_url)
+            .with_context(|| format!("Invalid webhook URL '{}'", self.webhook_url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!(
+                "Invalid webhook URL '{}': use http:// or https://",
+                self.webhook_url
+            );
+        }
+        Ok(url)
+    }
+}
+
+/// JSON body posted for an event. `text` says the same in a sentence, it is what
+/// Slack-compatible webhooks (Slack, Mattermost, Discord's `/slack` URLs) display.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub text: String,
+    pub node: String,
+    pub event: WebhookEvent,
+    pub value: Option<String>, // Error of a down node, text of an alert
+    pub timestamp: DateTime<Local>,
+    pub hostname: String,
+}
+
+/// Webhook notifications of `[notifications]`: `notify` drops the events filtered out
+/// or sent too recently for the same node, `take` hands the others to `run_app`, which
+/// posts them in the background.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    pub url: Url,
+    events: Vec<WebhookEvent>,
+    rate_limit: Duration,
+    hostname: String,
+    last_sent: HashMap<(String, WebhookEvent), Instant>,
+    pending: Vec<WebhookPayload>,
+}
+
+impl WebhookNotifier {
+    /// Notifier of a `[notifications]` section. An invalid URL is an error, webhooks
+    /// are not silently turned off.
+    pub fn new(config: &NotificationsConfig) -> Result<WebhookNotifier> {
+        Ok(WebhookNotifier {
+            url: config.url()?,
+            events: config.events.clone(),
+            rate_limit: Duration::from_secs(config.rate_limit_secs),
+            hostname: hostname::get()
+                .map(|host| host.to_string_lossy().into_owned())
+                .unwrap_or_else(|_| "unknown".to_string()),
+            last_sent: HashMap::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Queues `event` of `node` unless it is filtered out or the same event of the
+    /// same node was queued less than the rate limit ago, so a flapping node posts
+    /// once per rate limit and event.
+    pub fn notify(&mut self, node: &str, event: WebhookEvent, value: Option<String>, now: Instant) {
+        if !self.events.contains(&event) {
+            return;
+        }
+        let key = (node.to_string(), event);
+        if self
+            .last_sent
+            .get(&key)
+            .is_some_and(|sent| now.saturating_duration_since(*sent) < self.rate_limit)
+        {
+            return;
+        }
+        self.last_sent.insert(key, now);
+        let text = match &value {
+            Some(value) => format!("{} {}: {}", node, event.describe(), value),
+            None => format!("{} {}", node, event.describe()),
+        };
+        self.pending.push(WebhookPayload {
+            text: format!("[{}] {}", self.hostname, text),
+            node: node.to_string(),
+            event,
+            value,
+            timestamp: Local::now(),
+            hostname: self.hostname.clone(),
+        });
+    }
+
+    /// The notifications queued since the last call, oldest first.
+    pub fn take(&mut self) -> Vec<WebhookPayload> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Starts the task posting to `url` what is sent to the returned channel, one
+/// notification at a time. Notifications that still fail after the retries are
+/// dropped, their error goes to `failures`.
+pub fn start_delivery(url: Url, failures: mpsc::Sender<String>) -> mpsc::Sender<WebhookPayload> {
+    spawn_delivery(url, failures, RETRY_DELAY)
+}
+
+// `start_delivery` waiting `retry_delay` before the first retry
+fn spawn_delivery(
+    url: Url,
+    failures: mpsc::Sender<String>,
+    retry_delay: Duration,
+) -> mpsc::Sender<WebhookPayload> {
+    let (payloads, mut queued) = mpsc::channel::<WebhookPayload>(MAX_QUEUED_NOTIFICATIONS);
+    tokio::spawn(async move {
+        let client = Client::builder()
+            .timeout(POST_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        while let Some(payload) = queued.recv().await {
+            if let Err(e) = post_with_retry(&client, &url, &payload, retry_delay).await {
+                let _ = failures
+                    .send(format!("Webhook for {} failed: {:#}", payload.node, e))
+                    .await;
+            }
+        }
+    });
+    payloads
+}
+
+async fn post_with_retry(
+    client: &Client,
+    url: &Url,
+    payload: &WebhookPayload,
+    retry_delay: Duration,
+) -> Result<()> {
+    let mut delay = retry_delay;
+    let mut attempt = 1;
+    loop {
+        let result = client
+            .post(url.clone())
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url()); // Webhook URLs carry their secret in the path
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt == DELIVERY_ATTEMPTS => {
+                return Err(e).context(format!("gave up after {} attempts", attempt));
+            }
+            Err(_) => {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serve::read_test_request_body;
+    use serde_json::Value;
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    const TEST_RETRY_DELAY: Duration = Duration::from_millis(50);
+    const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn notifier(events: &[WebhookEvent]) -> WebhookNotifier {
+        WebhookNotifier::new(&NotificationsConfig {
+            webhook_url: "http://127.0.0.1:9/hook".to_string(),
+            events: events.to_vec(),
+            rate_limit_secs: 60,
+        })
+        .unwrap()
+    }
+
+    // Webhook answering its requests with `statuses` in turn, then with the last one.
+    // The time and JSON body of each request go to the returned channel
+    async fn mock_webhook(statuses: &[u16]) -> (Url, mpsc::UnboundedReceiver<(Instant, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/hook", listener.local_addr().unwrap())).unwrap();
+        let (requests, received) = mpsc::unbounded_channel();
+        let statuses = statuses.to_vec();
+        tokio::spawn(async move {
+            for i in 0.. {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let body = read_test_request_body(&mut stream).await;
+                let _ = requests.send((Instant::now(), serde_json::from_slice(&body).unwrap()));
+                let status = statuses[i.min(statuses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, received)
+    }
+
+    async fn next_request(
+        received: &mut mpsc::UnboundedReceiver<(Instant, Value)>,
+    ) -> (Instant, Value) {
+        tokio::time::timeout(TEST_TIMEOUT, received.recv())
+            .await
+            .expect("no request")
+            .unwrap()
+    }
+
+    #[test]
+    fn an_invalid_url_is_an_error() {
+        for webhook_url in ["hooks.example.com/T000", "ftp://hooks.example.com/T000"] {
+            let config = NotificationsConfig {
+                webhook_url: webhook_url.to_string(),
+                events: all_events(),
+                rate_limit_secs: DEFAULT_WEBHOOK_RATE_LIMIT_SECS,
+            };
+            assert!(WebhookNotifier::new(&config).is_err(), "{}", webhook_url);
+        }
+    }
+
+    #[test]
+    fn each_event_has_its_payload() {
+        let mut notifier = notifier(&WebhookEvent::ALL);
+        let now = Instant::now();
+        for event in WebhookEvent::ALL {
+            let value = (event != WebhookEvent::NodeRecovered).then(|| "why".to_string());
+            notifier.notify("antnode-3", event, value, now);
+        }
+        let payloads = notifier.take();
+        assert_eq!(payloads.len(), WebhookEvent::ALL.len());
+        let host = notifier.hostname.clone();
+        let expected_texts = [
+            format!("[{}] antnode-3 is down: why", host),
+            format!("[{}] antnode-3 is back up", host),
+            format!("[{}] antnode-3 is a fleet outlier: why", host),
+            format!("[{}] antnode-3 may be behind a NAT: why", host),
+        ];
+        for ((payload, event), text) in payloads.iter().zip(WebhookEvent::ALL).zip(expected_texts) {
+            let json = serde_json::to_value(payload).unwrap();
+            assert_eq!(json["event"], event.name());
+            assert_eq!(json["text"], text);
+            assert_eq!(json["node"], "antnode-3");
+            assert_eq!(json["hostname"], host.as_str());
+            match event {
+                WebhookEvent::NodeRecovered => assert!(json["value"].is_null()),
+                _ => assert_eq!(json["value"], "why"),
+            }
+            assert!(json["timestamp"].as_str().is_some_and(|t| t.contains('T')));
+        }
+        assert!(notifier.take().is_empty());
+    }
+
+    #[test]
+    fn filtered_out_events_are_dropped() {
+        let mut notifier = notifier(&[WebhookEvent::NodeDown]);
+        let now = Instant::now();
+        notifier.notify("antnode-1", WebhookEvent::Nat, None, now);
+        notifier.notify("antnode-1", WebhookEvent::NodeDown, None, now);
+        let events: Vec<WebhookEvent> = notifier.take().iter().map(|p| p.event).collect();
+        assert_eq!(events, [WebhookEvent::NodeDown]);
+    }
+
+    #[test]
+    fn each_node_and_event_is_rate_limited_on_its_own() {
+        let mut notifier = notifier(&WebhookEvent::ALL);
+        let start = Instant::now();
+        notifier.notify("antnode-1", WebhookEvent::NodeDown, None, start);
+        // Same node and event within the limit, dropped
+        notifier.notify(
+            "antnode-1",
+            WebhookEvent::NodeDown,
+            None,
+            start + Duration::from_secs(59),
+        );
+        // Another event of the node, or the same event of another node, are queued
+        notifier.notify("antnode-1", WebhookEvent::NodeRecovered, None, start);
+        notifier.notify("antnode-2", WebhookEvent::NodeDown, None, start);
+        let sent: Vec<(String, WebhookEvent)> = notifier
+            .take()
+            .into_iter()
+            .map(|payload| (payload.node, payload.event))
+            .collect();
+        assert_eq!(
+            sent,
+            [
+                ("antnode-1".to_string(), WebhookEvent::NodeDown),
+                ("antnode-1".to_string(), WebhookEvent::NodeRecovered),
+                ("antnode-2".to_string(), WebhookEvent::NodeDown),
+            ]
+        );
+
+        // Once the limit is over, the event is posted again
+        notifier.notify(
+            "antnode-1",
+            WebhookEvent::NodeDown,
+            None,
+            start + Duration::from_secs(60),
+        );
+        assert_eq!(notifier.take().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_failed_post_is_retried_with_a_growing_delay() {
+        let (url, mut received) = mock_webhook(&[500, 503, 200]).await;
+        let (failures_tx, mut failures) = mpsc::channel(4);
+        let payloads = spawn_delivery(url, failures_tx, TEST_RETRY_DELAY);
+        let mut notifier = notifier(&WebhookEvent::ALL);
+        notifier.notify("antnode-1", WebhookEvent::NodeDown, None, Instant::now());
+        payloads.send(notifier.take().remove(0)).await.unwrap();
+
+        let mut attempts = Vec::new();
+        for _ in 0..3 {
+            let (at, body) = next_request(&mut received).await;
+            assert_eq!(body["event"], "node_down");
+            attempts.push(at);
+        }
+        assert!(attempts[1] - attempts[0] >= TEST_RETRY_DELAY);
+        assert!(attempts[2] - attempts[1] >= TEST_RETRY_DELAY * 2);
+
+        // Delivered on the third attempt, no failure and no fourth attempt
+        drop(payloads);
+        assert_eq!(failures.recv().await, None);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn a_post_failing_three_times_is_dropped_with_an_error() {
+        let (url, mut received) = mock_webhook(&[500]).await;
+        let (failures_tx, mut failures) = mpsc::channel(4);
+        let payloads = spawn_delivery(url, failures_tx, TEST_RETRY_DELAY);
+        let mut notifier = notifier(&WebhookEvent::ALL);
+        notifier.notify("antnode-1", WebhookEvent::Outlier, None, Instant::now());
+        payloads.send(notifier.take().remove(0)).await.unwrap();
+
+        let failure = tokio::time::timeout(TEST_TIMEOUT, failures.recv())
+            .await
+            .expect("no failure reported")
+            .unwrap();
+        assert!(
+            failure.starts_with("Webhook for antnode-1 failed"),
+            "{}",
+            failure
+        );
+        assert!(failure.contains("gave up after 3 attempts"), "{}", failure);
+        for _ in 0..3 {
+            next_request(&mut received).await;
+        }
+        assert!(received.try_recv().is_err());
+    }
+}